@@ -1,6 +1,9 @@
 use cgmath::{point3, vec3, InnerSpace, MetricSpace};
 use log::info;
-use raytracer_core::render::camera::Camera;
+use raytracer_core::{
+    render::camera::Camera,
+    sampler::{Sampler, SamplerKind},
+};
 use std::time::SystemTime;
 
 fn main() {
@@ -28,6 +31,7 @@ fn benchmark_ray_casting() {
     let start = SystemTime::now();
 
     let rng = fastrand::Rng::new();
+    let mut sampler = Sampler::new(SamplerKind::Independent, 1, rng.u64(..));
 
     // this next bit is just to convince the compiler we need the results
     // to keep it from 'optimizing' away the code under test
@@ -35,7 +39,7 @@ fn benchmark_ray_casting() {
     let mut sum2 = 0.0;
 
     for _ in 0..ITER_SIZE {
-        let ray = camera.project_ray(rng.f64(), rng.f64());
+        let ray = camera.project_ray(rng.f64(), rng.f64(), &mut sampler);
         sum1 += ray.origin.distance2(point3(0.0, 0.0, 0.0));
         sum2 += ray.direction.magnitude2();
     }
@@ -1,109 +1,481 @@
 use std::{
     fs::File,
-    io::{self, Write},
+    io::{self, BufWriter, Write},
+    ops::ControlFlow,
     path::PathBuf,
-    sync::Arc,
-    thread::JoinHandle,
     time::SystemTime,
 };
 
 use cgmath::{point3, vec3, Deg, InnerSpace};
-use clap::Parser;
-use log::{debug, info};
+use clap::{Parser, ValueEnum};
+use log::{debug, error, info};
 use raytracer_core::{
+    background::Background,
     image::{
-        blend::{self, BlendingMode},
-        buffer::ImageBuffer,
-        ppm,
+        buffer::FloatImageBuffer,
+        display::DisplayTransform,
+        writer::{self, OutputFormat},
+    },
+    render::{camera::Camera, iter::TileOrder, renderer::Renderer},
+    scene::{
+        self, BackgroundDescription, DisplaySettings, EnvironmentMapDescription,
+        LightSamplingDescription, PhysicalSkyDescription, RenderSettings, SamplerDescription,
+        SceneDescription, SolidBackgroundDescription, ToneMapDescription, TransferDescription,
     },
-    render::{camera::Camera, iter::ChunkedPixelIterator, renderer::Renderer},
-    scene,
 };
 
 #[derive(Parser)]
 #[command(version)]
 struct CliArguments {
-    /// The number of threads to spawn
+    /// The number of threads to render on
     #[arg(short, long, default_value_t = 1)]
     threads: usize,
-    /// The width of the image to render
-    #[arg(short, long, default_value_t = 720)]
-    width: usize,
-    /// The height of the image to render
-    #[arg(short, long, default_value_t = 405)]
-    height: usize,
-    /// The number of sample rays cast per pixel
-    #[arg(short, long, default_value_t = 4)]
-    samples_per_pixel: usize,
-    /// The maximum number of ray bounces a sample ray can generate
-    #[arg(short, long, default_value_t = 4)]
-    max_ray_depth: usize,
+    /// Render a sample per pixel at a time, rewriting the output file after
+    /// each pass so it can be watched as it refines
+    #[arg(long)]
+    progressive: bool,
+    /// The order to render the image's tiles in
+    #[arg(long, value_enum, default_value_t = TileOrderKind::Scanline)]
+    tile_order: TileOrderKind,
+    /// The width of the image to render [default: 720, or the scene's setting]
+    #[arg(short, long)]
+    width: Option<usize>,
+    /// The height of the image to render [default: 405, or the scene's setting]
+    #[arg(short, long)]
+    height: Option<usize>,
+    /// The number of sample rays cast per pixel [default: 4, or the scene's setting]
+    #[arg(short, long)]
+    samples_per_pixel: Option<usize>,
+    /// The maximum number of ray bounces a sample ray can generate [default: 4, or the scene's setting]
+    #[arg(short, long)]
+    max_ray_depth: Option<usize>,
+    /// Stop sampling pixels once the noise left is under this fraction of their
+    /// brightness, eg 0.01, making --samples-per-pixel the most any pixel takes
+    /// [default: off, or the scene's setting]
+    #[arg(long)]
+    adaptive_threshold: Option<f64>,
+    /// How many samples every pixel takes with adaptive sampling [default: 16,
+    /// or the scene's setting]
+    #[arg(long)]
+    min_samples: Option<usize>,
+    /// Also write an image of how many samples each pixel took, in a format
+    /// picked from its extension. Can't be used with --progressive
+    #[arg(long)]
+    heatmap_file: Option<PathBuf>,
+    /// How to find the light reaching each bounce [default: power, or the scene's setting]
+    #[arg(long, value_enum)]
+    light_sampling: Option<LightSamplingKind>,
+    /// How the samples for each pixel are picked [default: sobol, or the scene's setting]
+    #[arg(long, value_enum)]
+    sampler: Option<SamplerKind>,
+    /// Where the random numbers behind every sample start from. The same seed
+    /// renders the same image, whatever the number of threads [default: 0, or
+    /// the scene's setting]
+    #[arg(long)]
+    seed: Option<u64>,
+    /// A scene file (TOML or JSON) to render. If not specified, renders a random test scene
+    #[arg(long)]
+    scene: Option<PathBuf>,
     /// The output to write the result to. If not specified, defaults to stdout
     #[arg(short, long)]
     output_file: Option<PathBuf>,
+    /// Brighten (positive) or darken (negative) the image, in stops [default: 0, or the scene's setting]
+    #[arg(long, allow_hyphen_values = true)]
+    exposure: Option<f64>,
+    /// How to bring values above 1 into range for 8-bit formats [default: none, or the scene's setting]
+    #[arg(long, value_enum)]
+    tone_map: Option<ToneMapKind>,
+    /// The brightness that maps to white for extended-reinhard [default: 4, or the scene's setting]
+    #[arg(long)]
+    white_point: Option<f64>,
+    /// How to encode colors for 8-bit formats [default: srgb, or the scene's setting]
+    #[arg(long, value_enum)]
+    transfer: Option<TransferKind>,
+    /// The image format to write [default: picked from the output file's
+    /// extension, or ppm for stdout]
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormatKind>,
+    /// Replace the scene's background. If not specified, picked from the other
+    /// background options, or the scene's background
+    #[arg(long, value_enum)]
+    background: Option<BackgroundKind>,
+    /// The color of a solid background, as R,G,B [default: 0,0,0]
+    #[arg(long, value_parser = parse_triple)]
+    background_color: Option<[f64; 3]>,
+    /// A Radiance HDR image to wrap around the scene as an environment map
+    #[arg(long)]
+    environment_map: Option<PathBuf>,
+    /// How far to turn the environment map around the vertical axis, in degrees
+    #[arg(long, default_value_t = 0.0)]
+    environment_rotation: f64,
+    /// The direction of the sun in a physical sky, as X,Y,Z [default: 0,1,1]
+    #[arg(long, value_parser = parse_triple, allow_hyphen_values = true)]
+    sun_direction: Option<[f64; 3]>,
+    /// How hazy a physical sky is, from 2 (clear) to 10 (hazy) [default: 3]
+    #[arg(long)]
+    turbidity: Option<f64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormatKind {
+    /// 8-bit PNG
+    Png,
+    /// 16-bit PNG
+    Png16,
+    /// Binary (P6) PPM
+    Ppm,
+    /// ASCII (P3) PPM
+    AsciiPpm,
+    /// OpenEXR with half floats, keeping the full range of the render
+    Exr,
+    /// OpenEXR with full floats
+    Exr32,
+    /// Portable Float Map
+    Pfm,
+}
+
+impl From<OutputFormatKind> for OutputFormat {
+    fn from(value: OutputFormatKind) -> Self {
+        match value {
+            OutputFormatKind::Png => OutputFormat::Png,
+            OutputFormatKind::Png16 => OutputFormat::Png16,
+            OutputFormatKind::Ppm => OutputFormat::Ppm,
+            OutputFormatKind::AsciiPpm => OutputFormat::AsciiPpm,
+            OutputFormatKind::Exr => OutputFormat::ExrHalf,
+            OutputFormatKind::Exr32 => OutputFormat::ExrFloat,
+            OutputFormatKind::Pfm => OutputFormat::Pfm,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LightSamplingKind {
+    /// Only count light that scattered rays happen to hit, apart from lights
+    /// they never could, like point lights
+    Off,
+    /// Sample lights directly too, using the balance heuristic
+    Balance,
+    /// Sample lights directly too, using the power heuristic
+    Power,
+}
+
+impl From<LightSamplingKind> for LightSamplingDescription {
+    fn from(value: LightSamplingKind) -> Self {
+        match value {
+            LightSamplingKind::Off => LightSamplingDescription::Off,
+            LightSamplingKind::Balance => LightSamplingDescription::Balance,
+            LightSamplingKind::Power => LightSamplingDescription::Power,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SamplerKind {
+    /// Every number picked at random on its own
+    Independent,
+    /// Jittered inside a grid of strata over each pixel
+    Stratified,
+    /// The Halton sequence, shifted at random for each pixel
+    Halton,
+    /// The Sobol sequence, Owen scrambled for each pixel
+    Sobol,
+    /// The Sobol sequence, shifted by blue noise for finer grained noise
+    BlueNoise,
+}
+
+impl From<SamplerKind> for SamplerDescription {
+    fn from(value: SamplerKind) -> Self {
+        match value {
+            SamplerKind::Independent => SamplerDescription::Independent,
+            SamplerKind::Stratified => SamplerDescription::Stratified,
+            SamplerKind::Halton => SamplerDescription::Halton,
+            SamplerKind::Sobol => SamplerDescription::Sobol,
+            SamplerKind::BlueNoise => SamplerDescription::BlueNoise,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TileOrderKind {
+    /// A row of tiles at a time, from the top
+    Scanline,
+    /// Outwards from the middle
+    Spiral,
+    /// Along a Hilbert curve
+    Hilbert,
+}
+
+impl From<TileOrderKind> for TileOrder {
+    fn from(value: TileOrderKind) -> Self {
+        match value {
+            TileOrderKind::Scanline => TileOrder::Scanline,
+            TileOrderKind::Spiral => TileOrder::Spiral,
+            TileOrderKind::Hilbert => TileOrder::Hilbert,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ToneMapKind {
+    /// Clip anything above 1
+    None,
+    Reinhard,
+    ExtendedReinhard,
+    /// A filmic curve fit to ACES
+    Aces,
+    /// A filmic curve that desaturates highlights
+    Agx,
+}
+
+impl From<ToneMapKind> for ToneMapDescription {
+    fn from(value: ToneMapKind) -> Self {
+        match value {
+            ToneMapKind::None => ToneMapDescription::None,
+            ToneMapKind::Reinhard => ToneMapDescription::Reinhard,
+            ToneMapKind::ExtendedReinhard => ToneMapDescription::ExtendedReinhard,
+            ToneMapKind::Aces => ToneMapDescription::Aces,
+            ToneMapKind::Agx => ToneMapDescription::Agx,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TransferKind {
+    /// The sRGB curve
+    Srgb,
+    /// A plain 2.2 gamma
+    #[value(name = "gamma-2.2")]
+    Gamma22,
+}
+
+impl From<TransferKind> for TransferDescription {
+    fn from(value: TransferKind) -> Self {
+        match value {
+            TransferKind::Srgb => TransferDescription::Srgb,
+            TransferKind::Gamma22 => TransferDescription::Gamma22,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BackgroundKind {
+    Solid,
+    Gradient,
+    EnvironmentMap,
+    PhysicalSky,
 }
 
 fn main() -> io::Result<()> {
     pretty_env_logger::init();
     let CliArguments {
         threads,
+        progressive,
+        tile_order,
         width,
         height,
         samples_per_pixel,
         max_ray_depth,
+        adaptive_threshold,
+        min_samples,
+        heatmap_file,
+        light_sampling,
+        sampler,
+        seed,
         output_file,
+        output_format,
+        exposure,
+        tone_map,
+        white_point,
+        transfer,
+        scene: scene_file,
+        background,
+        background_color,
+        environment_map,
+        environment_rotation,
+        sun_direction,
+        turbidity,
     } = CliArguments::parse();
 
+    let output_format = match (output_format, &output_file) {
+        (Some(format), _) => format.into(),
+        (None, Some(path)) => match OutputFormat::from_path(path) {
+            Some(format) => format,
+            None => {
+                error!(
+                    "Can't tell the image format of {}, use a .png, .ppm, .exr or .pfm extension or --output-format",
+                    path.display()
+                );
+                std::process::exit(1);
+            }
+        },
+        (None, None) => OutputFormat::Ppm,
+    };
+    if progressive && output_file.is_none() {
+        error!("Progressive rendering needs an --output-file to write each pass to");
+        std::process::exit(1);
+    }
+    if progressive && heatmap_file.is_some() {
+        error!("Progressive renders take one sample per pixel per pass, so have no heatmap");
+        std::process::exit(1);
+    }
+    let heatmap_format = match &heatmap_file {
+        Some(path) => match OutputFormat::from_path(path) {
+            Some(format) => Some(format),
+            None => {
+                error!(
+                    "Can't tell the image format of {}, use a .png, .ppm, .exr or .pfm extension",
+                    path.display()
+                );
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let background = match make_background(
+        background,
+        background_color,
+        environment_map,
+        environment_rotation,
+        sun_direction,
+        turbidity,
+    ) {
+        Ok(background) => background,
+        Err(err) => {
+            error!("Invalid background: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let description = match scene_file {
+        Some(path) => match SceneDescription::from_file(&path) {
+            Ok(description) => Some(description),
+            Err(err) => {
+                error!("Failed to load scene {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // command line arguments take precedence over the scene file
+    let defaults = description
+        .as_ref()
+        .map_or_else(RenderSettings::default, |d| d.render.clone());
+    let settings = RenderSettings {
+        width: width.unwrap_or(defaults.width),
+        height: height.unwrap_or(defaults.height),
+        samples_per_pixel: samples_per_pixel.unwrap_or(defaults.samples_per_pixel),
+        max_ray_depth: max_ray_depth.unwrap_or(defaults.max_ray_depth),
+        light_sampling: light_sampling.map_or(defaults.light_sampling, Into::into),
+        adaptive_threshold: adaptive_threshold.or(defaults.adaptive_threshold),
+        min_samples: min_samples.unwrap_or(defaults.min_samples),
+        sampler: sampler.map_or(defaults.sampler, Into::into),
+        seed: seed.unwrap_or(defaults.seed),
+    };
+    if let Err(err) = settings.validate() {
+        error!("Invalid render settings: {}", err);
+        std::process::exit(1);
+    }
+    let adaptive_sampling = settings.adaptive_sampling();
+    let RenderSettings {
+        width,
+        height,
+        samples_per_pixel,
+        max_ray_depth,
+        light_sampling,
+        sampler,
+        seed,
+        ..
+    } = settings;
+
+    let defaults = description
+        .as_ref()
+        .map_or_else(DisplaySettings::default, |d| d.display.clone());
+    let display = DisplaySettings {
+        exposure: exposure.unwrap_or(defaults.exposure),
+        tone_map: tone_map.map_or(defaults.tone_map, Into::into),
+        white_point: white_point.unwrap_or(defaults.white_point),
+        transfer: transfer.map_or(defaults.transfer, Into::into),
+    };
+    if let Err(err) = display.validate() {
+        error!("Invalid display settings: {}", err);
+        std::process::exit(1);
+    }
+    let display = display.build();
+
     debug!("Output dimensions: {} x {}", width, height);
 
     info!("Rendering image...");
     let start = SystemTime::now();
 
-    let mut threadpool = Vec::<JoinHandle<ImageBuffer>>::new();
-
-    let scene = Arc::new(scene::new_random_world());
-
-    for chunk in ChunkedPixelIterator::with_chunks(width, height, threads) {
-        info!("Spawning thread...");
-        // make a copy of the world specific to each thread
-        // this helps the borrow checker see the move into the thread, without
-        // having it try to move the top-level object.
-        let local_scene = scene.clone();
-        threadpool.push(std::thread::spawn(move || -> ImageBuffer {
-            let camera_position = point3(13.0, 2.0, 3.0);
-            let look_at = point3(0.0, 0.0, 0.0);
-            let camera = Camera::new(
-                camera_position,
-                look_at,
-                vec3(0.0, 1.0, 0.0),
-                width as f64 / height as f64,
-                Deg(20.0),
-                22.0,
-                (look_at - camera_position).magnitude(),
-                0.0,
-                1.0,
-            );
-            let renderer = Renderer::new(
-                width,
-                height,
-                samples_per_pixel,
-                max_ray_depth as i64,
-                camera,
-            );
-            let mut buf = ImageBuffer::new_rgb(width, height);
-            renderer.render_to_buffer(&local_scene, &mut buf, chunk);
-            buf
-        }));
-    }
-
-    let images = threadpool
-        .into_iter()
-        .map(move |i| {
-            let buf = i.join();
-            buf.unwrap()
-        })
-        .collect();
-    let result_image = blend::blend_images(images, BlendingMode::Add);
+    let (scene, camera) = match description {
+        Some(mut description) => {
+            description.render = settings;
+            let scene = match description.build_scene() {
+                Ok(scene) => scene,
+                Err(err) => {
+                    error!("Failed to build scene: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            (scene, description.build_camera())
+        }
+        None => (
+            scene::new_random_world(seed),
+            make_default_camera(width, height),
+        ),
+    };
+    let scene = match background {
+        Some(background) => scene.with_background(background),
+        None => scene,
+    };
+    let renderer = Renderer::new(
+        width,
+        height,
+        samples_per_pixel,
+        max_ray_depth as i64,
+        camera,
+    )
+    .with_light_sampling(light_sampling.into())
+    .with_sampler(sampler.into())
+    .with_seed(seed)
+    .with_tile_order(tile_order.into());
+    let renderer = match adaptive_sampling {
+        Some(adaptive_sampling) => renderer.with_adaptive_sampling(adaptive_sampling),
+        None => renderer,
+    };
+    info!("Rendering on {} threads...", threads);
+    let result_image = if progressive {
+        let mut failed = None;
+        let image = renderer.render_progressive(&scene, threads, |pass| {
+            info!("Finished pass {} of {}", pass.number, pass.total);
+            match write_output(output_file.as_ref(), pass.image, output_format, &display) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(err) => {
+                    failed = Some(err);
+                    ControlFlow::Break(())
+                }
+            }
+        });
+        if let Some(err) = failed {
+            return Err(err);
+        }
+        image
+    } else {
+        let mut image = FloatImageBuffer::new_rgb(width, height);
+        let counts = renderer.render_with_sample_counts(&scene, &mut image, threads);
+        let total: u64 = counts.counts.iter().map(|&count| count as u64).sum();
+        info!(
+            "Took {:.1} samples per pixel on average",
+            total as f64 / (width * height) as f64
+        );
+        if let (Some(path), Some(format)) = (&heatmap_file, heatmap_format) {
+            let heatmap = counts.heatmap(samples_per_pixel);
+            write_output(Some(path), &heatmap, format, &DisplayTransform::default())?;
+        }
+        image
+    };
 
     let end = SystemTime::now();
     info!(
@@ -111,17 +483,103 @@ fn main() -> io::Result<()> {
         end.duration_since(start).expect("you doltz").as_millis()
     );
 
-    let result = ppm::make_image(&result_image.data, result_image.width, result_image.height);
+    // progressive renders have already written out their last pass
+    if !progressive {
+        write_output(output_file.as_ref(), &result_image, output_format, &display)?;
+    }
+
+    Ok(())
+}
 
+/// Write the image to the output file, or stdout if there isn't one
+fn write_output(
+    output_file: Option<&PathBuf>,
+    image: &FloatImageBuffer,
+    format: OutputFormat,
+    display: &DisplayTransform,
+) -> io::Result<()> {
     if let Some(filepath) = output_file {
-        let mut file = File::create(filepath)?;
-        file.write(result.as_bytes())?;
-        file.flush()?;
+        let mut file = BufWriter::new(File::create(filepath)?);
+        writer::write_image(&mut file, image, format, display)?;
+        file.flush()
     } else {
-        let mut stdout = io::stdout();
-        stdout.write(result.as_bytes())?;
-        stdout.flush()?;
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        writer::write_image(&mut stdout, image, format, display)?;
+        stdout.flush()
+    }
+}
+
+/// The camera used for the random test scene
+fn make_default_camera(width: usize, height: usize) -> Camera {
+    let camera_position = point3(13.0, 2.0, 3.0);
+    let look_at = point3(0.0, 0.0, 0.0);
+    Camera::new(
+        camera_position,
+        look_at,
+        vec3(0.0, 1.0, 0.0),
+        width as f64 / height as f64,
+        Deg(20.0),
+        22.0,
+        (look_at - camera_position).magnitude(),
+        0.0,
+        1.0,
+    )
+}
+
+/// Build the background picked by the command line options, if any
+fn make_background(
+    kind: Option<BackgroundKind>,
+    color: Option<[f64; 3]>,
+    environment_map: Option<PathBuf>,
+    environment_rotation: f64,
+    sun_direction: Option<[f64; 3]>,
+    turbidity: Option<f64>,
+) -> Result<Option<Background>, String> {
+    let kind = match kind {
+        Some(kind) => kind,
+        None if environment_map.is_some() => BackgroundKind::EnvironmentMap,
+        None if sun_direction.is_some() || turbidity.is_some() => BackgroundKind::PhysicalSky,
+        None if color.is_some() => BackgroundKind::Solid,
+        None => return Ok(None),
     };
+    let description = match kind {
+        BackgroundKind::Solid => BackgroundDescription::Solid(SolidBackgroundDescription {
+            color: color.unwrap_or([0.0, 0.0, 0.0]),
+        }),
+        BackgroundKind::Gradient => return Ok(Some(Background::default())),
+        BackgroundKind::EnvironmentMap => {
+            BackgroundDescription::EnvironmentMap(EnvironmentMapDescription {
+                path: environment_map
+                    .ok_or("an environment map needs an --environment-map image")?,
+                rotation: environment_rotation,
+                intensity: 1.0,
+            })
+        }
+        BackgroundKind::PhysicalSky => BackgroundDescription::PhysicalSky(PhysicalSkyDescription {
+            sun_direction: sun_direction.unwrap_or([0.0, 1.0, 1.0]),
+            turbidity: turbidity.unwrap_or(3.0),
+            sun_intensity: None,
+        }),
+    };
+    description.validate().map_err(|err| err.to_string())?;
+    let background = description.build(None).map_err(|err| err.to_string())?;
+    Ok(Some(background))
+}
 
-    Ok(())
+/// Parse a comma-separated triple, like `0.5,0.7,1.0`
+fn parse_triple(value: &str) -> Result<[f64; 3], String> {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    match parts[..] {
+        [x, y, z] => {
+            let parse = |part: &str| {
+                part.parse::<f64>()
+                    .map_err(|_| format!("{:?} is not a number", part))
+            };
+            Ok([parse(x)?, parse(y)?, parse(z)?])
+        }
+        _ => Err(format!(
+            "expected 3 comma-separated numbers, got {:?}",
+            value
+        )),
+    }
 }
@@ -0,0 +1,155 @@
+use std::{f64::consts::PI, path::Path};
+
+use cgmath::{vec3, Deg, InnerSpace, Rad};
+
+use crate::{
+    geometry::{Ray, Vector},
+    image::{
+        buffer::FloatImageBuffer,
+        loader::{load_image, ColorSpace, ImageError},
+    },
+};
+
+use super::BackgroundTrait;
+
+/// An equirectangular (latitude-longitude) image wrapped around the scene
+///
+/// The top row of the image is straight up, the bottom row straight down,
+/// and the middle of the image looks down -z.
+pub struct EnvironmentMap {
+    image: FloatImageBuffer,
+    /// How far the map is turned around the y axis, from -z towards +x
+    rotation: Rad<f64>,
+    /// A multiplier on the brightness of the map
+    intensity: f64,
+}
+
+impl EnvironmentMap {
+    pub fn new<A: Into<Rad<f64>>>(image: FloatImageBuffer, rotation: A) -> EnvironmentMap {
+        assert!(
+            image.width > 0 && image.height > 0,
+            "Environment maps can't be empty"
+        );
+        EnvironmentMap {
+            image,
+            rotation: rotation.into(),
+            intensity: 1.0,
+        }
+    }
+
+    /// Read an environment map from a Radiance HDR file, or an sRGB PNG or
+    /// JPEG
+    pub fn load<P: AsRef<Path>>(path: P, rotation: Deg<f64>) -> Result<EnvironmentMap, ImageError> {
+        Ok(Self::new(load_image(path, ColorSpace::Srgb)?, rotation))
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Sample the image at fractional pixel coordinates, blending the 4
+    /// nearest pixels. The map wraps around horizontally.
+    fn bilinear(&self, x: f64, y: f64) -> Vector {
+        let width = self.image.width as i64;
+        let height = self.image.height as i64;
+        // pixel centers sit at half-integer coordinates
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let pixel = |px: i64, py: i64| -> Vector {
+            let px = px.rem_euclid(width) as usize;
+            let py = py.clamp(0, height - 1) as usize;
+            let [r, g, b] = self.image.pixel(px, py);
+            vec3(r as f64, g as f64, b as f64)
+        };
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = pixel(x0, y0) * (1.0 - tx) + pixel(x0 + 1, y0) * tx;
+        let bottom = pixel(x0, y0 + 1) * (1.0 - tx) + pixel(x0 + 1, y0 + 1) * tx;
+        return top * (1.0 - ty) + bottom * ty;
+    }
+}
+
+impl BackgroundTrait for EnvironmentMap {
+    fn color(&self, ray: &Ray) -> Vector {
+        let direction = ray.direction.normalize();
+        // the angle around the y axis, measured from -z towards +x, with the
+        // map's rotation taken off
+        let azimuth = direction.x.atan2(-direction.z) - self.rotation.0;
+        let polar = direction.y.clamp(-1.0, 1.0).acos();
+
+        let u = (0.5 + azimuth / (2.0 * PI)).rem_euclid(1.0);
+        let v = polar / PI;
+        let x = u * self.image.width as f64;
+        let y = v * self.image.height as f64;
+        return self.intensity * self.bilinear(x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use crate::image::buffer::BufferFormat;
+
+    use super::*;
+
+    /// A 4x2 map where each pixel's red channel is its column, and green is
+    /// its row
+    fn make_map(rotation: Deg<f64>) -> EnvironmentMap {
+        let mut data = vec![];
+        for y in 0..2 {
+            for x in 0..4 {
+                data.extend_from_slice(&[x as f32, y as f32, 1.0]);
+            }
+        }
+        let image = FloatImageBuffer {
+            format: BufferFormat::RGB32F,
+            width: 4,
+            height: 2,
+            data,
+        };
+        EnvironmentMap::new(image, rotation)
+    }
+
+    fn look(direction: Vector) -> Ray {
+        Ray::new(point3(0.0, 0.0, 0.0), direction, 0.0)
+    }
+
+    #[test]
+    fn when_color_given_direction_between_pixels_blends_them() {
+        let map = make_map(Deg(0.0));
+        // straight ahead is the middle of the map, between columns 1 and 2,
+        // and the horizon is between the two rows
+        let color = map.color(&look(vec3(0.0, 0.0, -1.0)));
+        assert!((color.x - 1.5).abs() < 1e-9);
+        assert!((color.y - 0.5).abs() < 1e-9);
+        // straight up is clamped to the top row
+        let color = map.color(&look(vec3(0.0, 1.0, 0.0)));
+        assert!(color.y.abs() < 1e-9);
+    }
+
+    #[test]
+    fn when_color_given_seam_wraps_around() {
+        let map = make_map(Deg(0.0));
+        // straight behind is the left and right edges of the map, which
+        // should blend the first and last columns
+        let color = map.color(&look(vec3(0.0, 0.0, 1.0)));
+        assert!((color.x - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn when_color_given_rotation_turns_map() {
+        let unrotated = make_map(Deg(0.0));
+        let rotated = make_map(Deg(90.0));
+        // turning the map by a quarter turn brings what was ahead around to
+        // the +x side
+        let ahead = unrotated.color(&look(vec3(0.0, 0.0, -1.0)));
+        let side = rotated.color(&look(vec3(1.0, 0.0, 0.0)));
+        assert!((ahead - side).magnitude() < 1e-9);
+    }
+}
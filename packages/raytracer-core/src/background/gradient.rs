@@ -0,0 +1,35 @@
+use cgmath::{vec3, InnerSpace};
+
+use crate::geometry::{Ray, Vector};
+
+use super::BackgroundTrait;
+
+/// A vertical blend between two colors, from straight down to straight up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gradient {
+    /// The color looking straight down
+    pub bottom: Vector,
+    /// The color looking straight up
+    pub top: Vector,
+}
+
+impl Gradient {
+    pub fn new(bottom: Vector, top: Vector) -> Gradient {
+        Gradient { bottom, top }
+    }
+}
+
+impl Default for Gradient {
+    /// A white-to-blue sky
+    fn default() -> Self {
+        Self::new(vec3(1.0, 1.0, 1.0), vec3(0.5, 0.7, 1.0))
+    }
+}
+
+impl BackgroundTrait for Gradient {
+    fn color(&self, ray: &Ray) -> Vector {
+        let unit_direction = ray.direction.normalize();
+        let t = 0.5 * (unit_direction.y + 1.0);
+        return (1.0 - t) * self.bottom + t * self.top;
+    }
+}
@@ -0,0 +1,67 @@
+//! Backgrounds light the scene from infinitely far away, and are what rays
+//! that leave the scene see.
+use std::sync::Arc;
+
+use crate::geometry::{Ray, Vector};
+
+mod environment_map;
+mod gradient;
+mod physical_sky;
+
+pub use environment_map::EnvironmentMap;
+pub use gradient::Gradient;
+pub use physical_sky::PhysicalSky;
+
+/// The light arriving from outside the scene, for rays that don't hit anything
+pub trait BackgroundTrait {
+    fn color(&self, ray: &Ray) -> Vector;
+}
+
+#[derive(Clone)]
+pub enum Background {
+    /// The same color in every direction
+    Solid(Vector),
+    Gradient(Gradient),
+    EnvironmentMap(Arc<EnvironmentMap>),
+    PhysicalSky(Arc<PhysicalSky>),
+}
+
+impl BackgroundTrait for Background {
+    #[inline(always)]
+    fn color(&self, ray: &Ray) -> Vector {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient(gradient) => gradient.color(ray),
+            Background::EnvironmentMap(environment_map) => environment_map.color(ray),
+            Background::PhysicalSky(sky) => sky.color(ray),
+        }
+    }
+}
+
+impl Default for Background {
+    /// The blue-white sky the renderer has always used
+    fn default() -> Self {
+        Self::Gradient(Gradient::default())
+    }
+}
+
+impl From<Vector> for Background {
+    fn from(value: Vector) -> Self {
+        Self::Solid(value)
+    }
+}
+impl From<Gradient> for Background {
+    fn from(value: Gradient) -> Self {
+        Self::Gradient(value)
+    }
+}
+impl From<Arc<EnvironmentMap>> for Background {
+    fn from(value: Arc<EnvironmentMap>) -> Self {
+        Self::EnvironmentMap(value)
+    }
+}
+impl From<Arc<PhysicalSky>> for Background {
+    fn from(value: Arc<PhysicalSky>) -> Self {
+        Self::PhysicalSky(value)
+    }
+}
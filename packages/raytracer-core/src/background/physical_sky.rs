@@ -0,0 +1,247 @@
+//! An analytic daylight model, after Preetham, Shirley & Smits (1999), "A
+//! Practical Analytic Model for Daylight".
+//!
+//! The sky's luminance and chromaticity are each fit by a Perez distribution
+//! over the angle from the zenith and the angle from the sun, with
+//! coefficients that depend on the turbidity (haziness) of the air.
+use std::f64::consts::FRAC_PI_2;
+
+use cgmath::{vec3, InnerSpace};
+
+use crate::geometry::{Ray, Vector};
+
+use super::BackgroundTrait;
+
+/// Scales the model's luminance, in kcd/m², so that a midday sky comes out
+/// around 0.5, in line with the other backgrounds
+const LUMINANCE_SCALE: f64 = 0.05;
+
+/// The angular radius of the sun's disk, in radians
+const SUN_ANGULAR_RADIUS: f64 = 0.00465;
+
+/// The radiance of the sun's disk before the atmosphere dims it. This is far
+/// dimmer than the real sun compared to the sky, which would otherwise swamp
+/// renders that don't sample lights directly with fireflies.
+const DEFAULT_SUN_INTENSITY: f64 = 1000.0;
+
+/// The sun can't quite set, as the model breaks down below the horizon
+const MAX_SUN_ZENITH: f64 = FRAC_PI_2 - 0.01;
+
+/// The coefficients of a Perez distribution, A through E
+#[derive(Clone, Copy, Debug)]
+struct Perez([f64; 5]);
+
+impl Perez {
+    /// Evaluate the distribution for a view direction at zenith angle theta,
+    /// and gamma from the sun
+    #[inline(always)]
+    fn evaluate(&self, theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.0;
+        let cos_gamma = gamma.cos();
+        return (1.0 + a * (b / theta.cos()).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma);
+    }
+}
+
+pub struct PhysicalSky {
+    /// Points towards the sun
+    sun_direction: Vector,
+    turbidity: f64,
+    sun_intensity: f64,
+    /// Distributions for luminance (Y), and the x and y chromaticities
+    distributions: [Perez; 3],
+    /// Y, x, y at the zenith, divided by each distribution at the zenith so
+    /// that they can just be multiplied by the distribution for a direction
+    zenith: [f64; 3],
+    /// The color of the sun's disk, after passing through the atmosphere
+    sun_color: Vector,
+}
+
+impl PhysicalSky {
+    /// Create a sky lit by a sun in the given direction
+    ///
+    /// Turbidity is how hazy the air is, where 2 is a very clear day and 10 is
+    /// a hazy one. The model is fit for turbidities from around 2 to 10.
+    pub fn new(sun_direction: Vector, turbidity: f64) -> PhysicalSky {
+        assert!(turbidity >= 1.0, "Turbidity must be at least 1");
+        let sun_direction = sun_direction.normalize();
+        let sun_zenith = sun_direction.y.clamp(-1.0, 1.0).acos().min(MAX_SUN_ZENITH);
+        let t = turbidity;
+
+        let distributions = [
+            Perez([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]),
+            Perez([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]),
+            Perez([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]),
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * sun_zenith);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = zenith_chromaticity(
+            t,
+            sun_zenith,
+            [
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+        );
+        let zenith_y = zenith_chromaticity(
+            t,
+            sun_zenith,
+            [
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+        );
+        let zenith = [zenith_luminance, zenith_x, zenith_y];
+        let zenith = [0, 1, 2].map(|i| zenith[i] / distributions[i].evaluate(0.0, sun_zenith));
+
+        PhysicalSky {
+            sun_direction,
+            turbidity,
+            sun_intensity: DEFAULT_SUN_INTENSITY,
+            distributions,
+            zenith,
+            sun_color: sun_transmittance(sun_zenith, turbidity),
+        }
+    }
+
+    /// Change how bright the sun's disk is. Use 0 to hide it.
+    pub fn with_sun_intensity(mut self, sun_intensity: f64) -> Self {
+        self.sun_intensity = sun_intensity;
+        self
+    }
+
+    pub fn sun_direction(&self) -> Vector {
+        self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+}
+
+impl BackgroundTrait for PhysicalSky {
+    fn color(&self, ray: &Ray) -> Vector {
+        let direction = ray.direction.normalize();
+        let cos_gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        if gamma < SUN_ANGULAR_RADIUS && direction.y > 0.0 {
+            return self.sun_intensity * self.sun_color;
+        }
+
+        // the model isn't defined below the horizon, so stretch the horizon
+        // down over the ground
+        let theta = direction.y.clamp(0.0, 1.0).acos().min(MAX_SUN_ZENITH);
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * self.distributions[i].evaluate(theta, gamma));
+        return LUMINANCE_SCALE * xyy_to_rgb(luminance, x, y);
+    }
+}
+
+/// The zenith chromaticity fit, as a cubic in the sun's zenith angle for each
+/// of turbidity squared, turbidity and 1
+fn zenith_chromaticity(turbidity: f64, sun_zenith: f64, coefficients: [[f64; 4]; 3]) -> f64 {
+    let angles = [sun_zenith.powi(3), sun_zenith.powi(2), sun_zenith, 1.0];
+    let cubic = |row: [f64; 4]| -> f64 { row.iter().zip(angles).map(|(c, a)| c * a).sum() };
+    return turbidity * turbidity * cubic(coefficients[0])
+        + turbidity * cubic(coefficients[1])
+        + cubic(coefficients[2]);
+}
+
+/// How much of the sun's light makes it through the atmosphere, for red,
+/// green and blue wavelengths (680, 550 and 440nm)
+fn sun_transmittance(sun_zenith: f64, turbidity: f64) -> Vector {
+    // relative optical mass of the air, from Kasten (1966)
+    let zenith_degrees = sun_zenith.to_degrees();
+    let optical_mass = 1.0 / (sun_zenith.cos() + 0.15 * (93.885 - zenith_degrees).powf(-1.253));
+    // Angstrom's turbidity formula for aerosols, with a wavelength exponent of 1.3
+    let beta = 0.04608 * turbidity - 0.04586;
+    let transmittance = |wavelength_um: f64| -> f64 {
+        let rayleigh = 0.008735 * wavelength_um.powf(-4.08);
+        let aerosol = beta * wavelength_um.powf(-1.3);
+        (-optical_mass * (rayleigh + aerosol)).exp()
+    };
+    return vec3(
+        transmittance(0.68),
+        transmittance(0.55),
+        transmittance(0.44),
+    );
+}
+
+/// Convert a luminance and chromaticity to linear sRGB
+fn xyy_to_rgb(luminance: f64, x: f64, y: f64) -> Vector {
+    if y <= 0.0 {
+        return vec3(0.0, 0.0, 0.0);
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    let rgb = vec3(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    );
+    // saturated colors near the sun can fall outside of sRGB
+    return vec3(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0));
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    fn look(direction: Vector) -> Ray {
+        Ray::new(point3(0.0, 0.0, 0.0), direction, 0.0)
+    }
+
+    #[test]
+    fn when_color_given_clear_midday_sky_returns_blue_zenith() {
+        let sky = PhysicalSky::new(vec3(0.0, 1.0, 1.0), 2.5);
+        let zenith = sky.color(&look(vec3(0.0, 1.0, 0.0)));
+        assert!(zenith.z > zenith.x, "Expected a blue sky, got {:?}", zenith);
+        assert!(zenith.y > 0.1 && zenith.y < 2.0);
+    }
+
+    #[test]
+    fn when_color_given_hazier_sky_returns_whiter_sky() {
+        let clear = PhysicalSky::new(vec3(0.0, 1.0, 1.0), 2.0);
+        let hazy = PhysicalSky::new(vec3(0.0, 1.0, 1.0), 8.0);
+        let saturation = |color: Vector| (color.z - color.x) / color.z;
+        let view = look(vec3(1.0, 0.5, 0.0));
+        assert!(saturation(hazy.color(&view)) < saturation(clear.color(&view)));
+    }
+
+    #[test]
+    fn when_color_given_sun_direction_returns_sun_disk() {
+        let sun_direction = vec3(0.3, 0.2, -1.0);
+        let sky = PhysicalSky::new(sun_direction, 3.0);
+        let sun = sky.color(&look(sun_direction));
+        let beside_sun = sky.color(&look(sun_direction + vec3(0.0, 0.05, 0.0)));
+        assert!(sun.magnitude() > 10.0 * beside_sun.magnitude());
+        // a low sun is reddened by passing through more air
+        assert!(sun.x > sun.z);
+        let hidden = PhysicalSky::new(sun_direction, 3.0).with_sun_intensity(0.0);
+        assert_eq!(hidden.color(&look(sun_direction)), vec3(0.0, 0.0, 0.0));
+    }
+}
@@ -1,5 +1,7 @@
 use std::mem::swap;
 
+use cgmath::EuclideanSpace;
+
 use super::{Point, Ray};
 
 #[derive(Clone, Debug)]
@@ -68,6 +70,20 @@ impl AxisAlignedBoundingBox {
             end_point: max,
         };
     }
+
+    /// The total area of the 6 faces of this AABB
+    ///
+    /// This is used by the surface area heuristic when building BVHs, as the
+    /// odds of a random ray hitting a box are proportional to its area.
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.end_point - self.start_point;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// The point in the middle of this AABB
+    pub fn centroid(&self) -> Point {
+        self.start_point.midpoint(self.end_point)
+    }
 }
 
 #[cfg(test)]
@@ -147,4 +163,16 @@ mod tests {
             "AABB did not end at the correct place"
         );
     }
+
+    #[test]
+    fn when_surface_area_given_unit_cube_returns_six() {
+        let aabb = AABB::new(ORIGIN, point3(1.0, 1.0, 1.0));
+        assert_eq!(aabb.surface_area(), 6.0);
+    }
+
+    #[test]
+    fn when_centroid_given_aabb_returns_midpoint() {
+        let aabb = AABB::new(ORIGIN, point3(2.0, 4.0, 6.0));
+        assert_eq!(aabb.centroid(), point3(1.0, 2.0, 3.0));
+    }
 }
@@ -0,0 +1,321 @@
+//! Motion blur for any object, by moving it between keyframes
+use std::sync::Arc;
+
+use cgmath::{
+    point3, vec3, ElementWise, InnerSpace, Matrix4, One, Quaternion, Rad, Rotation3, VectorSpace,
+};
+
+use crate::{sampler::Sampler, shader::Material};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    instance::AffineTransform,
+    ray::{Point, Ray, Vector},
+    sampling::{Sampleable, SurfaceSample},
+    sphere::Sphere,
+    Collision, Geometry, RayCollidable,
+};
+
+/// How many steps each span between keyframes is split into when finding
+/// bounds around everywhere an object goes during the shutter interval
+const BOUNDS_STEPS: usize = 8;
+
+/// Where an object is at a moment in time. It's scaled first, then rotated,
+/// then translated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vector,
+    pub rotation: Quaternion<f64>,
+    pub scale: Vector,
+}
+
+impl Keyframe {
+    /// A keyframe that leaves the object where it is
+    pub fn new(time: f64) -> Self {
+        Self {
+            time,
+            translation: vec3(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Vector) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    /// Turn the object around an axis through its origin. Between keyframes
+    /// it turns the shortest way round, so turns of half a circle or more
+    /// need keyframes in between.
+    pub fn with_rotation<A: Into<Rad<f64>>>(mut self, axis: Vector, angle: A) -> Self {
+        self.rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+        self
+    }
+
+    pub fn with_scale(self, factor: f64) -> Self {
+        self.with_non_uniform_scale(vec3(factor, factor, factor))
+    }
+
+    /// Stretch the object by a different amount along each axis
+    pub fn with_non_uniform_scale(mut self, factors: Vector) -> Self {
+        assert!(
+            factors.x != 0.0 && factors.y != 0.0 && factors.z != 0.0,
+            "A keyframe can't scale an object down to nothing"
+        );
+        self.scale = factors;
+        self
+    }
+
+    /// Blend towards another keyframe, moving in a straight line and turning
+    /// at a constant speed
+    fn lerp(&self, other: &Self, amount: f64) -> Self {
+        Self {
+            time: self.time + (other.time - self.time) * amount,
+            translation: self.translation.lerp(other.translation, amount),
+            rotation: self.rotation.slerp(other.rotation, amount),
+            scale: self.scale.lerp(other.scale, amount),
+        }
+    }
+
+    /// How far apart two keyframes' rotations are, as an angle
+    fn angle_to(&self, other: &Self) -> f64 {
+        let cosine = self.rotation.normalize().dot(other.rotation.normalize());
+        return 2.0 * cosine.abs().min(1.0).acos();
+    }
+
+    fn transform(&self) -> AffineTransform {
+        let rotation = self.rotation.normalize();
+        let transform = Matrix4::from_translation(self.translation)
+            * Matrix4::from(rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
+        // undoing each step in reverse is much cheaper than a general inverse
+        let inverse = Matrix4::from_nonuniform_scale(
+            1.0 / self.scale.x,
+            1.0 / self.scale.y,
+            1.0 / self.scale.z,
+        ) * Matrix4::from(rotation.conjugate())
+            * Matrix4::from_translation(-self.translation);
+        return AffineTransform::new_with_inverse(transform, inverse);
+    }
+}
+
+/// An object that moves, turns and changes size over time, blurring as it
+/// does while the shutter's open
+///
+/// Between keyframes it moves smoothly from one to the next, and before the
+/// first or after the last it stays put.
+pub struct AnimatedInstance {
+    pub object: Geometry,
+    /// In order of time, and never empty
+    keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedInstance {
+    pub fn new(object: Geometry, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "An animated object needs at least one keyframe"
+        );
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { object, keyframes }
+    }
+
+    /// A sphere whose center moves in a straight line from center_start at
+    /// time 0 to center_end at time 1
+    pub fn new_moving_sphere(
+        center_start: Point,
+        center_end: Point,
+        radius: f64,
+        material: Material,
+    ) -> Self {
+        let sphere = Sphere::new_with_material(point3(0.0, 0.0, 0.0), radius, material);
+        Self::new(
+            Arc::new(sphere).into(),
+            vec![
+                Keyframe::new(0.0).with_translation(center_start - point3(0.0, 0.0, 0.0)),
+                Keyframe::new(1.0).with_translation(center_end - point3(0.0, 0.0, 0.0)),
+            ],
+        )
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Where the object is at a moment in time
+    pub fn keyframe_at(&self, time: f64) -> Keyframe {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0].clone();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].clone();
+        }
+        let (previous, next) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let amount = (time - previous.time) / (next.time - previous.time);
+        return previous.lerp(next, amount);
+    }
+}
+
+impl RayCollidable for AnimatedInstance {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        self.keyframe_at(ray.time)
+            .transform()
+            .intersect(&self.object, ray, t_min, t_max)
+    }
+
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        self.keyframe_at(ray.time).transform().intersect_sampled(
+            &self.object,
+            ray,
+            t_min,
+            t_max,
+            sampler,
+        )
+    }
+
+    /// Bounds around everywhere the object goes between time_start and
+    /// time_end
+    ///
+    /// The object's bounds are placed at a number of steps through the
+    /// interval, including at every keyframe. Between steps, turning sweeps
+    /// the corners along arcs that bulge out past the boxes at either end, so
+    /// each box is padded by as far as an arc can bulge.
+    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        let bounds = self.object.get_bounds(time_start, time_end)?;
+        let extent = bounds
+            .start_point
+            .to_homogeneous()
+            .truncate()
+            .zip(bounds.end_point.to_homogeneous().truncate(), |a, b| {
+                a.abs().max(b.abs())
+            });
+        // the furthest any point in the bounds is from the object's origin
+        let radius = extent.magnitude();
+
+        let mut times = vec![time_start];
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|keyframe| keyframe.time)
+                .filter(|&time| time_start < time && time < time_end),
+        );
+        times.push(time_end);
+
+        let mut swept: Option<AxisAlignedBoundingBox> = Option::None;
+        for span in times.windows(2) {
+            let mut previous = self.keyframe_at(span[0]);
+            for step in 1..=BOUNDS_STEPS {
+                let time = span[0] + (span[1] - span[0]) * (step as f64 / BOUNDS_STEPS as f64);
+                let next = self.keyframe_at(time);
+                let angle = previous.angle_to(&next);
+                let largest_scale =
+                    |scale: Vector| scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+                let scale_change = largest_scale(next.scale - previous.scale);
+                let scale = largest_scale(previous.scale).max(largest_scale(next.scale));
+                // a turning corner strays from the straight line between its
+                // ends by at most r(1 - cos(angle / 2)), and growing while it
+                // turns can push it out a little further
+                let padding = radius * (scale * (1.0 - (angle / 2.0).cos()) + scale_change * angle);
+                let step_bounds = previous
+                    .transform()
+                    .bounds(&bounds)
+                    .bounding_box(&next.transform().bounds(&bounds));
+                let step_bounds = AxisAlignedBoundingBox::new(
+                    step_bounds.start_point.sub_element_wise(padding),
+                    step_bounds.end_point.add_element_wise(padding),
+                );
+                swept = Option::Some(match swept {
+                    Option::Some(swept) => swept.bounding_box(&step_bounds),
+                    Option::None => step_bounds,
+                });
+                previous = next;
+            }
+        }
+        return swept;
+    }
+}
+
+impl Sampleable for AnimatedInstance {
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        self.keyframe_at(time)
+            .transform()
+            .sample(&self.object, origin, time, sampler)
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.keyframe_at(ray.time)
+            .transform()
+            .pdf(&self.object, ray, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Deg;
+
+    use crate::geometry::cuboid::Cuboid;
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_moving_sphere_follows_its_center() {
+        let material = Arc::new(crate::shader::Lambertian::new(vec3(0.5, 0.5, 0.5))).into();
+        let sphere = AnimatedInstance::new_moving_sphere(
+            point3(0.0, 0.0, -5.0),
+            point3(0.0, 2.0, -5.0),
+            0.5,
+            material,
+        );
+        for (time, y) in [(0.0, 0.0), (0.5, 1.0), (1.0, 2.0), (2.0, 2.0)] {
+            let ray = Ray::new(point3(0.0, y, 0.0), vec3(0.0, 0.0, -1.0), time);
+            let collision = sphere.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+            assert!((collision.t - 4.5).abs() < 1e-9);
+            assert!((collision.normal - vec3(0.0, 0.0, 1.0)).magnitude() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn when_get_bounds_given_turning_box_holds_box_at_every_time() {
+        let cuboid: Geometry =
+            Arc::new(Cuboid::new(point3(1.0, -0.5, -0.5), point3(3.0, 0.5, 0.5))).into();
+        let animated = AnimatedInstance::new(
+            cuboid.clone(),
+            vec![
+                Keyframe::new(0.0),
+                Keyframe::new(1.0)
+                    .with_rotation(vec3(0.0, 1.0, 0.0), Deg(170.0))
+                    .with_scale(1.5)
+                    .with_translation(vec3(0.0, 1.0, 0.0)),
+            ],
+        );
+        let swept = animated.get_bounds(0.0, 1.0).unwrap();
+        for idx in 0..=1000 {
+            let time = idx as f64 / 1000.0;
+            let bounds = animated
+                .keyframe_at(time)
+                .transform()
+                .bounds(&cuboid.get_bounds(0.0, 1.0).unwrap());
+            for axis in 0..3 {
+                assert!(bounds.start_point[axis] >= swept.start_point[axis]);
+                assert!(bounds.end_point[axis] <= swept.end_point[axis]);
+            }
+        }
+        // and they aren't much bigger than they need to be
+        assert!(swept.end_point.x < 4.6);
+    }
+}
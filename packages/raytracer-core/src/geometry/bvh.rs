@@ -0,0 +1,549 @@
+//! A bounding volume hierarchy for accelerating ray queries
+//!
+//! The tree is built top-down, choosing splits with a binned surface area
+//! heuristic (SAH). Nodes are stored flat in depth-first order, so the left
+//! child of a branch is always the node right after it and only the right
+//! child needs an explicit index.
+//!
+//! Objects that can't be bounded (that is, `get_bounds` returns None) are kept
+//! off to the side and tested against every ray.
+
+use std::mem;
+
+use crate::sampler::Sampler;
+
+use super::{
+    aabb::{AxisAlignedBoundingBox, AABB},
+    Collision, Geometry, Point, Ray, RayCollidable,
+};
+
+/// The number of buckets centroids are sorted into when evaluating splits
+const SAH_BIN_COUNT: usize = 16;
+/// The cost of visiting a node, relative to the cost of testing a primitive
+const SAH_TRAVERSAL_COST: f64 = 0.125;
+/// Leaves larger than this are always split, regardless of the SAH
+const MAX_PRIMITIVES_IN_LEAF: usize = 8;
+/// How deep the tree is allowed to get. This also sizes the traversal stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug)]
+enum BVHNode {
+    Branch {
+        bounds: AABB,
+        /// The index of the right child. The left child is always next to this node.
+        right: usize,
+        /// The axis this node was split along, used to visit the nearest child first
+        axis: usize,
+    },
+    Leaf {
+        bounds: AABB,
+        /// Index of the first primitive in this leaf
+        start: usize,
+        /// How many primitives this leaf holds
+        count: usize,
+    },
+}
+
+impl BVHNode {
+    fn bounds(&self) -> &AABB {
+        match self {
+            Self::Branch { bounds, .. } => bounds,
+            Self::Leaf { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Scratch data for a primitive while the tree is being built
+struct BuildItem {
+    bounds: AABB,
+    centroid: Point,
+    /// The index of this item in the list handed to `BVH::new`
+    index: usize,
+}
+
+/// A tree of bounding boxes over a set of primitives, for testing rays against
+/// large numbers of objects in logarithmic time.
+#[derive(Clone)]
+pub struct BoundingVolumeHierarchy<T: RayCollidable = Geometry> {
+    nodes: Vec<BVHNode>,
+    /// The bounded primitives, reordered so that each leaf refers to a contiguous range
+    primitives: Vec<T>,
+    /// Primitives without a bounding box, which are tested linearly
+    unbounded: Vec<T>,
+}
+
+pub type BVH<T = Geometry> = BoundingVolumeHierarchy<T>;
+
+impl<T: RayCollidable> BoundingVolumeHierarchy<T> {
+    /// Build a BVH over the given primitives
+    ///
+    /// time_start and time_end are the shutter interval the tree will be
+    /// queried across. Rays with a time outside this interval may miss moving
+    /// objects.
+    pub fn new(primitives: Vec<T>, time_start: f64, time_end: f64) -> Self {
+        let mut items = Vec::with_capacity(primitives.len());
+        let mut bounded = Vec::with_capacity(primitives.len());
+        let mut unbounded = vec![];
+
+        for primitive in primitives {
+            match primitive.get_bounds(time_start, time_end) {
+                Option::Some(bounds) => {
+                    items.push(BuildItem {
+                        centroid: bounds.centroid(),
+                        bounds,
+                        index: bounded.len(),
+                    });
+                    bounded.push(Option::Some(primitive));
+                }
+                Option::None => unbounded.push(primitive),
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(items.len() * 2);
+        if !items.is_empty() {
+            Self::build_recursive(&mut nodes, &mut items, 0, 0);
+        }
+
+        // the build shuffles items around, so put the primitives in the same order
+        let primitives = items
+            .iter()
+            .map(|item| {
+                bounded[item.index]
+                    .take()
+                    .expect("BVH build referenced a primitive twice")
+            })
+            .collect();
+
+        Self {
+            nodes,
+            primitives,
+            unbounded,
+        }
+    }
+
+    /// How many nodes are in this tree, including leaves
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The box around every primitive that has one, leaving out infinite
+    /// ones like planes
+    pub fn bounded_bounds(&self) -> Option<&AABB> {
+        self.nodes.first().map(BVHNode::bounds)
+    }
+
+    fn build_recursive(
+        nodes: &mut Vec<BVHNode>,
+        items: &mut [BuildItem],
+        offset: usize,
+        depth: usize,
+    ) -> usize {
+        let bounds = items[1..]
+            .iter()
+            .fold(items[0].bounds.clone(), |acc, item| {
+                acc.bounding_box(&item.bounds)
+            });
+        let node_idx = nodes.len();
+
+        let leaf = BVHNode::Leaf {
+            bounds: bounds.clone(),
+            start: offset,
+            count: items.len(),
+        };
+
+        if items.len() == 1 || depth >= MAX_DEPTH - 1 {
+            nodes.push(leaf);
+            return node_idx;
+        }
+
+        let split = match Self::find_split(items, &bounds) {
+            Option::Some((axis, split_bin, cost)) => {
+                let leaf_cost = items.len() as f64;
+                if cost >= leaf_cost && items.len() <= MAX_PRIMITIVES_IN_LEAF {
+                    Option::None
+                } else {
+                    Some((axis, Self::partition(items, axis, split_bin)))
+                }
+            }
+            // every centroid is in the same spot, so no plane can separate them
+            Option::None => Option::None,
+        };
+
+        let (axis, mid) = match split {
+            Option::Some((axis, mid)) if mid != 0 && mid != items.len() => (axis, mid),
+            Option::Some(_) | Option::None if items.len() <= MAX_PRIMITIVES_IN_LEAF => {
+                nodes.push(leaf);
+                return node_idx;
+            }
+            // too many to stuff in a leaf, so fall back to splitting by count
+            _ => {
+                let axis = Self::largest_axis(items);
+                items.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+                (axis, items.len() / 2)
+            }
+        };
+
+        // reserve a slot for this node, the right index gets filled in below
+        nodes.push(BVHNode::Branch {
+            bounds,
+            right: 0,
+            axis,
+        });
+        let (left_items, right_items) = items.split_at_mut(mid);
+        Self::build_recursive(nodes, left_items, offset, depth + 1);
+        let right_idx = Self::build_recursive(nodes, right_items, offset + mid, depth + 1);
+        if let BVHNode::Branch { right, .. } = &mut nodes[node_idx] {
+            *right = right_idx;
+        }
+        node_idx
+    }
+
+    /// Find the cheapest split according to the SAH, as an (axis, bin, cost) triple.
+    ///
+    /// Items whose centroid lands in a bin <= the returned bin go to the left.
+    fn find_split(items: &[BuildItem], bounds: &AABB) -> Option<(usize, usize, f64)> {
+        let centroid_bounds = Self::centroid_bounds(items);
+        let parent_area = bounds.surface_area();
+        let mut best: Option<(usize, usize, f64)> = Option::None;
+
+        for axis in 0..3 {
+            let min = centroid_bounds.start_point[axis];
+            let extent = centroid_bounds.end_point[axis] - min;
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let mut bin_counts = [0usize; SAH_BIN_COUNT];
+            let mut bin_bounds: [Option<AABB>; SAH_BIN_COUNT] = Default::default();
+            for item in items {
+                let bin = bin_for(item.centroid[axis], min, extent);
+                bin_counts[bin] += 1;
+                bin_bounds[bin] = Option::Some(match &bin_bounds[bin] {
+                    Option::Some(aabb) => aabb.bounding_box(&item.bounds),
+                    Option::None => item.bounds.clone(),
+                });
+            }
+
+            // sweep from the right to get the area and count for every right half
+            let mut right_areas = [0.0; SAH_BIN_COUNT];
+            let mut right_counts = [0usize; SAH_BIN_COUNT];
+            let mut accumulated: Option<AABB> = Option::None;
+            let mut count = 0;
+            for bin in (1..SAH_BIN_COUNT).rev() {
+                accumulated = merge(accumulated, &bin_bounds[bin]);
+                count += bin_counts[bin];
+                right_counts[bin] = count;
+                right_areas[bin] = accumulated.as_ref().map_or(0.0, |b| b.surface_area());
+            }
+
+            // then sweep from the left, evaluating each split as we go
+            let mut accumulated: Option<AABB> = Option::None;
+            let mut count = 0;
+            for bin in 0..(SAH_BIN_COUNT - 1) {
+                accumulated = merge(accumulated, &bin_bounds[bin]);
+                count += bin_counts[bin];
+                let right_count = right_counts[bin + 1];
+                if count == 0 || right_count == 0 {
+                    continue;
+                }
+                let left_area = accumulated.as_ref().map_or(0.0, |b| b.surface_area());
+                let cost = SAH_TRAVERSAL_COST
+                    + (left_area * count as f64 + right_areas[bin + 1] * right_count as f64)
+                        / parent_area;
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, _, best_cost)| cost < *best_cost)
+                {
+                    best = Option::Some((axis, bin, cost));
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Reorder items so that everything left of the split bin comes first,
+    /// returning the index of the first item on the right.
+    fn partition(items: &mut [BuildItem], axis: usize, split_bin: usize) -> usize {
+        let centroid_bounds = Self::centroid_bounds(items);
+        let min = centroid_bounds.start_point[axis];
+        let extent = centroid_bounds.end_point[axis] - min;
+
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if bin_for(items[i].centroid[axis], min, extent) <= split_bin {
+                items.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+
+    fn centroid_bounds(items: &[BuildItem]) -> AABB {
+        let first = AABB::new(items[0].centroid, items[0].centroid);
+        items[1..].iter().fold(first, |acc, item| {
+            acc.bounding_box(&AABB::new(item.centroid, item.centroid))
+        })
+    }
+
+    fn largest_axis(items: &[BuildItem]) -> usize {
+        let centroid_bounds = Self::centroid_bounds(items);
+        let extent = centroid_bounds.end_point - centroid_bounds.start_point;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+#[inline(always)]
+fn bin_for(value: f64, min: f64, extent: f64) -> usize {
+    let bin = ((value - min) / extent * SAH_BIN_COUNT as f64) as usize;
+    usize::min(bin, SAH_BIN_COUNT - 1)
+}
+
+/// Pair a collision with its distance along the ray, for `closest_hit`
+#[inline(always)]
+fn with_t(collision: Option<Collision>) -> Option<(f64, Collision)> {
+    collision.map(|collision| (collision.t, collision))
+}
+
+#[inline(always)]
+fn merge(accumulated: Option<AABB>, other: &Option<AABB>) -> Option<AABB> {
+    match (accumulated, other) {
+        (Option::Some(a), Option::Some(b)) => Option::Some(a.bounding_box(b)),
+        (Option::Some(a), Option::None) => Option::Some(a),
+        (Option::None, b) => b.clone(),
+    }
+}
+
+impl<T: RayCollidable> BoundingVolumeHierarchy<T> {
+    /// Find the closest hit on any of the objects, testing each one the ray
+    /// might hit with `intersect`, which is given the object and the closest
+    /// hit so far, and returns how far along the ray it hit along with the hit
+    pub(super) fn closest_hit<H, F: FnMut(&T, f64) -> Option<(f64, H)>>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut intersect: F,
+    ) -> Option<H> {
+        let mut collision: Option<H> = Option::None;
+        let mut closest_hit = t_max;
+
+        for object in &self.unbounded {
+            if let Option::Some((t, i_collision)) = intersect(object, closest_hit) {
+                closest_hit = t;
+                collision = Option::Some(i_collision);
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return collision;
+        }
+
+        let mut stack = [0usize; MAX_DEPTH + 1];
+        let mut stack_size = 1;
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_idx = stack[stack_size];
+            let node = &self.nodes[node_idx];
+            if node
+                .bounds()
+                .will_intersect_aabb(ray, t_min, closest_hit)
+                .is_none()
+            {
+                continue;
+            }
+            match node {
+                BVHNode::Leaf { start, count, .. } => {
+                    for object in &self.primitives[*start..(*start + *count)] {
+                        if let Option::Some((t, i_collision)) = intersect(object, closest_hit) {
+                            closest_hit = t;
+                            collision = Option::Some(i_collision);
+                        }
+                    }
+                }
+                BVHNode::Branch { right, axis, .. } => {
+                    let (mut near, mut far) = (node_idx + 1, *right);
+                    if ray.direction[*axis] < 0.0 {
+                        mem::swap(&mut near, &mut far);
+                    }
+                    // push the far child first so the near one is visited first
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+            }
+        }
+
+        collision
+    }
+}
+
+impl<T: RayCollidable> RayCollidable for BoundingVolumeHierarchy<T> {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        self.closest_hit(ray, t_min, t_max, |object, t_max| {
+            with_t(object.will_intersect(ray, t_min, t_max))
+        })
+    }
+
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        self.closest_hit(ray, t_min, t_max, |object, t_max| {
+            with_t(object.will_intersect_sampled(ray, t_min, t_max, sampler))
+        })
+    }
+
+    fn get_bounds(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        if !self.unbounded.is_empty() {
+            return Option::None;
+        }
+        return self.bounded_bounds().cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{point3, vec3, InnerSpace};
+
+    use crate::{
+        geometry::{animated::AnimatedInstance, sphere::Sphere, Vector},
+        shader::Lambertian,
+    };
+
+    use super::*;
+
+    fn make_spheres(rng: &fastrand::Rng, count: usize) -> Vec<Geometry> {
+        let mut objects: Vec<Geometry> = vec![];
+        for i in 0..count {
+            let center = point3(
+                rng.f64() * 20.0 - 10.0,
+                rng.f64() * 20.0 - 10.0,
+                rng.f64() * 20.0 - 10.0,
+            );
+            let radius = rng.f64() * 0.5 + 0.05;
+            if i % 3 == 0 {
+                let center_end = center + vec3(0.0, rng.f64(), 0.0);
+                let material = Arc::new(Lambertian::new(vec3(1.0, 0.0, 0.0))).into();
+                let sphere =
+                    AnimatedInstance::new_moving_sphere(center, center_end, radius, material);
+                objects.push(Arc::new(sphere).into());
+            } else {
+                objects.push(Arc::new(Sphere::new(center, radius)).into());
+            }
+        }
+        objects
+    }
+
+    fn linear_scan(objects: &[Geometry], ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        let mut collision: Option<Collision> = None;
+        let mut closest_hit = t_max;
+        for object in objects {
+            if let Some(i_collision) = object.will_intersect(ray, t_min, closest_hit) {
+                closest_hit = i_collision.t;
+                collision = Some(i_collision);
+            }
+        }
+        collision
+    }
+
+    fn random_direction(rng: &fastrand::Rng) -> Vector {
+        vec3(rng.f64() - 0.5, rng.f64() - 0.5, rng.f64() - 0.5).normalize()
+    }
+
+    #[test]
+    fn when_will_intersect_given_random_rays_returns_same_collision_as_linear_scan() {
+        let rng = fastrand::Rng::with_seed(0xB0B);
+        let objects = make_spheres(&rng, 2000);
+        let bvh = BVH::new(objects.clone(), 0.0, 1.0);
+
+        let mut hits = 0;
+        for _ in 0..5000 {
+            let origin = point3(
+                rng.f64() * 30.0 - 15.0,
+                rng.f64() * 30.0 - 15.0,
+                rng.f64() * 30.0 - 15.0,
+            );
+            let ray = Ray::new(origin, random_direction(&rng), rng.f64());
+            let expected = linear_scan(&objects, &ray, 0.001, f64::INFINITY);
+            let actual = bvh.will_intersect(&ray, 0.001, f64::INFINITY);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    assert_eq!(expected.t, actual.t, "BVH found a different nearest hit");
+                    assert_eq!(expected.point, actual.point);
+                    assert_eq!(expected.normal, actual.normal);
+                }
+                (expected, actual) => panic!(
+                    "BVH and linear scan disagree on whether the ray hit: expected {:?}, got {:?}",
+                    expected.map(|c| c.t),
+                    actual.map(|c| c.t)
+                ),
+            }
+        }
+        assert!(hits > 0, "Test rays never hit anything");
+    }
+
+    #[test]
+    fn when_will_intersect_given_limited_interval_returns_same_collision_as_linear_scan() {
+        let rng = fastrand::Rng::with_seed(42);
+        let objects = make_spheres(&rng, 300);
+        let bvh = BVH::new(objects.clone(), 0.0, 1.0);
+
+        for _ in 0..2000 {
+            let ray = Ray::new(point3(0.0, 0.0, 0.0), random_direction(&rng), rng.f64());
+            let t_max = rng.f64() * 10.0;
+            let expected = linear_scan(&objects, &ray, 0.001, t_max).map(|c| c.t);
+            let actual = bvh.will_intersect(&ray, 0.001, t_max).map(|c| c.t);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn when_get_bounds_given_moving_spheres_returns_swept_bounds() {
+        let material = Arc::new(Lambertian::new(vec3(1.0, 0.0, 0.0))).into();
+        let sphere = AnimatedInstance::new_moving_sphere(
+            point3(0.0, 0.0, 0.0),
+            point3(0.0, 2.0, 0.0),
+            1.0,
+            material,
+        );
+        let bvh = BVH::new(vec![Geometry::from(Arc::new(sphere))], 0.0, 1.0);
+        let bounds = bvh.get_bounds(0.0, 1.0).unwrap();
+        assert_eq!(bounds.start_point, point3(-1.0, -1.0, -1.0));
+        assert_eq!(bounds.end_point, point3(1.0, 3.0, 1.0));
+    }
+
+    #[test]
+    fn when_will_intersect_given_empty_bvh_returns_none() {
+        let bvh: BVH = BVH::new(vec![], 0.0, 1.0);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(bvh.will_intersect(&ray, 0.001, f64::INFINITY).is_none());
+        assert!(bvh.get_bounds(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn when_new_given_coincident_objects_builds_leaves() {
+        let objects: Vec<Geometry> = (0..100)
+            .map(|_| Arc::new(Sphere::new(point3(1.0, 1.0, 1.0), 0.5)).into())
+            .collect();
+        let bvh = BVH::new(objects, 0.0, 1.0);
+        assert!(bvh.node_count() > 1);
+        let ray = Ray::new(point3(1.0, 1.0, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = bvh.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(collision.t, 3.5);
+    }
+}
@@ -0,0 +1,256 @@
+//! Volumes of smoke, fog or anything else that light scatters through
+//!
+//! Rather than bouncing off of a surface, light in a volume travels a random
+//! distance before it scatters. In a volume with the same density throughout,
+//! the odds of getting through a stretch of it fall off exponentially with
+//! its length, so the distance can be picked exactly. When it's less than
+//! the distance to the far side, the ray scatters there by the volume's phase
+//! function, which is a material like any other.
+use std::sync::Arc;
+
+use cgmath::{vec2, vec3, InnerSpace};
+
+use crate::{
+    sampler::{hash_ray, Sampler, SamplerTrait},
+    shader::{Isotropic, Material},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    ray::{Point, Ray},
+    sampling::{Sampleable, SurfaceSample},
+    Collision, Geometry, RayCollidable,
+};
+
+/// How far past where a ray enters the boundary to look for where it leaves,
+/// so that it doesn't find the entrance again
+const BOUNDARY_EPSILON: f64 = 1e-4;
+
+/// A volume with the same density throughout, filling a closed shape
+///
+/// The boundary is only used to find where rays enter and leave, so its
+/// material doesn't matter. Shapes that rays can enter more than once, like a
+/// torus, are only filled between the first two crossings.
+pub struct ConstantMedium {
+    pub boundary: Geometry,
+    /// The odds of scattering per unit of distance
+    pub density: f64,
+    pub phase_function: Material,
+}
+
+impl RayCollidable for ConstantMedium {
+    /// There's no sampler to pick the distance with here, so it's picked by
+    /// hashing the ray instead, which gives the same answer for the same ray
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        self.scatter(ray, t_min, t_max, || hash_ray(ray))
+    }
+
+    /// The distance the ray travels before it scatters is picked by the
+    /// path's sampler, so that renders only change with the seed
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        self.scatter(ray, t_min, t_max, || sampler.next_1d())
+    }
+
+    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        self.boundary.get_bounds(time_start, time_end)
+    }
+}
+
+impl ConstantMedium {
+    /// Where the ray scatters inside the boundary between t_min and t_max, if
+    /// it does at all, picked by `u` in [0, 1), which is only asked for once
+    /// the ray is known to pass through the boundary
+    fn scatter<U: FnOnce() -> f64>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        u: U,
+    ) -> Option<Collision> {
+        // look behind the ray as well, in case it starts inside
+        let entry = self
+            .boundary
+            .will_intersect(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self
+            .boundary
+            .will_intersect(ray, entry.t + BOUNDARY_EPSILON, f64::INFINITY)?;
+        return scatter_between(
+            ray,
+            self.density,
+            &self.phase_function,
+            entry.t.max(t_min),
+            exit.t.min(t_max),
+            u(),
+        );
+    }
+}
+
+/// Light is only sampled from surfaces, so volumes are never lights
+impl Sampleable for ConstantMedium {
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    fn sample(&self, _origin: &Point, _time: f64, _sampler: &mut Sampler) -> Option<SurfaceSample> {
+        return Option::None;
+    }
+
+    fn pdf(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> f64 {
+        return 0.0;
+    }
+}
+
+impl ConstantMedium {
+    /// Create a volume that scatters light evenly in every direction
+    pub fn new(boundary: Geometry, density: f64) -> Self {
+        let material = Isotropic::new(vec3(1.0, 1.0, 1.0));
+        Self::new_with_material(boundary, density, Arc::new(material).into())
+    }
+
+    pub fn new_with_material(boundary: Geometry, density: f64, phase_function: Material) -> Self {
+        Self {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+/// A thin haze filling the whole scene, scattering light on its way between
+/// objects
+///
+/// Rays that leave the scene leave the fog too, at the edge of the box around
+/// all of the objects, so the background is seen dimmed rather than blocked
+/// out completely.
+#[derive(Clone)]
+pub struct Fog {
+    /// The odds of scattering per unit of distance
+    pub density: f64,
+    pub phase_function: Material,
+}
+
+impl Fog {
+    pub fn new(density: f64, phase_function: Material) -> Self {
+        Self {
+            density,
+            phase_function,
+        }
+    }
+
+    /// Where the ray scatters between t_min and t_max, if it does at all,
+    /// picked by `u` in [0, 1)
+    pub fn will_scatter(&self, ray: &Ray, t_min: f64, t_max: f64, u: f64) -> Option<Collision> {
+        scatter_between(ray, self.density, &self.phase_function, t_min, t_max, u)
+    }
+
+    /// How much light makes it through the fog between t_min and t_max
+    /// without scattering
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if t_max <= t_min {
+            return 1.0;
+        }
+        return (-self.density * (t_max - t_min) * ray.direction.magnitude()).exp();
+    }
+}
+
+/// Pick where a ray scatters in a uniform volume it's inside of from t_enter
+/// to t_exit, if it gets that far, by `u` in [0, 1)
+fn scatter_between(
+    ray: &Ray,
+    density: f64,
+    phase_function: &Material,
+    t_enter: f64,
+    t_exit: f64,
+    u: f64,
+) -> Option<Collision> {
+    if t_exit <= t_enter {
+        return Option::None;
+    }
+    // t is measured in lengths of the ray's direction, which might not be 1
+    let ray_length = ray.direction.magnitude();
+    let distance_inside = (t_exit - t_enter) * ray_length;
+    let distance = -(1.0 - u).ln() / density;
+    if distance >= distance_inside {
+        return Option::None;
+    }
+    let t = t_enter + distance / ray_length;
+    return Option::Some(Collision {
+        point: ray.point_at(t),
+        // there's no surface, so this just faces back along the ray
+        normal: -ray.direction / ray_length,
+        t,
+        uv: vec2(0.0, 0.0),
+        uv_footprint: 0.0,
+        material: phase_function.clone(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use crate::{geometry::sphere::Sphere, sampler::SamplerKind};
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_rays_through_medium_passes_expected_fraction() {
+        let boundary: Geometry = Arc::new(Sphere::new(point3(0.0, 0.0, -5.0), 1.0)).into();
+        let medium = ConstantMedium::new(boundary, 0.5);
+        // a diameter of 2 at a density of 0.5 lets e^-1 of the rays through,
+        // and it shouldn't matter whether the ray starts inside
+        for (origin, t_inside) in [(point3(0.0, 0.0, 0.0), 4.0), (point3(0.0, 0.0, -4.5), 0.0)] {
+            let count = 40_000;
+            let mut passed = 0;
+            let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+            let ray = Ray::new(origin, vec3(0.0, 0.0, -2.0), 0.0);
+            for _ in 0..count {
+                match medium.will_intersect_sampled(&ray, 0.0, f64::INFINITY, &mut sampler) {
+                    Option::Some(collision) => {
+                        assert!(collision.t >= t_inside / 2.0 && collision.t <= 3.0);
+                    }
+                    Option::None => passed += 1,
+                }
+            }
+            let expected = if t_inside > 0.0 {
+                (-1.0f64).exp()
+            } else {
+                (-0.75f64).exp()
+            };
+            assert!((passed as f64 / count as f64 - expected).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn when_will_intersect_sampled_given_seed_scatters_where_the_seed_says() {
+        let boundary: Geometry = Arc::new(Sphere::new(point3(0.0, 0.0, -5.0), 1.0)).into();
+        let medium = ConstantMedium::new(boundary, 100.0);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let scatter_at = |seed| {
+            let mut sampler = Sampler::new(SamplerKind::Independent, 1, seed);
+            medium
+                .will_intersect_sampled(&ray, 0.0, f64::INFINITY, &mut sampler)
+                .unwrap()
+                .t
+        };
+        assert_eq!(scatter_at(1), scatter_at(1));
+        assert_ne!(scatter_at(1), scatter_at(2));
+    }
+
+    #[test]
+    fn when_will_intersect_given_no_sampler_still_scatters_in_dense_medium() {
+        let boundary: Geometry = Arc::new(Sphere::new(point3(0.0, 0.0, -5.0), 1.0)).into();
+        let medium = ConstantMedium::new(boundary, 100.0);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let first = medium.will_intersect(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(first.t >= 4.0 && first.t <= 6.0);
+        let second = medium.will_intersect(&ray, 0.0, f64::INFINITY).unwrap();
+        assert_eq!(first.t, second.t);
+    }
+}
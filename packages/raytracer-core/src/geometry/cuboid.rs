@@ -0,0 +1,159 @@
+//! Axis-aligned boxes, made of six quads
+use std::sync::Arc;
+
+use cgmath::vec3;
+
+use crate::{
+    sampler::{Sampler, SamplerTrait},
+    shader::{Lambertian, Material, MaterialTrait},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    quad::Quad,
+    ray::{Point, Ray},
+    sampling::{area_to_solid_angle, Sampleable, SurfaceSample},
+    Collision, RayCollidable,
+};
+
+/// A box between two opposite corners, with its sides facing outwards
+///
+/// Each side has its own texture coordinates running from 0 to 1.
+pub struct Cuboid {
+    /// The corner with the smallest coordinates
+    pub min: Point,
+    /// The corner with the largest coordinates
+    pub max: Point,
+    pub material: Material,
+    sides: [Quad; 6],
+}
+
+impl RayCollidable for Cuboid {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        let mut nearest = Option::None;
+        let mut t_max = t_max;
+        for side in &self.sides {
+            if let Option::Some(collision) = side.will_intersect(ray, t_min, t_max) {
+                t_max = collision.t;
+                nearest = Option::Some(collision);
+            }
+        }
+        return nearest;
+    }
+
+    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        // the sides' bounds are padded, in case the box is flat
+        return self.sides[1..]
+            .iter()
+            .filter_map(|side| side.get_bounds(time_start, time_end))
+            .try_fold(
+                self.sides[0].get_bounds(time_start, time_end)?,
+                |acc, bounds| Option::Some(acc.bounding_box(&bounds)),
+            );
+    }
+}
+
+impl Sampleable for Cuboid {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Pick a point evenly over the whole surface. Points on the far sides
+    /// are blocked by the near ones, so those samples are wasted.
+    fn sample(&self, origin: &Point, _time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        let mut target = sampler.next_1d() * self.area();
+        let side = self
+            .sides
+            .iter()
+            .find(|side| {
+                target -= side.area();
+                target < 0.0
+            })
+            .unwrap_or(&self.sides[5]);
+        let [u, v] = sampler.next_2d();
+        let point = side.point_at(u, v);
+        area_to_solid_angle(origin, point, side.normal(), 1.0 / self.area())
+    }
+
+    /// Only the nearest side the ray hits counts, as that's the only point a
+    /// sample towards it would reach
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        return match self.will_intersect(ray, t_min, t_max) {
+            Option::Some(collision) => area_to_solid_angle(
+                &ray.origin,
+                collision.point,
+                collision.normal,
+                1.0 / self.area(),
+            )
+            .map_or(0.0, |sample| sample.pdf),
+            Option::None => 0.0,
+        };
+    }
+}
+
+impl Cuboid {
+    pub fn new(min: Point, max: Point) -> Self {
+        let material = Lambertian::new(vec3(1.0, 0.0, 0.0));
+        Self::new_with_material(min, max, Arc::new(material).into())
+    }
+
+    /// Create a box between two corners, which can be given in any order
+    pub fn new_with_material(a: Point, b: Point, material: Material) -> Self {
+        let min = a.zip(b, f64::min);
+        let max = a.zip(b, f64::max);
+        let dx = vec3(max.x - min.x, 0.0, 0.0);
+        let dy = vec3(0.0, max.y - min.y, 0.0);
+        let dz = vec3(0.0, 0.0, max.z - min.z);
+        let side = |corner: Point, edge_u, edge_v| {
+            Quad::new_with_material(corner, edge_u, edge_v, material.clone())
+        };
+        // each side's edges are ordered so that their cross product points out
+        let sides = [
+            side(Point::new(min.x, min.y, max.z), dx, dy),
+            side(Point::new(max.x, min.y, max.z), -dz, dy),
+            side(Point::new(max.x, min.y, min.z), -dx, dy),
+            side(Point::new(min.x, min.y, min.z), dz, dy),
+            side(Point::new(min.x, max.y, max.z), dx, -dz),
+            side(Point::new(min.x, min.y, min.z), dx, dz),
+        ];
+        Self {
+            min,
+            max,
+            material,
+            sides,
+        }
+    }
+
+    /// The area of all six sides
+    #[inline(always)]
+    fn area(&self) -> f64 {
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, EuclideanSpace};
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_rays_from_each_axis_returns_outward_normals() {
+        let cuboid = Cuboid::new(point3(-1.0, -2.0, -3.0), point3(1.0, 2.0, 3.0));
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut direction = vec3(0.0, 0.0, 0.0);
+                direction[axis] = -sign;
+                let origin = Point::from_vec(direction * -10.0);
+                let ray = Ray::new(origin, direction, 0.0);
+                let collision = cuboid.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+                assert_eq!(collision.normal, -direction);
+                assert!((collision.point[axis] - cuboid.max[axis] * sign).abs() < 1e-12);
+                assert!(collision.uv.x >= 0.0 && collision.uv.x <= 1.0);
+                assert!(collision.uv.y >= 0.0 && collision.uv.y <= 1.0);
+            }
+        }
+        assert!((cuboid.area() - cuboid.sides.iter().map(Quad::area).sum::<f64>()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,159 @@
+//! Flat circles, for lamp shades, table tops and round lights
+use std::{f64::consts::PI, sync::Arc};
+
+use cgmath::{vec2, vec3, ElementWise, InnerSpace};
+
+use crate::{
+    sampler::{Sampler, SamplerTrait},
+    shader::{Lambertian, Material, MaterialTrait},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    ray::{Point, Ray, Vector},
+    sampling::{area_to_solid_angle, orthonormal_basis, Sampleable, SurfaceSample},
+    triangle::BOUNDS_PADDING,
+    Collision, RayCollidable,
+};
+
+/// A filled circle facing along its normal
+///
+/// u goes around the disk and v goes from its center out to its edge, so
+/// textures are mapped like a dartboard.
+pub struct Disk {
+    pub center: Point,
+    /// A unit vector, pointing out of the front of the disk
+    pub normal: Vector,
+    pub radius: f64,
+    pub material: Material,
+    /// Unit vectors in the plane of the disk, that angles are measured from
+    tangent: Vector,
+    bitangent: Vector,
+}
+
+impl RayCollidable for Disk {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1e-12 {
+            return Option::None;
+        }
+        let t = self.normal.dot(self.center - ray.origin) / denominator;
+        if t < t_min || t_max < t {
+            return Option::None;
+        }
+        let point = ray.point_at(t);
+        let offset = point - self.center;
+        let distance_squared = offset.magnitude2();
+        if distance_squared > self.radius * self.radius {
+            return Option::None;
+        }
+        let phi = offset.dot(self.bitangent).atan2(offset.dot(self.tangent)) + PI;
+        return Option::Some(Collision {
+            point,
+            normal: self.normal,
+            t,
+            uv: vec2(phi / (2.0 * PI), distance_squared.sqrt() / self.radius),
+            uv_footprint: ray.footprint_at(t) / self.radius,
+            material: self.material.clone(),
+        });
+    }
+
+    fn get_bounds(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        // how far the rim reaches along each axis, which is all of the radius
+        // along axes in the disk's plane and none along its normal
+        let extent = vec3(
+            (1.0 - self.normal.x * self.normal.x).max(0.0).sqrt(),
+            (1.0 - self.normal.y * self.normal.y).max(0.0).sqrt(),
+            (1.0 - self.normal.z * self.normal.z).max(0.0).sqrt(),
+        ) * self.radius;
+        return Option::Some(AxisAlignedBoundingBox::new(
+            (self.center - extent).sub_element_wise(BOUNDS_PADDING),
+            (self.center + extent).add_element_wise(BOUNDS_PADDING),
+        ));
+    }
+}
+
+impl Sampleable for Disk {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, _time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        // the square root spreads points evenly, rather than bunching them up
+        // in the middle
+        let [u, v] = sampler.next_2d();
+        let r = self.radius * u.sqrt();
+        let phi = 2.0 * PI * v;
+        let point = self.center + self.tangent * (r * phi.cos()) + self.bitangent * (r * phi.sin());
+        area_to_solid_angle(origin, point, self.normal, 1.0 / self.area())
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        return match self.will_intersect(ray, t_min, t_max) {
+            Option::Some(collision) => {
+                area_to_solid_angle(&ray.origin, collision.point, self.normal, 1.0 / self.area())
+                    .map_or(0.0, |sample| sample.pdf)
+            }
+            Option::None => 0.0,
+        };
+    }
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vector, radius: f64) -> Self {
+        let material = Lambertian::new(vec3(1.0, 0.0, 0.0));
+        Self::new_with_material(center, normal, radius, Arc::new(material).into())
+    }
+
+    pub fn new_with_material(
+        center: Point,
+        normal: Vector,
+        radius: f64,
+        material: Material,
+    ) -> Self {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        Self {
+            center,
+            normal,
+            radius,
+            material,
+            tangent,
+            bitangent,
+        }
+    }
+
+    #[inline(always)]
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_ray_inside_and_outside_rim_returns_hit_and_none() {
+        let disk = Disk::new(point3(0.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0), 1.0);
+        let ray = Ray::new(point3(0.5, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = disk.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(collision.t, 1.0);
+        assert!((collision.uv.y - 0.5).abs() < 1e-12);
+
+        let ray = Ray::new(point3(0.8, 0.8, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(disk.will_intersect(&ray, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn when_get_bounds_given_tilted_disk_returns_tight_bounds() {
+        let disk = Disk::new(point3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0), 2.0);
+        let bounds = disk.get_bounds(0.0, 0.0).unwrap();
+        let half = 2.0f64.sqrt();
+        assert!((bounds.end_point.x - half).abs() < 1e-5);
+        assert!((bounds.end_point.y - half).abs() < 1e-5);
+        assert!((bounds.end_point.z - 2.0).abs() < 1e-5);
+    }
+}
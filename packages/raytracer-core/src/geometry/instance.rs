@@ -0,0 +1,379 @@
+//! Placing a shared object in the scene with a transform
+use cgmath::{point3, InnerSpace, Matrix, Matrix3, Matrix4, Rad, SquareMatrix, Transform};
+
+use crate::sampler::Sampler;
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    ray::{Point, Ray, Vector},
+    sampling::{area_to_solid_angle, Sampleable, SurfaceSample},
+    Collision, Geometry, RayCollidable,
+};
+
+/// An affine transform from an object's space to the world, with everything
+/// needed to carry rays in and what they hit back out
+#[derive(Clone, Debug)]
+pub struct AffineTransform {
+    /// From the object's space to the world
+    transform: Matrix4<f64>,
+    /// From the world to the object's space
+    inverse: Matrix4<f64>,
+    /// The inverse transpose of the transform, which keeps normals at right
+    /// angles to their surfaces under non-uniform scales
+    normal_matrix: Matrix3<f64>,
+    /// How much the transform scales lengths by, on average
+    scale: f64,
+}
+
+impl AffineTransform {
+    pub fn new(transform: Matrix4<f64>) -> Self {
+        let inverse = transform
+            .invert()
+            .expect("An instance's transform must be invertible");
+        Self::new_with_inverse(transform, inverse)
+    }
+
+    /// Skip inverting the transform, for callers that can build its inverse
+    /// more cheaply themselves
+    pub fn new_with_inverse(transform: Matrix4<f64>, inverse: Matrix4<f64>) -> Self {
+        let linear = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_matrix = Matrix3::from_cols(
+            inverse.x.truncate(),
+            inverse.y.truncate(),
+            inverse.z.truncate(),
+        )
+        .transpose();
+        Self {
+            transform,
+            inverse,
+            normal_matrix,
+            scale: linear.determinant().abs().cbrt(),
+        }
+    }
+
+    /// The transform from the object's space to the world
+    pub fn matrix(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    /// A ray in the object's space, with the same `t` at every point as the
+    /// world ray it came from
+    #[inline(always)]
+    fn to_object(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(ray.origin),
+            self.inverse.transform_vector(ray.direction),
+            ray.time,
+        )
+        .with_spread(ray.spread / self.scale)
+    }
+
+    /// Carry a point sampled on the object, with its density over solid angle
+    /// as seen from the object's space, out into the world
+    ///
+    /// Non-uniform scales stretch some parts of the surface more than others,
+    /// so this goes through the density over area, which only changes by how
+    /// much the transform grows the area around the point.
+    fn sample_to_world(
+        &self,
+        origin: &Point,
+        local_ray: &Ray,
+        collision: &Collision,
+        local_pdf: f64,
+    ) -> Option<SurfaceSample> {
+        let offset = collision.point - local_ray.origin;
+        let cosine = offset.normalize().dot(collision.normal).abs();
+        let local_area_pdf = local_pdf * cosine / offset.magnitude2();
+        let normal = self.normal_matrix * collision.normal;
+        let area_scale = self.scale.powi(3) * normal.magnitude();
+        let point = self.transform.transform_point(collision.point);
+        area_to_solid_angle(
+            origin,
+            point,
+            normal.normalize(),
+            local_area_pdf / area_scale,
+        )
+    }
+
+    /// Test a world ray against an object in this transform's space
+    pub fn intersect(
+        &self,
+        object: &Geometry,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<Collision> {
+        let collision = object.will_intersect(&self.to_object(ray), t_min, t_max)?;
+        return Option::Some(self.collision_to_world(ray, collision));
+    }
+
+    /// Test a world ray against an object in this transform's space, with
+    /// the path's sampler for objects that need it
+    pub fn intersect_sampled(
+        &self,
+        object: &Geometry,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        let local_ray = self.to_object(ray);
+        let collision = object.will_intersect_sampled(&local_ray, t_min, t_max, sampler)?;
+        return Option::Some(self.collision_to_world(ray, collision));
+    }
+
+    /// Carry a collision with a ray in the object's space back out to the
+    /// world ray. t is the same in both, since the ray is carried over whole.
+    fn collision_to_world(&self, ray: &Ray, collision: Collision) -> Collision {
+        return Collision {
+            point: ray.point_at(collision.t),
+            normal: (self.normal_matrix * collision.normal).normalize(),
+            ..collision
+        };
+    }
+
+    /// Bounds in the world around bounds in the object's space
+    pub fn bounds(&self, bounds: &AxisAlignedBoundingBox) -> AxisAlignedBoundingBox {
+        let (min, max) = (bounds.start_point, bounds.end_point);
+        // a turned box's bounds must hold all eight of its corners
+        let corners = (0..8).map(|idx| {
+            let corner = point3(
+                if idx & 1 == 0 { min.x } else { max.x },
+                if idx & 2 == 0 { min.y } else { max.y },
+                if idx & 4 == 0 { min.z } else { max.z },
+            );
+            self.transform.transform_point(corner)
+        });
+        let (start_point, end_point) = corners.fold(
+            (
+                point3(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                point3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(start, end), corner| (start.zip(corner, f64::min), end.zip(corner, f64::max)),
+        );
+        return AxisAlignedBoundingBox::new(start_point, end_point);
+    }
+
+    /// Sample an object in this transform's space from a point in the world
+    pub fn sample(
+        &self,
+        object: &Geometry,
+        origin: &Point,
+        time: f64,
+        sampler: &mut Sampler,
+    ) -> Option<SurfaceSample> {
+        let local_origin = self.inverse.transform_point(*origin);
+        let sample = object.sample(&local_origin, time, sampler)?;
+        // find the sampled point's normal, in the same way as any other
+        // emissive object
+        let local_ray = Ray::new(local_origin, sample.direction, time);
+        let tolerance = 1e-6 * sample.distance.max(1.0);
+        let collision = object.will_intersect(
+            &local_ray,
+            sample.distance - tolerance,
+            sample.distance + tolerance,
+        )?;
+        self.sample_to_world(origin, &local_ray, &collision, sample.pdf)
+    }
+
+    /// The density `sample` would pick a world ray's direction with
+    pub fn pdf(&self, object: &Geometry, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let local_ray = self.to_object(ray);
+        let local_pdf = object.pdf(&local_ray, t_min, t_max);
+        if local_pdf == 0.0 {
+            return 0.0;
+        }
+        return match object.will_intersect(&local_ray, t_min, t_max) {
+            Option::Some(collision) => self
+                .sample_to_world(&ray.origin, &local_ray, &collision, local_pdf)
+                .map_or(0.0, |sample| sample.pdf),
+            Option::None => 0.0,
+        };
+    }
+}
+
+/// An object moved, turned and stretched into place by an affine transform
+///
+/// The object isn't copied, so one heavy mesh can be placed many times for
+/// the cost of a matrix each. Rays are carried into the object's own space to
+/// be tested against it, and whatever they hit is carried back out.
+pub struct Instance {
+    pub object: Geometry,
+    transform: AffineTransform,
+}
+
+impl Instance {
+    /// Place an object with the identity transform. Use the `with_` methods to
+    /// move it into place, each of which happens after the ones before.
+    pub fn new(object: Geometry) -> Self {
+        Self::new_with_transform(object, Matrix4::identity())
+    }
+
+    /// Place an object with an affine transform from its space to the world
+    pub fn new_with_transform(object: Geometry, transform: Matrix4<f64>) -> Self {
+        Self {
+            object,
+            transform: AffineTransform::new(transform),
+        }
+    }
+
+    pub fn with_translation(self, offset: Vector) -> Self {
+        self.then(Matrix4::from_translation(offset))
+    }
+
+    /// Turn the object around an axis through the origin
+    pub fn with_rotation<A: Into<Rad<f64>>>(self, axis: Vector, angle: A) -> Self {
+        self.then(Matrix4::from_axis_angle(axis.normalize(), angle))
+    }
+
+    pub fn with_scale(self, factor: f64) -> Self {
+        self.then(Matrix4::from_scale(factor))
+    }
+
+    /// Stretch the object by a different amount along each axis
+    pub fn with_non_uniform_scale(self, factors: Vector) -> Self {
+        self.then(Matrix4::from_nonuniform_scale(
+            factors.x, factors.y, factors.z,
+        ))
+    }
+
+    /// The transform from the object's space to the world
+    pub fn transform(&self) -> &Matrix4<f64> {
+        self.transform.matrix()
+    }
+
+    fn then(self, transform: Matrix4<f64>) -> Self {
+        let transform = transform * self.transform();
+        Self::new_with_transform(self.object, transform)
+    }
+}
+
+impl RayCollidable for Instance {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        self.transform.intersect(&self.object, ray, t_min, t_max)
+    }
+
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        self.transform
+            .intersect_sampled(&self.object, ray, t_min, t_max, sampler)
+    }
+
+    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        let bounds = self.object.get_bounds(time_start, time_end)?;
+        return Option::Some(self.transform.bounds(&bounds));
+    }
+}
+
+impl Sampleable for Instance {
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        self.transform.sample(&self.object, origin, time, sampler)
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.transform.pdf(&self.object, ray, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use std::f64::consts::PI;
+
+    use cgmath::{vec3, Deg};
+
+    use crate::{
+        geometry::{cuboid::Cuboid, sphere::Sphere},
+        shader::{DiffuseLight, Material},
+    };
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_stretched_sphere_returns_world_normal() {
+        let sphere = Arc::new(Sphere::new(point3(0.0, 0.0, 0.0), 1.0)).into();
+        let instance = Instance::new(sphere)
+            .with_non_uniform_scale(vec3(2.0, 1.0, 1.0))
+            .with_translation(vec3(0.0, 0.0, -5.0));
+        // an ellipsoid 4 wide, so a ray along x from the side hits it at x = 2
+        let ray = Ray::new(point3(10.0, 0.0, -5.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let collision = instance.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((collision.t - 8.0).abs() < 1e-9);
+        assert!((collision.point - point3(2.0, 0.0, -5.0)).magnitude() < 1e-9);
+
+        // off to the side the normal leans out further than the surface does
+        let ray = Ray::new(point3(1.0, 10.0, -5.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let collision = instance.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        let tangent = vec3(2.0, -collision.point.x / (2.0 * collision.point.y), 0.0);
+        assert!(collision.normal.dot(tangent).abs() < 1e-9);
+        assert!((collision.normal.magnitude() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn when_pdf_given_sampled_direction_on_stretched_light_matches_sample() {
+        let light: Material = Arc::new(DiffuseLight::new(vec3(1.0, 1.0, 1.0))).into();
+        let sphere = Arc::new(Sphere::new_with_material(point3(0.0, 0.0, 0.0), 1.0, light)).into();
+        let instance = Instance::new(sphere)
+            .with_non_uniform_scale(vec3(3.0, 0.5, 1.0))
+            .with_rotation(vec3(0.0, 0.0, 1.0), Deg(30.0))
+            .with_translation(vec3(0.0, 4.0, 0.0));
+        let origin = point3(1.0, 0.0, 0.5);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        for _ in 0..100 {
+            let sample = instance.sample(&origin, 0.0, &mut sampler).unwrap();
+            let ray = Ray::new(origin, sample.direction, 0.0);
+            let pdf = instance.pdf(&ray, 0.001, f64::INFINITY);
+            assert!((pdf / sample.pdf - 1.0).abs() < 1e-6);
+        }
+
+        // the samples' inverse densities average out to the solid angle the
+        // light covers, which is also the share of all directions that hit it
+        let count = 20_000;
+        let sampled = (0..count)
+            .map(|_| 1.0 / instance.sample(&origin, 0.0, &mut sampler).unwrap().pdf)
+            .sum::<f64>()
+            / count as f64;
+        let directions = 400_000;
+        let rng = fastrand::Rng::with_seed(3);
+        let hits = (0..directions)
+            .filter(|_| {
+                let z = 2.0 * rng.f64() - 1.0;
+                let phi = 2.0 * PI * rng.f64();
+                let r = (1.0 - z * z).sqrt();
+                let ray = Ray::new(origin, vec3(r * phi.cos(), r * phi.sin(), z), 0.0);
+                instance
+                    .will_intersect(&ray, 0.001, f64::INFINITY)
+                    .is_some()
+            })
+            .count();
+        let solid_angle = 4.0 * PI * hits as f64 / directions as f64;
+        assert!((sampled / solid_angle - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn when_get_bounds_given_rotated_box_returns_bounds_around_corners() {
+        let cuboid = Arc::new(Cuboid::new(point3(-1.0, -1.0, -1.0), point3(1.0, 1.0, 1.0))).into();
+        let instance = Instance::new(cuboid).with_rotation(vec3(0.0, 1.0, 0.0), Deg(45.0));
+        let bounds = instance.get_bounds(0.0, 1.0).unwrap();
+        let half_diagonal = 2.0f64.sqrt();
+        assert!((bounds.end_point.x - half_diagonal).abs() < 1e-5);
+        assert!((bounds.end_point.z - half_diagonal).abs() < 1e-5);
+        assert!((bounds.end_point.y - 1.0).abs() < 1e-5);
+    }
+}
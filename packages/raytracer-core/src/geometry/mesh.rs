@@ -0,0 +1,386 @@
+//! Indexed triangle meshes
+//!
+//! A mesh keeps its vertex positions, normals and texture coordinates in
+//! shared buffers, and each face refers to them by index. Faces are wrapped in
+//! their own BVH so that a mesh can be dropped into a scene as a single
+//! `Geometry`.
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Zero};
+use log::warn;
+
+use crate::{
+    sampler::{Sampler, SamplerTrait},
+    shader::{Material, MaterialTrait},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    bvh::BVH,
+    ray::{Point, Ray, TexCoord, Vector},
+    sampling::{
+        area_to_solid_angle, sample_triangle_point, triangle_area, triangle_pdf, Sampleable,
+        SurfaceSample,
+    },
+    triangle::{
+        geometric_normal, interpolate_normal, interpolate_uv, intersect_triangle, is_degenerate,
+        triangle_bounds, triangle_uv_footprint,
+    },
+    Collision, RayCollidable,
+};
+
+/// A single triangle in a mesh, as indices into the mesh's buffers
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshFace {
+    /// Indices into the position buffer, in counter-clockwise order
+    pub vertices: [usize; 3],
+    /// Indices into the normal buffer. If None, the face is flat-shaded.
+    pub normals: Option<[usize; 3]>,
+    /// Indices into the texture coordinate buffer
+    pub uvs: Option<[usize; 3]>,
+}
+
+impl MeshFace {
+    pub fn new(vertices: [usize; 3]) -> Self {
+        Self {
+            vertices,
+            normals: Option::None,
+            uvs: Option::None,
+        }
+    }
+}
+
+/// The vertex data for a mesh, shared between all of its faces
+pub struct MeshBuffers {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vector>,
+    pub uvs: Vec<TexCoord>,
+    pub faces: Vec<MeshFace>,
+    pub material: Material,
+}
+
+impl MeshBuffers {
+    #[inline(always)]
+    fn face_vertices(&self, face: &MeshFace) -> [Point; 3] {
+        [
+            self.positions[face.vertices[0]],
+            self.positions[face.vertices[1]],
+            self.positions[face.vertices[2]],
+        ]
+    }
+}
+
+/// A reference to one face of a mesh, which is what the mesh's BVH is built over
+#[derive(Clone)]
+struct MeshTriangle {
+    buffers: Arc<MeshBuffers>,
+    face: usize,
+}
+
+impl RayCollidable for MeshTriangle {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        let face = &self.buffers.faces[self.face];
+        let vertices = self.buffers.face_vertices(face);
+        let hit = intersect_triangle(ray, &vertices, t_min, t_max)?;
+        let normal = match face.normals {
+            Option::Some(indices) => {
+                let normals = indices.map(|idx| self.buffers.normals[idx]);
+                interpolate_normal(&normals, &hit.barycentric)
+            }
+            Option::None => geometric_normal(&vertices),
+        };
+        let uvs = face
+            .uvs
+            .map(|indices| indices.map(|idx| self.buffers.uvs[idx]));
+        Option::Some(Collision {
+            t: hit.t,
+            point: ray.point_at(hit.t),
+            normal,
+            uv: interpolate_uv(uvs.as_ref(), &hit.barycentric),
+            uv_footprint: triangle_uv_footprint(ray.footprint_at(hit.t), &vertices, uvs.as_ref()),
+            material: self.buffers.material.clone(),
+        })
+    }
+
+    fn get_bounds(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        let face = &self.buffers.faces[self.face];
+        return Option::Some(triangle_bounds(&self.buffers.face_vertices(face)));
+    }
+}
+
+/// A collection of triangles sharing vertex data and a material
+pub struct TriangleMesh {
+    buffers: Arc<MeshBuffers>,
+    bvh: BVH<MeshTriangle>,
+    /// The running total of the faces' areas, for picking faces in proportion
+    /// to their size when sampling the mesh as a light
+    area_cdf: Vec<f64>,
+}
+
+impl RayCollidable for TriangleMesh {
+    #[inline(always)]
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        self.bvh.will_intersect(ray, t_min, t_max)
+    }
+
+    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        self.bvh.get_bounds(time_start, time_end)
+    }
+}
+
+/// Meshes are sampled uniformly over their whole surface, so big faces get
+/// picked more often than small ones
+impl Sampleable for TriangleMesh {
+    fn is_emissive(&self) -> bool {
+        self.buffers.material.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, _time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        let area = self.area();
+        if area == 0.0 {
+            return Option::None;
+        }
+        let target = sampler.next_1d() * area;
+        let index = self
+            .area_cdf
+            .partition_point(|&total| total <= target)
+            .min(self.area_cdf.len() - 1);
+        let vertices = self.buffers.face_vertices(&self.buffers.faces[index]);
+        let point = sample_triangle_point(&vertices, sampler.next_2d());
+        area_to_solid_angle(origin, point, geometric_normal(&vertices), 1.0 / area)
+    }
+
+    /// The density depends on which face the ray hits, so this finds the
+    /// closest one through the BVH
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let area = self.area();
+        if area == 0.0 {
+            return 0.0;
+        }
+        let nearest_vertices = self.bvh.closest_hit(ray, t_min, t_max, |triangle, t_max| {
+            let vertices = self
+                .buffers
+                .face_vertices(&self.buffers.faces[triangle.face]);
+            intersect_triangle(ray, &vertices, t_min, t_max).map(|hit| (hit.t, vertices))
+        });
+        return nearest_vertices
+            .and_then(|vertices| triangle_pdf(&vertices, 1.0 / area, ray, t_min, t_max))
+            .unwrap_or(0.0);
+    }
+}
+
+impl TriangleMesh {
+    /// Create a mesh from a set of buffers
+    ///
+    /// Faces with no area are left out, since they can't be hit and have no
+    /// normal. Panics if any face refers to a vertex, normal or UV that
+    /// doesn't exist.
+    pub fn new(mut buffers: MeshBuffers) -> Self {
+        for face in &buffers.faces {
+            assert!(
+                face.vertices
+                    .iter()
+                    .all(|&idx| idx < buffers.positions.len()),
+                "Mesh face refers to a vertex that doesn't exist: {:?}",
+                face
+            );
+            assert!(
+                face.normals
+                    .is_none_or(|n| n.iter().all(|&idx| idx < buffers.normals.len())),
+                "Mesh face refers to a normal that doesn't exist: {:?}",
+                face
+            );
+            assert!(
+                face.uvs
+                    .is_none_or(|uv| uv.iter().all(|&idx| idx < buffers.uvs.len())),
+                "Mesh face refers to a UV that doesn't exist: {:?}",
+                face
+            );
+        }
+        let face_count = buffers.faces.len();
+        let positions = &buffers.positions;
+        buffers
+            .faces
+            .retain(|face| !is_degenerate(&face.vertices.map(|idx| positions[idx])));
+        if buffers.faces.len() < face_count {
+            warn!(
+                "Skipped {} mesh faces with no area",
+                face_count - buffers.faces.len()
+            );
+        }
+        let buffers = Arc::new(buffers);
+        let triangles = (0..buffers.faces.len())
+            .map(|face| MeshTriangle {
+                buffers: buffers.clone(),
+                face,
+            })
+            .collect();
+        // meshes don't move on their own, so the time interval doesn't matter
+        let bvh = BVH::new(triangles, 0.0, 0.0);
+        let area_cdf = buffers
+            .faces
+            .iter()
+            .scan(0.0, |total, face| {
+                *total += triangle_area(&buffers.face_vertices(face));
+                Option::Some(*total)
+            })
+            .collect();
+        Self {
+            buffers,
+            bvh,
+            area_cdf,
+        }
+    }
+
+    /// The total surface area of the mesh
+    #[inline(always)]
+    fn area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+
+    /// Create a mesh from a list of positions and faces, calculating smooth
+    /// vertex normals by averaging the normals of the faces around each vertex.
+    pub fn new_smooth(positions: Vec<Point>, faces: Vec<[usize; 3]>, material: Material) -> Self {
+        let mut normals = vec![Vector::zero(); positions.len()];
+        for face in &faces {
+            let vertices = face.map(|idx| positions[idx]);
+            // the cross product is proportional to the face's area, which
+            // weights big faces more heavily
+            let face_normal = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+            for &idx in face {
+                normals[idx] += face_normal;
+            }
+        }
+        let normals = normals
+            .into_iter()
+            .map(|normal| {
+                if normal.is_zero() {
+                    normal
+                } else {
+                    normal.normalize()
+                }
+            })
+            .collect();
+        let faces = faces
+            .into_iter()
+            .map(|vertices| MeshFace {
+                vertices,
+                normals: Option::Some(vertices),
+                uvs: Option::None,
+            })
+            .collect();
+        Self::new(MeshBuffers {
+            positions,
+            normals,
+            uvs: vec![],
+            faces,
+            material,
+        })
+    }
+
+    /// The vertex data backing this mesh
+    pub fn buffers(&self) -> &MeshBuffers {
+        &self.buffers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use crate::shader::Lambertian;
+
+    use super::*;
+
+    fn make_material() -> Material {
+        Arc::new(Lambertian::new(vec3(0.5, 0.5, 0.5))).into()
+    }
+
+    /// A unit square pyramid, with the point at +y
+    fn make_pyramid() -> (Vec<Point>, Vec<[usize; 3]>) {
+        let positions = vec![
+            point3(-0.5, 0.0, -0.5),
+            point3(0.5, 0.0, -0.5),
+            point3(0.5, 0.0, 0.5),
+            point3(-0.5, 0.0, 0.5),
+            point3(0.0, 1.0, 0.0),
+        ];
+        let faces = vec![
+            [0, 1, 2],
+            [0, 2, 3],
+            [3, 2, 4],
+            [2, 1, 4],
+            [1, 0, 4],
+            [0, 3, 4],
+        ];
+        (positions, faces)
+    }
+
+    #[test]
+    fn when_will_intersect_given_flat_mesh_returns_face_normal() {
+        let (positions, faces) = make_pyramid();
+        let mesh = TriangleMesh::new(MeshBuffers {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            faces: faces.into_iter().map(MeshFace::new).collect(),
+            material: make_material(),
+        });
+        let ray = Ray::new(point3(0.0, 0.25, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = mesh.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((collision.point.z - 0.375).abs() < 1e-12);
+        let expected_normal = vec3(0.0, 0.5, 1.0).normalize();
+        assert!((collision.normal - expected_normal).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn when_will_intersect_given_smooth_mesh_returns_interpolated_normal() {
+        let (positions, faces) = make_pyramid();
+        let mesh = TriangleMesh::new_smooth(positions, faces, make_material());
+        // right near the apex, the normal should be pointing mostly up
+        let ray = Ray::new(point3(0.0, 0.99, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = mesh.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(collision.normal.y > 0.9);
+        assert!((collision.normal.magnitude() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn when_new_given_faces_with_no_area_leaves_them_out() {
+        let (positions, mut faces) = make_pyramid();
+        // one with a repeated corner, and one with its corners in a line
+        faces.push([0, 0, 4]);
+        faces.push([0, 1, 1]);
+        let mesh = TriangleMesh::new(MeshBuffers {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            faces: faces.into_iter().map(MeshFace::new).collect(),
+            material: make_material(),
+        });
+        assert_eq!(mesh.buffers().faces.len(), 6);
+        let ray = Ray::new(point3(0.0, 0.25, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = mesh.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(!collision.normal.x.is_nan());
+    }
+
+    #[test]
+    fn when_pdf_given_ray_through_mesh_uses_nearest_face() {
+        let (positions, faces) = make_pyramid();
+        let front = [3, 2, 4].map(|idx| positions[idx]);
+        let mesh = TriangleMesh::new_smooth(positions, faces, make_material());
+        let ray = Ray::new(point3(0.0, 0.25, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let expected = triangle_pdf(&front, 1.0 / mesh.area(), &ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(mesh.pdf(&ray, 0.001, f64::INFINITY), expected);
+        let miss = Ray::new(point3(0.0, 2.0, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert_eq!(mesh.pdf(&miss, 0.001, f64::INFINITY), 0.0);
+    }
+
+    #[test]
+    fn when_get_bounds_given_mesh_returns_bounds_of_all_faces() {
+        let (positions, faces) = make_pyramid();
+        let mesh = TriangleMesh::new_smooth(positions, faces, make_material());
+        let bounds = mesh.get_bounds(0.0, 1.0).unwrap();
+        assert!((bounds.start_point - point3(-0.5, 0.0, -0.5)).magnitude() < 1e-5);
+        assert!((bounds.end_point - point3(0.5, 1.0, 0.5)).magnitude() < 1e-5);
+    }
+}
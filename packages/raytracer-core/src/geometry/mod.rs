@@ -1,11 +1,21 @@
 //! Helper classes for working with ray collisions
 
-pub use self::ray::{Point, Ray, Vector};
+pub use self::ray::{Point, Ray, TexCoord, Vector};
 pub use self::raycollidable::{Collision, Geometry, RayCollidable};
 
 pub mod aabb;
-pub mod moving_sphere;
+pub mod animated;
+pub mod bvh;
+pub mod constant_medium;
+pub mod cuboid;
+pub mod disk;
+pub mod instance;
+pub mod mesh;
+pub mod plane;
+pub mod quad;
 mod ray;
 mod raycollidable;
+pub mod sampling;
 pub mod sphere;
+pub mod triangle;
 pub mod util;
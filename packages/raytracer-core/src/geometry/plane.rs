@@ -0,0 +1,114 @@
+//! Infinite planes, for floors and horizons that go on forever
+use std::sync::Arc;
+
+use cgmath::{vec2, vec3, InnerSpace};
+
+use crate::{
+    sampler::Sampler,
+    shader::{Lambertian, Material},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    ray::{Point, Ray, Vector},
+    sampling::{orthonormal_basis, Sampleable, SurfaceSample},
+    Collision, RayCollidable,
+};
+
+/// A flat surface through a point that never ends
+///
+/// It can't be bounded, so BVHs keep it to the side and test it against every
+/// ray. Its texture coordinates are measured in world units from the point,
+/// so textures repeat across it rather than being stretched to infinity.
+pub struct Plane {
+    pub point: Point,
+    /// A unit vector, pointing out of the front of the plane
+    pub normal: Vector,
+    pub material: Material,
+    /// Unit vectors in the plane, that texture coordinates run along
+    tangent: Vector,
+    bitangent: Vector,
+}
+
+impl RayCollidable for Plane {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < 1e-12 {
+            return Option::None;
+        }
+        let t = self.normal.dot(self.point - ray.origin) / denominator;
+        if t < t_min || t_max < t {
+            return Option::None;
+        }
+        let point = ray.point_at(t);
+        let offset = point - self.point;
+        return Option::Some(Collision {
+            point,
+            normal: self.normal,
+            t,
+            uv: vec2(offset.dot(self.tangent), offset.dot(self.bitangent)),
+            uv_footprint: ray.footprint_at(t),
+            material: self.material.clone(),
+        });
+    }
+
+    fn get_bounds(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        return Option::None;
+    }
+}
+
+/// With an infinite area, there's no way to pick a point evenly over a plane,
+/// so even a glowing one is only found by rays that happen to hit it.
+impl Sampleable for Plane {
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    fn sample(&self, _origin: &Point, _time: f64, _sampler: &mut Sampler) -> Option<SurfaceSample> {
+        return Option::None;
+    }
+
+    fn pdf(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> f64 {
+        return 0.0;
+    }
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vector) -> Self {
+        let material = Lambertian::new(vec3(1.0, 0.0, 0.0));
+        Self::new_with_material(point, normal, Arc::new(material).into())
+    }
+
+    pub fn new_with_material(point: Point, normal: Vector, material: Material) -> Self {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        Self {
+            point,
+            normal,
+            material,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use crate::geometry::{bvh::BVH, Geometry};
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_plane_in_bvh_hits_far_away() {
+        let plane: Geometry =
+            Arc::new(Plane::new(point3(0.0, -1.0, 0.0), vec3(0.0, 1.0, 0.0))).into();
+        assert!(plane.get_bounds(0.0, 1.0).is_none());
+        let bvh = BVH::new(vec![plane], 0.0, 1.0);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(1e6, -1.0, 0.0), 0.0);
+        let collision = bvh.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((collision.point.y + 1.0).abs() < 1e-9);
+        assert_eq!(collision.normal, vec3(0.0, 1.0, 0.0));
+    }
+}
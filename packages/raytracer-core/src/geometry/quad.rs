@@ -0,0 +1,176 @@
+//! Flat parallelograms, the building block of walls, floors and boxes
+use std::sync::Arc;
+
+use cgmath::{vec2, vec3, ElementWise, InnerSpace};
+
+use crate::{
+    sampler::{Sampler, SamplerTrait},
+    shader::{Lambertian, Material, MaterialTrait},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    ray::{Point, Ray, Vector},
+    sampling::{area_to_solid_angle, Sampleable, SurfaceSample},
+    triangle::BOUNDS_PADDING,
+    Collision, RayCollidable,
+};
+
+/// A parallelogram, from one corner and the two edges leaving it
+///
+/// Its normal is `edge_u x edge_v`, and its texture coordinates run from 0 to
+/// 1 along each edge.
+pub struct Quad {
+    pub corner: Point,
+    pub edge_u: Vector,
+    pub edge_v: Vector,
+    pub material: Material,
+    /// The unit normal of the plane the quad lies in
+    normal: Vector,
+    /// `edge_u x edge_v` over its length squared, which turns a point on the
+    /// plane into its position along each edge
+    w: Vector,
+}
+
+impl RayCollidable for Quad {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        let denominator = self.normal.dot(ray.direction);
+        // parallel to the plane, so it never crosses it
+        if denominator.abs() < 1e-12 {
+            return Option::None;
+        }
+        let t = self.normal.dot(self.corner - ray.origin) / denominator;
+        if t < t_min || t_max < t {
+            return Option::None;
+        }
+        let point = ray.point_at(t);
+        let offset = point - self.corner;
+        let u = self.w.dot(offset.cross(self.edge_v));
+        let v = self.w.dot(self.edge_u.cross(offset));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return Option::None;
+        }
+        return Option::Some(Collision {
+            point,
+            normal: self.normal,
+            t,
+            uv: vec2(u, v),
+            // the whole texture is stretched over the quad's area
+            uv_footprint: ray.footprint_at(t) / self.area().sqrt(),
+            material: self.material.clone(),
+        });
+    }
+
+    fn get_bounds(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        let far_corner = self.corner + self.edge_u + self.edge_v;
+        let start_point = self
+            .corner
+            .zip(far_corner, f64::min)
+            .zip(self.corner + self.edge_u, f64::min)
+            .zip(self.corner + self.edge_v, f64::min)
+            .sub_element_wise(BOUNDS_PADDING);
+        let end_point = self
+            .corner
+            .zip(far_corner, f64::max)
+            .zip(self.corner + self.edge_u, f64::max)
+            .zip(self.corner + self.edge_v, f64::max)
+            .add_element_wise(BOUNDS_PADDING);
+        return Option::Some(AxisAlignedBoundingBox::new(start_point, end_point));
+    }
+}
+
+impl Sampleable for Quad {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, _time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        let [u, v] = sampler.next_2d();
+        let point = self.point_at(u, v);
+        area_to_solid_angle(origin, point, self.normal, 1.0 / self.area())
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        return self.pdf_with_area_pdf(ray, t_min, t_max, 1.0 / self.area());
+    }
+}
+
+impl Quad {
+    pub fn new(corner: Point, edge_u: Vector, edge_v: Vector) -> Self {
+        let material = Lambertian::new(vec3(1.0, 0.0, 0.0));
+        Self::new_with_material(corner, edge_u, edge_v, Arc::new(material).into())
+    }
+
+    pub fn new_with_material(
+        corner: Point,
+        edge_u: Vector,
+        edge_v: Vector,
+        material: Material,
+    ) -> Self {
+        let n = edge_u.cross(edge_v);
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            material,
+            normal: n.normalize(),
+            w: n / n.magnitude2(),
+        }
+    }
+
+    /// The unit normal, on the side `edge_u x edge_v` points to
+    #[inline(always)]
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    #[inline(always)]
+    pub fn area(&self) -> f64 {
+        self.edge_u.cross(self.edge_v).magnitude()
+    }
+
+    /// The point at a texture coordinate on the quad
+    #[inline(always)]
+    pub fn point_at(&self, u: f64, v: f64) -> Point {
+        self.corner + self.edge_u * u + self.edge_v * v
+    }
+
+    /// The density over solid angle of a ray hitting this quad, when it's part
+    /// of a surface sampled with the given density over area. 0 if the ray
+    /// misses.
+    pub fn pdf_with_area_pdf(&self, ray: &Ray, t_min: f64, t_max: f64, area_pdf: f64) -> f64 {
+        return match self.will_intersect(ray, t_min, t_max) {
+            Option::Some(collision) => {
+                area_to_solid_angle(&ray.origin, collision.point, self.normal, area_pdf)
+                    .map_or(0.0, |sample| sample.pdf)
+            }
+            Option::None => 0.0,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_ray_through_parallelogram_returns_uv() {
+        // slanted, so the edges aren't at right angles
+        let quad = Quad::new(
+            point3(0.0, 0.0, -1.0),
+            vec3(2.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+        );
+        let ray = Ray::new(point3(2.0, 0.5, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = quad.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(collision.t, 1.0);
+        assert_eq!(collision.normal, vec3(0.0, 0.0, 1.0));
+        assert!((collision.uv - vec2(0.75, 0.5)).magnitude() < 1e-12);
+
+        // inside the bounding rectangle, but outside the slanted edge
+        let ray = Ray::new(point3(0.2, 0.5, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(quad.will_intersect(&ray, 0.001, f64::INFINITY).is_none());
+    }
+}
@@ -1,12 +1,19 @@
-use cgmath::{Point3, Vector3};
+use cgmath::{Point3, Vector2, Vector3};
 
 pub type Vector = Vector3<f64>;
 pub type Point = Point3<f64>;
+/// A 2D coordinate on the surface of an object, for eg mapping textures
+pub type TexCoord = Vector2<f64>;
 
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
     pub time: f64,
+    /// How quickly the ray's footprint widens, per unit of distance along it
+    ///
+    /// A camera ray covers about a pixel wherever it lands, which is what
+    /// textures use to decide how much to blur. 0 for an infinitely thin ray.
+    pub spread: f64,
 }
 
 impl Ray {
@@ -14,11 +21,23 @@ impl Ray {
         return self.origin + distance * self.direction;
     }
 
+    /// The width of the ray's footprint at a distance along it
+    #[inline(always)]
+    pub fn footprint_at(&self, distance: f64) -> f64 {
+        return self.spread * distance;
+    }
+
     pub fn new(origin: Point, direction: Vector, time: f64) -> Self {
         Self {
             origin,
             direction,
             time,
+            spread: 0.0,
         }
     }
+
+    pub fn with_spread(mut self, spread: f64) -> Self {
+        self.spread = spread;
+        self
+    }
 }
@@ -1,21 +1,27 @@
 use std::sync::Arc;
 
-use crate::shader::Material;
+use crate::{sampler::Sampler, shader::Material};
 
 use super::{
-    aabb::AxisAlignedBoundingBox, moving_sphere::MovingSphere, sphere::Sphere, Point, Ray, Vector,
+    aabb::AxisAlignedBoundingBox, animated::AnimatedInstance, constant_medium::ConstantMedium,
+    cuboid::Cuboid, disk::Disk, instance::Instance, mesh::TriangleMesh, plane::Plane, quad::Quad,
+    sphere::Sphere, triangle::Triangle, Point, Ray, TexCoord, Vector,
 };
 
 /** An object representing a collision between a ray and a `RayCollidable`
 
 The `point` is the point at which the collision occurred, `normal` is the
 outward surface normal at the point of collision, and `t` is the distance
-along the ray that the collision occurred.
+along the ray that the collision occurred. `uv` is the surface's texture
+coordinate at the point of collision, and `uv_footprint` is roughly how much
+of the texture the ray covers there, for filtering. It's 0 if unknown.
  */
 pub struct Collision {
     pub point: Point,
     pub normal: Vector,
     pub t: f64,
+    pub uv: TexCoord,
+    pub uv_footprint: f64,
     pub material: Material,
 }
 
@@ -33,6 +39,22 @@ pub trait RayCollidable {
     //! If a collision will not happen, return None
     fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision>;
 
+    /// Like `will_intersect`, but with the path's sampler, for objects that
+    /// need random numbers to decide where a ray hits them, like volumes that
+    /// rays scatter inside of. Everything else ignores it. Those objects still
+    /// answer `will_intersect` without a sampler, by hashing the ray, so it
+    /// gives the same answer every time, but only this spreads the answers
+    /// out over the path's samples.
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        self.will_intersect(ray, t_min, t_max)
+    }
+
     /// Return a bounding box for this object between the specified time intervals.
     fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox>;
 }
@@ -40,7 +62,19 @@ pub trait RayCollidable {
 #[derive(Clone)]
 pub enum Geometry {
     Sphere(Arc<Sphere>),
-    MovingSphere(Arc<MovingSphere>),
+    Triangle(Arc<Triangle>),
+    TriangleMesh(Arc<TriangleMesh>),
+    Quad(Arc<Quad>),
+    Disk(Arc<Disk>),
+    /// Infinite, so it's left out of BVHs and tested against every ray
+    Plane(Arc<Plane>),
+    Cuboid(Arc<Cuboid>),
+    /// Another object, moved into place with a transform
+    Instance(Arc<Instance>),
+    /// Another object, moved between keyframes over time for motion blur
+    AnimatedInstance(Arc<AnimatedInstance>),
+    /// A volume that rays scatter inside of, rather than off of
+    ConstantMedium(Arc<ConstantMedium>),
 }
 
 impl RayCollidable for Geometry {
@@ -48,14 +82,51 @@ impl RayCollidable for Geometry {
     fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
         match self {
             Self::Sphere(sphere) => sphere.will_intersect(ray, t_min, t_max),
-            Self::MovingSphere(sphere) => sphere.will_intersect(ray, t_min, t_max),
+            Self::Triangle(triangle) => triangle.will_intersect(ray, t_min, t_max),
+            Self::TriangleMesh(mesh) => mesh.will_intersect(ray, t_min, t_max),
+            Self::Quad(quad) => quad.will_intersect(ray, t_min, t_max),
+            Self::Disk(disk) => disk.will_intersect(ray, t_min, t_max),
+            Self::Plane(plane) => plane.will_intersect(ray, t_min, t_max),
+            Self::Cuboid(cuboid) => cuboid.will_intersect(ray, t_min, t_max),
+            Self::Instance(instance) => instance.will_intersect(ray, t_min, t_max),
+            Self::AnimatedInstance(instance) => instance.will_intersect(ray, t_min, t_max),
+            Self::ConstantMedium(medium) => medium.will_intersect(ray, t_min, t_max),
+        }
+    }
+
+    #[inline(always)]
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        match self {
+            Self::Instance(instance) => instance.will_intersect_sampled(ray, t_min, t_max, sampler),
+            Self::AnimatedInstance(instance) => {
+                instance.will_intersect_sampled(ray, t_min, t_max, sampler)
+            }
+            Self::ConstantMedium(medium) => {
+                medium.will_intersect_sampled(ray, t_min, t_max, sampler)
+            }
+            // nothing else needs the sampler
+            _ => self.will_intersect(ray, t_min, t_max),
         }
     }
 
     fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
         match self {
-            Self::MovingSphere(sphere) => sphere.get_bounds(time_start, time_end),
             Self::Sphere(sphere) => sphere.get_bounds(time_start, time_end),
+            Self::Triangle(triangle) => triangle.get_bounds(time_start, time_end),
+            Self::TriangleMesh(mesh) => mesh.get_bounds(time_start, time_end),
+            Self::Quad(quad) => quad.get_bounds(time_start, time_end),
+            Self::Disk(disk) => disk.get_bounds(time_start, time_end),
+            Self::Plane(plane) => plane.get_bounds(time_start, time_end),
+            Self::Cuboid(cuboid) => cuboid.get_bounds(time_start, time_end),
+            Self::Instance(instance) => instance.get_bounds(time_start, time_end),
+            Self::AnimatedInstance(instance) => instance.get_bounds(time_start, time_end),
+            Self::ConstantMedium(medium) => medium.get_bounds(time_start, time_end),
         }
     }
 }
@@ -71,4 +142,12 @@ macro_rules! make_from {
 }
 
 make_from!(Sphere);
-make_from!(MovingSphere);
+make_from!(Triangle);
+make_from!(TriangleMesh);
+make_from!(Quad);
+make_from!(Disk);
+make_from!(Plane);
+make_from!(Cuboid);
+make_from!(Instance);
+make_from!(AnimatedInstance);
+make_from!(ConstantMedium);
@@ -0,0 +1,309 @@
+//! Picking points on the surface of emissive objects, for light sampling
+//!
+//! Rather than waiting for scattered rays to stumble onto a light, the
+//! renderer can aim a ray straight at one. To weigh those rays correctly it
+//! needs to know how likely each direction was, as a density over solid angle
+//! as seen from the point being lit.
+use std::f64::consts::PI;
+
+use cgmath::InnerSpace;
+
+use crate::sampler::Sampler;
+
+use super::{
+    triangle::{geometric_normal, intersect_triangle},
+    Geometry, Point, Ray, Vector,
+};
+
+/// A direction towards a point on a surface, picked from somewhere in the
+/// scene
+pub struct SurfaceSample {
+    /// A unit vector pointing at the sampled point
+    pub direction: Vector,
+    /// How far away the sampled point is
+    pub distance: f64,
+    /// The density of picking this direction, over solid angle
+    pub pdf: f64,
+}
+
+/// Objects that can have points on their surface picked for light sampling
+pub trait Sampleable {
+    /// Whether this object gives off light, and so is worth sampling
+    fn is_emissive(&self) -> bool;
+
+    /// Pick a direction from the origin towards a point on this object, at
+    /// the given time, with the point taken from the sampler. Returns None if
+    /// the object can't be seen from there by sampling, eg if the origin is
+    /// inside a sphere.
+    fn sample(&self, origin: &Point, time: f64, sampler: &mut Sampler) -> Option<SurfaceSample>;
+
+    /// The density that `sample` would pick the ray's direction with, if the
+    /// ray hits this object between t_min and t_max. 0 if it doesn't.
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64;
+}
+
+impl Sampleable for Geometry {
+    #[inline(always)]
+    fn is_emissive(&self) -> bool {
+        match self {
+            Self::Sphere(sphere) => sphere.is_emissive(),
+            Self::Triangle(triangle) => triangle.is_emissive(),
+            Self::TriangleMesh(mesh) => mesh.is_emissive(),
+            Self::Quad(quad) => quad.is_emissive(),
+            Self::Disk(disk) => disk.is_emissive(),
+            Self::Plane(plane) => plane.is_emissive(),
+            Self::Cuboid(cuboid) => cuboid.is_emissive(),
+            Self::Instance(instance) => instance.is_emissive(),
+            Self::AnimatedInstance(instance) => instance.is_emissive(),
+            Self::ConstantMedium(medium) => medium.is_emissive(),
+        }
+    }
+
+    #[inline(always)]
+    fn sample(&self, origin: &Point, time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        match self {
+            Self::Sphere(sphere) => sphere.sample(origin, time, sampler),
+            Self::Triangle(triangle) => triangle.sample(origin, time, sampler),
+            Self::TriangleMesh(mesh) => mesh.sample(origin, time, sampler),
+            Self::Quad(quad) => quad.sample(origin, time, sampler),
+            Self::Disk(disk) => disk.sample(origin, time, sampler),
+            Self::Plane(plane) => plane.sample(origin, time, sampler),
+            Self::Cuboid(cuboid) => cuboid.sample(origin, time, sampler),
+            Self::Instance(instance) => instance.sample(origin, time, sampler),
+            Self::AnimatedInstance(instance) => instance.sample(origin, time, sampler),
+            Self::ConstantMedium(medium) => medium.sample(origin, time, sampler),
+        }
+    }
+
+    #[inline(always)]
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self {
+            Self::Sphere(sphere) => sphere.pdf(ray, t_min, t_max),
+            Self::Triangle(triangle) => triangle.pdf(ray, t_min, t_max),
+            Self::TriangleMesh(mesh) => mesh.pdf(ray, t_min, t_max),
+            Self::Quad(quad) => quad.pdf(ray, t_min, t_max),
+            Self::Disk(disk) => disk.pdf(ray, t_min, t_max),
+            Self::Plane(plane) => plane.pdf(ray, t_min, t_max),
+            Self::Cuboid(cuboid) => cuboid.pdf(ray, t_min, t_max),
+            Self::Instance(instance) => instance.pdf(ray, t_min, t_max),
+            Self::AnimatedInstance(instance) => instance.pdf(ray, t_min, t_max),
+            Self::ConstantMedium(medium) => medium.pdf(ray, t_min, t_max),
+        }
+    }
+}
+
+/// Pick a direction towards a sphere, uniformly over the cone of directions
+/// it covers as seen from the origin, from a point in the unit square
+pub fn sample_sphere(
+    center: Point,
+    radius: f64,
+    origin: &Point,
+    u: [f64; 2],
+) -> Option<SurfaceSample> {
+    let to_center = center - origin;
+    let distance_squared = to_center.magnitude2();
+    let radius_squared = radius * radius;
+    if distance_squared <= radius_squared {
+        return Option::None;
+    }
+    let distance = distance_squared.sqrt();
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    // written this way to keep its precision for small, far-off spheres
+    let one_minus_cos_theta_max = radius_squared / distance_squared / (1.0 + cos_theta_max);
+
+    let axis = to_center / distance;
+    let direction = sample_cone(axis, one_minus_cos_theta_max, u);
+    let cos_theta = direction.dot(axis);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+    // the nearest of the two points along the direction that are on the sphere
+    let half_chord = (radius_squared - distance_squared * sin_theta * sin_theta)
+        .max(0.0)
+        .sqrt();
+    return Option::Some(SurfaceSample {
+        direction,
+        distance: distance * cos_theta - half_chord,
+        pdf: cone_pdf(one_minus_cos_theta_max),
+    });
+}
+
+/// Pick a direction uniformly from a cone around a unit axis, from a point
+/// in the unit square
+///
+/// The cone's size is given by one minus the cosine of its half-angle, which
+/// keeps its precision for narrow cones.
+pub fn sample_cone(axis: Vector, one_minus_cos_theta_max: f64, u: [f64; 2]) -> Vector {
+    let cos_theta = 1.0 - u[0] * one_minus_cos_theta_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u[1];
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    return axis * cos_theta
+        + tangent * (sin_theta * phi.cos())
+        + bitangent * (sin_theta * phi.sin());
+}
+
+/// The density `sample_cone` picks each direction in the cone with, which is
+/// one over the cone's solid angle
+#[inline(always)]
+pub fn cone_pdf(one_minus_cos_theta_max: f64) -> f64 {
+    return 1.0 / (2.0 * PI * one_minus_cos_theta_max);
+}
+
+/// The density `sample_sphere` picks any direction that hits the sphere with
+#[inline(always)]
+pub fn sphere_pdf(center: Point, radius: f64, origin: &Point) -> f64 {
+    let distance_squared = (center - origin).magnitude2();
+    let radius_squared = radius * radius;
+    if distance_squared <= radius_squared {
+        return 0.0;
+    }
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    let one_minus_cos_theta_max = radius_squared / distance_squared / (1.0 + cos_theta_max);
+    return cone_pdf(one_minus_cos_theta_max);
+}
+
+/// Pick a point uniformly over a triangle's area, from a point in the unit
+/// square
+#[inline(always)]
+pub fn sample_triangle_point(vertices: &[Point; 3], u: [f64; 2]) -> Point {
+    let root = u[0].sqrt();
+    let b1 = 1.0 - root;
+    let b2 = u[1] * root;
+    return vertices[0] + (vertices[1] - vertices[0]) * b1 + (vertices[2] - vertices[0]) * b2;
+}
+
+/// The area of a triangle, which is half the length of its edges' cross
+/// product
+#[inline(always)]
+pub fn triangle_area(vertices: &[Point; 3]) -> f64 {
+    0.5 * (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .magnitude()
+}
+
+/// Turn a point picked with a density over area into a direction picked with
+/// a density over solid angle
+///
+/// Surfaces seen edge-on cover next to no solid angle, so their directions
+/// come out with a huge density. Returns None when that would be infinite.
+pub fn area_to_solid_angle(
+    origin: &Point,
+    point: Point,
+    normal: Vector,
+    area_pdf: f64,
+) -> Option<SurfaceSample> {
+    let offset = point - origin;
+    let distance_squared = offset.magnitude2();
+    if distance_squared == 0.0 {
+        return Option::None;
+    }
+    let distance = distance_squared.sqrt();
+    let direction = offset / distance;
+    let cosine = direction.dot(normal).abs();
+    if cosine == 0.0 {
+        return Option::None;
+    }
+    return Option::Some(SurfaceSample {
+        direction,
+        distance,
+        pdf: area_pdf * distance_squared / cosine,
+    });
+}
+
+/// The density over solid angle of a ray hitting a triangle that's part of a
+/// surface sampled with the given density over area. None if the ray misses.
+#[inline(always)]
+pub fn triangle_pdf(
+    vertices: &[Point; 3],
+    area_pdf: f64,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<f64> {
+    let hit = intersect_triangle(ray, vertices, t_min, t_max)?;
+    let point = ray.point_at(hit.t);
+    let sample = area_to_solid_angle(&ray.origin, point, geometric_normal(vertices), area_pdf)?;
+    return Option::Some(sample.pdf);
+}
+
+/// Two unit vectors that make a right-handed basis with the given unit normal
+///
+/// From Duff et al. (2017), "Building an Orthonormal Basis, Revisited"
+#[inline(always)]
+pub fn orthonormal_basis(normal: Vector) -> (Vector, Vector) {
+    let sign = 1.0f64.copysign(normal.z);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    let tangent = Vector::new(
+        1.0 + sign * normal.x * normal.x * a,
+        sign * b,
+        -sign * normal.x,
+    );
+    let bitangent = Vector::new(b, sign + normal.y * normal.y * a, -normal.y);
+    return (tangent, bitangent);
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use super::*;
+
+    #[test]
+    fn when_sample_sphere_given_outside_origin_returns_points_on_the_sphere() {
+        let center = point3(0.0, 0.0, -4.0);
+        let origin = point3(0.0, 0.0, 0.0);
+        let expected_pdf = sphere_pdf(center, 1.0, &origin);
+        let rng = fastrand::Rng::with_seed(7);
+        for _ in 0..100 {
+            let u = [rng.f64(), rng.f64()];
+            let sample = sample_sphere(center, 1.0, &origin, u).unwrap();
+            let point = origin + sample.direction * sample.distance;
+            assert!(((point - center).magnitude() - 1.0).abs() < 1e-9);
+            assert!((sample.direction.magnitude() - 1.0).abs() < 1e-12);
+            assert_eq!(sample.pdf, expected_pdf);
+        }
+        assert!(sample_sphere(center, 1.0, &point3(0.0, 0.0, -4.5), [0.5, 0.5]).is_none());
+    }
+
+    #[test]
+    fn when_sample_sphere_given_many_samples_pdf_integrates_to_one() {
+        // the solid angle of the cone is 1 / pdf, so check it against a brute
+        // force count of directions that hit the sphere
+        let center = point3(0.0, 3.0, 0.0);
+        let pdf = sphere_pdf(center, 1.5, &point3(0.0, 0.0, 0.0));
+        let rng = fastrand::Rng::with_seed(11);
+        let mut hits = 0;
+        let count = 1_000_000;
+        for _ in 0..count {
+            let z = 2.0 * rng.f64() - 1.0;
+            let phi = 2.0 * PI * rng.f64();
+            let r = (1.0 - z * z).sqrt();
+            let direction = vec3(r * phi.cos(), z, r * phi.sin());
+            let to_center = center - point3(0.0, 0.0, 0.0);
+            let along = to_center.dot(direction);
+            if along > 0.0 && (to_center - direction * along).magnitude2() <= 1.5 * 1.5 {
+                hits += 1;
+            }
+        }
+        let solid_angle = 4.0 * PI * hits as f64 / count as f64;
+        // about 7% of directions hit, so the count's standard deviation is
+        // under 0.4% of it, and this allows for more than 5 of them
+        assert!((solid_angle * pdf - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn when_orthonormal_basis_given_normal_returns_perpendicular_vectors() {
+        for normal in [
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(1.0, 2.0, 3.0).normalize(),
+        ] {
+            let (tangent, bitangent) = orthonormal_basis(normal);
+            assert!(tangent.dot(normal).abs() < 1e-12);
+            assert!(bitangent.dot(normal).abs() < 1e-12);
+            assert!(tangent.dot(bitangent).abs() < 1e-12);
+            assert!((tangent.cross(bitangent) - normal).magnitude() < 1e-12);
+        }
+    }
+}
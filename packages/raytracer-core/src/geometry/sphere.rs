@@ -1,12 +1,18 @@
 use std::sync::Arc;
 
-use cgmath::{vec3, ElementWise, InnerSpace};
+use std::f64::consts::PI;
 
-use crate::shader::{Lambertian, Material};
+use cgmath::{vec2, vec3, ElementWise, InnerSpace};
+
+use crate::{
+    sampler::{Sampler, SamplerTrait},
+    shader::{Lambertian, Material, MaterialTrait},
+};
 
 use super::{
     aabb::AxisAlignedBoundingBox,
-    ray::{Point, Ray},
+    ray::{Point, Ray, TexCoord, Vector},
+    sampling::{sample_sphere, sphere_pdf, Sampleable, SurfaceSample},
     Collision, RayCollidable,
 };
 
@@ -43,6 +49,8 @@ impl RayCollidable for Sphere {
                 t: root,
                 point,
                 normal,
+                uv: sphere_uv(&normal),
+                uv_footprint: sphere_uv_footprint(ray.footprint_at(root), self.radius),
                 material,
             })
         }
@@ -53,6 +61,23 @@ impl RayCollidable for Sphere {
     }
 }
 
+impl Sampleable for Sphere {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, _time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        sample_sphere(self.center, self.radius, origin, sampler.next_2d())
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        return match self.will_intersect(ray, t_min, t_max) {
+            Option::Some(_) => sphere_pdf(self.center, self.radius, &ray.origin),
+            Option::None => 0.0,
+        };
+    }
+}
+
 impl Sphere {
     pub fn new(center: Point, radius: f64) -> Sphere {
         let material = Lambertian::new(vec3(1.0, 0.0, 0.0));
@@ -68,6 +93,26 @@ impl Sphere {
     }
 }
 
+/// Map a point on the unit sphere to texture coordinates
+///
+/// u goes around the y axis starting from -x, and v goes from the bottom of
+/// the sphere to the top.
+#[inline(always)]
+pub fn sphere_uv(normal: &Vector) -> TexCoord {
+    let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+    let phi = (-normal.z).atan2(normal.x) + PI;
+    return vec2(phi / (2.0 * PI), theta / PI);
+}
+
+/// How much of a sphere's texture a footprint of the given width covers
+///
+/// v runs half way around the sphere, so that's what this measures against.
+/// u is stretched more than this everywhere but the equator.
+#[inline(always)]
+pub fn sphere_uv_footprint(footprint: f64, radius: f64) -> f64 {
+    return footprint / (PI * radius.abs());
+}
+
 impl From<&Sphere> for AxisAlignedBoundingBox {
     fn from(value: &Sphere) -> Self {
         return Self {
@@ -76,3 +121,22 @@ impl From<&Sphere> for AxisAlignedBoundingBox {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    #[test]
+    fn when_will_intersect_given_ray_at_equator_returns_uv() {
+        let sphere = Sphere::new(point3(0.0, 0.0, 0.0), 2.0);
+        // coming in along -x hits the +x side of the sphere, halfway around
+        let ray = Ray::new(point3(5.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), 0.0);
+        let collision = sphere.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((collision.uv - vec2(0.5, 0.5)).magnitude() < 1e-12);
+        // and the poles are at the bottom and top of the texture
+        assert!((sphere_uv(&vec3(0.0, 1.0, 0.0)).y - 1.0).abs() < 1e-12);
+        assert!(sphere_uv(&vec3(0.0, -1.0, 0.0)).y.abs() < 1e-12);
+    }
+}
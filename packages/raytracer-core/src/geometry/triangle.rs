@@ -0,0 +1,400 @@
+//! Triangle primitives, and the ray-triangle test shared with meshes
+use std::sync::Arc;
+
+use cgmath::{vec2, vec3, ElementWise, InnerSpace, Zero};
+
+use crate::{
+    sampler::{Sampler, SamplerTrait},
+    shader::{Lambertian, Material, MaterialTrait},
+};
+
+use super::{
+    aabb::AxisAlignedBoundingBox,
+    ray::{Point, Ray, TexCoord, Vector},
+    sampling::{
+        area_to_solid_angle, sample_triangle_point, triangle_area, triangle_pdf, Sampleable,
+        SurfaceSample,
+    },
+    Collision, RayCollidable,
+};
+
+/// How much to pad flat shapes' bounds by, since axis-aligned ones would
+/// otherwise have a bounding box with no thickness (which AABBs can't hit)
+pub(super) const BOUNDS_PADDING: f64 = 1e-6;
+
+/// The result of a successful ray-triangle test
+pub struct TriangleHit {
+    /// The distance along the ray of the hit
+    pub t: f64,
+    /// The barycentric weights of each vertex at the hit point
+    pub barycentric: [f64; 3],
+}
+
+/// Test a ray against a triangle using the watertight algorithm from Woop,
+/// Benthin & Wald (2013), "Watertight Ray/Triangle Intersection".
+///
+/// Unlike Möller-Trumbore, rays passing exactly through a shared edge or
+/// vertex are guaranteed to hit at least one of the triangles touching it, so
+/// meshes won't have cracks.
+pub fn intersect_triangle(
+    ray: &Ray,
+    vertices: &[Point; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<TriangleHit> {
+    let direction = ray.direction;
+
+    // permute the axes so that z is the dimension the ray is travelling in most
+    let abs_direction = vec3(direction.x.abs(), direction.y.abs(), direction.z.abs());
+    let kz = if abs_direction.x > abs_direction.y && abs_direction.x > abs_direction.z {
+        0
+    } else if abs_direction.y > abs_direction.z {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    // swapping x and y keeps the winding order intact
+    if direction[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // shear constants that transform the ray direction to +z
+    let shear_x = direction[kx] / direction[kz];
+    let shear_y = direction[ky] / direction[kz];
+    let shear_z = 1.0 / direction[kz];
+
+    let a = vertices[0] - ray.origin;
+    let b = vertices[1] - ray.origin;
+    let c = vertices[2] - ray.origin;
+
+    let ax = a[kx] - shear_x * a[kz];
+    let ay = a[ky] - shear_y * a[kz];
+    let bx = b[kx] - shear_x * b[kz];
+    let by = b[ky] - shear_y * b[kz];
+    let cx = c[kx] - shear_x * c[kz];
+    let cy = c[ky] - shear_y * c[kz];
+
+    // scaled barycentric coordinates, as 2D edge functions
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return Option::None;
+    }
+
+    let determinant = u + v + w;
+    if determinant == 0.0 {
+        return Option::None;
+    }
+
+    let az = shear_z * a[kz];
+    let bz = shear_z * b[kz];
+    let cz = shear_z * c[kz];
+    let t = (u * az + v * bz + w * cz) / determinant;
+
+    if t < t_min || t_max < t {
+        return Option::None;
+    }
+
+    return Option::Some(TriangleHit {
+        t,
+        barycentric: [u / determinant, v / determinant, w / determinant],
+    });
+}
+
+/// The normal of the plane containing a triangle, following the right-hand
+/// rule for counter-clockwise winding
+///
+/// A triangle with no area isn't in any one plane, so it gets a zero vector
+/// rather than a normal full of NaNs.
+#[inline(always)]
+pub fn geometric_normal(vertices: &[Point; 3]) -> Vector {
+    let normal = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+    if normal.is_zero() {
+        return normal;
+    }
+    return normal.normalize();
+}
+
+/// Whether a triangle has no area, because its corners are all on one line
+#[inline(always)]
+pub fn is_degenerate(vertices: &[Point; 3]) -> bool {
+    (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .is_zero()
+}
+
+/// Bounds around a triangle, padded so that they always have some thickness
+pub fn triangle_bounds(vertices: &[Point; 3]) -> AxisAlignedBoundingBox {
+    let start_point = vertices[0]
+        .zip(vertices[1], f64::min)
+        .zip(vertices[2], f64::min)
+        .sub_element_wise(BOUNDS_PADDING);
+    let end_point = vertices[0]
+        .zip(vertices[1], f64::max)
+        .zip(vertices[2], f64::max)
+        .add_element_wise(BOUNDS_PADDING);
+    AxisAlignedBoundingBox::new(start_point, end_point)
+}
+
+/// A single, free-standing triangle
+///
+/// For anything with more than a handful of triangles, use a `TriangleMesh`
+/// instead, which shares vertex data between faces.
+pub struct Triangle {
+    /// The corners of this triangle, in counter-clockwise order
+    pub vertices: [Point; 3],
+    /// Optional per-vertex normals, for smooth shading
+    pub normals: Option<[Vector; 3]>,
+    /// Optional per-vertex texture coordinates. If None, the barycentric
+    /// weights of the second and third vertices are used instead.
+    pub uvs: Option<[TexCoord; 3]>,
+    pub material: Material,
+}
+
+/// Triangles with no area can't be hit or sampled, since they have no normal
+impl RayCollidable for Triangle {
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        if is_degenerate(&self.vertices) {
+            return Option::None;
+        }
+        let hit = intersect_triangle(ray, &self.vertices, t_min, t_max)?;
+        let normal = match &self.normals {
+            Option::Some(normals) => interpolate_normal(normals, &hit.barycentric),
+            Option::None => geometric_normal(&self.vertices),
+        };
+        Option::Some(Collision {
+            t: hit.t,
+            point: ray.point_at(hit.t),
+            normal,
+            uv: interpolate_uv(self.uvs.as_ref(), &hit.barycentric),
+            uv_footprint: triangle_uv_footprint(
+                ray.footprint_at(hit.t),
+                &self.vertices,
+                self.uvs.as_ref(),
+            ),
+            material: self.material.clone(),
+        })
+    }
+
+    fn get_bounds(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        return Option::Some(triangle_bounds(&self.vertices));
+    }
+}
+
+impl Sampleable for Triangle {
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample(&self, origin: &Point, _time: f64, sampler: &mut Sampler) -> Option<SurfaceSample> {
+        if is_degenerate(&self.vertices) {
+            return Option::None;
+        }
+        let point = sample_triangle_point(&self.vertices, sampler.next_2d());
+        let normal = geometric_normal(&self.vertices);
+        area_to_solid_angle(origin, point, normal, 1.0 / triangle_area(&self.vertices))
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if is_degenerate(&self.vertices) {
+            return 0.0;
+        }
+        let area_pdf = 1.0 / triangle_area(&self.vertices);
+        triangle_pdf(&self.vertices, area_pdf, ray, t_min, t_max).unwrap_or(0.0)
+    }
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point; 3]) -> Self {
+        let material = Lambertian::new(vec3(1.0, 0.0, 0.0));
+        Self::new_with_material(vertices, Arc::new(material).into())
+    }
+
+    pub fn new_with_material(vertices: [Point; 3], material: Material) -> Self {
+        Self {
+            vertices,
+            normals: Option::None,
+            uvs: Option::None,
+            material,
+        }
+    }
+
+    /// Create a smooth-shaded triangle, with a normal for each vertex
+    pub fn new_with_normals(
+        vertices: [Point; 3],
+        normals: [Vector; 3],
+        material: Material,
+    ) -> Self {
+        Self {
+            vertices,
+            normals: Option::Some(normals),
+            uvs: Option::None,
+            material,
+        }
+    }
+
+    /// Give each vertex a texture coordinate
+    pub fn with_uvs(mut self, uvs: [TexCoord; 3]) -> Self {
+        self.uvs = Option::Some(uvs);
+        self
+    }
+}
+
+/// Blend per-vertex normals together using barycentric weights
+#[inline(always)]
+pub fn interpolate_normal(normals: &[Vector; 3], barycentric: &[f64; 3]) -> Vector {
+    (normals[0] * barycentric[0] + normals[1] * barycentric[1] + normals[2] * barycentric[2])
+        .normalize()
+}
+
+/// How much of a triangle's texture a footprint of the given width covers,
+/// by comparing the triangle's area in texture space to its area in the world
+#[inline(always)]
+pub fn triangle_uv_footprint(
+    footprint: f64,
+    vertices: &[Point; 3],
+    uvs: Option<&[TexCoord; 3]>,
+) -> f64 {
+    let world_area = (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .magnitude();
+    let uv_area = match uvs {
+        Option::Some(uvs) => {
+            let (a, b) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+            (a.x * b.y - a.y * b.x).abs()
+        }
+        // the barycentric fallback covers half of the unit square
+        Option::None => 1.0,
+    };
+    if world_area == 0.0 {
+        return 0.0;
+    }
+    return footprint * (uv_area / world_area).sqrt();
+}
+
+/// Blend per-vertex texture coordinates together using barycentric weights,
+/// falling back to the weights themselves if there aren't any
+#[inline(always)]
+pub fn interpolate_uv(uvs: Option<&[TexCoord; 3]>, barycentric: &[f64; 3]) -> TexCoord {
+    match uvs {
+        Option::Some(uvs) => {
+            uvs[0] * barycentric[0] + uvs[1] * barycentric[1] + uvs[2] * barycentric[2]
+        }
+        Option::None => vec2(barycentric[1], barycentric[2]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    const VERTICES: [Point; 3] = [
+        point3(-1.0, -1.0, -2.0),
+        point3(1.0, -1.0, -2.0),
+        point3(0.0, 1.0, -2.0),
+    ];
+
+    #[test]
+    fn when_will_intersect_given_ray_through_center_returns_collision() {
+        let triangle = Triangle::new(VERTICES);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = triangle.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(collision.t, 2.0);
+        assert_eq!(collision.point, point3(0.0, 0.0, -2.0));
+        assert_eq!(collision.normal, vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn when_will_intersect_given_ray_outside_edges_returns_none() {
+        let triangle = Triangle::new(VERTICES);
+        let ray = Ray::new(point3(1.0, 1.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(triangle
+            .will_intersect(&ray, 0.001, f64::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn when_intersect_triangle_given_ray_through_shared_edge_hits_a_triangle() {
+        // two triangles forming a quad, split along the diagonal x == y
+        let lower = [
+            point3(0.0, 0.0, -1.0),
+            point3(1.0, 0.0, -1.0),
+            point3(1.0, 1.0, -1.0),
+        ];
+        let upper = [
+            point3(0.0, 0.0, -1.0),
+            point3(1.0, 1.0, -1.0),
+            point3(0.0, 1.0, -1.0),
+        ];
+        for i in 1..100 {
+            let offset = i as f64 / 100.0;
+            // this crosses the plane of the quad right on the diagonal
+            let ray = Ray::new(
+                point3(offset - 0.1, offset - 0.3, 0.0),
+                vec3(0.1, 0.3, -1.0),
+                0.0,
+            );
+            let hit_lower = intersect_triangle(&ray, &lower, 0.0, f64::INFINITY);
+            let hit_upper = intersect_triangle(&ray, &upper, 0.0, f64::INFINITY);
+            assert!(
+                hit_lower.is_some() || hit_upper.is_some(),
+                "Ray slipped through the crack between two triangles at {}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn when_will_intersect_given_vertex_normals_returns_interpolated_normal() {
+        let normals = [
+            vec3(-1.0, 0.0, 1.0).normalize(),
+            vec3(1.0, 0.0, 1.0).normalize(),
+            vec3(0.0, 0.0, 1.0),
+        ];
+        let material: Material = Arc::new(Lambertian::new(vec3(0.5, 0.5, 0.5))).into();
+        let triangle = Triangle::new_with_normals(VERTICES, normals, material);
+        // straight down the middle, so the x components of the first two cancel out
+        let ray = Ray::new(point3(0.0, -0.5, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = triangle.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(collision.normal.x.abs() < 1e-12);
+        assert!((collision.normal.magnitude() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn when_triangle_given_vertices_in_a_line_is_never_hit_or_sampled() {
+        let vertices = [
+            point3(-1.0, 0.0, -2.0),
+            point3(0.0, 0.0, -2.0),
+            point3(1.0, 0.0, -2.0),
+        ];
+        assert!(is_degenerate(&vertices));
+        assert_eq!(geometric_normal(&vertices), vec3(0.0, 0.0, 0.0));
+        let triangle = Triangle::new(vertices);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(triangle
+            .will_intersect(&ray, 0.001, f64::INFINITY)
+            .is_none());
+        assert_eq!(triangle.pdf(&ray, 0.001, f64::INFINITY), 0.0);
+        let mut sampler = Sampler::new(crate::sampler::SamplerKind::Independent, 1, 0);
+        assert!(triangle
+            .sample(&point3(0.0, 1.0, 0.0), 0.0, &mut sampler)
+            .is_none());
+    }
+
+    #[test]
+    fn when_get_bounds_given_flat_triangle_returns_padded_bounds() {
+        let triangle = Triangle::new(VERTICES);
+        let bounds = triangle.get_bounds(0.0, 0.0).unwrap();
+        assert!(bounds.end_point.z > bounds.start_point.z);
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(bounds
+            .will_intersect_aabb(&ray, 0.001, f64::INFINITY)
+            .is_some());
+    }
+}
@@ -1,46 +1,35 @@
 pub mod vector {
-    use cgmath::{vec3, InnerSpace};
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
 
-    use crate::geometry::{Ray, Vector};
-
-    /// Returns a vector of 3 random values in [0, 1)
-    #[inline(always)]
-    pub fn random_vector() -> Vector {
-        let rng = fastrand::Rng::new();
-        vec3(rng.f64(), rng.f64(), rng.f64())
-    }
+    use cgmath::vec3;
 
-    #[inline(always)]
-    pub fn random_vector_in_unit_sphere() -> Vector {
-        let mut direction: Vector;
-        loop {
-            direction = random_vector();
-            if direction.magnitude2() < 1.0 {
-                break;
-            }
-        }
-        direction
-    }
+    use crate::geometry::{Ray, Vector};
 
+    /// Turns a point in the unit square into a direction, spread evenly over
+    /// the unit sphere, so evenly spread points give evenly spread directions
     #[inline(always)]
-    pub fn random_unit_vector() -> Vector {
-        random_vector_in_unit_sphere().normalize()
+    pub fn unit_vector_from(u: [f64; 2]) -> Vector {
+        let z = 1.0 - 2.0 * u[0];
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+        vec3(r * phi.cos(), r * phi.sin(), z)
     }
 
+    /// Turns a point in the unit square into a point in the unit disk, with
+    /// the square squashed into rings so that neighbouring points stay
+    /// neighbours (Shirley and Chiu's concentric mapping)
     #[inline(always)]
-    pub fn random_vector_in_disk() -> Vector {
-        let rng = fastrand::Rng::new();
-        let mut x: f64;
-        let mut y: f64;
-        loop {
-            x = rng.f64();
-            y = rng.f64();
-            let mag = x * x + y * y;
-            if mag < 1.0 {
-                break;
-            }
+    pub fn point_in_disk_from(u: [f64; 2]) -> Vector {
+        let (a, b) = (2.0 * u[0] - 1.0, 2.0 * u[1] - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return vec3(0.0, 0.0, 0.0);
         }
-        vec3(x, y, 0.0)
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, FRAC_PI_4 * (b / a))
+        } else {
+            (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+        };
+        vec3(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     #[inline(always)]
@@ -54,7 +43,7 @@ pub mod vector {
     /// The raytracing engine by default does not calculate face normals, and
     /// the normals on the Collision record are outward normals.
     #[inline(always)]
-    pub fn to_face_normal(ray: Ray, outward_normal: Vector) -> Vector {
+    pub fn to_face_normal(ray: &Ray, outward_normal: Vector) -> Vector {
         let is_front_face = cgmath::dot(ray.direction, outward_normal) < 0.0;
         return if is_front_face {
             outward_normal
@@ -62,4 +51,49 @@ pub mod vector {
             -outward_normal
         };
     }
+
+    #[cfg(test)]
+    mod tests {
+        use cgmath::InnerSpace;
+
+        use super::*;
+
+        /// The middles of the cells of an n by n grid over the unit square
+        fn grid(n: usize) -> impl Iterator<Item = [f64; 2]> {
+            (0..n * n)
+                .map(move |i| [(i % n) as f64 + 0.5, (i / n) as f64 + 0.5].map(|v| v / n as f64))
+        }
+
+        #[test]
+        fn when_unit_vector_from_given_even_points_fills_every_octant() {
+            let mut octants = [0; 8];
+            for u in grid(64) {
+                let vector = unit_vector_from(u);
+                assert!((vector.magnitude() - 1.0).abs() < 1e-9);
+                let octant = (vector.x < 0.0) as usize
+                    + 2 * (vector.y < 0.0) as usize
+                    + 4 * (vector.z < 0.0) as usize;
+                octants[octant] += 1;
+            }
+            // each octant should get an eighth of them
+            for samples in octants {
+                let fraction = samples as f64 / 4096.0;
+                assert!((0.1..0.15).contains(&fraction), "{:?}", octants);
+            }
+        }
+
+        #[test]
+        fn when_point_in_disk_from_given_even_points_fills_every_quadrant() {
+            let mut quadrants = [0; 4];
+            for u in grid(64) {
+                let vector = point_in_disk_from(u);
+                assert!(vector.magnitude2() < 1.0 && vector.z == 0.0);
+                quadrants[(vector.x < 0.0) as usize + 2 * (vector.y < 0.0) as usize] += 1;
+            }
+            for samples in quadrants {
+                let fraction = samples as f64 / 4096.0;
+                assert!((0.2..0.3).contains(&fraction), "{:?}", quadrants);
+            }
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use super::buffer::ImageBuffer;
+use super::buffer::{FloatImageBuffer, ImageBuffer};
 
 pub enum BlendingMode {
     Add,
@@ -26,3 +26,27 @@ pub fn blend_images(images: Vec<ImageBuffer>, blending_mode: BlendingMode) -> Im
 
     output
 }
+
+pub fn blend_float_images(
+    images: Vec<FloatImageBuffer>,
+    blending_mode: BlendingMode,
+) -> FloatImageBuffer {
+    let mut images = images.into_iter();
+    let mut output = images.next().expect("Can't blend zero images");
+
+    for image in images {
+        assert!(
+            image.format == output.format
+                && image.width == output.width
+                && image.height == output.height,
+            "Blended images must be the same size and format"
+        );
+        for (sample, value) in output.data.iter_mut().zip(image.data) {
+            *sample = match blending_mode {
+                BlendingMode::Add => *sample + value,
+            }
+        }
+    }
+
+    output
+}
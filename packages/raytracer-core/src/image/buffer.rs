@@ -2,16 +2,52 @@
 //!
 //! Stride defaults to 3 if not provided, for an RGB format. Use 4 to include
 //! an alpha channel, for eg RGBA.
+//!
+//! Renders and loaded images like textures are kept as linear floats instead,
+//! in a `FloatImageBuffer`, and only converted to 8 bits when exported by a
+//! `display::DisplayTransform`.
 
 #[allow(non_snake_case)]
 pub mod BufferFormat {
-    #[derive(Debug, PartialEq)]
+    /// What each channel of a pixel is stored as
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum SampleType {
+        U8,
+        F32,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
     pub struct Metadata {
         pub stride: usize,
+        pub sample_type: SampleType,
+    }
+
+    impl Metadata {
+        /// The same channels, stored as a different type
+        pub fn with_sample_type(&self, sample_type: SampleType) -> Metadata {
+            Metadata {
+                sample_type,
+                ..self.clone()
+            }
+        }
     }
 
-    pub const RGB8: Metadata = Metadata { stride: 3 };
-    pub const RGBA8: Metadata = Metadata { stride: 4 };
+    pub const RGB8: Metadata = Metadata {
+        stride: 3,
+        sample_type: SampleType::U8,
+    };
+    pub const RGBA8: Metadata = Metadata {
+        stride: 4,
+        sample_type: SampleType::U8,
+    };
+    pub const RGB32F: Metadata = Metadata {
+        stride: 3,
+        sample_type: SampleType::F32,
+    };
+    pub const RGBA32F: Metadata = Metadata {
+        stride: 4,
+        sample_type: SampleType::F32,
+    };
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,6 +61,7 @@ pub struct ImageBuffer {
 
 impl ImageBuffer {
     pub fn new(width: usize, height: usize, format: BufferFormat::Metadata) -> ImageBuffer {
+        debug_assert_eq!(format.sample_type, BufferFormat::SampleType::U8);
         let stride = format.stride;
         ImageBuffer {
             width,
@@ -43,9 +80,87 @@ impl ImageBuffer {
     }
 }
 
+/// A linear, floating-point image, which can hold values above 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatImageBuffer {
+    /* A buffer holding the data in a flat list. Read from top row, left-to-right. */
+    pub data: Vec<f32>,
+    pub width: usize,
+    pub height: usize,
+    pub format: BufferFormat::Metadata,
+}
+
+impl FloatImageBuffer {
+    pub fn new(width: usize, height: usize, format: BufferFormat::Metadata) -> FloatImageBuffer {
+        debug_assert_eq!(format.sample_type, BufferFormat::SampleType::F32);
+        let stride = format.stride;
+        FloatImageBuffer {
+            width,
+            height,
+            format,
+            data: vec![0.0; width * height * stride],
+        }
+    }
+
+    pub fn new_rgb(width: usize, height: usize) -> FloatImageBuffer {
+        Self::new(width, height, BufferFormat::RGB32F)
+    }
+
+    pub fn new_rgba(width: usize, height: usize) -> FloatImageBuffer {
+        Self::new(width, height, BufferFormat::RGBA32F)
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.format.stride == 4
+    }
+
+    /// The color of the pixel at (x, y), without any alpha
+    #[inline(always)]
+    #[allow(clippy::identity_op)]
+    pub fn pixel(&self, x: usize, y: usize) -> [f32; 3] {
+        let idx = (y * self.width + x) * self.format.stride;
+        [self.data[idx + 0], self.data[idx + 1], self.data[idx + 2]]
+    }
+
+    /// Set the color of the pixel at (x, y), leaving any alpha alone
+    #[inline(always)]
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [f32; 3]) {
+        let idx = (y * self.width + x) * self.format.stride;
+        self.data[idx..idx + 3].copy_from_slice(&color);
+    }
+
+    /// Copy out a single channel, eg 0 for red or 3 for alpha
+    pub fn channel(&self, channel: usize) -> Vec<f32> {
+        assert!(channel < self.format.stride, "Channel out of range");
+        self.data
+            .iter()
+            .skip(channel)
+            .step_by(self.format.stride)
+            .copied()
+            .collect()
+    }
+}
+
 pub mod convert {
-    use super::{BufferFormat, ImageBuffer};
+    use super::{BufferFormat, FloatImageBuffer, ImageBuffer};
+
+    /// Scale an 8-bit image to [0, 1], without undoing any gamma
+    pub fn rgb8_to_float(buffer: &ImageBuffer) -> FloatImageBuffer {
+        FloatImageBuffer {
+            data: buffer
+                .data
+                .iter()
+                .map(|&sample| sample as f32 / 255.0)
+                .collect(),
+            width: buffer.width,
+            height: buffer.height,
+            format: buffer
+                .format
+                .with_sample_type(BufferFormat::SampleType::F32),
+        }
+    }
 
+    #[allow(clippy::identity_op)]
     pub fn rgb_to_rgba(rgb_buffer: &ImageBuffer, fill: u8) -> ImageBuffer {
         assert!(
             rgb_buffer.format == BufferFormat::RGB8,
@@ -106,4 +221,16 @@ mod tests {
 
         assert_eq!(reference_output, converted_image);
     }
+
+    #[test]
+    fn when_rgb8_to_float_given_rgba8_image_returns_float_format() {
+        assert_ne!(BufferFormat::RGB8, BufferFormat::RGB32F);
+        assert_ne!(BufferFormat::RGBA8, BufferFormat::RGBA32F);
+
+        let mut image = ImageBuffer::new_rgba(1, 1);
+        image.data = vec![255, 0, 51, 255];
+        let converted = convert::rgb8_to_float(&image);
+        assert_eq!(converted.format, BufferFormat::RGBA32F);
+        assert_eq!(converted.data, vec![1.0, 0.0, 0.2, 1.0]);
+    }
 }
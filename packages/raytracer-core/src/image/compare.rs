@@ -0,0 +1,249 @@
+//! Measuring how far one image is from another
+//!
+//! This is for checking renders against reference images, where a few stray
+//! samples are expected to land differently but the picture as a whole
+//! should stay the same. Differences are measured over the whole image (RMSE
+//! and PSNR) and pixel by pixel, so that a render that's slightly off
+//! everywhere and one that's badly off in one spot can both be caught.
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+use super::buffer::FloatImageBuffer;
+
+/// How different two images are
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageDifference {
+    /// The root mean square of the differences of every channel of every
+    /// pixel
+    pub rmse: f64,
+    /// The peak signal-to-noise ratio in decibels, taking 1 as the peak, which
+    /// is infinite for identical images
+    pub psnr: f64,
+    /// The biggest difference of any channel of any pixel
+    pub max_difference: f32,
+    /// How many pixels have a channel further off than the pixel threshold
+    pub pixels_over_threshold: usize,
+    /// The fraction of all of the pixels that are over the threshold
+    pub fraction_over_threshold: f64,
+}
+
+/// How different an image can be from its reference and still pass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// The lowest PSNR allowed, in decibels
+    pub min_psnr: f64,
+    /// How far off any channel of a pixel can be before the pixel counts as
+    /// different
+    pub pixel_threshold: f32,
+    /// The largest fraction of pixels allowed to be different
+    pub max_fraction_over: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            min_psnr: 35.0,
+            pixel_threshold: 0.1,
+            max_fraction_over: 0.01,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompareError {
+    /// The images weren't the same width and height
+    SizeMismatch {
+        actual: (usize, usize),
+        expected: (usize, usize),
+    },
+}
+
+impl Display for CompareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SizeMismatch { actual, expected } => write!(
+                f,
+                "image is {}x{} but the reference is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl Error for CompareError {}
+
+impl ImageDifference {
+    /// Whether the difference is small enough to pass
+    pub fn is_within(&self, tolerance: &Tolerance) -> bool {
+        self.psnr >= tolerance.min_psnr
+            && self.fraction_over_threshold <= tolerance.max_fraction_over
+    }
+}
+
+impl Display for ImageDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RMSE {:.5}, PSNR {:.2} dB, largest difference {:.3}, {} pixels ({:.2}%) over the threshold",
+            self.rmse,
+            self.psnr,
+            self.max_difference,
+            self.pixels_over_threshold,
+            self.fraction_over_threshold * 100.0
+        )
+    }
+}
+
+/// Measure how different an image is from a reference of the same size,
+/// counting the pixels with a channel more than `pixel_threshold` off. Any
+/// alpha is ignored.
+pub fn compare_images(
+    actual: &FloatImageBuffer,
+    expected: &FloatImageBuffer,
+    pixel_threshold: f32,
+) -> Result<ImageDifference, CompareError> {
+    check_sizes(actual, expected)?;
+    let mut squared_error = 0.0;
+    let mut max_difference: f32 = 0.0;
+    let mut pixels_over_threshold = 0;
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let differences = pixel_difference(actual, expected, x, y);
+            squared_error += differences
+                .iter()
+                .map(|&difference| (difference * difference) as f64)
+                .sum::<f64>();
+            let largest = differences.into_iter().fold(0.0, f32::max);
+            max_difference = max_difference.max(largest);
+            if largest > pixel_threshold {
+                pixels_over_threshold += 1;
+            }
+        }
+    }
+    let pixels = actual.width * actual.height;
+    let rmse = (squared_error / (pixels * 3).max(1) as f64).sqrt();
+    return Ok(ImageDifference {
+        rmse,
+        psnr: -20.0 * rmse.log10(),
+        max_difference,
+        pixels_over_threshold,
+        fraction_over_threshold: pixels_over_threshold as f64 / pixels.max(1) as f64,
+    });
+}
+
+/// An image of where two images differ, for looking over when a comparison
+/// fails
+///
+/// Each pixel is the difference of each channel, scaled up by `gain` so that
+/// small differences show up. Pixels over the threshold are painted solid
+/// magenta, so that they stand out however small they are.
+pub fn difference_image(
+    actual: &FloatImageBuffer,
+    expected: &FloatImageBuffer,
+    pixel_threshold: f32,
+    gain: f32,
+) -> Result<FloatImageBuffer, CompareError> {
+    check_sizes(actual, expected)?;
+    let mut image = FloatImageBuffer::new_rgb(actual.width, actual.height);
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let differences = pixel_difference(actual, expected, x, y);
+            let color = if differences
+                .iter()
+                .any(|&difference| difference > pixel_threshold)
+            {
+                [1.0, 0.0, 1.0]
+            } else {
+                differences.map(|difference| (difference * gain).min(1.0))
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+    return Ok(image);
+}
+
+fn check_sizes(actual: &FloatImageBuffer, expected: &FloatImageBuffer) -> Result<(), CompareError> {
+    if actual.width != expected.width || actual.height != expected.height {
+        return Err(CompareError::SizeMismatch {
+            actual: (actual.width, actual.height),
+            expected: (expected.width, expected.height),
+        });
+    }
+    Ok(())
+}
+
+#[inline(always)]
+fn pixel_difference(
+    actual: &FloatImageBuffer,
+    expected: &FloatImageBuffer,
+    x: usize,
+    y: usize,
+) -> [f32; 3] {
+    let (a, b) = (actual.pixel(x, y), expected.pixel(x, y));
+    [0, 1, 2].map(|c| (a[c] - b[c]).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> FloatImageBuffer {
+        let mut image = FloatImageBuffer::new_rgb(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let value = (y * width + x) as f32 / (width * height) as f32;
+                image.set_pixel(x, y, [value, 1.0 - value, 0.5]);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn when_compare_images_given_identical_images_returns_no_difference() {
+        let image = gradient(8, 4);
+        let difference = compare_images(&image, &image, 0.1).unwrap();
+        assert_eq!(difference.rmse, 0.0);
+        assert_eq!(difference.psnr, f64::INFINITY);
+        assert_eq!(difference.pixels_over_threshold, 0);
+        assert!(difference.is_within(&Tolerance::default()));
+    }
+
+    #[test]
+    fn when_compare_images_given_one_wrong_pixel_counts_it() {
+        let expected = gradient(10, 10);
+        let mut actual = expected.clone();
+        actual.set_pixel(3, 7, [1.0, 1.0, 0.5]);
+        let difference = compare_images(&actual, &expected, 0.1).unwrap();
+        assert_eq!(difference.pixels_over_threshold, 1);
+        assert_eq!(difference.fraction_over_threshold, 0.01);
+        // only the red and green channels are off
+        let [red, green, _] = expected.pixel(3, 7).map(|value| (1.0 - value) as f64);
+        let rmse = ((red * red + green * green) / 300.0).sqrt();
+        assert!((difference.rmse - rmse).abs() < 1e-6);
+        assert!((difference.psnr + 20.0 * rmse.log10()).abs() < 1e-6);
+
+        let strict = Tolerance {
+            max_fraction_over: 0.0,
+            ..Tolerance::default()
+        };
+        assert!(!difference.is_within(&strict));
+
+        let diff = difference_image(&actual, &expected, 0.1, 10.0).unwrap();
+        assert_eq!(diff.pixel(3, 7), [1.0, 0.0, 1.0]);
+        assert_eq!(diff.pixel(4, 7), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn when_compare_images_given_different_sizes_returns_error() {
+        let result = compare_images(&gradient(4, 4), &gradient(4, 5), 0.1);
+        assert_eq!(
+            result,
+            Err(CompareError::SizeMismatch {
+                actual: (4, 4),
+                expected: (4, 5)
+            })
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Turning linear renders into images for display
+//!
+//! Renders are kept as linear radiance, which can go far above 1. Before
+//! they're written to an 8-bit format, a display transform scales them by the
+//! exposure, compresses highlights with a tone mapping curve, and encodes the
+//! result for a display with a transfer function.
+use super::buffer::{BufferFormat::SampleType, FloatImageBuffer, ImageBuffer};
+
+/// Rec. 709 / sRGB luminance weights
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// How values above 1 are brought down into the range a display can show
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ToneMap {
+    /// Clip anything above 1
+    #[default]
+    Clamp,
+    /// Reinhard et al. (2002), L / (1 + L), which never quite reaches white
+    Reinhard,
+    /// Reinhard's curve, extended so that the given luminance maps to white
+    ExtendedReinhard { white_point: f32 },
+    /// Stephen Hill's fit of the ACES reference and output transforms, a
+    /// filmic curve with a toe and shoulder
+    Aces,
+    /// An approximation of Troy Sobotka's AgX, which desaturates highlights
+    /// towards white instead of skewing their hue
+    Agx,
+}
+
+/// How linear values are encoded for a display
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum TransferFunction {
+    /// The sRGB OETF, with its linear segment near black
+    #[default]
+    Srgb,
+    /// A plain power curve, eg 2.2
+    Gamma(f32),
+}
+
+impl TransferFunction {
+    #[inline(always)]
+    pub fn encode(&self, value: f32) -> f32 {
+        match self {
+            TransferFunction::Srgb => {
+                if value <= 0.0031308 {
+                    12.92 * value
+                } else {
+                    1.055 * value.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            TransferFunction::Gamma(gamma) => value.powf(1.0 / gamma),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct DisplayTransform {
+    /// In stops, so 1 doubles the brightness and -1 halves it
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    pub transfer: TransferFunction,
+}
+
+impl DisplayTransform {
+    pub fn new(exposure: f32, tone_map: ToneMap, transfer: TransferFunction) -> Self {
+        Self {
+            exposure,
+            tone_map,
+            transfer,
+        }
+    }
+
+    /// Map a linear color to display values in [0, 1]
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        let scale = self.exposure.exp2();
+        let color = color.map(|c| (c * scale).max(0.0));
+        let mapped = match self.tone_map {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white_point } => scale_luminance(color, |l| {
+                l * (1.0 + l / (white_point * white_point)) / (1.0 + l)
+            }),
+            ToneMap::Aces => aces(color),
+            ToneMap::Agx => agx(color),
+        };
+        return mapped.map(|c| self.transfer.encode(c.clamp(0.0, 1.0)));
+    }
+
+    /// Convert a render to 8 bits for display. Alpha is kept linear.
+    pub fn to_rgb8(&self, image: &FloatImageBuffer) -> ImageBuffer {
+        let format = image.format.with_sample_type(SampleType::U8);
+        let mut buffer = ImageBuffer::new(image.width, image.height, format);
+        self.quantize(image, &mut buffer.data, |value| {
+            (255.0 * value).round() as u8
+        });
+        buffer
+    }
+
+    /// Convert a render to 16-bit samples for display, laid out the same way
+    /// as the render. Alpha is kept linear.
+    pub fn to_rgb16(&self, image: &FloatImageBuffer) -> Vec<u16> {
+        let mut samples = vec![0; image.data.len()];
+        self.quantize(image, &mut samples, |value| {
+            (65535.0 * value).round() as u16
+        });
+        samples
+    }
+
+    /// Fill in samples for every pixel of a render, from values in [0, 1]
+    fn quantize<T, F: Fn(f32) -> T>(
+        &self,
+        image: &FloatImageBuffer,
+        samples: &mut [T],
+        to_sample: F,
+    ) {
+        let stride = image.format.stride;
+        for (pixel, samples) in image
+            .data
+            .chunks_exact(stride)
+            .zip(samples.chunks_exact_mut(stride))
+        {
+            let color = self.apply([pixel[0], pixel[1], pixel[2]]);
+            for (sample, value) in samples.iter_mut().zip(color) {
+                *sample = to_sample(value);
+            }
+            if stride == 4 {
+                samples[3] = to_sample(pixel[3].clamp(0.0, 1.0));
+            }
+        }
+    }
+}
+
+/// Tone map a color's luminance, scaling its channels to match so that the
+/// hue is kept
+#[inline(always)]
+fn scale_luminance<F: Fn(f32) -> f32>(color: [f32; 3], curve: F) -> [f32; 3] {
+    let luminance = dot(LUMINANCE, color);
+    if luminance <= 0.0 {
+        return [0.0; 3];
+    }
+    let scale = curve(luminance) / luminance;
+    return color.map(|c| c * scale);
+}
+
+fn aces(color: [f32; 3]) -> [f32; 3] {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f32; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f32; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let color = mul(INPUT, color).map(|c| {
+        let a = c * (c + 0.0245786) - 0.000090537;
+        let b = c * (0.983729 * c + 0.432951) + 0.238081;
+        a / b
+    });
+    return mul(OUTPUT, color);
+}
+
+fn agx(color: [f32; 3]) -> [f32; 3] {
+    const INSET: [[f32; 3]; 3] = [
+        [0.842479, 0.078434, 0.079224],
+        [0.042328, 0.878469, 0.079166],
+        [0.042376, 0.078434, 0.879143],
+    ];
+    const OUTSET: [[f32; 3]; 3] = [
+        [1.196879, -0.098021, -0.099030],
+        [-0.052897, 1.151903, -0.098961],
+        [-0.052972, -0.098043, 1.151074],
+    ];
+    // the range of stops around middle grey that the curve covers
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+
+    let color = mul(INSET, color).map(|c| {
+        let c = ((c.max(1e-10).log2() - MIN_EV) / (MAX_EV - MIN_EV)).clamp(0.0, 1.0);
+        // a polynomial fit of the sigmoid contrast curve
+        let c2 = c * c;
+        let c4 = c2 * c2;
+        15.5 * c4 * c2 - 40.14 * c4 * c + 31.96 * c4 - 6.868 * c2 * c + 0.4298 * c2 + 0.1191 * c
+            - 0.00232
+    });
+    // the curve's output is display encoded with a 2.2 gamma, so undo that to
+    // leave the transfer function to the display transform
+    return mul(OUTSET, color).map(|c| c.max(0.0).powf(2.2));
+}
+
+#[inline(always)]
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline(always)]
+fn mul(matrix: [[f32; 3]; 3], color: [f32; 3]) -> [f32; 3] {
+    return matrix.map(|row| dot(row, color));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::buffer::BufferFormat;
+
+    use super::*;
+
+    const GREY: [f32; 3] = [0.18, 0.18, 0.18];
+
+    #[test]
+    fn when_apply_given_default_transform_encodes_srgb() {
+        let transform = DisplayTransform::default();
+        let [r, g, b] = transform.apply([0.0, 0.18, 4.0]);
+        assert_eq!(r, 0.0);
+        // middle grey is a bit under half way in sRGB
+        assert!((g - 0.4613).abs() < 1e-3);
+        assert!((b - 1.0).abs() < 1e-6);
+        let gamma = DisplayTransform::new(0.0, ToneMap::Clamp, TransferFunction::Gamma(2.2));
+        assert!((gamma.apply(GREY)[0] - 0.18f32.powf(1.0 / 2.2)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn when_apply_given_exposure_scales_by_stops() {
+        let linear = TransferFunction::Gamma(1.0);
+        let transform = DisplayTransform::new(1.0, ToneMap::Clamp, linear);
+        assert!((transform.apply(GREY)[0] - 0.36).abs() < 1e-6);
+        let transform = DisplayTransform::new(-2.0, ToneMap::Clamp, linear);
+        assert!((transform.apply(GREY)[0] - 0.045).abs() < 1e-6);
+    }
+
+    #[test]
+    fn when_apply_given_tone_map_keeps_highlights_below_white() {
+        let linear = TransferFunction::Gamma(1.0);
+        let reinhard = DisplayTransform::new(0.0, ToneMap::Reinhard, linear);
+        assert!((reinhard.apply([1.0; 3])[0] - 0.5).abs() < 1e-6);
+        let extended = ToneMap::ExtendedReinhard { white_point: 4.0 };
+        let extended = DisplayTransform::new(0.0, extended, linear);
+        assert!((extended.apply([4.0; 3])[0] - 1.0).abs() < 1e-6);
+
+        for tone_map in [ToneMap::Reinhard, ToneMap::Aces, ToneMap::Agx] {
+            let transform = DisplayTransform::new(0.0, tone_map, linear);
+            let dim = transform.apply([2.0; 3])[0];
+            let bright = transform.apply([8.0; 3])[0];
+            assert!(
+                dim < bright && bright < 1.0,
+                "{:?} should keep increasing below white, got {} and {}",
+                tone_map,
+                dim,
+                bright
+            );
+            assert!(transform.apply([0.0; 3])[0] < 0.01);
+        }
+    }
+
+    #[test]
+    fn when_to_rgb8_given_rgba_image_keeps_alpha() {
+        let mut image = FloatImageBuffer::new_rgba(1, 1);
+        image.data = vec![1e9, 0.0, -1.0, 0.5];
+        let converted = DisplayTransform::default().to_rgb8(&image);
+        assert_eq!(converted.format, BufferFormat::RGBA8);
+        assert_eq!(converted.data, vec![255, 0, 0, 128]);
+    }
+}
@@ -0,0 +1,320 @@
+//! Reader for Radiance HDR (RGBE) images
+//!
+//! HDR files store each pixel as an 8-bit mantissa per channel with a shared
+//! 8-bit exponent, which is enough to hold the range of real light sources.
+//! They're the usual format for environment maps.
+//!
+//! ```text
+//! #?RADIANCE
+//! FORMAT=32-bit_rle_rgbe
+//!
+//! -Y 512 +X 1024
+//! <scanlines>
+//! ```
+//!
+//! Scanlines may be flat, or use either of the run-length encodings. Only the
+//! standard top-to-bottom, left-to-right orientation is supported.
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use super::buffer::{BufferFormat, FloatImageBuffer};
+
+const SUPPORTED_FORMAT: &str = "32-bit_rle_rgbe";
+/// Anything bigger is more likely to be a corrupt header than a real image
+const MAX_PIXELS: u64 = 1 << 28;
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io(io::Error),
+    /// The file didn't start with a Radiance header
+    NotHdr,
+    /// The pixel format wasn't RGBE, eg XYZE
+    UnsupportedFormat(String),
+    /// The resolution line was missing, malformed, or in an unusual orientation
+    InvalidResolution(String),
+    /// A scanline's run-length encoding didn't add up
+    InvalidScanline(usize),
+}
+
+impl Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::NotHdr => write!(f, "not a Radiance HDR file"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "unsupported pixel format {:?}", format)
+            }
+            Self::InvalidResolution(line) => {
+                write!(f, "unsupported resolution line {:?}", line)
+            }
+            Self::InvalidScanline(row) => write!(f, "corrupt scanline at row {}", row),
+        }
+    }
+}
+
+impl Error for HdrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Option::Some(err),
+            _ => Option::None,
+        }
+    }
+}
+
+impl From<io::Error> for HdrError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Read and decode an HDR file from disk
+pub fn load_hdr<P: AsRef<Path>>(path: P) -> Result<FloatImageBuffer, HdrError> {
+    let file = File::open(path)?;
+    read_hdr(BufReader::new(file))
+}
+
+/// Decode an HDR image from a reader
+pub fn read_hdr<R: BufRead>(mut reader: R) -> Result<FloatImageBuffer, HdrError> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !(line.starts_with("#?RADIANCE") || line.starts_with("#?RGBE")) {
+        return Err(HdrError::NotHdr);
+    }
+
+    // header variables, up to the blank line
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(HdrError::InvalidResolution(String::new()));
+        }
+        let variable = line.trim();
+        if variable.is_empty() {
+            break;
+        }
+        if let Option::Some(format) = variable.strip_prefix("FORMAT=") {
+            if format != SUPPORTED_FORMAT {
+                return Err(HdrError::UnsupportedFormat(format.to_string()));
+            }
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (width, height) = parse_resolution(line.trim())?;
+
+    // grow the image a row at a time, so a truncated file fails at the end of
+    // its data rather than after allocating the whole image
+    let mut data = Vec::with_capacity(width * 3);
+    let mut scanline = vec![[0u8; 4]; width];
+    for row in 0..height {
+        read_scanline(&mut reader, &mut scanline).map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidData => HdrError::InvalidScanline(row),
+            _ => HdrError::Io(err),
+        })?;
+        for rgbe in &scanline {
+            data.extend_from_slice(&rgbe_to_float(*rgbe));
+        }
+    }
+
+    Ok(FloatImageBuffer {
+        format: BufferFormat::RGB32F,
+        width,
+        height,
+        data,
+    })
+}
+
+fn parse_resolution(line: &str) -> Result<(usize, usize), HdrError> {
+    let invalid = || HdrError::InvalidResolution(line.to_string());
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    match tokens[..] {
+        ["-Y", height, "+X", width] => {
+            let height: usize = height.parse().map_err(|_| invalid())?;
+            let width: usize = width.parse().map_err(|_| invalid())?;
+            // counted in u64, as usize is only 32 bits on the web
+            let pixels = (width as u64).checked_mul(height as u64);
+            if pixels.is_none_or(|pixels| pixels == 0 || pixels > MAX_PIXELS) {
+                return Err(invalid());
+            }
+            Ok((width, height))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn read_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    if width == 0 {
+        return Ok(());
+    }
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+
+    // the newer encoding stores each channel separately, and is flagged by a
+    // pixel that would otherwise be meaningless
+    let is_new_rle = (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2;
+    if is_new_rle {
+        if first[2] & 0x80 != 0 || ((first[2] as usize) << 8 | first[3] as usize) != width {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        return read_new_rle_scanline(reader, scanline);
+    }
+
+    scanline[0] = first;
+    read_old_scanline(reader, scanline)
+}
+
+/// Read a flat scanline, which may contain old-style runs
+fn read_old_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let mut x = 1;
+    let mut shift = 0;
+    while x < scanline.len() {
+        let mut pixel = [0u8; 4];
+        reader.read_exact(&mut pixel)?;
+        if pixel[0] == 1 && pixel[1] == 1 && pixel[2] == 1 {
+            // repeat the previous pixel, with consecutive runs counting in
+            // increasingly significant bytes. Runs of nothing would only
+            // shift the count past the size of any scanline.
+            let count = match (pixel[3] as usize).checked_shl(shift) {
+                Option::Some(count) if count > 0 && count <= scanline.len() - x => count,
+                _ => return Err(io::ErrorKind::InvalidData.into()),
+            };
+            let previous = scanline[x - 1];
+            scanline[x..x + count].fill(previous);
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = pixel;
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(())
+}
+
+fn read_new_rle_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let count = count[0] as usize;
+            if count > 128 {
+                // a run of one value
+                let count = count - 128;
+                if x + count > width {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+                x += count;
+            } else {
+                // a run of literal values
+                if count == 0 || x + count > width {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                let mut values = [0u8; 128];
+                reader.read_exact(&mut values[..count])?;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                x += count;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[inline(always)]
+fn rgbe_to_float(rgbe: [u8; 4]) -> [f32; 3] {
+    let [r, g, b, e] = rgbe;
+    if e == 0 {
+        return [0.0, 0.0, 0.0];
+    }
+    // the mantissas are fractions of 256, and the exponent is biased by 128
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    return [
+        (r as f32 + 0.5) * scale,
+        (g as f32 + 0.5) * scale,
+        (b as f32 + 0.5) * scale,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_header(width: usize, height: usize) -> Vec<u8> {
+        format!(
+            "#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn when_read_hdr_given_flat_scanlines_returns_image() {
+        let mut file = make_header(2, 2);
+        // 1.0, 0.5, 2.0 and black
+        file.extend_from_slice(&[128, 128, 128, 129, 128, 128, 128, 128]);
+        file.extend_from_slice(&[128, 128, 128, 130, 0, 0, 0, 0]);
+        let image = read_hdr(&file[..]).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert!((image.pixel(0, 0)[0] - 1.0).abs() < 0.01);
+        assert!((image.pixel(1, 0)[1] - 0.5).abs() < 0.01);
+        assert!((image.pixel(0, 1)[2] - 2.0).abs() < 0.02);
+        assert_eq!(image.pixel(1, 1), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn when_read_hdr_given_rle_scanlines_returns_image() {
+        let width = 10;
+        let mut file = make_header(width, 1);
+        file.extend_from_slice(&[2, 2, 0, width as u8]);
+        // red: a run of 10 values
+        file.extend_from_slice(&[128 + 10, 64]);
+        // green: 2 literals, then a run of 8
+        file.extend_from_slice(&[2, 10, 20, 128 + 8, 0]);
+        // blue and exponent: runs of 10
+        file.extend_from_slice(&[128 + 10, 0, 128 + 10, 129]);
+        let image = read_hdr(&file[..]).unwrap();
+        assert_eq!(image.width, width);
+        let first = image.pixel(0, 0);
+        let last = image.pixel(9, 0);
+        assert!((first[0] - 0.5).abs() < 0.01 && (last[0] - 0.5).abs() < 0.01);
+        assert!(first[1] > 0.0 && last[1] < first[1]);
+    }
+
+    #[test]
+    fn when_read_hdr_given_xyze_image_returns_error() {
+        let file = b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\x80\x80\x80\x80";
+        let err = read_hdr(&file[..]).unwrap_err();
+        assert!(matches!(err, HdrError::UnsupportedFormat(_)));
+    }
+
+    #[test]
+    fn when_read_hdr_given_malformed_file_returns_error() {
+        for (width, height) in [(0, 0), (1 << 20, 1 << 20), (usize::MAX, 2)] {
+            let err = read_hdr(&make_header(width, height)[..]).unwrap_err();
+            assert!(matches!(err, HdrError::InvalidResolution(_)));
+        }
+
+        // a pixel, then runs of nothing that would shift the count too far
+        let mut file = make_header(2, 1);
+        file.extend_from_slice(&[128, 128, 128, 129]);
+        for _ in 0..10 {
+            file.extend_from_slice(&[1, 1, 1, 0]);
+        }
+        let err = read_hdr(&file[..]).unwrap_err();
+        assert!(matches!(err, HdrError::InvalidScanline(0)));
+    }
+}
@@ -0,0 +1,277 @@
+//! Loading PNG, JPEG and Radiance HDR images into linear float buffers
+//!
+//! The format is picked from the file's contents rather than its extension.
+//! Images can be decoded straight from bytes with `decode_image`, which is
+//! what the WASM build uses since it has no filesystem.
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use super::{
+    buffer::FloatImageBuffer,
+    hdr::{read_hdr, HdrError},
+};
+
+/// How the values stored in an 8 or 16-bit image map onto linear values
+///
+/// HDR images are always linear, so this is ignored for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// sRGB-encoded color, which is what most color images (like albedo maps)
+    /// are stored as
+    Srgb,
+    /// Raw data, like roughness or normal maps, which shouldn't be decoded
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Hdr,
+}
+
+impl ImageFormat {
+    /// Recognize an image format from the first few bytes of a file
+    pub fn detect(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Option::Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Option::Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+            Option::Some(ImageFormat::Hdr)
+        } else {
+            Option::None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The image wasn't a PNG, JPEG or HDR file
+    UnknownFormat,
+    Png(png::DecodingError),
+    Jpeg(jpeg_decoder::Error),
+    Hdr(HdrError),
+    /// The image decoded, but had no pixels
+    Empty,
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::UnknownFormat => write!(f, "not a PNG, JPEG or Radiance HDR image"),
+            Self::Png(err) => write!(f, "invalid PNG: {}", err),
+            Self::Jpeg(err) => write!(f, "invalid JPEG: {}", err),
+            Self::Hdr(err) => write!(f, "invalid HDR: {}", err),
+            Self::Empty => write!(f, "image has no pixels"),
+        }
+    }
+}
+
+impl Error for ImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Option::Some(err),
+            Self::Png(err) => Option::Some(err),
+            Self::Jpeg(err) => Option::Some(err),
+            Self::Hdr(err) => Option::Some(err),
+            Self::UnknownFormat | Self::Empty => Option::None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Read and decode an image from disk
+pub fn load_image<P: AsRef<Path>>(
+    path: P,
+    color_space: ColorSpace,
+) -> Result<FloatImageBuffer, ImageError> {
+    let bytes = fs::read(path)?;
+    decode_image(&bytes, color_space)
+}
+
+/// Decode an image that's already in memory
+///
+/// Images with no pixels are an error, so that textures and environment maps
+/// always have something to sample.
+pub fn decode_image(bytes: &[u8], color_space: ColorSpace) -> Result<FloatImageBuffer, ImageError> {
+    let image = match ImageFormat::detect(bytes) {
+        Option::Some(ImageFormat::Png) => decode_png(bytes, color_space)?,
+        Option::Some(ImageFormat::Jpeg) => decode_jpeg(bytes, color_space)?,
+        Option::Some(ImageFormat::Hdr) => read_hdr(Cursor::new(bytes)).map_err(ImageError::Hdr)?,
+        Option::None => return Err(ImageError::UnknownFormat),
+    };
+    if image.width == 0 || image.height == 0 {
+        return Err(ImageError::Empty);
+    }
+    Ok(image)
+}
+
+fn decode_png(bytes: &[u8], color_space: ColorSpace) -> Result<FloatImageBuffer, ImageError> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    // unpack palettes and low bit depths, but keep 16-bit samples as they are
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(ImageError::Png)?;
+    let mut data = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(ImageError::Png)?;
+
+    let channels = info.color_type.samples();
+    let samples: Vec<f32> = match info.bit_depth {
+        png::BitDepth::Sixteen => data[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|sample| decode_sample(u16::from_be_bytes([sample[0], sample[1]]), color_space))
+            .collect(),
+        _ => {
+            let table = make_decode_table(color_space);
+            data[..info.buffer_size()]
+                .iter()
+                .map(|&sample| table[sample as usize])
+                .collect()
+        }
+    };
+    Ok(to_rgb(
+        &samples,
+        info.width as usize,
+        info.height as usize,
+        channels,
+    ))
+}
+
+fn decode_jpeg(bytes: &[u8], color_space: ColorSpace) -> Result<FloatImageBuffer, ImageError> {
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(bytes));
+    let data = decoder.decode().map_err(ImageError::Jpeg)?;
+    // info is always there after a successful decode
+    let info = decoder.info().unwrap();
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    let table = make_decode_table(color_space);
+    let image = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            let samples: Vec<f32> = data.iter().map(|&s| table[s as usize]).collect();
+            to_rgb(&samples, width, height, 1)
+        }
+        jpeg_decoder::PixelFormat::L16 => {
+            let samples: Vec<f32> = data
+                .chunks_exact(2)
+                .map(|s| decode_sample(u16::from_be_bytes([s[0], s[1]]), color_space))
+                .collect();
+            to_rgb(&samples, width, height, 1)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            let samples: Vec<f32> = data.iter().map(|&s| table[s as usize]).collect();
+            to_rgb(&samples, width, height, 3)
+        }
+        jpeg_decoder::PixelFormat::CMYK32 => {
+            // Adobe's CMYK JPEGs store inverted values, so this is just a
+            // multiply by the key
+            let samples: Vec<f32> = data
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = cmyk[3] as u32;
+                    [0, 1, 2].map(|i| table[(cmyk[i] as u32 * k / 255) as usize])
+                })
+                .collect();
+            to_rgb(&samples, width, height, 3)
+        }
+    };
+    Ok(image)
+}
+
+/// Convert grey, grey-alpha, RGB or RGBA samples into an RGB image. Alpha is
+/// dropped.
+fn to_rgb(samples: &[f32], width: usize, height: usize, channels: usize) -> FloatImageBuffer {
+    let mut image = FloatImageBuffer::new_rgb(width, height);
+    for (idx, pixel) in samples.chunks_exact(channels).enumerate() {
+        let color = match channels {
+            1 | 2 => [pixel[0], pixel[0], pixel[0]],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        };
+        image.set_pixel(idx % width, idx / width, color);
+    }
+    image
+}
+
+/// A lookup table from 8-bit values to linear floats
+fn make_decode_table(color_space: ColorSpace) -> [f32; 256] {
+    let mut table = [0.0; 256];
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = decode_sample(value as u16 * 257, color_space);
+    }
+    table
+}
+
+/// Convert a 16-bit sample to a linear float
+#[inline(always)]
+fn decode_sample(value: u16, color_space: ColorSpace) -> f32 {
+    let value = value as f32 / u16::MAX as f32;
+    match color_space {
+        ColorSpace::Srgb => srgb_to_linear(value),
+        ColorSpace::Linear => value,
+    }
+}
+
+/// The sRGB electro-optical transfer function, from IEC 61966-2-1
+#[inline(always)]
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a small 8-bit image as a PNG
+    fn make_png(width: u32, height: u32, color_type: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn when_decode_image_given_srgb_png_returns_linear_values() {
+        let png = make_png(2, 1, png::ColorType::Rgb, &[255, 128, 0, 0, 0, 255]);
+        let image = decode_image(&png, ColorSpace::Srgb).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        let [r, g, b] = image.pixel(0, 0);
+        assert_eq!((r, b), (1.0, 0.0));
+        // sRGB 128 is about 21.6% as bright as white, not 50%
+        assert!((g - 0.2158).abs() < 1e-3);
+
+        let raw = decode_image(&png, ColorSpace::Linear).unwrap();
+        assert!((raw.pixel(0, 0)[1] - 128.0 / 255.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn when_decode_image_given_grey_alpha_png_returns_grey_rgb() {
+        let png = make_png(1, 1, png::ColorType::GrayscaleAlpha, &[255, 0]);
+        let image = decode_image(&png, ColorSpace::Srgb).unwrap();
+        assert_eq!(image.pixel(0, 0), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn when_decode_image_given_unknown_bytes_returns_error() {
+        let err = decode_image(b"GIF89a", ColorSpace::Srgb).unwrap_err();
+        assert!(matches!(err, ImageError::UnknownFormat));
+    }
+}
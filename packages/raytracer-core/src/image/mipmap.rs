@@ -0,0 +1,229 @@
+//! Pre-filtered chains of images for sampling textures without aliasing
+//!
+//! Each level of a mip map is half the size of the one before it, down to a
+//! single pixel. Sampling picks the levels whose pixels are about as big as
+//! the area being looked up, so distant textures blur instead of shimmering.
+use super::buffer::FloatImageBuffer;
+
+/// What happens to texture coordinates outside of [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// Tile the image
+    #[default]
+    Repeat,
+    /// Stretch the edge pixels outwards
+    Clamp,
+    /// Tile the image, flipping every other copy so that edges line up
+    Mirror,
+}
+
+impl WrapMode {
+    /// Wrap a pixel index into [0, size)
+    #[inline(always)]
+    pub fn wrap(&self, idx: i64, size: usize) -> usize {
+        let size = size as i64;
+        let idx = match self {
+            WrapMode::Repeat => idx.rem_euclid(size),
+            WrapMode::Clamp => idx.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let idx = idx.rem_euclid(2 * size);
+                if idx < size {
+                    idx
+                } else {
+                    2 * size - 1 - idx
+                }
+            }
+        };
+        return idx as usize;
+    }
+}
+
+/// How a mip map blends pixels together
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// The closest pixel of the full-size image
+    Nearest,
+    /// The 4 closest pixels of the full-size image
+    Bilinear,
+    /// Bilinear samples of the two levels nearest the footprint, blended
+    #[default]
+    Trilinear,
+}
+
+pub struct MipMap {
+    /// The full-size image first, then each level down to 1x1
+    levels: Vec<FloatImageBuffer>,
+    wrap: WrapMode,
+}
+
+impl MipMap {
+    pub fn new(image: FloatImageBuffer, wrap: WrapMode) -> MipMap {
+        assert!(
+            image.width > 0 && image.height > 0,
+            "Mip maps can't be empty"
+        );
+        let mut levels = vec![image];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = downsample(last);
+            levels.push(next);
+        }
+        MipMap { levels, wrap }
+    }
+
+    pub fn levels(&self) -> &[FloatImageBuffer] {
+        &self.levels
+    }
+
+    pub fn wrap(&self) -> WrapMode {
+        self.wrap
+    }
+
+    /// Look up a texture coordinate, where (0, 0) is the top-left corner of
+    /// the image
+    ///
+    /// The footprint is roughly how wide the area being looked up is, as a
+    /// fraction of the image. It's only used by trilinear filtering.
+    pub fn sample(&self, u: f64, v: f64, footprint: f64, filter: Filter) -> [f32; 3] {
+        match filter {
+            Filter::Nearest => self.nearest(0, u, v),
+            Filter::Bilinear => self.bilinear(0, u, v),
+            Filter::Trilinear => {
+                let image = &self.levels[0];
+                let texels = footprint * image.width.max(image.height) as f64;
+                let lod = if texels > 1.0 { texels.log2() } else { 0.0 };
+                let lod = lod.min((self.levels.len() - 1) as f64);
+                let level = lod.floor() as usize;
+                let t = (lod - level as f64) as f32;
+                let fine = self.bilinear(level, u, v);
+                if t == 0.0 {
+                    return fine;
+                }
+                let coarse = self.bilinear(level + 1, u, v);
+                return [0, 1, 2].map(|i| fine[i] * (1.0 - t) + coarse[i] * t);
+            }
+        }
+    }
+
+    fn nearest(&self, level: usize, u: f64, v: f64) -> [f32; 3] {
+        let image = &self.levels[level];
+        let x = (u * image.width as f64).floor() as i64;
+        let y = (v * image.height as f64).floor() as i64;
+        return image.pixel(
+            self.wrap.wrap(x, image.width),
+            self.wrap.wrap(y, image.height),
+        );
+    }
+
+    fn bilinear(&self, level: usize, u: f64, v: f64) -> [f32; 3] {
+        let image = &self.levels[level];
+        // pixel centers sit at half-integer coordinates
+        let x = u * image.width as f64 - 0.5;
+        let y = v * image.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = ((x - x0) as f32, (y - y0) as f32);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let pixel = |px: i64, py: i64| {
+            image.pixel(
+                self.wrap.wrap(px, image.width),
+                self.wrap.wrap(py, image.height),
+            )
+        };
+        let (p00, p10) = (pixel(x0, y0), pixel(x0 + 1, y0));
+        let (p01, p11) = (pixel(x0, y0 + 1), pixel(x0 + 1, y0 + 1));
+        return [0, 1, 2].map(|i| {
+            let top = p00[i] * (1.0 - tx) + p10[i] * tx;
+            let bottom = p01[i] * (1.0 - tx) + p11[i] * tx;
+            top * (1.0 - ty) + bottom * ty
+        });
+    }
+}
+
+/// Halve an image with a box filter. Odd sizes round down, with each output
+/// pixel averaging every input pixel it overlaps.
+fn downsample(image: &FloatImageBuffer) -> FloatImageBuffer {
+    let width = (image.width / 2).max(1);
+    let height = (image.height / 2).max(1);
+    // the input pixels that overlap output pixel idx
+    let span = |idx: usize, from: usize, to: usize| {
+        let start = idx * from / to;
+        let end = ((idx + 1) * from).div_ceil(to);
+        start..end
+    };
+    let mut result = FloatImageBuffer::new_rgb(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 3];
+            let mut count = 0.0;
+            for sy in span(y, image.height, height) {
+                for sx in span(x, image.width, width) {
+                    let pixel = image.pixel(sx, sy);
+                    sum = [0, 1, 2].map(|i| sum[i] + pixel[i]);
+                    count += 1.0;
+                }
+            }
+            result.set_pixel(x, y, sum.map(|c| c / count));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A checkerboard of black and white pixels
+    fn make_checkerboard(width: usize, height: usize) -> FloatImageBuffer {
+        let mut image = FloatImageBuffer::new_rgb(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let value = ((x + y) % 2) as f32;
+                image.set_pixel(x, y, [value; 3]);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn when_new_given_odd_sized_image_builds_levels_down_to_one_pixel() {
+        let mipmap = MipMap::new(make_checkerboard(5, 3), WrapMode::Repeat);
+        let sizes: Vec<(usize, usize)> = mipmap
+            .levels()
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(sizes, vec![(5, 3), (2, 1), (1, 1)]);
+        // a checkerboard averages out to grey
+        let [r, _, _] = mipmap.levels()[2].pixel(0, 0);
+        assert!((r - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn when_wrap_given_out_of_range_index_returns_wrapped_index() {
+        assert_eq!(WrapMode::Repeat.wrap(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.wrap(5, 4), 1);
+        assert_eq!(WrapMode::Clamp.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Clamp.wrap(5, 4), 3);
+        assert_eq!(WrapMode::Mirror.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.wrap(5, 4), 2);
+        assert_eq!(WrapMode::Mirror.wrap(8, 4), 0);
+    }
+
+    #[test]
+    fn when_sample_given_wide_footprint_returns_blurred_color() {
+        let mipmap = MipMap::new(make_checkerboard(8, 8), WrapMode::Repeat);
+        // the center of the first pixel
+        let (u, v) = (1.0 / 16.0, 1.0 / 16.0);
+        assert_eq!(mipmap.sample(u, v, 0.0, Filter::Trilinear), [0.0; 3]);
+        assert_eq!(mipmap.sample(u, v, 1.0, Filter::Bilinear), [0.0; 3]);
+        // a footprint the size of the whole image averages all of it
+        let [r, _, _] = mipmap.sample(u, v, 1.0, Filter::Trilinear);
+        assert!((r - 0.5).abs() < 1e-6);
+        // in between, the levels are blended
+        let [r, _, _] = mipmap.sample(u, v, 1.5 / 8.0, Filter::Trilinear);
+        assert!(r > 0.0 && r < 0.5);
+    }
+}
@@ -1,3 +1,11 @@
 pub mod blend;
 pub mod buffer;
+pub mod compare;
+pub mod display;
+pub mod hdr;
+pub mod loader;
+pub mod mipmap;
+pub mod openexr;
+pub mod pfm;
 pub mod ppm;
+pub mod writer;
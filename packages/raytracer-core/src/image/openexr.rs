@@ -0,0 +1,138 @@
+//! Writing OpenEXR images, for compositing renders in other tools
+//!
+//! EXR files hold any number of named layers, each with their own channels.
+//! Layers here are RGB or RGBA, stored as either half or full floats.
+use std::io::{self, Cursor, Write};
+
+use exr::prelude::{
+    f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, ImageAttributes, IntegerBounds,
+    Layer, LayerAttributes, SmallVec, Vec2, WritableImage,
+};
+
+use super::buffer::FloatImageBuffer;
+
+/// How many bits each sample is stored with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrPrecision {
+    /// 16-bit floats, which are plenty for color and half the size
+    Half,
+    /// 32-bit floats, for data like depth that needs the precision
+    Float,
+}
+
+/// Write a single image as an EXR with one unnamed layer
+pub fn write_exr<W: Write>(
+    writer: &mut W,
+    image: &FloatImageBuffer,
+    precision: ExrPrecision,
+) -> io::Result<()> {
+    write_exr_layers(writer, &[("", image)], precision)
+}
+
+/// Write several images as the layers of one EXR, eg a beauty pass alongside
+/// AOVs. Every layer must be the same size.
+///
+/// Each layer is stored as its own part of the file, with the given name.
+/// An empty name leaves the layer unnamed.
+pub fn write_exr_layers<W: Write>(
+    writer: &mut W,
+    layers: &[(&str, &FloatImageBuffer)],
+    precision: ExrPrecision,
+) -> io::Result<()> {
+    assert!(!layers.is_empty(), "EXRs need at least one layer");
+    let (_, first) = layers[0];
+    let size = Vec2(first.width, first.height);
+
+    let layers: SmallVec<[_; 2]> = layers
+        .iter()
+        .map(|(name, image)| {
+            assert!(
+                image.width == first.width && image.height == first.height,
+                "Every EXR layer must be the same size"
+            );
+            let names = ["R", "G", "B", "A"];
+            let channels = (0..image.format.stride)
+                .map(|channel| {
+                    let samples = image.channel(channel);
+                    let samples = match precision {
+                        ExrPrecision::Half => {
+                            FlatSamples::F16(samples.into_iter().map(f16::from_f32).collect())
+                        }
+                        ExrPrecision::Float => FlatSamples::F32(samples),
+                    };
+                    AnyChannel::new(names[channel], samples)
+                })
+                .collect();
+            let attributes = if name.is_empty() {
+                LayerAttributes::default()
+            } else {
+                LayerAttributes::named(*name)
+            };
+            Layer::new(
+                size,
+                attributes,
+                Encoding::FAST_LOSSLESS,
+                AnyChannels::sort(channels),
+            )
+        })
+        .collect();
+    let image = Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+        layers,
+    );
+
+    // EXRs are written out of order, so they need to be put together in
+    // memory for writers that can't seek, like stdout
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write()
+        .to_buffered(&mut buffer)
+        .map_err(io::Error::other)?;
+    writer.write_all(buffer.get_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use exr::prelude::{read, ReadChannels, ReadLayers};
+
+    use super::*;
+
+    fn make_image(fill: f32) -> FloatImageBuffer {
+        let mut image = FloatImageBuffer::new_rgba(2, 2);
+        image.data.fill(fill);
+        image
+    }
+
+    #[test]
+    fn when_write_exr_layers_given_two_layers_writes_named_channels() {
+        let beauty = make_image(4.5);
+        let albedo = make_image(0.25);
+        let mut bytes = vec![];
+        write_exr_layers(
+            &mut bytes,
+            &[("beauty", &beauty), ("albedo", &albedo)],
+            ExrPrecision::Half,
+        )
+        .unwrap();
+
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .unwrap();
+        assert_eq!(image.layer_data.len(), 2);
+        let beauty = &image.layer_data[0];
+        assert_eq!(
+            beauty.attributes.layer_name.as_ref().unwrap().to_string(),
+            "beauty"
+        );
+        assert_eq!(beauty.channel_data.list.len(), 4);
+        // values over 1 survive, which they couldn't in an 8-bit image
+        let red = &beauty.channel_data.list[3];
+        assert_eq!(red.name.to_string(), "R");
+        assert_eq!(red.sample_data.value_by_flat_index(0).to_f32(), 4.5);
+    }
+}
@@ -0,0 +1,55 @@
+//! PFM (Portable Float Map) is PPM's floating-point cousin
+//!
+//! ```text
+//! PF
+//! 3 2
+//! -1.0
+//! <raw samples>
+//! ```
+//!
+//! The header is followed by 32-bit floats, 3 per pixel, with rows running
+//! bottom-to-top. A negative scale means the floats are little-endian.
+use std::io::{self, Write};
+
+use super::buffer::FloatImageBuffer;
+
+const PFM_HEADER: &str = "PF";
+
+/// Stream an image out as a little-endian PFM. Any alpha channel is dropped.
+pub fn write_pfm<W: Write>(writer: &mut W, image: &FloatImageBuffer) -> io::Result<()> {
+    write!(
+        writer,
+        "{}\n{} {}\n-1.0\n",
+        PFM_HEADER, image.width, image.height
+    )?;
+    let mut row = Vec::with_capacity(image.width * 3 * 4);
+    for y in (0..image.height).rev() {
+        row.clear();
+        for x in 0..image.width {
+            for sample in image.pixel(x, y) {
+                row.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_rows_bottom_to_top() {
+        let mut image = FloatImageBuffer::new_rgba(1, 2);
+        image.data = vec![1.0, 2.0, 3.0, 0.5, 4.0, 5.0, 6.0, 0.5];
+        let mut result = vec![];
+        write_pfm(&mut result, &image).unwrap();
+
+        let mut expected = b"PF\n1 2\n-1.0\n".to_vec();
+        for sample in [4.0f32, 5.0, 6.0, 1.0, 2.0, 3.0] {
+            expected.extend_from_slice(&sample.to_le_bytes());
+        }
+        assert_eq!(result, expected);
+    }
+}
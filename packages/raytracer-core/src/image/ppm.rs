@@ -11,24 +11,75 @@
 //!
 //! is a valid 3pxx2px image of ASCII triplets read left-to-right, top-to-
 //! bottom.
+//!
+//! The binary variant, P6, has the same header but stores each channel as a
+//! single byte, which is far smaller and faster to read.
+use std::io::{self, Write};
+
+use super::buffer::ImageBuffer;
 
 const PPM_HEADER: &str = "P3";
+const BINARY_PPM_HEADER: &str = "P6";
 const PPM_BITDEPTH: usize = 255;
 const IMG_STRIDE: usize = 3;
 
-pub fn make_image(bitmap: &Vec<u8>, width: usize, height: usize) -> String {
-    let header = format!("{}\n{}\t{}\n{}", PPM_HEADER, width, height, PPM_BITDEPTH);
-    let mut outputs = vec![header];
-    let length = width * height;
-    for i in 0..length {
-        let idx = i * IMG_STRIDE;
+pub fn make_image(bitmap: &[u8], width: usize, height: usize) -> String {
+    let mut output = vec![];
+    write_ascii(&mut output, bitmap, IMG_STRIDE, width, height)
+        .expect("Writing to a Vec can't fail");
+    // every byte written is ASCII
+    return String::from_utf8(output).unwrap();
+}
+
+/// Stream an image out as an ASCII (P3) PPM. Any alpha channel is dropped.
+pub fn write_p3<W: Write>(writer: &mut W, image: &ImageBuffer) -> io::Result<()> {
+    write_ascii(
+        writer,
+        &image.data,
+        image.format.stride,
+        image.width,
+        image.height,
+    )
+}
+
+/// Stream an image out as a binary (P6) PPM. Any alpha channel is dropped.
+pub fn write_p6<W: Write>(writer: &mut W, image: &ImageBuffer) -> io::Result<()> {
+    write!(
+        writer,
+        "{}\n{} {}\n{}\n",
+        BINARY_PPM_HEADER, image.width, image.height, PPM_BITDEPTH
+    )?;
+    let stride = image.format.stride;
+    if stride == IMG_STRIDE {
+        return writer.write_all(&image.data[..image.width * image.height * stride]);
+    }
+    for pixel in image.data.chunks_exact(stride) {
+        writer.write_all(&pixel[..IMG_STRIDE])?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::identity_op)]
+fn write_ascii<W: Write>(
+    writer: &mut W,
+    bitmap: &[u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+) -> io::Result<()> {
+    write!(
+        writer,
+        "{}\n{}\t{}\n{}",
+        PPM_HEADER, width, height, PPM_BITDEPTH
+    )?;
+    for i in 0..width * height {
+        let idx = i * stride;
         let r = bitmap[idx + 0];
         let g = bitmap[idx + 1];
         let b = bitmap[idx + 2];
-        outputs.push(format!("{}\t{}\t{}", r, g, b));
+        write!(writer, "\n{}\t{}\t{}", r, g, b)?;
     }
-    let file = outputs.join("\n");
-    return file;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -45,6 +96,17 @@ mod tests {
 255\t255\t255
 0\t0\t0";
 
+    #[test]
+    fn writes_binary_image_without_alpha() {
+        let mut image = ImageBuffer::new_rgba(2, 1);
+        image.data = vec![255, 0, 0, 128, 0, 0, 255, 128];
+        let mut result = vec![];
+        write_p6(&mut result, &image).unwrap();
+        let mut expected = b"P6\n2 1\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 0, 0, 0, 0, 255]);
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn writes_simple_image() {
         let test_bitmap = vec![
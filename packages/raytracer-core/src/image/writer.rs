@@ -0,0 +1,252 @@
+//! Writing rendered images out as PNG, PPM, OpenEXR or PFM files
+//!
+//! Everything here streams into a `Write`, so images can go straight to a
+//! file or stdout without being copied into one big string first. Renders are
+//! only tone mapped down to 8 bits for the formats that need it.
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use super::{
+    buffer::{convert, BufferFormat, FloatImageBuffer, ImageBuffer},
+    display::DisplayTransform,
+    openexr::{self, ExrPrecision},
+    pfm, ppm,
+};
+
+/// A file format to write images in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// An 8-bit PNG, with alpha for RGBA8 buffers
+    Png,
+    /// A 16-bit PNG, with alpha for RGBA8 buffers
+    Png16,
+    /// A binary (P6) PPM
+    Ppm,
+    /// An ASCII (P3) PPM, which is large but easy to read
+    AsciiPpm,
+    /// An OpenEXR image with half-float samples
+    ExrHalf,
+    /// An OpenEXR image with full-float samples
+    ExrFloat,
+    /// A little-endian PFM
+    Pfm,
+}
+
+impl OutputFormat {
+    /// Pick a format from a file's extension, eg `.png` or `.ppm`
+    ///
+    /// PPMs are always written in binary, and EXRs with half floats. Returns
+    /// None for extensions that aren't recognized.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<OutputFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Option::Some(OutputFormat::Png),
+            "ppm" | "pnm" => Option::Some(OutputFormat::Ppm),
+            "exr" => Option::Some(OutputFormat::ExrHalf),
+            "pfm" => Option::Some(OutputFormat::Pfm),
+            _ => Option::None,
+        }
+    }
+
+    /// Whether the format keeps values above 1, rather than tone mapping them
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::ExrHalf | OutputFormat::ExrFloat | OutputFormat::Pfm
+        )
+    }
+}
+
+/// Write a render in the given format
+///
+/// Formats that can't hold values above 1 get the display transform applied
+/// first. HDR formats are written as-is, so they can be graded later.
+pub fn write_image<W: Write>(
+    writer: &mut W,
+    image: &FloatImageBuffer,
+    format: OutputFormat,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::ExrHalf => openexr::write_exr(writer, image, ExrPrecision::Half),
+        OutputFormat::ExrFloat => openexr::write_exr(writer, image, ExrPrecision::Float),
+        OutputFormat::Pfm => pfm::write_pfm(writer, image),
+        OutputFormat::Png16 => write_png16(writer, image, display),
+        _ => write_ldr_image(writer, &display.to_rgb8(image), format),
+    }
+}
+
+/// Write an 8-bit image in the given format. HDR formats store the 8-bit
+/// values scaled to [0, 1], and 16-bit PNGs widen them.
+pub fn write_ldr_image<W: Write>(
+    writer: &mut W,
+    image: &ImageBuffer,
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Png => write_png(writer, image, png::BitDepth::Eight),
+        OutputFormat::Png16 => write_png(writer, image, png::BitDepth::Sixteen),
+        OutputFormat::Ppm => ppm::write_p6(writer, image),
+        OutputFormat::AsciiPpm => ppm::write_p3(writer, image),
+        _ => write_image(
+            writer,
+            &convert::rgb8_to_float(image),
+            format,
+            &DisplayTransform::default(),
+        ),
+    }
+}
+
+/// Write an image as a PNG, tagged as sRGB
+///
+/// For a 16-bit PNG, each 8-bit value is widened to cover the full 16-bit
+/// range, so that white stays white.
+pub fn write_png<W: Write>(
+    writer: &mut W,
+    image: &ImageBuffer,
+    bit_depth: png::BitDepth,
+) -> io::Result<()> {
+    let has_alpha = image.format == BufferFormat::RGBA8;
+    let mut png_writer = png_header(writer, image.width, image.height, has_alpha, bit_depth)?;
+    let mut stream = png_writer.stream_writer()?;
+
+    let row_length = image.width * image.format.stride;
+    let mut row = Vec::with_capacity(row_length * 2);
+    for samples in image.data.chunks_exact(row_length).take(image.height) {
+        row.clear();
+        match bit_depth {
+            png::BitDepth::Sixteen => {
+                for &sample in samples {
+                    row.extend_from_slice(&(sample as u16 * 257).to_be_bytes());
+                }
+            }
+            _ => row.extend_from_slice(samples),
+        }
+        stream.write_all(&row)?;
+    }
+    stream.finish()?;
+    Ok(())
+}
+
+/// Write a render as a 16-bit PNG, tagged as sRGB, applying the display
+/// transform straight to 16-bit samples so that smooth gradients don't band
+pub fn write_png16<W: Write>(
+    writer: &mut W,
+    image: &FloatImageBuffer,
+    display: &DisplayTransform,
+) -> io::Result<()> {
+    let mut png_writer = png_header(
+        writer,
+        image.width,
+        image.height,
+        image.has_alpha(),
+        png::BitDepth::Sixteen,
+    )?;
+    let mut stream = png_writer.stream_writer()?;
+
+    let samples = display.to_rgb16(image);
+    let row_length = image.width * image.format.stride;
+    let mut row = Vec::with_capacity(row_length * 2);
+    for samples in samples.chunks_exact(row_length).take(image.height) {
+        row.clear();
+        for &sample in samples {
+            row.extend_from_slice(&sample.to_be_bytes());
+        }
+        stream.write_all(&row)?;
+    }
+    stream.finish()?;
+    Ok(())
+}
+
+fn png_header<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    has_alpha: bool,
+    bit_depth: png::BitDepth,
+) -> io::Result<png::Writer<W>> {
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(if has_alpha {
+        png::ColorType::Rgba
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_depth(bit_depth);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    Ok(encoder.write_header()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::image::display::{ToneMap, TransferFunction};
+
+    use super::*;
+
+    /// Decode a PNG, returning its color type, bit depth and samples
+    fn read_png(bytes: &[u8]) -> (png::ColorType, png::BitDepth, Vec<u8>) {
+        let decoder = png::Decoder::new(bytes);
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        data.truncate(info.buffer_size());
+        (info.color_type, info.bit_depth, data)
+    }
+
+    #[test]
+    fn when_write_image_given_rgba_png_keeps_alpha() {
+        let mut image = ImageBuffer::new_rgba(2, 1);
+        image.data = vec![255, 0, 0, 128, 0, 0, 255, 255];
+        let mut bytes = vec![];
+        write_ldr_image(&mut bytes, &image, OutputFormat::Png).unwrap();
+        let (color_type, bit_depth, data) = read_png(&bytes);
+        assert_eq!(color_type, png::ColorType::Rgba);
+        assert_eq!(bit_depth, png::BitDepth::Eight);
+        assert_eq!(data, image.data);
+    }
+
+    #[test]
+    fn when_write_image_given_16_bit_png_widens_samples() {
+        let mut image = ImageBuffer::new_rgb(1, 1);
+        image.data = vec![255, 128, 0];
+        let mut bytes = vec![];
+        write_ldr_image(&mut bytes, &image, OutputFormat::Png16).unwrap();
+        let (color_type, bit_depth, data) = read_png(&bytes);
+        assert_eq!(color_type, png::ColorType::Rgb);
+        assert_eq!(bit_depth, png::BitDepth::Sixteen);
+        assert_eq!(data, vec![0xff, 0xff, 0x80, 0x80, 0, 0]);
+    }
+
+    #[test]
+    fn when_write_image_given_16_bit_png_keeps_full_precision() {
+        let mut image = FloatImageBuffer::new_rgb(1, 1);
+        image.set_pixel(0, 0, [1.0, 0.5, 0.0]);
+        let display = DisplayTransform::new(0.0, ToneMap::Clamp, TransferFunction::Gamma(1.0));
+        let mut bytes = vec![];
+        write_image(&mut bytes, &image, OutputFormat::Png16, &display).unwrap();
+        let (color_type, bit_depth, data) = read_png(&bytes);
+        assert_eq!(color_type, png::ColorType::Rgb);
+        assert_eq!(bit_depth, png::BitDepth::Sixteen);
+        // half way is 0x8000, where going through 8 bits would give 0x8080
+        assert_eq!(data, vec![0xff, 0xff, 0x80, 0x00, 0, 0]);
+    }
+
+    #[test]
+    fn when_from_path_given_extension_returns_format() {
+        assert_eq!(
+            OutputFormat::from_path("out/render.PNG"),
+            Option::Some(OutputFormat::Png)
+        );
+        assert_eq!(
+            OutputFormat::from_path("render.ppm"),
+            Option::Some(OutputFormat::Ppm)
+        );
+        assert_eq!(
+            OutputFormat::from_path("render.exr"),
+            Option::Some(OutputFormat::ExrHalf)
+        );
+        assert_eq!(OutputFormat::from_path("render.gif"), Option::None);
+        assert_eq!(OutputFormat::from_path("render"), Option::None);
+    }
+}
@@ -1,11 +1,20 @@
+// explicit returns are house style, so clippy is kept quiet about them. Any
+// other lint is allowed only where it's needed.
+#![allow(clippy::needless_return)]
+
+pub mod background;
 pub mod geometry;
 pub mod image;
+pub mod light;
 pub mod render;
+pub mod sampler;
 pub mod scene;
 pub mod shader;
+pub mod texture;
+pub mod volume;
 
 #[cfg(feature = "wasm")]
 mod wasm_util;
 
-#[cfg(target = "wasm32")]
+#[cfg(target_arch = "wasm32")]
 extern crate wasm_bindgen;
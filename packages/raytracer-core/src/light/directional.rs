@@ -0,0 +1,141 @@
+use cgmath::{vec3, InnerSpace, Rad};
+
+use crate::{
+    geometry::{
+        sampling::{cone_pdf, sample_cone},
+        Point, Ray, Vector,
+    },
+    sampler::{Sampler, SamplerTrait},
+};
+
+use super::{LightSample, LightTrait};
+
+/// A light infinitely far away, like the sun
+///
+/// With an angular diameter of 0, all of its light arrives from one direction
+/// and it casts perfectly sharp shadows. Giving it a size softens them, and
+/// lets rays that leave the scene see its disk.
+pub struct DirectionalLight {
+    /// A unit vector pointing towards the light
+    pub direction: Vector,
+    /// The light received by a surface facing the light, however big it is
+    pub irradiance: Vector,
+    /// One minus the cosine of the angle from the middle of the light's disk
+    /// to its edge, or 0 if it has no size
+    one_minus_cos_radius: f64,
+}
+
+impl DirectionalLight {
+    /// Create a light shining from the given direction, covering an angle of
+    /// the sky as wide as its angular diameter. The sun is about half a
+    /// degree across.
+    pub fn new<A: Into<Rad<f64>>>(
+        direction: Vector,
+        irradiance: Vector,
+        angular_diameter: A,
+    ) -> Self {
+        let radius = angular_diameter.into().0 / 2.0;
+        // 1 - cos(x) = 2 sin^2(x / 2), which keeps its precision for tiny disks
+        let one_minus_cos_radius = 2.0 * (radius / 2.0).sin().powi(2);
+        Self {
+            direction: direction.normalize(),
+            irradiance,
+            one_minus_cos_radius,
+        }
+    }
+
+    #[inline(always)]
+    fn is_delta(&self) -> bool {
+        self.one_minus_cos_radius == 0.0
+    }
+
+    /// Whether a direction points somewhere within the light's disk
+    #[inline(always)]
+    fn covers(&self, direction: Vector) -> bool {
+        let cosine = self.direction.dot(direction.normalize());
+        return !self.is_delta() && 1.0 - cosine <= self.one_minus_cos_radius;
+    }
+
+    /// The light coming from each direction within the disk, which spreads
+    /// the irradiance over the disk's solid angle
+    #[inline(always)]
+    fn radiance(&self) -> Vector {
+        self.irradiance * cone_pdf(self.one_minus_cos_radius)
+    }
+}
+
+impl LightTrait for DirectionalLight {
+    fn sample(&self, _point: &Point, _time: f64, sampler: &mut Sampler) -> Option<LightSample> {
+        if self.is_delta() {
+            return Option::Some(LightSample {
+                direction: self.direction,
+                distance: f64::INFINITY,
+                radiance: self.irradiance,
+                pdf: 1.0,
+                is_delta: true,
+            });
+        }
+        return Option::Some(LightSample {
+            direction: sample_cone(self.direction, self.one_minus_cos_radius, sampler.next_2d()),
+            distance: f64::INFINITY,
+            radiance: self.radiance(),
+            pdf: cone_pdf(self.one_minus_cos_radius),
+            is_delta: false,
+        });
+    }
+
+    fn pdf(&self, ray: &Ray, _t_min: f64, t_max: f64) -> f64 {
+        // anything in the way blocks the light
+        if t_max < f64::INFINITY || !self.covers(ray.direction) {
+            return 0.0;
+        }
+        return cone_pdf(self.one_minus_cos_radius);
+    }
+
+    fn escaped(&self, ray: &Ray) -> Vector {
+        if self.covers(ray.direction) {
+            self.radiance()
+        } else {
+            vec3(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, Deg};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    #[test]
+    fn when_sample_given_angular_diameter_stays_within_disk() {
+        let light = DirectionalLight::new(vec3(0.0, 1.0, 0.0), vec3(3.0, 3.0, 3.0), Deg(10.0));
+        let origin = point3(0.0, 0.0, 0.0);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        for _ in 0..100 {
+            let sample = light.sample(&origin, 0.0, &mut sampler).unwrap();
+            assert!(sample.direction.y >= 5.0f64.to_radians().cos() - 1e-12);
+            // however big the disk, the light it delivers is the same
+            assert!((sample.radiance.x / sample.pdf - 3.0).abs() < 1e-9);
+            let ray = Ray::new(origin, sample.direction, 0.0);
+            assert_eq!(light.pdf(&ray, 0.0, f64::INFINITY), sample.pdf);
+            assert_eq!(light.pdf(&ray, 0.0, 10.0), 0.0);
+        }
+        let away = Ray::new(origin, vec3(1.0, 1.0, 0.0), 0.0);
+        assert_eq!(light.escaped(&away), vec3(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn when_sample_given_no_diameter_returns_single_direction() {
+        let light = DirectionalLight::new(vec3(0.0, 2.0, 0.0), vec3(3.0, 3.0, 3.0), Deg(0.0));
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let sample = light
+            .sample(&point3(0.0, 0.0, 0.0), 0.0, &mut sampler)
+            .unwrap();
+        assert!(sample.is_delta);
+        assert_eq!(sample.direction, vec3(0.0, 1.0, 0.0));
+        assert_eq!(sample.radiance, vec3(3.0, 3.0, 3.0));
+    }
+}
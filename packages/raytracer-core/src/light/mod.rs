@@ -0,0 +1,186 @@
+//! Lights that the renderer can aim shadow rays at directly
+//!
+//! Point, spot and directional lights have no surface, so they can only be
+//! found by sampling them. Sphere and quad lights are surfaces as well, and
+//! add themselves to the scene so that they can be seen and hit. Any other
+//! emissive object in a scene is sampled as a light too.
+use std::sync::Arc;
+
+use cgmath::vec3;
+
+use crate::{
+    geometry::{sampling::Sampleable, Geometry, Point, Ray, RayCollidable, Vector},
+    sampler::Sampler,
+    shader::MaterialTrait,
+};
+
+mod directional;
+mod point;
+mod quad_light;
+mod sphere_light;
+mod spot;
+
+pub use directional::DirectionalLight;
+pub use point::PointLight;
+pub use quad_light::QuadLight;
+pub use sphere_light::SphereLight;
+pub use spot::SpotLight;
+
+/// Light arriving at a point from a direction picked on a light
+pub struct LightSample {
+    /// A unit vector pointing at the light
+    pub direction: Vector,
+    /// How far away the light is, which is infinite for directional lights
+    pub distance: f64,
+    /// The light arriving from the direction. For lights without a surface,
+    /// this is the light reaching the point, already divided by the distance
+    /// squared.
+    pub radiance: Vector,
+    /// The density of picking this direction, over solid angle. 1 for lights
+    /// that can only be reached from one direction.
+    pub pdf: f64,
+    /// Whether only this one direction reaches the light, so that no
+    /// scattered ray could ever find it
+    pub is_delta: bool,
+}
+
+pub trait LightTrait {
+    /// Pick a direction from the point towards this light, at the given time,
+    /// with any random choices taken from the sampler. Returns None if no
+    /// light from it can reach the point.
+    fn sample(&self, point: &Point, time: f64, sampler: &mut Sampler) -> Option<LightSample>;
+
+    /// The density that `sample` would pick the ray's direction with, if the
+    /// ray reaches this light between t_min and t_max. Lights that can only
+    /// be reached by sampling them are never hit, so this defaults to 0.
+    fn pdf(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> f64 {
+        return 0.0;
+    }
+
+    /// The light seen along a ray that's left the scene without hitting
+    /// anything, for lights that are infinitely far away
+    fn escaped(&self, _ray: &Ray) -> Vector {
+        return vec3(0.0, 0.0, 0.0);
+    }
+
+    /// The surface this light shines from, to add to the scene. None for
+    /// lights that can't be seen.
+    fn geometry(&self) -> Option<Geometry> {
+        return Option::None;
+    }
+}
+
+#[derive(Clone)]
+pub enum Light {
+    Point(Arc<PointLight>),
+    Spot(Arc<SpotLight>),
+    Directional(Arc<DirectionalLight>),
+    Sphere(Arc<SphereLight>),
+    Quad(Arc<QuadLight>),
+    /// An object in the scene with an emissive material
+    Surface(Geometry),
+}
+
+impl LightTrait for Light {
+    #[inline(always)]
+    fn sample(&self, point: &Point, time: f64, sampler: &mut Sampler) -> Option<LightSample> {
+        match self {
+            Light::Point(light) => light.sample(point, time, sampler),
+            Light::Spot(light) => light.sample(point, time, sampler),
+            Light::Directional(light) => light.sample(point, time, sampler),
+            Light::Sphere(light) => light.sample(point, time, sampler),
+            Light::Quad(light) => light.sample(point, time, sampler),
+            Light::Surface(surface) => sample_surface(surface, point, time, sampler),
+        }
+    }
+
+    #[inline(always)]
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self {
+            Light::Point(light) => light.pdf(ray, t_min, t_max),
+            Light::Spot(light) => light.pdf(ray, t_min, t_max),
+            Light::Directional(light) => light.pdf(ray, t_min, t_max),
+            Light::Sphere(light) => light.pdf(ray, t_min, t_max),
+            Light::Quad(light) => light.pdf(ray, t_min, t_max),
+            Light::Surface(surface) => surface.pdf(ray, t_min, t_max),
+        }
+    }
+
+    #[inline(always)]
+    fn escaped(&self, ray: &Ray) -> Vector {
+        match self {
+            Light::Directional(light) => light.escaped(ray),
+            _ => vec3(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        match self {
+            Light::Point(light) => light.geometry(),
+            Light::Spot(light) => light.geometry(),
+            Light::Directional(light) => light.geometry(),
+            Light::Sphere(light) => light.geometry(),
+            Light::Quad(light) => light.geometry(),
+            // already part of the scene
+            Light::Surface(_) => Option::None,
+        }
+    }
+}
+
+/// Sample an emissive object, finding the light it gives off by looking at
+/// its material where the sampled direction hits it
+fn sample_surface(
+    surface: &Geometry,
+    point: &Point,
+    time: f64,
+    sampler: &mut Sampler,
+) -> Option<LightSample> {
+    let sample = surface.sample(point, time, sampler)?;
+    let ray = Ray::new(*point, sample.direction, time);
+    // leave some room for rounding, so that the sampled point is hit. Any of
+    // the surface in front of it will block the shadow ray anyway.
+    let tolerance = 1e-6 * sample.distance.max(1.0);
+    let collision = surface.will_intersect(
+        &ray,
+        sample.distance - tolerance,
+        sample.distance + tolerance,
+    )?;
+    return Option::Some(LightSample {
+        direction: sample.direction,
+        distance: collision.t,
+        radiance: collision.material.emitted(&ray, &collision),
+        pdf: sample.pdf,
+        is_delta: false,
+    });
+}
+
+impl From<Arc<PointLight>> for Light {
+    fn from(value: Arc<PointLight>) -> Self {
+        Self::Point(value)
+    }
+}
+impl From<Arc<SpotLight>> for Light {
+    fn from(value: Arc<SpotLight>) -> Self {
+        Self::Spot(value)
+    }
+}
+impl From<Arc<DirectionalLight>> for Light {
+    fn from(value: Arc<DirectionalLight>) -> Self {
+        Self::Directional(value)
+    }
+}
+impl From<Arc<SphereLight>> for Light {
+    fn from(value: Arc<SphereLight>) -> Self {
+        Self::Sphere(value)
+    }
+}
+impl From<Arc<QuadLight>> for Light {
+    fn from(value: Arc<QuadLight>) -> Self {
+        Self::Quad(value)
+    }
+}
+impl From<Geometry> for Light {
+    fn from(value: Geometry) -> Self {
+        Self::Surface(value)
+    }
+}
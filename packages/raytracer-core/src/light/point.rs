@@ -0,0 +1,72 @@
+use cgmath::InnerSpace;
+
+use crate::{
+    geometry::{Point, Vector},
+    sampler::Sampler,
+};
+
+use super::{LightSample, LightTrait};
+
+/// A light that shines equally in every direction from a single point
+///
+/// Its light falls off with the square of the distance from it. Since it has
+/// no size, it casts perfectly sharp shadows.
+pub struct PointLight {
+    pub position: Point,
+    /// The light given off in each direction, which is what a surface facing
+    /// it a distance of 1 away receives
+    pub intensity: Vector,
+}
+
+impl PointLight {
+    pub fn new(position: Point, intensity: Vector) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl LightTrait for PointLight {
+    fn sample(&self, point: &Point, _time: f64, _sampler: &mut Sampler) -> Option<LightSample> {
+        let offset = self.position - point;
+        let distance_squared = offset.magnitude2();
+        if distance_squared == 0.0 {
+            return Option::None;
+        }
+        let distance = distance_squared.sqrt();
+        return Option::Some(LightSample {
+            direction: offset / distance,
+            distance,
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0,
+            is_delta: true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    #[test]
+    fn when_sample_given_distance_falls_off_with_its_square() {
+        let light = PointLight::new(point3(0.0, 2.0, 0.0), vec3(8.0, 8.0, 8.0));
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let near = light
+            .sample(&point3(0.0, 0.0, 0.0), 0.0, &mut sampler)
+            .unwrap();
+        assert_eq!(near.direction, vec3(0.0, 1.0, 0.0));
+        assert_eq!(near.distance, 2.0);
+        assert_eq!(near.radiance, vec3(2.0, 2.0, 2.0));
+        let far = light
+            .sample(&point3(0.0, -2.0, 0.0), 0.0, &mut sampler)
+            .unwrap();
+        assert_eq!(far.radiance, vec3(0.5, 0.5, 0.5));
+        assert!(far.is_delta);
+    }
+}
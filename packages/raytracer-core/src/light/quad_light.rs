@@ -0,0 +1,307 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use cgmath::InnerSpace;
+
+use crate::{
+    geometry::{quad::Quad, sampling::Sampleable, Geometry, Point, Ray, RayCollidable, Vector},
+    sampler::{Sampler, SamplerTrait},
+    shader::DiffuseLight,
+};
+
+use super::{LightSample, LightTrait};
+
+/// Below this solid angle, a quad is too small or too far away for spherical
+/// rectangle sampling to keep its precision, so it's sampled by area instead
+const MIN_SOLID_ANGLE: f64 = 1e-6;
+
+/// A glowing rectangle, like a light panel or a softbox
+///
+/// It's sampled uniformly over the solid angle it covers, using Ureña et al.
+/// (2013), "An Area-Preserving Parametrization for Spherical Rectangles". That
+/// keeps the noise down for points close to the light, where sampling by area
+/// would bunch samples up on the near side.
+pub struct QuadLight {
+    pub corner: Point,
+    pub edge_u: Vector,
+    pub edge_v: Vector,
+    /// The light given off by each point on its surface
+    pub emit: Vector,
+    /// Whether light is given off the back as well as the front, which is the
+    /// side `edge_u x edge_v` points to
+    pub two_sided: bool,
+    quad: Arc<Quad>,
+}
+
+impl QuadLight {
+    /// Create a rectangular light from one corner and the two edges leaving
+    /// it, which must be at right angles to each other
+    pub fn new(corner: Point, edge_u: Vector, edge_v: Vector, emit: Vector) -> Self {
+        Self::new_with_sides(corner, edge_u, edge_v, emit, false)
+    }
+
+    pub fn new_two_sided(corner: Point, edge_u: Vector, edge_v: Vector, emit: Vector) -> Self {
+        Self::new_with_sides(corner, edge_u, edge_v, emit, true)
+    }
+
+    fn new_with_sides(
+        corner: Point,
+        edge_u: Vector,
+        edge_v: Vector,
+        emit: Vector,
+        two_sided: bool,
+    ) -> Self {
+        assert!(
+            edge_u.magnitude2() > 0.0 && edge_v.magnitude2() > 0.0,
+            "A quad light's edges can't be empty"
+        );
+        assert!(
+            edge_u.normalize().dot(edge_v.normalize()).abs() < 1e-6,
+            "A quad light's edges must be at right angles"
+        );
+        let material = if two_sided {
+            DiffuseLight::new_two_sided(emit)
+        } else {
+            DiffuseLight::new(emit)
+        };
+        let quad = Quad::new_with_material(corner, edge_u, edge_v, Arc::new(material).into());
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            emit,
+            two_sided,
+            quad: Arc::new(quad),
+        }
+    }
+
+    /// Whether the point is on a side of the quad that light comes out of
+    #[inline(always)]
+    fn lights(&self, point: &Point) -> bool {
+        let height = (point - self.corner).dot(self.quad.normal());
+        return if self.two_sided {
+            height != 0.0
+        } else {
+            height > 0.0
+        };
+    }
+}
+
+impl LightTrait for QuadLight {
+    fn sample(&self, point: &Point, time: f64, sampler: &mut Sampler) -> Option<LightSample> {
+        if !self.lights(point) {
+            return Option::None;
+        }
+        let rectangle = SphericalRectangle::new(self, point);
+        if rectangle.solid_angle <= MIN_SOLID_ANGLE {
+            let sample = self.quad.sample(point, time, sampler)?;
+            return Option::Some(LightSample {
+                direction: sample.direction,
+                distance: sample.distance,
+                radiance: self.emit,
+                pdf: sample.pdf,
+                is_delta: false,
+            });
+        }
+        let [u, v] = sampler.next_2d();
+        let offset = rectangle.sample(u, v) - point;
+        let distance = offset.magnitude();
+        if distance == 0.0 {
+            return Option::None;
+        }
+        return Option::Some(LightSample {
+            direction: offset / distance,
+            distance,
+            radiance: self.emit,
+            pdf: 1.0 / rectangle.solid_angle,
+            is_delta: false,
+        });
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if !self.lights(&ray.origin) {
+            return 0.0;
+        }
+        let rectangle = SphericalRectangle::new(self, &ray.origin);
+        if rectangle.solid_angle <= MIN_SOLID_ANGLE {
+            return self.quad.pdf(ray, t_min, t_max);
+        }
+        return match self.quad.will_intersect(ray, t_min, t_max) {
+            Option::Some(_) => 1.0 / rectangle.solid_angle,
+            Option::None => 0.0,
+        };
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        Option::Some(self.quad.clone().into())
+    }
+}
+
+/// A rectangle projected onto the unit sphere around a point, in a frame
+/// where the rectangle lies in the plane z = z0 and its edges run along x and
+/// y
+struct SphericalRectangle {
+    origin: Point,
+    x: Vector,
+    y: Vector,
+    z: Vector,
+    x0: f64,
+    x1: f64,
+    y0: f64,
+    y1: f64,
+    z0: f64,
+    b0: f64,
+    b1: f64,
+    k: f64,
+    solid_angle: f64,
+}
+
+impl SphericalRectangle {
+    fn new(quad: &QuadLight, origin: &Point) -> Self {
+        let width = quad.edge_u.magnitude();
+        let height = quad.edge_v.magnitude();
+        let x = quad.edge_u / width;
+        let y = quad.edge_v / height;
+        let mut z = x.cross(y);
+        let offset = quad.corner - origin;
+        let mut z0 = offset.dot(z);
+        // keep the rectangle on the -z side of the origin
+        if z0 > 0.0 {
+            z = -z;
+            z0 = -z0;
+        }
+        let x0 = offset.dot(x);
+        let y0 = offset.dot(y);
+        let x1 = x0 + width;
+        let y1 = y0 + height;
+
+        // the normals of the planes through the origin and each edge
+        let normal = |a: f64, b: f64, c: f64| {
+            let length = (a * a + b * b + c * c).sqrt();
+            (a / length, b / length, c / length)
+        };
+        let n0 = normal(0.0, z0, -y0);
+        let n1 = normal(-z0, 0.0, x1);
+        let n2 = normal(0.0, -z0, y1);
+        let n3 = normal(z0, 0.0, -x0);
+        let dot = |a: (f64, f64, f64), b: (f64, f64, f64)| a.0 * b.0 + a.1 * b.1 + a.2 * b.2;
+        // the interior angles at each corner
+        let g0 = (-dot(n0, n1)).clamp(-1.0, 1.0).acos();
+        let g1 = (-dot(n1, n2)).clamp(-1.0, 1.0).acos();
+        let g2 = (-dot(n2, n3)).clamp(-1.0, 1.0).acos();
+        let g3 = (-dot(n3, n0)).clamp(-1.0, 1.0).acos();
+        let k = 2.0 * PI - g2 - g3;
+
+        Self {
+            origin: *origin,
+            x,
+            y,
+            z,
+            x0,
+            x1,
+            y0,
+            y1,
+            z0,
+            b0: n0.2,
+            b1: n2.2,
+            k,
+            solid_angle: (g0 + g1 - k).max(0.0),
+        }
+    }
+
+    /// Map a point in the unit square to a point on the rectangle, so that
+    /// the directions to them are uniform over the solid angle it covers
+    fn sample(&self, u: f64, v: f64) -> Point {
+        let au = u * self.solid_angle + self.k;
+        let fu = (au.cos() * self.b0 - self.b1) / au.sin();
+        let cu = (1.0f64.copysign(fu) / (fu * fu + self.b0 * self.b0).sqrt()).clamp(-1.0, 1.0);
+        let xu = (-(cu * self.z0) / (1.0 - cu * cu).max(1e-12).sqrt()).clamp(self.x0, self.x1);
+
+        let d = (xu * xu + self.z0 * self.z0).sqrt();
+        let h0 = self.y0 / (d * d + self.y0 * self.y0).sqrt();
+        let h1 = self.y1 / (d * d + self.y1 * self.y1).sqrt();
+        let hv = h0 + v * (h1 - h0);
+        let hv2 = hv * hv;
+        let yv = if hv2 < 1.0 - 1e-12 {
+            (hv * d / (1.0 - hv2).sqrt()).clamp(self.y0, self.y1)
+        } else {
+            self.y1
+        };
+        return self.origin + self.x * xu + self.y * yv + self.z * self.z0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    fn make_light() -> QuadLight {
+        // a 2x1 panel facing down, 1 above the origin
+        QuadLight::new(
+            point3(-1.0, 1.0, -0.5),
+            vec3(2.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+            vec3(4.0, 4.0, 4.0),
+        )
+    }
+
+    #[test]
+    fn when_sample_given_point_below_returns_points_on_the_quad() {
+        let light = make_light();
+        let origin = point3(0.3, 0.0, 0.2);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        for _ in 0..200 {
+            let sample = light.sample(&origin, 0.0, &mut sampler).unwrap();
+            let target = origin + sample.direction * sample.distance;
+            assert!((target.y - 1.0).abs() < 1e-9);
+            assert!(target.x >= -1.0 - 1e-9 && target.x <= 1.0 + 1e-9);
+            assert!(target.z >= -0.5 - 1e-9 && target.z <= 0.5 + 1e-9);
+            let ray = Ray::new(origin, sample.direction, 0.0);
+            assert!((light.pdf(&ray, 0.0, f64::INFINITY) - sample.pdf).abs() < 1e-9);
+        }
+        // it only shines downwards
+        assert!(light
+            .sample(&point3(0.0, 2.0, 0.0), 0.0, &mut sampler)
+            .is_none());
+    }
+
+    #[test]
+    fn when_sample_given_many_samples_covers_solid_angle_uniformly() {
+        // estimate the quad's solid angle by sampling it by area, and check
+        // it against the spherical rectangle's
+        let light = make_light();
+        let origin = point3(0.3, 0.0, 0.2);
+        let count = 100_000;
+        let mut solid_angle = 0.0;
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        for _ in 0..count {
+            let sample = light.quad.sample(&origin, 0.0, &mut sampler).unwrap();
+            solid_angle += 1.0 / sample.pdf;
+        }
+        solid_angle /= count as f64;
+        let rectangle = SphericalRectangle::new(&light, &origin);
+        assert!((rectangle.solid_angle / solid_angle - 1.0).abs() < 0.01);
+
+        // and that the samples are spread evenly over it, by checking that
+        // each half of the quad gets its share
+        let mut left = 0;
+        let rng = fastrand::Rng::with_seed(5);
+        for _ in 0..count {
+            if rectangle.sample(rng.f64(), rng.f64()).x < 0.0 {
+                left += 1;
+            }
+        }
+        let left_quad = QuadLight::new(
+            point3(-1.0, 1.0, -0.5),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+            vec3(4.0, 4.0, 4.0),
+        );
+        let left_share =
+            SphericalRectangle::new(&left_quad, &origin).solid_angle / rectangle.solid_angle;
+        assert!((left as f64 / count as f64 - left_share).abs() < 0.01);
+    }
+}
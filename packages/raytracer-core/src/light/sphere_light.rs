@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use crate::{
+    geometry::{
+        sampling::{sample_sphere, sphere_pdf},
+        sphere::Sphere,
+        Geometry, Point, Ray, RayCollidable, Vector,
+    },
+    sampler::{Sampler, SamplerTrait},
+    shader::DiffuseLight,
+};
+
+use super::{LightSample, LightTrait};
+
+/// A glowing ball, sampled over the cone of directions it covers
+///
+/// That's the solid angle it takes up as seen from the point being lit, so
+/// every sample lands on the side facing the point.
+pub struct SphereLight {
+    sphere: Arc<Sphere>,
+    /// The light given off by each point on its surface
+    pub emit: Vector,
+}
+
+impl SphereLight {
+    pub fn new(center: Point, radius: f64, emit: Vector) -> Self {
+        let material = Arc::new(DiffuseLight::new(emit)).into();
+        Self {
+            sphere: Arc::new(Sphere::new_with_material(center, radius, material)),
+            emit,
+        }
+    }
+
+    pub fn center(&self) -> Point {
+        self.sphere.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.sphere.radius
+    }
+}
+
+impl LightTrait for SphereLight {
+    fn sample(&self, point: &Point, _time: f64, sampler: &mut Sampler) -> Option<LightSample> {
+        // inside the sphere, there's no cone to sample
+        let sample = sample_sphere(self.center(), self.radius(), point, sampler.next_2d())?;
+        return Option::Some(LightSample {
+            direction: sample.direction,
+            distance: sample.distance,
+            radiance: self.emit,
+            pdf: sample.pdf,
+            is_delta: false,
+        });
+    }
+
+    fn pdf(&self, ray: &Ray, t_min: f64, t_max: f64) -> f64 {
+        return match self.sphere.will_intersect(ray, t_min, t_max) {
+            Option::Some(_) => sphere_pdf(self.center(), self.radius(), &ray.origin),
+            Option::None => 0.0,
+        };
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        Option::Some(self.sphere.clone().into())
+    }
+}
@@ -0,0 +1,126 @@
+use cgmath::{InnerSpace, Rad};
+
+use crate::{
+    geometry::{Point, Vector},
+    sampler::Sampler,
+};
+
+use super::{LightSample, LightTrait};
+
+/// A point light that only shines within a cone
+///
+/// Inside the falloff angle the light is at full strength, and it fades out
+/// smoothly between there and the edge of the cone.
+pub struct SpotLight {
+    pub position: Point,
+    /// A unit vector down the middle of the cone
+    pub direction: Vector,
+    /// The light given off down the middle of the cone
+    pub intensity: Vector,
+    /// The cosine of the angle from the middle of the cone to its edge
+    cos_cone_angle: f64,
+    /// The cosine of the angle from the middle of the cone to where the light
+    /// starts to fade
+    cos_falloff_angle: f64,
+}
+
+impl SpotLight {
+    /// Create a spot light shining in a direction, with angles measured from
+    /// the middle of the cone. The falloff angle should be no wider than the
+    /// cone.
+    pub fn new<A: Into<Rad<f64>>, B: Into<Rad<f64>>>(
+        position: Point,
+        direction: Vector,
+        intensity: Vector,
+        cone_angle: A,
+        falloff_angle: B,
+    ) -> Self {
+        let cone_angle: Rad<f64> = cone_angle.into();
+        let falloff_angle: Rad<f64> = falloff_angle.into();
+        assert!(
+            falloff_angle <= cone_angle,
+            "A spot light's falloff angle can't be wider than its cone"
+        );
+        Self {
+            position,
+            direction: direction.normalize(),
+            intensity,
+            cos_cone_angle: cone_angle.0.cos(),
+            cos_falloff_angle: falloff_angle.0.cos(),
+        }
+    }
+
+    /// How much of the light's intensity leaves in a direction, from 1 inside
+    /// the falloff angle to 0 outside the cone
+    fn falloff(&self, direction: Vector) -> f64 {
+        let cosine = self.direction.dot(direction);
+        if cosine >= self.cos_falloff_angle {
+            return 1.0;
+        }
+        if cosine <= self.cos_cone_angle {
+            return 0.0;
+        }
+        let t = (cosine - self.cos_cone_angle) / (self.cos_falloff_angle - self.cos_cone_angle);
+        // smoothstep, so there's no hard edge where the fade starts or stops
+        return t * t * (3.0 - 2.0 * t);
+    }
+}
+
+impl LightTrait for SpotLight {
+    fn sample(&self, point: &Point, _time: f64, _sampler: &mut Sampler) -> Option<LightSample> {
+        let offset = self.position - point;
+        let distance_squared = offset.magnitude2();
+        if distance_squared == 0.0 {
+            return Option::None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = offset / distance;
+        let falloff = self.falloff(-direction);
+        if falloff == 0.0 {
+            return Option::None;
+        }
+        return Option::Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * (falloff / distance_squared),
+            pdf: 1.0,
+            is_delta: true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3, Deg};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    #[test]
+    fn when_sample_given_angle_fades_smoothly_towards_edge_of_cone() {
+        let light = SpotLight::new(
+            point3(0.0, 1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 1.0),
+            Deg(45.0),
+            Deg(30.0),
+        );
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let mut brightness = |x: f64| {
+            light
+                .sample(&point3(x, 0.0, 0.0), 0.0, &mut sampler)
+                .map_or(0.0, |sample| sample.radiance.x * sample.distance.powi(2))
+        };
+        // straight below, and anywhere inside the falloff angle, is at full
+        // strength
+        assert!((brightness(0.0) - 1.0).abs() < 1e-12);
+        assert!((brightness(0.5) - 1.0).abs() < 1e-12);
+        // between 30 and 45 degrees it fades
+        let fading = [0.6, 0.7, 0.8, 0.9].map(&mut brightness);
+        assert!(fading.windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(fading.iter().all(|&b| b > 0.0 && b < 1.0));
+        // and outside the cone it's dark
+        assert!(brightness(1.5) == 0.0);
+    }
+}
@@ -2,7 +2,12 @@
 
 use cgmath::{Angle, Deg, InnerSpace};
 
-use crate::geometry::{util, Point, Ray, Vector};
+use crate::{
+    geometry::{util, Point, Ray, Vector},
+    sampler::{Sampler, SamplerTrait},
+};
+
+#[derive(Clone)]
 pub struct Camera {
     origin: Point,
     /// The time in scene-seconds to start casting rays from
@@ -29,11 +34,13 @@ pub struct Camera {
 }
 
 impl Camera {
-    /// Project a ray into space from a UV screenspace coordinate
-    pub fn project_ray(&self, u: f64, v: f64) -> Ray {
-        let Vector { x, y, z: _ } = self.lens_radius * util::vector::random_vector_in_disk();
+    /// Project a ray into space from a UV screenspace coordinate, with the
+    /// point on the lens and the time it's cast at taken from the sampler
+    pub fn project_ray(&self, u: f64, v: f64, sampler: &mut Sampler) -> Ray {
+        let lens = util::vector::point_in_disk_from(sampler.next_2d());
+        let Vector { x, y, z: _ } = self.lens_radius * lens;
         let offset = self.screen_u * x + self.screen_v * y;
-        let time = fastrand::f64() * (self.time_end - self.time_start) + self.time_start;
+        let time = sampler.next_1d() * (self.time_end - self.time_start) + self.time_start;
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
@@ -41,6 +48,14 @@ impl Camera {
         )
     }
 
+    /// How quickly a ray through one pixel widens, for an image that's the
+    /// given number of pixels tall. See `Ray::spread`.
+    pub fn pixel_spread(&self, image_height: usize) -> f64 {
+        // rays reach the film plane at t = 1
+        return self.vertical.magnitude() / image_height as f64;
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         camera_position: Point,
         look_at: Point,
@@ -52,8 +67,10 @@ impl Camera {
         time_start: f64,
         time_end: f64,
     ) -> Camera {
+        // image rows run top to bottom, so the screen is flipped vertically
+        // (but not horizontally, or the image would be mirrored)
         let height = -2.0 * (field_of_view / 2.0).tan();
-        let width = aspect_ratio * height;
+        let width = -aspect_ratio * height;
 
         let inverse_camera_direction = (camera_position - look_at).normalize();
         let screen_u = local_up.cross(inverse_camera_direction).normalize();
@@ -81,3 +98,35 @@ impl Camera {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    #[test]
+    fn when_project_ray_given_image_corners_looks_the_same_way() {
+        let camera = Camera::new(
+            point3(0.0, 0.0, 0.0),
+            point3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+            2.0,
+            Deg(90.0),
+            1000.0,
+            1.0,
+            0.0,
+            0.0,
+        );
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let mut direction = |u, v| camera.project_ray(u, v, &mut sampler).direction;
+        // u runs left to right across the image, and v top to bottom
+        let (left, right) = (direction(0.0, 0.5), direction(1.0, 0.5));
+        let (top, bottom) = (direction(0.5, 0.0), direction(0.5, 1.0));
+        assert!(left.x < -1.0 && right.x > 1.0, "{:?} {:?}", left, right);
+        assert!(top.y > 0.5 && bottom.y < -0.5, "{:?} {:?}", top, bottom);
+        assert!([left, right, top, bottom].iter().all(|d| d.z < 0.0));
+    }
+}
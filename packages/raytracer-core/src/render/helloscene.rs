@@ -2,34 +2,25 @@ use cgmath::{point3, vec3, Deg};
 use log::{debug, info};
 
 use crate::{
-    image::buffer::ImageBuffer,
+    image::{
+        buffer::{FloatImageBuffer, ImageBuffer},
+        display::DisplayTransform,
+    },
     render::{camera::Camera, iter::ChunkedPixelIterator, renderer::Renderer},
-    scene::new_test_world,
+    scene::{new_test_world, SceneGraph},
 };
 
-pub fn render_helloworld() -> ImageBuffer {
-    const WIDTH: usize = 720;
-    const HEIGHT: usize = 405;
+const WIDTH: usize = 720;
+const HEIGHT: usize = 405;
 
-    let camera = Camera::new(
-        point3(0.0, 0.0, 0.0),
-        point3(0.0, 0.0, -1.0),
-        vec3(0.0, 1.0, 0.0),
-        WIDTH as f64 / HEIGHT as f64,
-        Deg(45.0),
-        2.0,
-        1.0,
-        0.0,
-        0.0,
-    );
-
-    let renderer = Renderer::new_from_defaults(WIDTH, HEIGHT, camera);
+pub fn render_helloworld() -> ImageBuffer {
+    let renderer = make_renderer();
 
     debug!("Output dimensions: {} x {}", WIDTH, HEIGHT);
 
     let scene = new_test_world();
 
-    let mut buf = ImageBuffer::new_rgb(WIDTH, HEIGHT);
+    let mut buf = FloatImageBuffer::new_rgb(WIDTH, HEIGHT);
 
     let width = buf.width;
     let height = buf.height;
@@ -44,5 +35,27 @@ pub fn render_helloworld() -> ImageBuffer {
         renderer.render_to_buffer(&scene, &mut buf, chunk);
     }
 
-    buf
+    DisplayTransform::default().to_rgb8(&buf)
+}
+
+/// The renderer and scene `render_helloworld` uses, for rendering it some
+/// other way, like a pass at a time
+pub fn helloworld_scene() -> (Renderer, SceneGraph) {
+    (make_renderer(), new_test_world())
+}
+
+fn make_renderer() -> Renderer {
+    let camera = Camera::new(
+        point3(0.0, 0.0, 0.0),
+        point3(0.0, 0.0, -1.0),
+        vec3(0.0, 1.0, 0.0),
+        WIDTH as f64 / HEIGHT as f64,
+        Deg(45.0),
+        2.0,
+        1.0,
+        0.0,
+        0.0,
+    );
+
+    Renderer::new_from_defaults(WIDTH, HEIGHT, camera)
 }
@@ -63,21 +63,240 @@ impl Iterator for ChunkedPixelIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         let current_chunk = self.current_chunk;
+        if current_chunk >= self.chunks {
+            self.current_chunk = 0;
+            return Option::None;
+        }
         self.current_chunk += 1;
+        // spread any leftover pixels over the chunks, rather than dropping
+        // them off the end of the image
         let max_size = self.width * self.height;
-        let chunk_size = max_size / self.chunks;
-
-        let idx = chunk_size * current_chunk;
-        return if idx < max_size {
-            Option::Some(PixelIterator {
-                width: self.width,
-                height: self.height,
-                idx,
-                max_size: idx + chunk_size,
-            })
-        } else {
-            self.current_chunk = 0;
-            Option::None
+        return Option::Some(PixelIterator {
+            width: self.width,
+            height: self.height,
+            idx: max_size * current_chunk / self.chunks,
+            max_size: max_size * (current_chunk + 1) / self.chunks,
+        });
+    }
+}
+
+/// A rectangle of pixels, rendered as a unit so that nearby pixels are worked
+/// on together
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// The pixels in the tile, a row at a time
+    pub fn pixels(&self) -> impl Iterator<Item = Pixel> {
+        let Tile {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| Pixel { x, y }))
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+/// Which order to render an image's tiles in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// A row of tiles at a time, from the top
+    #[default]
+    Scanline,
+    /// Outwards from the middle, which is usually the interesting part of
+    /// an image, so previews fill that in first
+    Spiral,
+    /// Along a Hilbert curve, which never strays far from the last tile, so
+    /// what it renders is more likely to still be in the cache
+    Hilbert,
+}
+
+/// Cover an image in square tiles. Tiles along the right and bottom edges are
+/// cut short to fit.
+pub struct TileIterator {
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    /// The column and row of each tile, in the order they're rendered in
+    cells: std::vec::IntoIter<[usize; 2]>,
+}
+
+impl TileIterator {
+    pub fn new(width: usize, height: usize, tile_size: usize) -> Self {
+        Self::new_with_order(width, height, tile_size, TileOrder::Scanline)
+    }
+
+    pub fn new_with_order(width: usize, height: usize, tile_size: usize, order: TileOrder) -> Self {
+        assert!(tile_size > 0, "Tiles must have some size");
+        let columns = width.div_ceil(tile_size);
+        let rows = height.div_ceil(tile_size);
+        let cells = match order {
+            TileOrder::Scanline => (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| [column, row]))
+                .collect(),
+            TileOrder::Spiral => spiral_cells(columns, rows),
+            TileOrder::Hilbert => hilbert_cells(columns, rows),
         };
+        Self {
+            width,
+            height,
+            tile_size,
+            cells: Vec::into_iter(cells),
+        }
+    }
+}
+
+impl ExactSizeIterator for TileIterator {}
+
+impl Iterator for TileIterator {
+    type Item = Tile;
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.cells.size_hint()
+    }
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [column, row] = self.cells.next()?;
+        let x = column * self.tile_size;
+        let y = row * self.tile_size;
+        return Option::Some(Tile {
+            x,
+            y,
+            width: self.tile_size.min(self.width - x),
+            height: self.tile_size.min(self.height - y),
+        });
+    }
+}
+
+/// Walk a square spiral out from the middle of the grid, one ring at a time,
+/// skipping the parts of the rings outside of it
+fn spiral_cells(columns: usize, rows: usize) -> Vec<[usize; 2]> {
+    let total = columns * rows;
+    let mut cells = Vec::with_capacity(total);
+    if total == 0 {
+        return cells;
+    }
+    let (mut x, mut y) = (((columns - 1) / 2) as i64, ((rows - 1) / 2) as i64);
+    // right, down, left, up, taking one more step every other turn
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut turn = 0;
+    let mut steps = 1;
+    cells.push([x as usize, y as usize]);
+    while cells.len() < total {
+        for _ in 0..2 {
+            let (dx, dy) = directions[turn % 4];
+            for _ in 0..steps {
+                x += dx;
+                y += dy;
+                if x >= 0 && y >= 0 && (x as usize) < columns && (y as usize) < rows {
+                    cells.push([x as usize, y as usize]);
+                }
+            }
+            turn += 1;
+        }
+        steps += 1;
+    }
+    return cells;
+}
+
+/// Follow a Hilbert curve over the smallest square with a power of two side
+/// that covers the grid, keeping the cells inside of it
+fn hilbert_cells(columns: usize, rows: usize) -> Vec<[usize; 2]> {
+    let side = columns.max(rows).next_power_of_two();
+    let mut cells = Vec::with_capacity(columns * rows);
+    for distance in 0..side * side {
+        let [x, y] = hilbert_point(side, distance);
+        if x < columns && y < rows {
+            cells.push([x, y]);
+        }
+    }
+    return cells;
+}
+
+/// The cell a distance along a Hilbert curve over a square grid lands on
+fn hilbert_point(side: usize, distance: usize) -> [usize; 2] {
+    let (mut x, mut y) = (0, 0);
+    let mut t = distance;
+    let mut size = 1;
+    while size < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        // turn the quadrant the right way round
+        if ry == 0 {
+            if rx == 1 {
+                x = size - 1 - x;
+                y = size - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += size * rx;
+        y += size * ry;
+        t /= 4;
+        size *= 2;
+    }
+    return [x, y];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_chunked_pixel_iterator_given_remainder_covers_every_pixel_once() {
+        // 7 * 5 pixels don't split evenly over 4 chunks
+        let (width, height) = (7, 5);
+        let mut seen = vec![0; width * height];
+        for chunk in ChunkedPixelIterator::with_chunks(width, height, 4) {
+            for Pixel { x, y } in chunk {
+                seen[y * width + x] += 1;
+            }
+        }
+        assert!(seen.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn when_tile_iterator_given_uneven_size_covers_every_pixel_once() {
+        let (width, height) = (70, 33);
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let mut seen = vec![0; width * height];
+            for tile in TileIterator::new_with_order(width, height, 32, order) {
+                for Pixel { x, y } in tile.pixels() {
+                    seen[y * width + x] += 1;
+                }
+            }
+            assert!(seen.iter().all(|&count| count == 1), "{:?}", order);
+            assert_eq!(
+                TileIterator::new_with_order(width, height, 32, order).count(),
+                6
+            );
+        }
+    }
+
+    #[test]
+    fn when_tile_iterator_given_spiral_and_hilbert_orders_moves_between_neighbours() {
+        let tiles: Vec<Tile> =
+            TileIterator::new_with_order(160, 160, 32, TileOrder::Spiral).collect();
+        // 5 by 5 tiles, starting in the middle
+        assert_eq!((tiles[0].x, tiles[0].y), (64, 64));
+        assert_eq!((tiles[1].x, tiles[1].y), (96, 64));
+        assert_eq!((tiles[24].x, tiles[24].y), (128, 0));
+
+        let tiles: Vec<Tile> =
+            TileIterator::new_with_order(256, 256, 32, TileOrder::Hilbert).collect();
+        assert_eq!(tiles.len(), 64);
+        for pair in tiles.windows(2) {
+            let distance = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(distance, 32, "Each tile should be next to the last");
+        }
     }
 }
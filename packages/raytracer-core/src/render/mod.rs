@@ -1,6 +1,8 @@
 pub mod camera;
 mod helloscene;
 pub mod iter;
+pub mod pool;
+pub mod progressive;
 pub mod renderer;
 
-pub use self::helloscene::render_helloworld;
+pub use self::helloscene::{helloworld_scene, render_helloworld};
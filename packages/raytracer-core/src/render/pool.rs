@@ -0,0 +1,128 @@
+//! Rendering an image on several threads at once
+//!
+//! The image is cut into small tiles, which are dealt out to the threads in
+//! the order they should be rendered in, so the image fills in that way. Some
+//! parts of an image take far longer to render than others,
+//! like glass compared to empty sky, so a thread that runs out of tiles steals
+//! the last of another thread's rather than sitting idle.
+//! Finished tiles are written straight into the one shared image, so memory
+//! doesn't grow with the number of threads.
+use std::{collections::VecDeque, sync::Mutex, thread};
+
+use crate::image::buffer::FloatImageBuffer;
+
+use super::iter::{Tile, TileIterator, TileOrder};
+
+/// How many pixels along each side of a tile
+pub const TILE_SIZE: usize = 32;
+
+/// The tiles each thread has left to render
+struct TileQueues {
+    queues: Vec<Mutex<VecDeque<Tile>>>,
+}
+
+impl TileQueues {
+    /// Deal the tiles out to the workers in turn, so that between them they
+    /// work through the tiles in order
+    fn new(tiles: impl Iterator<Item = Tile>, workers: usize) -> Self {
+        let mut queues = vec![VecDeque::new(); workers];
+        for (idx, tile) in tiles.enumerate() {
+            queues[idx % workers].push_back(tile);
+        }
+        Self {
+            queues: queues.into_iter().map(Mutex::new).collect(),
+        }
+    }
+
+    /// The next tile for a worker to render, taken from the front of its own
+    /// queue, or stolen from the back of someone else's once that's empty
+    fn next(&self, worker: usize) -> Option<Tile> {
+        if let Option::Some(tile) = self.queues[worker].lock().unwrap().pop_front() {
+            return Option::Some(tile);
+        }
+        let workers = self.queues.len();
+        for offset in 1..workers {
+            let victim = &self.queues[(worker + offset) % workers];
+            if let Option::Some(tile) = victim.lock().unwrap().pop_back() {
+                return Option::Some(tile);
+            }
+        }
+        return Option::None;
+    }
+}
+
+/// Render every tile of the buffer on a number of threads, starting from the
+/// first in the order given, with `render` returning the colors of a tile's
+/// pixels a row at a time
+///
+/// With a single thread, everything is rendered on the calling thread, so this
+/// also works where threads can't be spawned, like on the web.
+pub fn render_tiles<F>(buf: &mut FloatImageBuffer, threads: usize, order: TileOrder, render: F)
+where
+    F: Fn(&Tile) -> Vec<[f32; 3]> + Sync,
+{
+    let tiles = TileIterator::new_with_order(buf.width, buf.height, TILE_SIZE, order);
+    let workers = threads.clamp(1, tiles.len().max(1));
+    let queues = TileQueues::new(tiles, workers);
+    let buf = Mutex::new(buf);
+
+    let work = |worker: usize| {
+        while let Option::Some(tile) = queues.next(worker) {
+            let colors = render(&tile);
+            let mut buf = buf.lock().unwrap();
+            for (pixel, color) in tile.pixels().zip(colors) {
+                buf.set_pixel(pixel.x, pixel.y, color);
+            }
+        }
+    };
+    if workers == 1 {
+        work(0);
+        return;
+    }
+    thread::scope(|scope| {
+        for worker in 0..workers {
+            let work = &work;
+            scope.spawn(move || work(worker));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_render_tiles_given_many_threads_renders_every_pixel_once() {
+        let mut buf = FloatImageBuffer::new_rgb(100, 70);
+        let rendered = Mutex::new(vec![0; 100 * 70]);
+        render_tiles(&mut buf, 5, TileOrder::Hilbert, |tile| {
+            let mut rendered = rendered.lock().unwrap();
+            tile.pixels()
+                .map(|pixel| {
+                    rendered[pixel.y * 100 + pixel.x] += 1;
+                    [pixel.x as f32, pixel.y as f32, 1.0]
+                })
+                .collect()
+        });
+        assert!(rendered
+            .into_inner()
+            .unwrap()
+            .iter()
+            .all(|&count| count == 1));
+        for (x, y) in [(0, 0), (99, 69), (31, 32), (64, 5)] {
+            assert_eq!(buf.pixel(x, y), [x as f32, y as f32, 1.0]);
+        }
+    }
+
+    #[test]
+    fn when_next_given_empty_run_steals_from_the_back_of_another() {
+        let tiles: Vec<Tile> = TileIterator::new(128, 32, 32).collect();
+        let queues = TileQueues::new(tiles.iter().copied(), 2);
+        assert_eq!(queues.next(0), Option::Some(tiles[0]));
+        assert_eq!(queues.next(0), Option::Some(tiles[2]));
+        // worker 0's tiles are done, so it takes the last of worker 1's
+        assert_eq!(queues.next(0), Option::Some(tiles[3]));
+        assert_eq!(queues.next(1), Option::Some(tiles[1]));
+        assert_eq!(queues.next(1), Option::None);
+    }
+}
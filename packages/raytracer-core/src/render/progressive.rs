@@ -0,0 +1,78 @@
+//! Rendering a sample per pixel at a time, so the image can be shown as it
+//! refines rather than only once it's finished
+use crate::image::buffer::FloatImageBuffer;
+
+/// How far a progressive render has got, handed out after every pass
+pub struct Pass<'a> {
+    /// How many passes have been rendered so far, counting from 1
+    pub number: usize,
+    /// How many passes there will be if the render isn't stopped early
+    pub total: usize,
+    /// The average of every pass so far
+    pub image: &'a FloatImageBuffer,
+}
+
+/// A running average of the passes of a render
+pub struct Accumulator {
+    image: FloatImageBuffer,
+    passes: usize,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            image: FloatImageBuffer::new_rgb(width, height),
+            passes: 0,
+        }
+    }
+
+    /// Blend in another pass, which must be the same size
+    pub fn add(&mut self, pass: &FloatImageBuffer) {
+        assert_eq!(
+            (pass.width, pass.height, pass.format.stride),
+            (
+                self.image.width,
+                self.image.height,
+                self.image.format.stride
+            ),
+            "Passes must match the accumulated image"
+        );
+        self.passes += 1;
+        let weight = 1.0 / self.passes as f32;
+        for (average, value) in self.image.data.iter_mut().zip(&pass.data) {
+            *average += (value - *average) * weight;
+        }
+    }
+
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    /// The average of every pass so far
+    pub fn image(&self) -> &FloatImageBuffer {
+        &self.image
+    }
+
+    pub fn into_image(self) -> FloatImageBuffer {
+        self.image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_add_given_passes_returns_their_average() {
+        let mut accumulator = Accumulator::new(2, 1);
+        for value in [1.0, 2.0, 6.0] {
+            let mut pass = FloatImageBuffer::new_rgb(2, 1);
+            pass.set_pixel(0, 0, [value, 0.0, 1.0]);
+            pass.set_pixel(1, 0, [0.5, value * 2.0, 1.0]);
+            accumulator.add(&pass);
+        }
+        assert_eq!(accumulator.passes(), 3);
+        assert_eq!(accumulator.image().pixel(0, 0), [3.0, 0.0, 1.0]);
+        assert_eq!(accumulator.image().pixel(1, 0), [0.5, 6.0, 1.0]);
+    }
+}
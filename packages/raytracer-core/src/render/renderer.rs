@@ -1,23 +1,162 @@
-use cgmath::{vec3, ElementWise, InnerSpace, Vector3};
+use std::{ops::ControlFlow, ops::Range, sync::Mutex};
+
+use cgmath::{vec3, ElementWise, Vector3, Zero};
 
 use crate::{
-    geometry::{Ray, RayCollidable},
-    image::buffer::ImageBuffer,
+    geometry::{Collision, Ray, RayCollidable},
+    image::buffer::FloatImageBuffer,
+    sampler::{Sampler, SamplerKind, SamplerTrait},
     scene::SceneGraph,
     shader::MaterialTrait,
 };
 
 use super::{
     camera::Camera,
-    iter::{Pixel, PixelIterator},
+    iter::{Pixel, PixelIterator, TileOrder},
+    pool::render_tiles,
+    progressive::{Accumulator, Pass},
 };
 
+/// How the renderer finds the light reaching each point a path bounces off
+///
+/// Scattered rays only find lights by chance, which for small lights is
+/// rarely. Sampling lights directly aims a shadow ray at a point on one at
+/// every bounce as well. Each strategy is good at what the other is bad at,
+/// so their results are blended with multiple importance sampling (Veach &
+/// Guibas, 1995), weighting each towards whichever was likelier to pick the
+/// direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LightSampling {
+    /// Only follow scattered rays, counting any light they happen to hit.
+    /// Lights that no ray could hit, like point lights, are still sampled.
+    Off,
+    /// Sample lights as well, weighting by each strategy's density
+    Balance,
+    /// Sample lights as well, weighting by the square of each strategy's
+    /// density, which is usually a little less noisy
+    #[default]
+    Power,
+}
+
+impl LightSampling {
+    /// How much of a sample to keep, when it was picked with the density
+    /// `pdf` and the other strategy would've picked it with `other_pdf`
+    #[inline(always)]
+    pub fn weight(&self, pdf: f64, other_pdf: f64) -> f64 {
+        let (a, b) = match self {
+            LightSampling::Off => return 1.0,
+            LightSampling::Balance => (pdf, other_pdf),
+            LightSampling::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+        if a + b == 0.0 {
+            return 0.0;
+        }
+        return a / (a + b);
+    }
+}
+
+/// Spending more samples on noisy pixels, like those in a caustic, than on
+/// flat ones, like a clear sky
+///
+/// Each pixel keeps track of how much its brightness varies from sample to
+/// sample. Once it's taken the minimum, it stops as soon as the standard
+/// error of its average brightness falls under the threshold, as a fraction
+/// of that brightness. Noisy pixels carry on up to the renderer's samples per
+/// pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    /// How much noise to leave, eg 0.01 to stop once a pixel is probably
+    /// within 1% of where it would end up
+    pub threshold: f64,
+    /// How many samples every pixel gets, so a few that happen to agree
+    /// aren't mistaken for a smooth pixel
+    pub min_samples: usize,
+}
+
+impl AdaptiveSampling {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            min_samples: 16,
+        }
+    }
+
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Whether a pixel can stop, given the mean of its samples' brightness,
+    /// the sum of their squared differences from it, and how many there were
+    #[inline(always)]
+    fn is_converged(&self, mean: f64, squares: f64, samples: usize) -> bool {
+        if samples < self.min_samples.max(2) {
+            return false;
+        }
+        let n = samples as f64;
+        let standard_error = (squares / ((n - 1.0) * n)).sqrt();
+        // pixels that are nearly black would otherwise never be close enough
+        return standard_error <= self.threshold * mean.max(MIN_ADAPTIVE_BRIGHTNESS);
+    }
+}
+
+/// The brightness below which adaptive sampling measures noise in absolute
+/// terms rather than relative to the pixel
+const MIN_ADAPTIVE_BRIGHTNESS: f64 = 0.01;
+
+/// How many samples each pixel of a render took
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampleCounts {
+    pub width: usize,
+    pub height: usize,
+    /// A row at a time, from the top
+    pub counts: Vec<u32>,
+}
+
+impl SampleCounts {
+    pub fn count(&self, x: usize, y: usize) -> u32 {
+        self.counts[y * self.width + x]
+    }
+
+    /// Show the counts as an image, from dark purple for none through to
+    /// yellow for `max_samples`
+    pub fn heatmap(&self, max_samples: usize) -> FloatImageBuffer {
+        // the viridis color map, which still reads in order in grayscale
+        const STOPS: [[f32; 3]; 5] = [
+            [0.267, 0.005, 0.329],
+            [0.229, 0.322, 0.546],
+            [0.128, 0.567, 0.551],
+            [0.369, 0.789, 0.383],
+            [0.993, 0.906, 0.144],
+        ];
+        let mut image = FloatImageBuffer::new_rgb(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let amount = (self.count(x, y) as f32 / max_samples.max(1) as f32).min(1.0);
+                let position = amount * (STOPS.len() - 1) as f32;
+                let idx = (position as usize).min(STOPS.len() - 2);
+                let blend = position - idx as f32;
+                let (from, to) = (STOPS[idx], STOPS[idx + 1]);
+                // the stops are sRGB, but the image is linear
+                let color = [0, 1, 2].map(|c| (from[c] + (to[c] - from[c]) * blend).powf(2.2));
+                image.set_pixel(x, y, color);
+            }
+        }
+        image
+    }
+}
+
 pub struct Renderer {
     width: usize,
     height: usize,
     samples_per_pixel: usize,
     max_ray_casts: i64,
     camera: Camera,
+    light_sampling: LightSampling,
+    tile_order: TileOrder,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    sampler: SamplerKind,
+    seed: u64,
 }
 
 impl Renderer {
@@ -34,68 +173,688 @@ impl Renderer {
             samples_per_pixel,
             max_ray_casts,
             camera,
+            light_sampling: LightSampling::default(),
+            tile_order: TileOrder::default(),
+            adaptive_sampling: Option::None,
+            sampler: SamplerKind::default(),
+            seed: 0,
         }
     }
 
+    pub fn with_light_sampling(mut self, light_sampling: LightSampling) -> Self {
+        self.light_sampling = light_sampling;
+        self
+    }
+
+    /// The order `render_parallel` works through the image's tiles in
+    pub fn with_tile_order(mut self, tile_order: TileOrder) -> Self {
+        self.tile_order = tile_order;
+        self
+    }
+
+    /// Stop sampling pixels once they're smooth enough, treating the
+    /// samples per pixel as the most any pixel takes. Progressive renders
+    /// take one sample per pixel per pass regardless.
+    pub fn with_adaptive_sampling(mut self, adaptive_sampling: AdaptiveSampling) -> Self {
+        self.adaptive_sampling = Option::Some(adaptive_sampling);
+        self
+    }
+
+    /// How the samples for each pixel are picked
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Where the random numbers behind every sample start from. The same
+    /// seed renders the same image, however many threads render it and in
+    /// whatever order.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
     pub fn new_from_defaults(width: usize, height: usize, camera: Camera) -> Self {
         Self::new(width, height, 16, 16, camera)
     }
 
+    /// Render the pixels from an iterator into a buffer, as linear radiance
+    ///
+    /// Nothing is clipped or gamma corrected, so that the buffer can be tone
+    /// mapped or written out as HDR later on.
     pub fn render_to_buffer(
         &self,
         scene: &SceneGraph,
-        buf: &mut ImageBuffer,
+        buf: &mut FloatImageBuffer,
         iterator: PixelIterator,
     ) {
-        let rng = fastrand::Rng::new();
+        let mut sampler = self.make_sampler();
         for Pixel { x, y } in iterator {
-            let i = x;
-            let j = y;
-            let mut color = vec3(0.0, 0.0, 0.0);
-            for _ in 0..self.samples_per_pixel {
-                let u: f64 = ((i as f64) + rng.f64()) / (self.width - 1) as f64;
-                let v: f64 = ((j as f64) + rng.f64()) / (self.height - 1) as f64;
-                let ray = self.camera.project_ray(u, v);
-                color += ray_color(&ray, scene, 0.001, self.max_ray_casts);
+            let (color, _) =
+                self.render_pixel(scene, x, y, 0..self.samples_per_pixel, &mut sampler);
+            buf.set_pixel(x, y, color);
+        }
+    }
+
+    /// Render the whole image into a buffer on a number of threads, as
+    /// linear radiance
+    ///
+    /// The image is split into tiles that threads take turns at, so they stay
+    /// busy even when some parts of the image are much slower than others.
+    pub fn render_parallel(&self, scene: &SceneGraph, buf: &mut FloatImageBuffer, threads: usize) {
+        self.render_with_sample_counts(scene, buf, threads);
+    }
+
+    /// Render like `render_parallel`, also returning how many samples each
+    /// pixel took, which only varies with adaptive sampling
+    pub fn render_with_sample_counts(
+        &self,
+        scene: &SceneGraph,
+        buf: &mut FloatImageBuffer,
+        threads: usize,
+    ) -> SampleCounts {
+        let counts = Mutex::new(vec![0; buf.width * buf.height]);
+        render_tiles(buf, threads, self.tile_order, |tile| {
+            let mut sampler = self.make_sampler();
+            let (colors, samples): (Vec<[f32; 3]>, Vec<usize>) = tile
+                .pixels()
+                .map(|Pixel { x, y }| {
+                    self.render_pixel(scene, x, y, 0..self.samples_per_pixel, &mut sampler)
+                })
+                .unzip();
+            let mut counts = counts.lock().unwrap();
+            for (Pixel { x, y }, samples) in tile.pixels().zip(samples) {
+                counts[y * self.width + x] = samples as u32;
+            }
+            colors
+        });
+        return SampleCounts {
+            width: self.width,
+            height: self.height,
+            counts: counts.into_inner().unwrap(),
+        };
+    }
+
+    /// Render the whole image a sample per pixel at a time on a number of
+    /// threads, handing the average so far to `on_pass` after each pass
+    ///
+    /// There are as many passes as samples per pixel, unless `on_pass` breaks
+    /// out early. Either way, the average of the passes rendered is returned.
+    pub fn render_progressive<F>(
+        &self,
+        scene: &SceneGraph,
+        threads: usize,
+        mut on_pass: F,
+    ) -> FloatImageBuffer
+    where
+        F: FnMut(Pass<'_>) -> ControlFlow<()>,
+    {
+        let mut accumulator = Accumulator::new(self.width, self.height);
+        for number in 1..=self.samples_per_pixel {
+            self.render_pass(scene, threads, &mut accumulator);
+            let progress = Pass {
+                number,
+                total: self.samples_per_pixel,
+                image: accumulator.image(),
+            };
+            if on_pass(progress).is_break() {
+                break;
+            }
+        }
+        return accumulator.into_image();
+    }
+
+    /// Render a single sample for every pixel, blending it into the passes
+    /// before it
+    ///
+    /// This is for callers that can't wait in `render_progressive` between
+    /// passes, like a web page that has to hand control back to the browser
+    /// to show each one.
+    pub fn render_pass(&self, scene: &SceneGraph, threads: usize, accumulator: &mut Accumulator) {
+        let mut pass = FloatImageBuffer::new_rgb(self.width, self.height);
+        // each pass takes the next of every pixel's samples
+        let index = accumulator.passes();
+        render_tiles(&mut pass, threads, self.tile_order, |tile| {
+            let mut sampler = self.make_sampler();
+            tile.pixels()
+                .map(|Pixel { x, y }| {
+                    self.render_pixel(scene, x, y, index..index + 1, &mut sampler)
+                        .0
+                })
+                .collect()
+        });
+        accumulator.add(&pass);
+    }
+
+    fn make_sampler(&self) -> Sampler {
+        Sampler::new(self.sampler, self.samples_per_pixel, self.seed)
+    }
+
+    /// Average a range of a single pixel's samples, returning the average and
+    /// how many samples it took, which is fewer when adaptive sampling finds
+    /// the pixel smooth enough early on
+    fn render_pixel(
+        &self,
+        scene: &SceneGraph,
+        x: usize,
+        y: usize,
+        samples: Range<usize>,
+        sampler: &mut Sampler,
+    ) -> ([f32; 3], usize) {
+        let spread = self.camera.pixel_spread(self.height);
+        let mut color = vec3(0.0, 0.0, 0.0);
+        // the running mean of the samples' brightness, and the sum of their
+        // squared differences from it (Welford, 1962)
+        let (mut mean, mut squares) = (0.0, 0.0);
+        let mut taken = 0;
+        for index in samples {
+            sampler.start_pixel_sample(x, y, index);
+            let [jitter_x, jitter_y] = sampler.next_2d();
+            let u: f64 = ((x as f64) + jitter_x) / (self.width - 1) as f64;
+            let v: f64 = ((y as f64) + jitter_y) / (self.height - 1) as f64;
+            let ray = self.camera.project_ray(u, v, sampler).with_spread(spread);
+            let sample = ray_color(
+                &ray,
+                scene,
+                0.001,
+                self.max_ray_casts,
+                self.light_sampling,
+                sampler,
+            );
+            color += sample;
+            taken += 1;
+
+            if let Option::Some(adaptive_sampling) = &self.adaptive_sampling {
+                let brightness = 0.2126 * sample.x + 0.7152 * sample.y + 0.0722 * sample.z;
+                let difference = brightness - mean;
+                mean += difference / taken as f64;
+                squares += difference * (brightness - mean);
+                if adaptive_sampling.is_converged(mean, squares, taken) {
+                    break;
+                }
             }
-            color /= self.samples_per_pixel as f64;
-            let idx = (j * self.width + i) * 3;
-            buf.data[idx + 0] = (256.0 * color[0].sqrt()).round() as u8;
-            buf.data[idx + 1] = (256.0 * color[1].sqrt()).round() as u8;
-            buf.data[idx + 2] = (256.0 * color[2].sqrt()).round() as u8;
         }
+        color /= taken.max(1) as f64;
+        return ([color[0] as f32, color[1] as f32, color[2] as f32], taken);
     }
 }
 
-fn ray_color<T: RayCollidable>(
+fn ray_color(
     ray: &Ray,
-    scene: &T,
+    scene: &SceneGraph,
     min_clip: f64,
     max_depth: i64,
+    light_sampling: LightSampling,
+    sampler: &mut Sampler,
+) -> Vector3<f64> {
+    trace(
+        ray,
+        scene,
+        min_clip,
+        max_depth,
+        light_sampling,
+        Option::None,
+        sampler,
+    )
+}
+
+/// Follow a path through the scene, returning the light that comes back along
+/// it
+///
+/// `scatter_pdf` is the density the material the ray scattered off picked
+/// its direction with, which is None for camera rays and specular bounces.
+/// Lights can't be sampled for those, so any light they hit counts in full.
+fn trace(
+    ray: &Ray,
+    scene: &SceneGraph,
+    min_clip: f64,
+    max_depth: i64,
+    light_sampling: LightSampling,
+    scatter_pdf: Option<f64>,
+    sampler: &mut Sampler,
 ) -> Vector3<f64> {
     if max_depth < 0 {
         return vec3(0.0, 0.0, 0.0);
     }
-    match scene.will_intersect(&ray, min_clip, f64::INFINITY) {
+    let hit = scene.will_intersect_sampled(ray, min_clip, f64::INFINITY, sampler);
+    // fog and volumes can scatter the ray before it gets to whatever it hit,
+    // in which case their phase function is scattered off like any other
+    // material. Glowing volumes aren't sampled as lights, so the light they
+    // give off on the way counts in full.
+    let t_hit = hit.as_ref().map_or(f64::INFINITY, |collision| collision.t);
+    let interaction = scene.medium_interaction(ray, min_clip, t_hit, sampler);
+    let collision = match interaction.collision.or(hit) {
         Option::None => {
-            // do nothing
-        }
-        Option::Some(collision) => {
-            return match collision.material.scatter(ray, &collision) {
-                Option::None => vec3(0.0, 0.0, 0.0),
-                Option::Some((attenuation, scatter_ray)) => {
-                    return attenuation.mul_element_wise(ray_color(
-                        &scatter_ray,
-                        scene,
-                        min_clip,
-                        max_depth - 1,
-                    ));
+            // backgrounds aren't sampled, but lights like the sun can be
+            let mut light = scene.escaped_light(ray);
+            if let Option::Some(scatter_pdf) = scatter_pdf {
+                if !light.is_zero() {
+                    let light_pdf = scene.light_pdf(ray, min_clip, f64::INFINITY);
+                    light *= light_sampling.weight(scatter_pdf, light_pdf);
                 }
-            };
+            }
+            return interaction.emitted + scene.background_color(ray) + light;
+        }
+        Option::Some(collision) => collision,
+    };
+
+    let mut color = collision.material.emitted(ray, &collision);
+    if let Option::Some(scatter_pdf) = scatter_pdf {
+        if !color.is_zero() {
+            // this light could also have been sampled from the last bounce
+            let light_pdf = scene.light_pdf(ray, min_clip, collision.t);
+            color *= light_sampling.weight(scatter_pdf, light_pdf);
+        }
+    }
+    color += interaction.emitted;
+
+    let (attenuation, scatter_ray) = match collision.material.scatter(ray, &collision, sampler) {
+        Option::None => return color,
+        Option::Some(scattered) => scattered,
+    };
+    // past the last bounce, light the scattered ray hits isn't counted, so
+    // neither is light sampled towards it
+    let samples_lights = !collision.material.is_specular() && max_depth > 0;
+    if samples_lights {
+        color += sample_light(ray, &collision, scene, min_clip, light_sampling, sampler);
+    }
+    // without light sampling, any light the scattered ray hits counts in full
+    let scatter_pdf = if samples_lights && light_sampling != LightSampling::Off {
+        Option::Some(
+            collision
+                .material
+                .pdf(ray, &collision, scatter_ray.direction),
+        )
+    } else {
+        Option::None
+    };
+    return color
+        + attenuation.mul_element_wise(trace(
+            &scatter_ray,
+            scene,
+            min_clip,
+            max_depth - 1,
+            light_sampling,
+            scatter_pdf,
+            sampler,
+        ));
+}
+
+/// Estimate the light reaching a collision directly from a light, by casting
+/// a shadow ray towards a point picked on one
+fn sample_light(
+    ray: &Ray,
+    collision: &Collision,
+    scene: &SceneGraph,
+    min_clip: f64,
+    light_sampling: LightSampling,
+    sampler: &mut Sampler,
+) -> Vector3<f64> {
+    let black = vec3(0.0, 0.0, 0.0);
+    let sample = match scene.sample_light(&collision.point, ray.time, sampler) {
+        Option::Some(sample) => sample,
+        Option::None => return black,
+    };
+    // without light sampling, lights with a surface are left for scattered
+    // rays to find, and only the ones they never could are sampled
+    if light_sampling == LightSampling::Off && !sample.is_delta {
+        return black;
+    }
+    let bsdf = collision
+        .material
+        .evaluate(ray, collision, sample.direction);
+    if bsdf.is_zero() || sample.radiance.is_zero() {
+        return black;
+    }
+
+    // stop just short of the light, so that its own surface doesn't block it
+    let shadow_ray = Ray::new(collision.point, sample.direction, ray.time);
+    let t_max = sample.distance - 1e-6 * sample.distance.max(1.0);
+    if scene
+        .will_intersect_sampled(&shadow_ray, min_clip, t_max, sampler)
+        .is_some()
+    {
+        return black;
+    }
+    // volumes made of surfaces block shadow rays at random, in proportion to
+    // how thick they are, but fog and voxel volumes dim them instead
+    let transmittance = scene.transmittance(&shadow_ray, min_clip, t_max, sampler);
+
+    // nothing but sampling could've found a light with no size
+    let weight = if sample.is_delta {
+        1.0
+    } else {
+        let scatter_pdf = collision.material.pdf(ray, collision, sample.direction);
+        light_sampling.weight(sample.pdf, scatter_pdf)
+    };
+    return bsdf.mul_element_wise(sample.radiance) * (transmittance * weight / sample.pdf);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::PI, sync::Arc};
+
+    use cgmath::point3;
+
+    use crate::{
+        background::Background,
+        geometry::{constant_medium::Fog, sphere::Sphere, Vector},
+        light::PointLight,
+        shader::{DiffuseLight, Isotropic, Lambertian},
+    };
+
+    use super::*;
+
+    #[test]
+    fn when_ray_color_given_black_background_returns_only_emitted_light() {
+        let emit: Vector = vec3(2.0, 1.0, 0.5);
+        let scene = SceneGraph::new(vec![
+            Arc::new(Sphere::new_with_material(
+                point3(0.0, 0.0, -2.0),
+                0.5,
+                Arc::new(DiffuseLight::new(emit)).into(),
+            ))
+            .into(),
+            Arc::new(Sphere::new_with_material(
+                point3(0.0, 0.0, 2.0),
+                0.5,
+                Arc::new(Lambertian::new(vec3(0.5, 0.5, 0.5))).into(),
+            ))
+            .into(),
+        ])
+        .with_background(Background::Solid(vec3(0.0, 0.0, 0.0)));
+
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let at_light = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert_eq!(
+            ray_color(
+                &at_light,
+                &scene,
+                0.001,
+                4,
+                LightSampling::Power,
+                &mut sampler
+            ),
+            emit
+        );
+        let at_sky = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        assert_eq!(
+            ray_color(
+                &at_sky,
+                &scene,
+                0.001,
+                4,
+                LightSampling::Power,
+                &mut sampler
+            ),
+            vec3(0.0, 0.0, 0.0)
+        );
+        // the diffuse sphere is only lit by the light, which it reflects half of
+        let at_wall = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let color = ray_color(
+            &at_wall,
+            &scene,
+            0.001,
+            4,
+            LightSampling::Power,
+            &mut sampler,
+        );
+        assert!(color.x <= emit.x * 0.5 && color.y <= emit.y * 0.5 && color.z <= emit.z * 0.5);
+    }
+
+    /// The mean and variance of many estimates of the light coming back
+    /// along a ray, by luminance
+    fn estimate(ray: &Ray, scene: &SceneGraph, light_sampling: LightSampling) -> (f64, f64) {
+        let count = 4000;
+        let mut sampler = Sampler::new(SamplerKind::Independent, count, 0);
+        let samples: Vec<f64> = (0..count)
+            .map(|idx| {
+                sampler.start_pixel_sample(0, 0, idx);
+                let color = ray_color(ray, scene, 0.001, 4, light_sampling, &mut sampler);
+                0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+            })
+            .collect();
+        let mean = samples.iter().sum::<f64>() / count as f64;
+        let variance =
+            samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / (count - 1) as f64;
+        (mean, variance)
+    }
+
+    #[test]
+    fn when_ray_color_given_small_light_sampling_lights_converges_faster() {
+        // a diffuse floor lit only by a small, bright light, which scattered
+        // rays rarely find
+        let scene = SceneGraph::new(vec![
+            Arc::new(Sphere::new_with_material(
+                point3(0.0, -1000.0, 0.0),
+                1000.0,
+                Arc::new(Lambertian::new(vec3(0.5, 0.5, 0.5))).into(),
+            ))
+            .into(),
+            Arc::new(Sphere::new_with_material(
+                point3(0.0, 2.0, 0.0),
+                0.2,
+                Arc::new(DiffuseLight::new(vec3(25.0, 25.0, 25.0))).into(),
+            ))
+            .into(),
+        ])
+        .with_background(Background::Solid(vec3(0.0, 0.0, 0.0)));
+        let at_floor = Ray::new(point3(0.0, 1.0, 3.0), vec3(0.0, -1.0, -3.0), 0.0);
+
+        let (mean, variance) = estimate(&at_floor, &scene, LightSampling::Off);
+        for light_sampling in [LightSampling::Balance, LightSampling::Power] {
+            let (sampled_mean, sampled_variance) = estimate(&at_floor, &scene, light_sampling);
+            assert!(
+                sampled_variance * 100.0 < variance,
+                "{:?} should be far less noisy, got a variance of {} against {}",
+                light_sampling,
+                sampled_variance,
+                variance
+            );
+            // both are estimating the same thing, so they should agree to
+            // within the noise of the worse one
+            let standard_error = (variance / 4000.0).sqrt();
+            assert!(
+                (sampled_mean - mean).abs() < 5.0 * standard_error,
+                "{:?} should converge to the same result, got {} against {}",
+                light_sampling,
+                sampled_mean,
+                mean
+            );
         }
     }
 
-    let unit_direction = ray.direction.normalize();
-    let t = 0.5 * (unit_direction.y + 1.0);
-    return (1.0 - t) * vec3(1.0, 1.0, 1.0) + t * vec3(0.5, 0.7, 1.0);
+    #[test]
+    fn when_ray_color_given_point_light_without_light_sampling_still_lights() {
+        // a point light can't be hit by a scattered ray, so it has to be
+        // sampled however lights are being sampled
+        let scene = SceneGraph::new(vec![Arc::new(Sphere::new_with_material(
+            point3(0.0, -1000.0, 0.0),
+            1000.0,
+            Arc::new(Lambertian::new(vec3(0.5, 0.5, 0.5))).into(),
+        ))
+        .into()])
+        .with_background(Background::Solid(vec3(0.0, 0.0, 0.0)))
+        .with_lights(vec![Arc::new(PointLight::new(
+            point3(0.0, 2.0, 0.0),
+            vec3(4.0, 4.0, 4.0),
+        ))
+        .into()]);
+        let at_floor = Ray::new(point3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+
+        let (mean, _) = estimate(&at_floor, &scene, LightSampling::Off);
+        // straight below the light, 2 away, the floor gets 4 / 2^2 = 1 of its
+        // light, and sends back 0.5 / pi of that
+        let expected = 0.5 / PI;
+        assert!(
+            (mean - expected).abs() < 1e-6,
+            "Expected {} from the point light, got {}",
+            expected,
+            mean
+        );
+        let (sampled_mean, _) = estimate(&at_floor, &scene, LightSampling::Power);
+        assert!((sampled_mean - mean).abs() < 1e-9);
+    }
+
+    #[test]
+    fn when_ray_color_given_fog_dims_light_behind_it() {
+        // fog that absorbs everything it scatters only lets through the light
+        // that never scattered at all
+        let emit: Vector = vec3(1.0, 1.0, 1.0);
+        let density = 0.2;
+        let scene = SceneGraph::new(vec![
+            Arc::new(Sphere::new_with_material(
+                point3(0.0, 0.0, -4.0),
+                0.5,
+                Arc::new(DiffuseLight::new(emit)).into(),
+            ))
+            .into(),
+            Arc::new(Sphere::new(point3(0.0, 0.0, 2.0), 0.5)).into(),
+        ])
+        .with_background(Background::Solid(vec3(1.0, 1.0, 1.0)))
+        .with_fog(Fog::new(
+            density,
+            Arc::new(Isotropic::new(vec3(0.0, 0.0, 0.0))).into(),
+        ));
+
+        let at_light = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let (mean, _) = estimate(&at_light, &scene, LightSampling::Power);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let expected = (-density * 3.5f64).exp();
+        assert!(
+            (mean - expected).abs() < 0.03,
+            "Expected {} of the light through the fog, got {}",
+            expected,
+            mean
+        );
+        assert!(
+            (scene.transmittance(&at_light, 0.0, 3.5, &mut sampler) - expected).abs() < 1e-12,
+            "Fog should dim the light by the same amount when sampled directly"
+        );
+    }
+
+    #[test]
+    fn when_render_progressive_given_break_stops_after_that_pass() {
+        // nothing but a solid background, so every pass is the same color
+        let scene =
+            SceneGraph::new(vec![]).with_background(Background::Solid(vec3(0.25, 0.5, 1.0)));
+        let camera = Camera::new(
+            point3(0.0, 0.0, 0.0),
+            point3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+            2.0,
+            cgmath::Deg(45.0),
+            22.0,
+            1.0,
+            0.0,
+            0.0,
+        );
+        let renderer = Renderer::new(40, 20, 8, 4, camera);
+        let mut passes = vec![];
+        let image = renderer.render_progressive(&scene, 2, |pass| {
+            passes.push((pass.number, pass.total));
+            assert_eq!(pass.image.pixel(39, 19), [0.25, 0.5, 1.0]);
+            if pass.number == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(passes, vec![(1, 8), (2, 8), (3, 8)]);
+        assert_eq!(image.pixel(0, 0), [0.25, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn when_render_with_sample_counts_given_flat_image_stops_at_min_samples() {
+        let scene = SceneGraph::new(vec![]).with_background(Background::Solid(vec3(0.5, 0.5, 0.5)));
+        let camera = Camera::new(
+            point3(0.0, 0.0, 0.0),
+            point3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+            1.0,
+            cgmath::Deg(45.0),
+            22.0,
+            1.0,
+            0.0,
+            0.0,
+        );
+        let renderer = Renderer::new(20, 20, 64, 4, camera.clone());
+        let mut buf = FloatImageBuffer::new_rgb(20, 20);
+        let counts = renderer.render_with_sample_counts(&scene, &mut buf, 2);
+        assert!(counts.counts.iter().all(|&count| count == 64));
+
+        let renderer = Renderer::new(20, 20, 64, 4, camera)
+            .with_adaptive_sampling(AdaptiveSampling::new(0.01).with_min_samples(4));
+        let counts = renderer.render_with_sample_counts(&scene, &mut buf, 2);
+        assert!(counts.counts.iter().all(|&count| count == 4));
+        assert_eq!(buf.pixel(10, 10), [0.5, 0.5, 0.5]);
+        // the fewest samples are at the dark end of the heatmap
+        let heatmap = counts.heatmap(64);
+        assert!(heatmap.pixel(0, 0)[1] < 0.01);
+    }
+
+    #[test]
+    fn when_render_parallel_given_same_seed_returns_identical_images() {
+        let scene = crate::scene::new_random_world(9).with_fog(Fog::new(
+            0.02,
+            Arc::new(Isotropic::new(vec3(0.8, 0.8, 0.8))).into(),
+        ));
+        let camera = Camera::new(
+            point3(13.0, 2.0, 3.0),
+            point3(0.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            2.0,
+            cgmath::Deg(20.0),
+            16.0,
+            10.0,
+            0.0,
+            1.0,
+        );
+        let render = |threads: usize, tile_order: TileOrder, seed: u64| {
+            let renderer = Renderer::new(48, 24, 4, 4, camera.clone())
+                .with_tile_order(tile_order)
+                .with_seed(seed);
+            let mut buf = FloatImageBuffer::new_rgb(48, 24);
+            renderer.render_parallel(&scene, &mut buf, threads);
+            buf.data
+        };
+        let image = render(1, TileOrder::Scanline, 1);
+        // no matter how the work is split up, every pixel gets the same
+        // samples
+        assert!(image == render(3, TileOrder::Hilbert, 1));
+        assert!(image != render(1, TileOrder::Scanline, 2));
+    }
+
+    #[test]
+    fn when_is_converged_given_noisy_samples_waits_for_more() {
+        let adaptive_sampling = AdaptiveSampling::new(0.05).with_min_samples(4);
+        // samples of 0 and 2 in turn, with a mean of 1 and a variance of about
+        // 1, so the standard error only drops under 0.05 past 400 samples
+        let (mut mean, mut squares) = (0.0, 0.0);
+        let mut converged_at = Option::None;
+        for taken in 1..=1000 {
+            let brightness = if taken % 2 == 0 { 0.0 } else { 2.0 };
+            let difference = brightness - mean;
+            mean += difference / taken as f64;
+            squares += difference * (brightness - mean);
+            if adaptive_sampling.is_converged(mean, squares, taken) {
+                converged_at = Option::Some(taken);
+                break;
+            }
+        }
+        let converged_at = converged_at.unwrap();
+        assert!(converged_at > 395 && converged_at < 410, "{}", converged_at);
+    }
 }
@@ -0,0 +1,206 @@
+use std::sync::OnceLock;
+
+use super::{hash, sobol::scrambled_sobol, SamplePosition, SamplerTrait, ONE_MINUS_EPSILON};
+
+/// How many pixels along each side of the blue noise texture, which repeats
+/// across the image
+const TEXTURE_SIZE: usize = 64;
+
+/// Samples from the same scrambled Sobol points in every pixel, each shifted
+/// by a blue noise texture, so that what noise is left looks finer grained
+///
+/// Neighbouring values of blue noise are as different from each other as they
+/// can be, so neighbouring pixels err in different directions, and the errors
+/// average out when the image is looked at from a distance (Heitz and Belcour,
+/// 2019). Each dimension reads the texture from a different place, so the
+/// dimensions aren't shifted together.
+#[derive(Clone)]
+pub struct BlueNoiseSampler {
+    samples_per_pixel: u32,
+    seed: u64,
+    position: SamplePosition,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1) as u32,
+            seed,
+            position: SamplePosition::default(),
+        }
+    }
+
+    /// How far to shift a dimension for the current pixel
+    fn shift(&self, dimension: u64) -> f64 {
+        let offset = hash(&[self.seed, dimension, 0x5eed]);
+        let x = (self.position.x as usize + offset as usize) % TEXTURE_SIZE;
+        let y = (self.position.y as usize + (offset >> 32) as usize) % TEXTURE_SIZE;
+        return texture()[y * TEXTURE_SIZE + x] as f64;
+    }
+
+    fn sample(&self, dimension: u64) -> [f64; 2] {
+        let bits = hash(&[self.seed, dimension]);
+        let [u, v] = scrambled_sobol(self.position.index, self.samples_per_pixel, bits);
+        let wrap = |value: f64| (value - value.floor()).min(ONE_MINUS_EPSILON);
+        return [
+            wrap(u + self.shift(dimension)),
+            wrap(v + self.shift(dimension + 1)),
+        ];
+    }
+}
+
+impl SamplerTrait for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.position.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.position.advance(1);
+        let [u, _] = self.sample(dimension);
+        u
+    }
+
+    fn next_2d(&mut self) -> [f64; 2] {
+        let dimension = self.position.advance(2);
+        self.sample(dimension)
+    }
+}
+
+/// The blue noise texture, made the first time it's needed
+fn texture() -> &'static [f32] {
+    static TEXTURE: OnceLock<Vec<f32>> = OnceLock::new();
+    TEXTURE.get_or_init(|| void_and_cluster(TEXTURE_SIZE, 0xb1e))
+}
+
+/// Make a square of blue noise that wraps around at the edges, with every
+/// value from 0 to 1 appearing once, with Ulichney's void-and-cluster method
+///
+/// Points are put down one at a time, each in the middle of the biggest gap
+/// left between the points before it, and their order gives their value.
+/// How crowded a spot is is measured by adding up a Gaussian around every
+/// point.
+fn void_and_cluster(size: usize, seed: u64) -> Vec<f32> {
+    let count = size * size;
+    let sigma: f64 = 1.5;
+    // how much a point adds to how crowded the spots around it are, by how far
+    // away they are, going the short way around the edges
+    let kernel: Vec<f64> = (0..count)
+        .map(|i| {
+            let (dx, dy) = (i % size, i / size);
+            let dx = dx.min(size - dx) as f64;
+            let dy = dy.min(size - dy) as f64;
+            (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+
+    struct Pattern<'a> {
+        size: usize,
+        kernel: &'a [f64],
+        points: Vec<bool>,
+        energy: Vec<f64>,
+    }
+
+    impl Pattern<'_> {
+        fn toggle(&mut self, i: usize) {
+            self.points[i] = !self.points[i];
+            let sign = if self.points[i] { 1.0 } else { -1.0 };
+            let (px, py) = (i % self.size, i / self.size);
+            for (j, energy) in self.energy.iter_mut().enumerate() {
+                let dx = (j % self.size + self.size - px) % self.size;
+                let dy = (j / self.size + self.size - py) % self.size;
+                *energy += sign * self.kernel[dy * self.size + dx];
+            }
+        }
+
+        /// The most crowded point
+        fn tightest_cluster(&self) -> usize {
+            (0..self.points.len())
+                .filter(|&i| self.points[i])
+                .max_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+                .unwrap()
+        }
+
+        /// The emptiest spot without a point
+        fn largest_void(&self) -> usize {
+            (0..self.points.len())
+                .filter(|&i| !self.points[i])
+                .min_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+                .unwrap()
+        }
+    }
+
+    let mut pattern = Pattern {
+        size,
+        kernel: &kernel,
+        points: vec![false; count],
+        energy: vec![0.0; count],
+    };
+
+    // start from a tenth of the spots picked at random, then move points out
+    // of clusters into voids until they're as spread out as they'll get
+    let rng = fastrand::Rng::with_seed(seed);
+    let initial = count / 10;
+    while pattern.points.iter().filter(|&&point| point).count() < initial {
+        let i = rng.usize(0..count);
+        if !pattern.points[i] {
+            pattern.toggle(i);
+        }
+    }
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    // the starting points are ranked by taking them away again, most crowded
+    // first
+    let mut removing = Pattern {
+        size,
+        kernel: &kernel,
+        points: pattern.points.clone(),
+        energy: pattern.energy.clone(),
+    };
+    for rank in (0..initial).rev() {
+        let cluster = removing.tightest_cluster();
+        removing.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+    // and the rest by filling in the gaps
+    for rank in initial..count {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    return ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count as f32)
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_void_and_cluster_given_size_returns_values_unlike_their_neighbours() {
+        let size = 32;
+        let noise = void_and_cluster(size, 1);
+        let mut sorted = noise.clone();
+        sorted.sort_by(f32::total_cmp);
+        for (rank, value) in sorted.iter().enumerate() {
+            assert_eq!(*value, (rank as f32 + 0.5) / (size * size) as f32);
+        }
+        // neighbouring white noise values are a third apart on average
+        let difference: f32 = (0..size * size)
+            .map(|i| (noise[i] - noise[(i + 1) % size + i / size * size]).abs())
+            .sum::<f32>()
+            / (size * size) as f32;
+        assert!(difference > 0.4, "{}", difference);
+    }
+}
@@ -0,0 +1,81 @@
+use super::{hash, to_unit, SamplePosition, SamplerTrait, ONE_MINUS_EPSILON};
+
+/// The bases of the dimensions of the Halton sequence, one prime for each.
+/// Past these, the higher primes give points that line up badly, and later
+/// bounces matter so little that plain random numbers do as well.
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Samples from the Halton sequence, where each dimension counts up in a
+/// different prime base with its digits mirrored after the point
+///
+/// Every pixel would get the same points, so each dimension is shifted by a
+/// random amount for each pixel, wrapping around past 1.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    position: SamplePosition,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            position: SamplePosition::default(),
+        }
+    }
+
+    fn sample(&self, dimension: u64) -> f64 {
+        let SamplePosition { x, y, index, .. } = self.position;
+        let shift = hash(&[self.seed, x, y, dimension]);
+        let Option::Some(&base) = PRIMES.get(dimension as usize) else {
+            return to_unit(hash(&[shift, index]));
+        };
+        let value = radical_inverse(base, index) + to_unit(shift);
+        return (value - value.floor()).min(ONE_MINUS_EPSILON);
+    }
+}
+
+impl SamplerTrait for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.position.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.position.advance(1);
+        self.sample(dimension)
+    }
+
+    fn next_2d(&mut self) -> [f64; 2] {
+        let dimension = self.position.advance(2);
+        [self.sample(dimension), self.sample(dimension + 1)]
+    }
+}
+
+/// The digits of a number in some base, mirrored around the point
+fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut digits = 0u64;
+    let mut scale = 1.0;
+    while index > 0 {
+        digits = digits * base + index % base;
+        scale *= inverse_base;
+        index /= base;
+    }
+    return (digits as f64 * scale).min(ONE_MINUS_EPSILON);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_radical_inverse_given_index_mirrors_its_digits() {
+        assert_eq!(radical_inverse(2, 0), 0.0);
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-12);
+    }
+}
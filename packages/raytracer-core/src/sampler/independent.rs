@@ -0,0 +1,36 @@
+use super::{hash, SamplerTrait};
+
+/// Picks every number at random on its own, which is simple but clumpy
+///
+/// Each sample gets its own random number generator, seeded by the pixel and
+/// sample, so a render doesn't depend on the order its pixels are rendered in.
+#[derive(Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    rng: fastrand::Rng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+}
+
+impl SamplerTrait for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.rng = fastrand::Rng::with_seed(hash(&[self.seed, x as u64, y as u64, index as u64]));
+    }
+
+    #[inline(always)]
+    fn next_1d(&mut self) -> f64 {
+        self.rng.f64()
+    }
+
+    #[inline(always)]
+    fn next_2d(&mut self) -> [f64; 2] {
+        [self.rng.f64(), self.rng.f64()]
+    }
+}
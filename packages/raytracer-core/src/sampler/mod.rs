@@ -0,0 +1,353 @@
+//! Where the random numbers behind each sample come from
+//!
+//! A sample is a point in many dimensions: two for where in the pixel its ray
+//! goes, two for where on the lens, one for when, and more for every bounce.
+//! Picking each of them independently leaves a pixel's samples clumped
+//! together in places and missing others, which shows up as noise. The
+//! samplers here spread each pixel's samples out more evenly, each in their
+//! own way, so the same number of samples makes for a smoother image.
+//!
+//! Samplers hand out the dimensions of a sample one or two at a time, in the
+//! order they're asked for, and start over with each new sample.
+use crate::geometry::Ray;
+
+mod blue_noise;
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use blue_noise::BlueNoiseSampler;
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+pub trait SamplerTrait {
+    /// Start on the `index`th sample of the pixel at (x, y), out of the
+    /// samples per pixel the sampler was made for
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize);
+
+    /// The next dimension of the current sample, in [0, 1)
+    fn next_1d(&mut self) -> f64;
+
+    /// The next two dimensions of the current sample, which are spread out
+    /// together rather than each on their own
+    fn next_2d(&mut self) -> [f64; 2];
+}
+
+/// The kinds of sampler there are, for picking one in settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    /// Every number is picked at random on its own
+    Independent,
+    /// Each pixel is split into a grid of strata, and each sample is jittered
+    /// inside of a different one
+    Stratified,
+    /// The Halton sequence, shifted at random for each pixel
+    Halton,
+    /// The Sobol sequence, scrambled differently for each pixel
+    #[default]
+    Sobol,
+    /// The Sobol sequence, shifted for each pixel by blue noise, so that
+    /// what noise is left looks finer grained
+    BlueNoise,
+}
+
+#[derive(Clone)]
+pub enum Sampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+    BlueNoise(BlueNoiseSampler),
+}
+
+impl Sampler {
+    /// Create a sampler for renders with a number of samples per pixel. The
+    /// same seed gives the same samples.
+    pub fn new(kind: SamplerKind, samples_per_pixel: usize, seed: u64) -> Self {
+        match kind {
+            SamplerKind::Independent => IndependentSampler::new(seed).into(),
+            SamplerKind::Stratified => StratifiedSampler::new(samples_per_pixel, seed).into(),
+            SamplerKind::Halton => HaltonSampler::new(seed).into(),
+            SamplerKind::Sobol => SobolSampler::new(samples_per_pixel, seed).into(),
+            SamplerKind::BlueNoise => BlueNoiseSampler::new(samples_per_pixel, seed).into(),
+        }
+    }
+
+    /// A random number generator seeded by the next dimension, for when
+    /// there's no telling how many numbers will be needed, like when tracking
+    /// a ray through a volume
+    pub fn rng(&mut self) -> fastrand::Rng {
+        fastrand::Rng::with_seed(mix_bits(self.next_1d().to_bits()))
+    }
+}
+
+impl SamplerTrait for Sampler {
+    #[inline(always)]
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        match self {
+            Sampler::Independent(sampler) => sampler.start_pixel_sample(x, y, index),
+            Sampler::Stratified(sampler) => sampler.start_pixel_sample(x, y, index),
+            Sampler::Halton(sampler) => sampler.start_pixel_sample(x, y, index),
+            Sampler::Sobol(sampler) => sampler.start_pixel_sample(x, y, index),
+            Sampler::BlueNoise(sampler) => sampler.start_pixel_sample(x, y, index),
+        }
+    }
+
+    #[inline(always)]
+    fn next_1d(&mut self) -> f64 {
+        match self {
+            Sampler::Independent(sampler) => sampler.next_1d(),
+            Sampler::Stratified(sampler) => sampler.next_1d(),
+            Sampler::Halton(sampler) => sampler.next_1d(),
+            Sampler::Sobol(sampler) => sampler.next_1d(),
+            Sampler::BlueNoise(sampler) => sampler.next_1d(),
+        }
+    }
+
+    #[inline(always)]
+    fn next_2d(&mut self) -> [f64; 2] {
+        match self {
+            Sampler::Independent(sampler) => sampler.next_2d(),
+            Sampler::Stratified(sampler) => sampler.next_2d(),
+            Sampler::Halton(sampler) => sampler.next_2d(),
+            Sampler::Sobol(sampler) => sampler.next_2d(),
+            Sampler::BlueNoise(sampler) => sampler.next_2d(),
+        }
+    }
+}
+
+impl From<IndependentSampler> for Sampler {
+    fn from(value: IndependentSampler) -> Self {
+        Sampler::Independent(value)
+    }
+}
+
+impl From<StratifiedSampler> for Sampler {
+    fn from(value: StratifiedSampler) -> Self {
+        Sampler::Stratified(value)
+    }
+}
+
+impl From<HaltonSampler> for Sampler {
+    fn from(value: HaltonSampler) -> Self {
+        Sampler::Halton(value)
+    }
+}
+
+impl From<SobolSampler> for Sampler {
+    fn from(value: SobolSampler) -> Self {
+        Sampler::Sobol(value)
+    }
+}
+
+impl From<BlueNoiseSampler> for Sampler {
+    fn from(value: BlueNoiseSampler) -> Self {
+        Sampler::BlueNoise(value)
+    }
+}
+
+/// The pixel, sample and dimension a sampler is up to
+#[derive(Clone, Copy, Debug, Default)]
+struct SamplePosition {
+    x: u64,
+    y: u64,
+    index: u64,
+    dimension: u64,
+}
+
+impl SamplePosition {
+    fn start(&mut self, x: usize, y: usize, index: usize) {
+        *self = SamplePosition {
+            x: x as u64,
+            y: y as u64,
+            index: index as u64,
+            dimension: 0,
+        };
+    }
+
+    /// Move on by a number of dimensions, returning the first of them
+    #[inline(always)]
+    fn advance(&mut self, dimensions: u64) -> u64 {
+        let dimension = self.dimension;
+        self.dimension += dimensions;
+        dimension
+    }
+}
+
+/// Scramble the bits of a number thoroughly, with the finalizer from
+/// SplitMix64
+#[inline(always)]
+fn mix_bits(mut value: u64) -> u64 {
+    value ^= value >> 31;
+    value = value.wrapping_mul(0x7fb5d329728ea185);
+    value ^= value >> 27;
+    value = value.wrapping_mul(0x81dadef4bc2dd44d);
+    value ^= value >> 33;
+    value
+}
+
+/// Combine some numbers into one that looks random
+#[inline(always)]
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |hash, &value| {
+        mix_bits(hash.wrapping_mul(0x9e3779b97f4a7c15) ^ value)
+    })
+}
+
+/// A number in [0, 1) that looks random, but is always the same for the same
+/// ray, for picking things where there's no sampler to hand
+pub(crate) fn hash_ray(ray: &Ray) -> f64 {
+    let Ray {
+        origin,
+        direction,
+        time,
+        ..
+    } = ray;
+    to_unit(hash(&[
+        origin.x.to_bits(),
+        origin.y.to_bits(),
+        origin.z.to_bits(),
+        direction.x.to_bits(),
+        direction.y.to_bits(),
+        direction.z.to_bits(),
+        time.to_bits(),
+    ]))
+}
+
+/// The largest f64 below 1, so that samples never reach it
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Turn random bits into a number in [0, 1)
+#[inline(always)]
+fn to_unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Turn a 32-bit fraction into a number in [0, 1)
+#[inline(always)]
+fn fraction_to_unit(bits: u32) -> f64 {
+    (bits as f64 / (1u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+}
+
+/// Where `index` lands in a random shuffle of the numbers below `length`,
+/// picked by the seed, without having to shuffle all of them (Kensler, 2013,
+/// "Correlated Multi-Jittered Sampling")
+fn permutation_element(index: u32, length: u32, seed: u32) -> u32 {
+    if length <= 1 {
+        return 0;
+    }
+    // shuffle within the next power of two up, trying again whenever the
+    // result lands past the end
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    let mut i = index;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    return (i.wrapping_add(seed)) % length;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The worst error in estimating the integral of a smooth function over
+    /// the unit square across many pixels, as a root mean square
+    fn integration_error(kind: SamplerKind, samples_per_pixel: usize) -> f64 {
+        let mut sampler = Sampler::new(kind, samples_per_pixel, 7);
+        let function = |[u, v]: [f64; 2]| (u * 3.0).sin() * v * v;
+        // worked out by hand
+        let exact = (1.0 - 3.0f64.cos()) / 3.0 / 3.0;
+        let pixels = 256;
+        let mut squared_error = 0.0;
+        for pixel in 0..pixels {
+            let mut sum = 0.0;
+            for index in 0..samples_per_pixel {
+                sampler.start_pixel_sample(pixel % 16, pixel / 16, index);
+                let point = sampler.next_2d();
+                assert!(point.iter().all(|&value| (0.0..1.0).contains(&value)));
+                sum += function(point);
+            }
+            let error = sum / samples_per_pixel as f64 - exact;
+            squared_error += error * error;
+        }
+        (squared_error / pixels as f64).sqrt()
+    }
+
+    #[test]
+    fn when_integrating_given_even_samplers_beats_independent_samples() {
+        let independent = integration_error(SamplerKind::Independent, 64);
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+            SamplerKind::BlueNoise,
+        ] {
+            let error = integration_error(kind, 64);
+            assert!(
+                error < independent / 3.0,
+                "{:?} should be well under {}, got {}",
+                kind,
+                independent,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn when_next_2d_given_square_sample_count_covers_every_stratum_once() {
+        for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+            let mut sampler = Sampler::new(kind, 16, 3);
+            for dimension in 0..4 {
+                let mut strata = [0; 16];
+                for index in 0..16 {
+                    sampler.start_pixel_sample(5, 9, index);
+                    for _ in 0..dimension {
+                        sampler.next_2d();
+                    }
+                    let [u, v] = sampler.next_2d();
+                    strata[(v * 4.0) as usize * 4 + (u * 4.0) as usize] += 1;
+                }
+                assert_eq!(strata, [1; 16], "{:?} in dimension {}", kind, dimension);
+            }
+        }
+    }
+
+    #[test]
+    fn when_permutation_element_given_every_index_returns_a_shuffle() {
+        for length in [1, 2, 7, 16, 100] {
+            let mut seen: Vec<u32> = (0..length)
+                .map(|index| permutation_element(index, length, 0xdecaf))
+                .collect();
+            seen.sort();
+            assert_eq!(seen, (0..length).collect::<Vec<u32>>());
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::{fraction_to_unit, hash, permutation_element, SamplePosition, SamplerTrait};
+
+/// Samples from the first two dimensions of the Sobol sequence, which between
+/// them are spread evenly over every way of cutting the square into boxes of
+/// power-of-two sizes
+///
+/// Only two dimensions are used, so for every pair of dimensions the order of
+/// the samples is shuffled, to keep them from lining up with each other. Each
+/// pixel's points are scrambled differently as well (Owen scrambling), which
+/// keeps how evenly they're spread but stops the pixels from all sharing the
+/// same pattern.
+#[derive(Clone)]
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    seed: u64,
+    position: SamplePosition,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1) as u32,
+            seed,
+            position: SamplePosition::default(),
+        }
+    }
+}
+
+impl SamplerTrait for SobolSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.position.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.position.advance(1);
+        let SamplePosition { x, y, index, .. } = self.position;
+        let bits = hash(&[self.seed, x, y, dimension]);
+        let [u, _] = scrambled_sobol(index, self.samples_per_pixel, bits);
+        u
+    }
+
+    fn next_2d(&mut self) -> [f64; 2] {
+        let dimension = self.position.advance(2);
+        let SamplePosition { x, y, index, .. } = self.position;
+        let bits = hash(&[self.seed, x, y, dimension]);
+        scrambled_sobol(index, self.samples_per_pixel, bits)
+    }
+}
+
+/// The `index`th of a number of 2D Sobol points, with their order shuffled
+/// and their values scrambled by the random bits given
+pub(super) fn scrambled_sobol(index: u64, count: u32, bits: u64) -> [f64; 2] {
+    let index = permutation_element(index as u32 % count, count, bits as u32);
+    let scramble = (bits >> 32) as u32;
+    return [
+        fraction_to_unit(owen_scramble(index.reverse_bits(), scramble)),
+        fraction_to_unit(owen_scramble(
+            sobol_second_dimension(index),
+            scramble.wrapping_mul(0x9e3779b9) ^ 0x68bc21eb,
+        )),
+    ];
+}
+
+/// The second dimension of the Sobol sequence, as a fraction out of 2^32. The
+/// first is the index with its bits reversed.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    return result;
+}
+
+/// Flip bits of a fraction, where whether each is flipped depends on the ones
+/// above it, so that points stay just as evenly spread (Burley, 2020,
+/// "Practical Hash-based Owen Scrambling")
+fn owen_scramble(value: u32, seed: u32) -> u32 {
+    let mut value = value.reverse_bits();
+    value ^= value.wrapping_mul(0x3d20adea);
+    value = value.wrapping_add(seed);
+    value = value.wrapping_mul((seed >> 16) | 1);
+    value ^= value.wrapping_mul(0x05526c56);
+    value ^= value.wrapping_mul(0x53a22864);
+    return value.reverse_bits();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_sobol_second_dimension_given_first_indices_returns_known_points() {
+        let points: Vec<u32> = (0..4).map(sobol_second_dimension).collect();
+        assert_eq!(points, [0, 1 << 31, 3 << 30, 1 << 30]);
+    }
+}
@@ -0,0 +1,67 @@
+use super::{hash, permutation_element, to_unit, SamplePosition, SamplerTrait};
+
+/// Splits each dimension of a pixel's samples into strata, one for each
+/// sample, and jitters each sample inside of its own
+///
+/// Two dimensions at a time are split into a grid, as square as the number of
+/// samples allows. Which sample gets which stratum is shuffled differently for
+/// each pixel and dimension, so the dimensions don't line up with each other.
+#[derive(Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    /// The columns of the grid used for two dimensions at a time. There are
+    /// enough rows to give every sample a cell, with any left over unused.
+    columns: u32,
+    rows: u32,
+    seed: u64,
+    position: SamplePosition,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1) as u32;
+        let columns = (samples_per_pixel as f64).sqrt().ceil() as u32;
+        Self {
+            samples_per_pixel,
+            columns,
+            rows: samples_per_pixel.div_ceil(columns),
+            seed,
+            position: SamplePosition::default(),
+        }
+    }
+
+    /// A hash of the pixel and dimension, and one of the sample as well
+    #[inline(always)]
+    fn hashes(&self, dimension: u64) -> (u64, u64) {
+        let SamplePosition { x, y, index, .. } = self.position;
+        let stratum_hash = hash(&[self.seed, x, y, dimension]);
+        (stratum_hash, hash(&[stratum_hash, index]))
+    }
+}
+
+impl SamplerTrait for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.position.start(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.position.advance(1);
+        let (stratum_hash, jitter_hash) = self.hashes(dimension);
+        let index = self.position.index as u32 % self.samples_per_pixel;
+        let stratum = permutation_element(index, self.samples_per_pixel, stratum_hash as u32);
+        return (stratum as f64 + to_unit(jitter_hash)) / self.samples_per_pixel as f64;
+    }
+
+    fn next_2d(&mut self) -> [f64; 2] {
+        let dimension = self.position.advance(2);
+        let (stratum_hash, jitter_hash) = self.hashes(dimension);
+        let cells = self.columns * self.rows;
+        let index = self.position.index as u32 % cells;
+        let stratum = permutation_element(index, cells, stratum_hash as u32);
+        let (column, row) = (stratum % self.columns, stratum / self.columns);
+        return [
+            (column as f64 + to_unit(jitter_hash)) / self.columns as f64,
+            (row as f64 + to_unit(jitter_hash.rotate_left(32))) / self.rows as f64,
+        ];
+    }
+}
@@ -0,0 +1,183 @@
+//! What rays see when they leave the scene
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use cgmath::Deg;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    background::{Background, EnvironmentMap, Gradient, PhysicalSky},
+    geometry::Vector,
+};
+
+use super::{check_fields, to_vector, SceneFileError};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SolidBackgroundDescription {
+    pub color: [f64; 3],
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GradientDescription {
+    #[serde(default = "default_gradient_bottom")]
+    pub bottom: [f64; 3],
+    #[serde(default = "default_gradient_top")]
+    pub top: [f64; 3],
+}
+
+fn default_gradient_bottom() -> [f64; 3] {
+    let Vector { x, y, z } = Gradient::default().bottom;
+    [x, y, z]
+}
+
+fn default_gradient_top() -> [f64; 3] {
+    let Vector { x, y, z } = Gradient::default().top;
+    [x, y, z]
+}
+
+/// An equirectangular image, usually a Radiance HDR file
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentMapDescription {
+    /// Relative paths are resolved against the scene file's directory
+    pub path: PathBuf,
+    /// How far to turn the map around the y axis, in degrees
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicalSkyDescription {
+    /// Points towards the sun, which should be above the horizon
+    pub sun_direction: [f64; 3],
+    /// How hazy the air is, from 2 (clear) to 10 (hazy)
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f64,
+    /// How bright the sun's disk is, or 0 to hide it
+    #[serde(default)]
+    pub sun_intensity: Option<f64>,
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+/// What rays that leave the scene see, picked by the `type` key
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackgroundDescription {
+    Solid(SolidBackgroundDescription),
+    Gradient(GradientDescription),
+    EnvironmentMap(EnvironmentMapDescription),
+    PhysicalSky(PhysicalSkyDescription),
+}
+
+impl BackgroundDescription {
+    pub(super) fn check_fields(type_name: &str, fields: Value) -> Result<(), SceneFileError> {
+        match type_name {
+            "solid" => check_fields::<SolidBackgroundDescription>(fields),
+            "gradient" => check_fields::<GradientDescription>(fields),
+            "environment_map" => check_fields::<EnvironmentMapDescription>(fields),
+            "physical_sky" => check_fields::<PhysicalSkyDescription>(fields),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check for values that parse, but that we can't render
+    pub fn validate(&self) -> Result<(), SceneFileError> {
+        let key = |field: &str| format!("background.{}", field);
+        match self {
+            Self::EnvironmentMap(map) if map.intensity < 0.0 => Err(SceneFileError::invalid(
+                key("intensity"),
+                "must not be negative",
+            )),
+            Self::PhysicalSky(sky) if sky.sun_direction[1] <= 0.0 => Err(SceneFileError::invalid(
+                key("sun_direction"),
+                "must point above the horizon",
+            )),
+            Self::PhysicalSky(sky) if sky.turbidity < 1.0 => Err(SceneFileError::invalid(
+                key("turbidity"),
+                "must be at least 1",
+            )),
+            Self::PhysicalSky(sky) if sky.sun_intensity.is_some_and(|i| i < 0.0) => Err(
+                SceneFileError::invalid(key("sun_intensity"), "must not be negative"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create the background, loading any images relative to base_dir
+    pub fn build(&self, base_dir: Option<&Path>) -> Result<Background, SceneFileError> {
+        let background = match self {
+            Self::Solid(solid) => Background::Solid(to_vector(solid.color)),
+            Self::Gradient(gradient) => {
+                Gradient::new(to_vector(gradient.bottom), to_vector(gradient.top)).into()
+            }
+            Self::EnvironmentMap(map) => {
+                let path = match base_dir {
+                    Option::Some(base_dir) => base_dir.join(&map.path),
+                    Option::None => map.path.clone(),
+                };
+                let environment_map = EnvironmentMap::load(path, Deg(map.rotation))
+                    .map_err(|error| SceneFileError::Image {
+                        key: "background.path".to_string(),
+                        error,
+                    })?
+                    .with_intensity(map.intensity);
+                Arc::new(environment_map).into()
+            }
+            Self::PhysicalSky(sky) => {
+                let mut physical_sky =
+                    PhysicalSky::new(to_vector(sky.sun_direction), sky.turbidity);
+                if let Option::Some(sun_intensity) = sky.sun_intensity {
+                    physical_sky = physical_sky.with_sun_intensity(sun_intensity);
+                }
+                Arc::new(physical_sky).into()
+            }
+        };
+        Ok(background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::description::tests::parse_test_scene;
+
+    use super::*;
+
+    fn physical_sky(sun_direction: &str) -> String {
+        format!(
+            r#"
+[background]
+type = "physical_sky"
+sun_direction = {}
+"#,
+            sun_direction
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_background_builds_background() {
+        let description = parse_test_scene(&physical_sky("[0, 1, 1]")).unwrap();
+        let scene = description.build_scene().unwrap();
+        assert!(matches!(scene.background(), Background::PhysicalSky(_)));
+
+        let err = parse_test_scene(&physical_sky("[0, -1, 1]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "background.sun_direction: must point above the horizon"
+        );
+    }
+}
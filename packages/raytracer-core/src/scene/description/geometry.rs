@@ -0,0 +1,673 @@
+//! The objects in a scene, and how they're checked and built
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use cgmath::{vec3, Deg, Matrix4, Quaternion, Rotation3};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    geometry::{
+        animated::{AnimatedInstance, Keyframe},
+        constant_medium::ConstantMedium,
+        cuboid::Cuboid,
+        disk::Disk,
+        instance::Instance,
+        plane::Plane,
+        quad::Quad,
+        sphere::Sphere,
+        triangle::{is_degenerate, Triangle},
+        Geometry,
+    },
+    scene::obj::load_obj,
+    shader::Material,
+};
+
+use super::{
+    check_fields, split_tag, to_point, to_vector, MaterialDescription, SceneDescription,
+    SceneFileError,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SphereDescription {
+    pub center: [f64; 3],
+    pub radius: f64,
+    pub material: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MovingSphereDescription {
+    pub center_start: [f64; 3],
+    pub center_end: [f64; 3],
+    pub radius: f64,
+    pub material: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TriangleDescription {
+    pub vertices: [[f64; 3]; 3],
+    pub material: String,
+}
+
+/// A parallelogram, from one corner and the two edges leaving it. It faces
+/// along `edge_u × edge_v`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct QuadDescription {
+    pub corner: [f64; 3],
+    pub edge_u: [f64; 3],
+    pub edge_v: [f64; 3],
+    pub material: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiskDescription {
+    pub center: [f64; 3],
+    pub normal: [f64; 3],
+    pub radius: f64,
+    pub material: String,
+}
+
+/// An infinite plane through a point
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneDescription {
+    pub point: [f64; 3],
+    pub normal: [f64; 3],
+    pub material: String,
+}
+
+/// An axis-aligned box between two opposite corners
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BoxDescription {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    pub material: String,
+}
+
+/// A Wavefront OBJ model, using the materials from its MTL files
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ModelDescription {
+    /// Relative paths are resolved against the scene file's directory
+    pub path: PathBuf,
+}
+
+/// Another object, moved into place. It's scaled first, then rotated, then
+/// translated.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceDescription {
+    pub object: Box<ObjectDescription>,
+    /// How much to stretch the object along each axis
+    #[serde(default = "default_scale")]
+    pub scale: [f64; 3],
+    /// In degrees, around the x, y and z axes in that order
+    #[serde(default)]
+    pub rotate: [f64; 3],
+    #[serde(default)]
+    pub translate: [f64; 3],
+}
+
+/// A volume of smoke or fog filling a closed shape. Its material should be
+/// a phase function, like `isotropic` or `henyey_greenstein`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConstantMediumDescription {
+    /// Only used for its shape, so it can't be a model
+    pub boundary: Box<ObjectDescription>,
+    /// The odds of scattering per unit of distance
+    pub density: f64,
+    pub material: String,
+}
+
+/// Where an animated object is at a moment in time, moved the same way as an
+/// instance
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeDescription {
+    pub time: f64,
+    #[serde(default = "default_scale")]
+    pub scale: [f64; 3],
+    /// In degrees, around the x, y and z axes in that order
+    #[serde(default)]
+    pub rotate: [f64; 3],
+    #[serde(default)]
+    pub translate: [f64; 3],
+}
+
+impl KeyframeDescription {
+    fn build(&self) -> Keyframe {
+        let [x, y, z] = self.rotate;
+        Keyframe {
+            rotation: Quaternion::from_angle_z(Deg(z))
+                * Quaternion::from_angle_y(Deg(y))
+                * Quaternion::from_angle_x(Deg(x)),
+            ..Keyframe::new(self.time)
+                .with_non_uniform_scale(to_vector(self.scale))
+                .with_translation(to_vector(self.translate))
+        }
+    }
+}
+
+/// Another object, moving between keyframes for motion blur. Between two
+/// keyframes it turns the shortest way round.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AnimatedDescription {
+    pub object: Box<ObjectDescription>,
+    pub keyframes: Vec<KeyframeDescription>,
+}
+
+fn default_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+/// An object in the scene, picked by the `type` key
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ObjectDescription {
+    Sphere(SphereDescription),
+    MovingSphere(MovingSphereDescription),
+    Triangle(TriangleDescription),
+    Quad(QuadDescription),
+    Disk(DiskDescription),
+    Plane(PlaneDescription),
+    Box(BoxDescription),
+    Model(ModelDescription),
+    Instance(InstanceDescription),
+    Animated(AnimatedDescription),
+    ConstantMedium(ConstantMediumDescription),
+}
+
+impl ObjectDescription {
+    pub(super) fn check_fields(type_name: &str, fields: Value) -> Result<(), SceneFileError> {
+        match type_name {
+            "sphere" => check_fields::<SphereDescription>(fields),
+            "moving_sphere" => check_fields::<MovingSphereDescription>(fields),
+            "triangle" => check_fields::<TriangleDescription>(fields),
+            "quad" => check_fields::<QuadDescription>(fields),
+            "disk" => check_fields::<DiskDescription>(fields),
+            "plane" => check_fields::<PlaneDescription>(fields),
+            "box" => check_fields::<BoxDescription>(fields),
+            "model" => check_fields::<ModelDescription>(fields),
+            "instance" | "animated" => {
+                // the object inside is tagged too, so check it on its own first
+                if let Option::Some((type_name, object)) = fields.get("object").and_then(split_tag)
+                {
+                    Self::check_fields(type_name, object).map_err(|e| e.within("object"))?;
+                }
+                match type_name {
+                    "instance" => check_fields::<InstanceDescription>(fields),
+                    _ => check_fields::<AnimatedDescription>(fields),
+                }
+            }
+            "constant_medium" => {
+                if let Option::Some((type_name, boundary)) =
+                    fields.get("boundary").and_then(split_tag)
+                {
+                    Self::check_fields(type_name, boundary).map_err(|e| e.within("boundary"))?;
+                }
+                check_fields::<ConstantMediumDescription>(fields)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether this is a model, or moves one into place, which makes many
+    /// objects rather than one
+    fn contains_model(&self) -> bool {
+        match self {
+            Self::Model(_) => true,
+            Self::Instance(instance) => instance.object.contains_model(),
+            Self::Animated(animated) => animated.object.contains_model(),
+            _ => false,
+        }
+    }
+
+    /// The name of the material this object uses, if it names one
+    fn material(&self) -> Option<&String> {
+        match self {
+            Self::Sphere(sphere) => Option::Some(&sphere.material),
+            Self::MovingSphere(sphere) => Option::Some(&sphere.material),
+            Self::Triangle(triangle) => Option::Some(&triangle.material),
+            Self::Quad(quad) => Option::Some(&quad.material),
+            Self::Disk(disk) => Option::Some(&disk.material),
+            Self::Plane(plane) => Option::Some(&plane.material),
+            Self::Box(cuboid) => Option::Some(&cuboid.material),
+            Self::ConstantMedium(medium) => Option::Some(&medium.material),
+            // the object inside is checked on its own
+            Self::Model(_) | Self::Instance(_) | Self::Animated(_) => Option::None,
+        }
+    }
+}
+
+impl SceneDescription {
+    /// Check an object, which might be inside of an instance, at the given key
+    pub(super) fn validate_object(
+        &self,
+        object: &ObjectDescription,
+        object_key: &str,
+    ) -> Result<(), SceneFileError> {
+        let key = |field: &str| format!("{}.{}", object_key, field);
+        let radius = match object {
+            ObjectDescription::Sphere(sphere) => Option::Some(sphere.radius),
+            ObjectDescription::MovingSphere(sphere) => Option::Some(sphere.radius),
+            ObjectDescription::Disk(disk) => Option::Some(disk.radius),
+            _ => Option::None,
+        };
+        if radius.is_some_and(|radius| radius <= 0.0) {
+            return Err(SceneFileError::invalid(key("radius"), "must be positive"));
+        }
+        match object {
+            ObjectDescription::Disk(DiskDescription { normal, .. })
+            | ObjectDescription::Plane(PlaneDescription { normal, .. })
+                if *normal == [0.0; 3] =>
+            {
+                return Err(SceneFileError::invalid(key("normal"), "must not be zero"));
+            }
+            ObjectDescription::Quad(quad)
+                if to_vector(quad.edge_u).cross(to_vector(quad.edge_v)) == vec3(0.0, 0.0, 0.0) =>
+            {
+                return Err(SceneFileError::invalid(
+                    key("edge_v"),
+                    "must not be zero or parallel to edge_u",
+                ));
+            }
+            ObjectDescription::Triangle(triangle)
+                if is_degenerate(&triangle.vertices.map(to_point)) =>
+            {
+                return Err(SceneFileError::invalid(
+                    key("vertices"),
+                    "must not all lie on one line",
+                ));
+            }
+            ObjectDescription::Box(cuboid)
+                if (0..3).any(|axis| cuboid.min[axis] >= cuboid.max[axis]) =>
+            {
+                return Err(SceneFileError::invalid(
+                    key("max"),
+                    "must be greater than min along every axis",
+                ));
+            }
+            _ => {}
+        }
+        if let Option::Some(material) = object.material() {
+            if !self.materials.contains_key(material) {
+                return Err(SceneFileError::Invalid {
+                    key: key("material"),
+                    message: format!("unknown material {:?}", material),
+                });
+            }
+        }
+        if let ObjectDescription::Instance(instance) = object {
+            if instance.scale.contains(&0.0) {
+                return Err(SceneFileError::invalid(key("scale"), "must not be zero"));
+            }
+            self.validate_object(&instance.object, &key("object"))?;
+        }
+        if let ObjectDescription::ConstantMedium(medium) = object {
+            if medium.density <= 0.0 {
+                return Err(SceneFileError::invalid(key("density"), "must be positive"));
+            }
+            if !matches!(
+                self.materials.get(&medium.material),
+                Option::Some(MaterialDescription::Isotropic(_))
+                    | Option::Some(MaterialDescription::HenyeyGreenstein(_))
+            ) {
+                return Err(SceneFileError::invalid(
+                    key("material"),
+                    "must be an isotropic or henyey_greenstein material",
+                ));
+            }
+            if medium.boundary.contains_model() {
+                return Err(SceneFileError::invalid(
+                    key("boundary"),
+                    "must be a single shape, not a model",
+                ));
+            }
+            self.validate_object(&medium.boundary, &key("boundary"))?;
+        }
+        if let ObjectDescription::Animated(animated) = object {
+            if animated.keyframes.is_empty() {
+                return Err(SceneFileError::invalid(
+                    key("keyframes"),
+                    "must have at least one keyframe",
+                ));
+            }
+            for (idx, keyframe) in animated.keyframes.iter().enumerate() {
+                if keyframe.scale.contains(&0.0) {
+                    return Err(SceneFileError::invalid(
+                        key(&format!("keyframes[{}].scale", idx)),
+                        "must not be zero",
+                    ));
+                }
+            }
+            self.validate_object(&animated.object, &key("object"))?;
+        }
+        Ok(())
+    }
+
+    /// Create an object, which might be inside of an instance, adding it to
+    /// the list. Models add all of their pieces.
+    pub(super) fn build_object(
+        &self,
+        object: &ObjectDescription,
+        key: &str,
+        materials: &BTreeMap<&String, Material>,
+        objects: &mut Vec<Geometry>,
+    ) -> Result<(), SceneFileError> {
+        // validation has already checked that every material exists
+        let material = |name: &String| materials[name].clone();
+        match object {
+            ObjectDescription::Sphere(sphere) => objects.push(
+                Arc::new(Sphere::new_with_material(
+                    to_point(sphere.center),
+                    sphere.radius,
+                    material(&sphere.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::MovingSphere(sphere) => objects.push(
+                Arc::new(AnimatedInstance::new_moving_sphere(
+                    to_point(sphere.center_start),
+                    to_point(sphere.center_end),
+                    sphere.radius,
+                    material(&sphere.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::Triangle(triangle) => objects.push(
+                Arc::new(Triangle::new_with_material(
+                    triangle.vertices.map(to_point),
+                    material(&triangle.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::Quad(quad) => objects.push(
+                Arc::new(Quad::new_with_material(
+                    to_point(quad.corner),
+                    to_vector(quad.edge_u),
+                    to_vector(quad.edge_v),
+                    material(&quad.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::Disk(disk) => objects.push(
+                Arc::new(Disk::new_with_material(
+                    to_point(disk.center),
+                    to_vector(disk.normal),
+                    disk.radius,
+                    material(&disk.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::Plane(plane) => objects.push(
+                Arc::new(Plane::new_with_material(
+                    to_point(plane.point),
+                    to_vector(plane.normal),
+                    material(&plane.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::Box(cuboid) => objects.push(
+                Arc::new(Cuboid::new_with_material(
+                    to_point(cuboid.min),
+                    to_point(cuboid.max),
+                    material(&cuboid.material),
+                ))
+                .into(),
+            ),
+            ObjectDescription::Instance(instance) => {
+                let mut inner = vec![];
+                self.build_object(
+                    &instance.object,
+                    &format!("{}.object", key),
+                    materials,
+                    &mut inner,
+                )?;
+                let [x, y, z] = instance.rotate;
+                let transform = Matrix4::from_translation(to_vector(instance.translate))
+                    * Matrix4::from_angle_z(Deg(z))
+                    * Matrix4::from_angle_y(Deg(y))
+                    * Matrix4::from_angle_x(Deg(x))
+                    * Matrix4::from_nonuniform_scale(
+                        instance.scale[0],
+                        instance.scale[1],
+                        instance.scale[2],
+                    );
+                // a model is many objects, so each one gets the same transform
+                objects.extend(inner.into_iter().map(|object| {
+                    Geometry::from(Arc::new(Instance::new_with_transform(object, transform)))
+                }));
+            }
+            ObjectDescription::Animated(animated) => {
+                let mut inner = vec![];
+                self.build_object(
+                    &animated.object,
+                    &format!("{}.object", key),
+                    materials,
+                    &mut inner,
+                )?;
+                let keyframes: Vec<Keyframe> = animated
+                    .keyframes
+                    .iter()
+                    .map(KeyframeDescription::build)
+                    .collect();
+                objects.extend(inner.into_iter().map(|object| {
+                    Geometry::from(Arc::new(AnimatedInstance::new(object, keyframes.clone())))
+                }));
+            }
+            ObjectDescription::ConstantMedium(medium) => {
+                // validation has already checked that the boundary is one shape
+                let mut boundary = vec![];
+                self.build_object(
+                    &medium.boundary,
+                    &format!("{}.boundary", key),
+                    materials,
+                    &mut boundary,
+                )?;
+                objects.extend(boundary.into_iter().map(|boundary| {
+                    Geometry::from(Arc::new(ConstantMedium::new_with_material(
+                        boundary,
+                        medium.density,
+                        material(&medium.material),
+                    )))
+                }));
+            }
+            ObjectDescription::Model(model) => {
+                let path = match &self.base_dir {
+                    Option::Some(base_dir) => base_dir.join(&model.path),
+                    Option::None => model.path.clone(),
+                };
+                let model = load_obj(path).map_err(|error| SceneFileError::Model {
+                    key: format!("{}.path", key),
+                    error,
+                })?;
+                objects.extend(model.to_geometry());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use crate::{
+        geometry::{Ray, RayCollidable},
+        scene::description::tests::parse_test_scene,
+    };
+
+    use super::*;
+
+    #[test]
+    fn when_from_toml_str_given_unknown_material_returns_error_at_key() {
+        let err = parse_test_scene(
+            r#"
+[[objects]]
+type = "sphere"
+center = [0, 0, -3]
+radius = 0.5
+material = "plastic"
+"#,
+        )
+        .unwrap_err();
+        match err {
+            SceneFileError::Invalid { key, .. } => assert_eq!(key, "objects[2].material"),
+            _ => panic!("Expected a validation error, got {}", err),
+        }
+    }
+
+    /// A floor, and a box whose far corner is at `max`
+    fn flat_shapes(max: &str) -> String {
+        format!(
+            r#"
+[[objects]]
+type = "plane"
+point = [0, -0.5, 0]
+normal = [0, 1, 0]
+material = "red"
+
+[[objects]]
+type = "box"
+min = [-1, -0.5, -4]
+max = {}
+material = "red"
+"#,
+            max
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_flat_shapes_builds_objects() {
+        let description = parse_test_scene(&flat_shapes("[1, 1, -3]")).unwrap();
+        let scene = description.build_scene().unwrap();
+        // far behind the camera, the floor goes on forever
+        let ray = Ray::new(point3(0.0, 2.0, 0.0), vec3(0.0, -1.0, 100.0), 0.0);
+        let collision = scene.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((collision.point.y + 0.5).abs() < 1e-9);
+
+        let err = parse_test_scene(&flat_shapes("[1, 1, -5]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "objects[3].max: must be greater than min along every axis"
+        );
+    }
+
+    fn triangle(vertices: &str) -> String {
+        format!(
+            r#"
+[[objects]]
+type = "triangle"
+vertices = {}
+material = "red"
+"#,
+            vertices
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_triangle_in_a_line_returns_error() {
+        assert!(parse_test_scene(&triangle("[[0, 0, -2], [1, 0, -2], [0, 1, -2]]")).is_ok());
+
+        let err = parse_test_scene(&triangle("[[0, 0, -2], [1, 0, -2], [2, 0, -2]]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "objects[2].vertices: must not all lie on one line"
+        );
+    }
+
+    /// A box stretched, turned and moved into place
+    fn instance(min: &str, material: &str) -> String {
+        format!(
+            r#"
+[[objects]]
+type = "instance"
+scale = [1, 2, 1]
+rotate = [0, 45, 0]
+translate = [0, 0, -5]
+
+[objects.object]
+type = "box"
+min = {}
+max = [1, 1, 1]
+material = "{}"
+"#,
+            min, material
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_instance_builds_transformed_object() {
+        let description = parse_test_scene(&instance("[-1, 0, -1]", "red")).unwrap();
+        let scene = description.build_scene().unwrap();
+        // turned 45 degrees, the box's corner points at the camera
+        let ray = Ray::new(point3(0.0, 1.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = scene.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((collision.t - (5.0 - 2.0f64.sqrt())).abs() < 1e-9);
+
+        let err = parse_test_scene(&instance("[-1, 0, -1]", "blue")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "objects[2].object.material: unknown material \"blue\""
+        );
+        let err = parse_test_scene(&instance("\"none\"", "red")).unwrap_err();
+        match err {
+            SceneFileError::Parse { key, .. } => assert_eq!(key, "objects[2].object.min"),
+            _ => panic!("Expected a parse error, got {}", err),
+        }
+    }
+
+    /// A sphere moving from left to right between time 0 and 1, with
+    /// `last_keyframe` added to the second keyframe
+    fn animated(last_keyframe: &str) -> String {
+        format!(
+            r#"
+[[objects]]
+type = "animated"
+
+[objects.object]
+type = "sphere"
+center = [0, 0, 0]
+radius = 0.5
+material = "red"
+
+[[objects.keyframes]]
+time = 0
+translate = [-2, 0, -5]
+
+[[objects.keyframes]]
+time = 1
+translate = [2, 0, -5]
+rotate = [0, 90, 0]
+{}
+"#,
+            last_keyframe
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_animated_object_moves_between_keyframes() {
+        let mut description = parse_test_scene(&animated("")).unwrap();
+        // the shutter has to be open for the whole move
+        description.camera.time_end = 1.0;
+        let scene = description.build_scene().unwrap();
+        for (time, x) in [(0.0, -2.0), (0.25, -1.0), (1.0, 2.0)] {
+            let ray = Ray::new(point3(x, 0.0, 0.0), vec3(0.0, 0.0, -1.0), time);
+            let collision = scene.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+            assert!((collision.t - 4.5).abs() < 1e-9);
+        }
+
+        let err = parse_test_scene(&animated("scale = [1, 0, 1]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "objects[2].keyframes[1].scale: must not be zero"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Lights that aren't objects, like point lights and the sun
+use std::sync::Arc;
+
+use cgmath::{Deg, InnerSpace};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::light::{DirectionalLight, Light, PointLight, QuadLight, SphereLight, SpotLight};
+
+use super::{check_fields, to_point, to_vector, SceneFileError};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PointLightDescription {
+    pub position: [f64; 3],
+    pub intensity: [f64; 3],
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SpotLightDescription {
+    pub position: [f64; 3],
+    pub direction: [f64; 3],
+    pub intensity: [f64; 3],
+    /// In degrees, from the middle of the cone to its edge
+    pub cone_angle: f64,
+    /// In degrees, where the light starts to fade. If not set, the cone has a
+    /// hard edge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub falloff_angle: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DirectionalLightDescription {
+    /// Points towards the light
+    pub direction: [f64; 3],
+    pub irradiance: [f64; 3],
+    /// In degrees. 0 gives perfectly sharp shadows.
+    #[serde(default)]
+    pub angular_diameter: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SphereLightDescription {
+    pub center: [f64; 3],
+    pub radius: f64,
+    pub emit: [f64; 3],
+}
+
+/// A rectangle, from one corner and the two edges leaving it
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct QuadLightDescription {
+    pub corner: [f64; 3],
+    pub edge_u: [f64; 3],
+    pub edge_v: [f64; 3],
+    pub emit: [f64; 3],
+    /// If not set, only the side facing along `edge_u × edge_v` glows
+    #[serde(default)]
+    pub two_sided: bool,
+}
+
+/// A light in the scene, picked by the `type` key
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LightDescription {
+    Point(PointLightDescription),
+    Spot(SpotLightDescription),
+    Directional(DirectionalLightDescription),
+    Sphere(SphereLightDescription),
+    Quad(QuadLightDescription),
+}
+
+impl LightDescription {
+    pub(super) fn check_fields(type_name: &str, fields: Value) -> Result<(), SceneFileError> {
+        match type_name {
+            "point" => check_fields::<PointLightDescription>(fields),
+            "spot" => check_fields::<SpotLightDescription>(fields),
+            "directional" => check_fields::<DirectionalLightDescription>(fields),
+            "sphere" => check_fields::<SphereLightDescription>(fields),
+            "quad" => check_fields::<QuadLightDescription>(fields),
+            _ => Ok(()),
+        }
+    }
+
+    /// The light's color, which is named differently for each type
+    fn color(&self) -> (&str, [f64; 3]) {
+        match self {
+            Self::Point(light) => ("intensity", light.intensity),
+            Self::Spot(light) => ("intensity", light.intensity),
+            Self::Directional(light) => ("irradiance", light.irradiance),
+            Self::Sphere(light) => ("emit", light.emit),
+            Self::Quad(light) => ("emit", light.emit),
+        }
+    }
+
+    /// Check for values that parse, but that we can't render
+    pub(super) fn validate(&self, idx: usize) -> Result<(), SceneFileError> {
+        let key = |field: &str| format!("lights[{}].{}", idx, field);
+        let (color_key, color) = self.color();
+        if color.iter().any(|&channel| channel < 0.0) {
+            return Err(SceneFileError::invalid(
+                key(color_key),
+                "must not be negative",
+            ));
+        }
+        let is_zero = |vector: [f64; 3]| vector == [0.0; 3];
+        match self {
+            Self::Spot(spot) if is_zero(spot.direction) => Err(SceneFileError::invalid(
+                key("direction"),
+                "must not be zero",
+            )),
+            Self::Spot(spot) if !(spot.cone_angle > 0.0 && spot.cone_angle <= 180.0) => Err(
+                SceneFileError::invalid(key("cone_angle"), "must be between 0 and 180 degrees"),
+            ),
+            Self::Spot(spot)
+                if spot
+                    .falloff_angle
+                    .is_some_and(|angle| !(angle >= 0.0 && angle <= spot.cone_angle)) =>
+            {
+                Err(SceneFileError::invalid(
+                    key("falloff_angle"),
+                    "must be between 0 degrees and the cone angle",
+                ))
+            }
+            Self::Directional(directional) if is_zero(directional.direction) => Err(
+                SceneFileError::invalid(key("direction"), "must not be zero"),
+            ),
+            Self::Directional(directional)
+                if !(directional.angular_diameter >= 0.0
+                    && directional.angular_diameter < 180.0) =>
+            {
+                Err(SceneFileError::invalid(
+                    key("angular_diameter"),
+                    "must be at least 0 and less than 180 degrees",
+                ))
+            }
+            Self::Sphere(sphere) if sphere.radius <= 0.0 => {
+                Err(SceneFileError::invalid(key("radius"), "must be positive"))
+            }
+            Self::Quad(quad) if is_zero(quad.edge_u) => {
+                Err(SceneFileError::invalid(key("edge_u"), "must not be zero"))
+            }
+            Self::Quad(quad) if is_zero(quad.edge_v) => {
+                Err(SceneFileError::invalid(key("edge_v"), "must not be zero"))
+            }
+            Self::Quad(quad)
+                if to_vector(quad.edge_u)
+                    .normalize()
+                    .dot(to_vector(quad.edge_v).normalize())
+                    .abs()
+                    >= 1e-6 =>
+            {
+                Err(SceneFileError::invalid(
+                    key("edge_v"),
+                    "must be at right angles to edge_u",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn build(&self) -> Light {
+        match self {
+            Self::Point(point) => Arc::new(PointLight::new(
+                to_point(point.position),
+                to_vector(point.intensity),
+            ))
+            .into(),
+            Self::Spot(spot) => Arc::new(SpotLight::new(
+                to_point(spot.position),
+                to_vector(spot.direction),
+                to_vector(spot.intensity),
+                Deg(spot.cone_angle),
+                Deg(spot.falloff_angle.unwrap_or(spot.cone_angle)),
+            ))
+            .into(),
+            Self::Directional(directional) => Arc::new(DirectionalLight::new(
+                to_vector(directional.direction),
+                to_vector(directional.irradiance),
+                Deg(directional.angular_diameter),
+            ))
+            .into(),
+            Self::Sphere(sphere) => Arc::new(SphereLight::new(
+                to_point(sphere.center),
+                sphere.radius,
+                to_vector(sphere.emit),
+            ))
+            .into(),
+            Self::Quad(quad) if quad.two_sided => Arc::new(QuadLight::new_two_sided(
+                to_point(quad.corner),
+                to_vector(quad.edge_u),
+                to_vector(quad.edge_v),
+                to_vector(quad.emit),
+            ))
+            .into(),
+            Self::Quad(quad) => Arc::new(QuadLight::new(
+                to_point(quad.corner),
+                to_vector(quad.edge_u),
+                to_vector(quad.edge_v),
+                to_vector(quad.emit),
+            ))
+            .into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::description::tests::parse_test_scene;
+
+    use super::*;
+
+    /// A point light, and a quad light with the given second edge
+    fn lights(edge_v: &str) -> String {
+        format!(
+            r#"
+[[lights]]
+type = "point"
+position = [0, 2, 0]
+intensity = [1, 1, 1]
+
+[[lights]]
+type = "quad"
+corner = [-1, 2, -2]
+edge_u = [2, 0, 0]
+edge_v = {}
+emit = [4, 4, 4]
+"#,
+            edge_v
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_lights_builds_lights() {
+        let description = parse_test_scene(&lights("[0, 0, 2]")).unwrap();
+        let scene = description.build_scene().unwrap();
+        assert!(matches!(scene.lights(), [Light::Point(_), Light::Quad(_)]));
+        // the quad can be seen, but the point light can't
+        assert_eq!(scene.objects().len(), 3);
+
+        let err = parse_test_scene(&lights("[1, 0, 2]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "lights[1].edge_v: must be at right angles to edge_u"
+        );
+    }
+}
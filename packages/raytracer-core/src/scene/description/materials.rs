@@ -0,0 +1,200 @@
+//! Materials, which may take their colors from textures
+use std::{collections::BTreeMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    shader::{
+        Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metallic,
+    },
+    texture::Texture,
+};
+
+use super::{check_fields, to_vector, SceneFileError};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LambertianDescription {
+    /// A constant color. Exactly one of this or `texture` must be set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub albedo: Option<[f64; 3]>,
+    /// The name of a texture to color the material with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MetallicDescription {
+    /// A constant color. Exactly one of this or `texture` must be set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub albedo: Option<[f64; 3]>,
+    /// The name of a texture to color the material with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub fuzziness: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DielectricDescription {
+    pub refraction_index: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiffuseLightDescription {
+    /// The light given off, which can be brighter than 1
+    pub emit: [f64; 3],
+    /// Whether the back of the surface glows too
+    #[serde(default)]
+    pub two_sided: bool,
+}
+
+/// The phase function of a volume, scattering light evenly in every direction
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct IsotropicDescription {
+    /// A constant color. Exactly one of this or `texture` must be set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub albedo: Option<[f64; 3]>,
+    /// The name of a texture to color the material with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+}
+
+/// The phase function of a volume, scattering light mostly forwards or
+/// mostly backwards
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HenyeyGreensteinDescription {
+    /// A constant color. Exactly one of this or `texture` must be set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub albedo: Option<[f64; 3]>,
+    /// The name of a texture to color the material with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+    /// From -1 for straight back, through 0 for evenly, to 1 for straight on
+    pub anisotropy: f64,
+}
+
+/// A material, picked by the `type` key
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialDescription {
+    Lambertian(LambertianDescription),
+    Metallic(MetallicDescription),
+    Dielectric(DielectricDescription),
+    DiffuseLight(DiffuseLightDescription),
+    Isotropic(IsotropicDescription),
+    HenyeyGreenstein(HenyeyGreensteinDescription),
+}
+
+impl MaterialDescription {
+    /// The constant albedo and texture name of this material, if it has them
+    pub(super) fn albedo(&self) -> Option<(Option<[f64; 3]>, Option<&String>)> {
+        match self {
+            Self::Lambertian(lambertian) => {
+                Option::Some((lambertian.albedo, lambertian.texture.as_ref()))
+            }
+            Self::Metallic(metallic) => Option::Some((metallic.albedo, metallic.texture.as_ref())),
+            Self::Isotropic(isotropic) => {
+                Option::Some((isotropic.albedo, isotropic.texture.as_ref()))
+            }
+            Self::HenyeyGreenstein(phase) => Option::Some((phase.albedo, phase.texture.as_ref())),
+            _ => Option::None,
+        }
+    }
+
+    pub(super) fn check_fields(type_name: &str, fields: Value) -> Result<(), SceneFileError> {
+        match type_name {
+            "lambertian" => check_fields::<LambertianDescription>(fields),
+            "metallic" => check_fields::<MetallicDescription>(fields),
+            "dielectric" => check_fields::<DielectricDescription>(fields),
+            "diffuse_light" => check_fields::<DiffuseLightDescription>(fields),
+            "isotropic" => check_fields::<IsotropicDescription>(fields),
+            "henyey_greenstein" => check_fields::<HenyeyGreensteinDescription>(fields),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Validation has already checked that every material has exactly one of
+/// albedo or texture, and that every texture exists
+pub(super) fn build_material(
+    material: &MaterialDescription,
+    textures: &BTreeMap<&String, Texture>,
+) -> Material {
+    let albedo = |albedo: Option<[f64; 3]>, texture: &Option<String>| -> Texture {
+        match (albedo, texture) {
+            (_, Option::Some(texture)) => textures[texture].clone(),
+            (albedo, Option::None) => to_vector(albedo.unwrap_or_default()).into(),
+        }
+    };
+    match material {
+        MaterialDescription::Lambertian(lambertian) => Arc::new(Lambertian::new_textured(albedo(
+            lambertian.albedo,
+            &lambertian.texture,
+        )))
+        .into(),
+        MaterialDescription::Metallic(metallic) => Arc::new(Metallic::new_textured(
+            albedo(metallic.albedo, &metallic.texture),
+            metallic.fuzziness,
+        ))
+        .into(),
+        MaterialDescription::Dielectric(dielectric) => {
+            Arc::new(Dielectric::new(dielectric.refraction_index)).into()
+        }
+        MaterialDescription::DiffuseLight(light) if light.two_sided => {
+            Arc::new(DiffuseLight::new_two_sided(to_vector(light.emit))).into()
+        }
+        MaterialDescription::DiffuseLight(light) => {
+            Arc::new(DiffuseLight::new(to_vector(light.emit))).into()
+        }
+        MaterialDescription::Isotropic(isotropic) => Arc::new(Isotropic::new_textured(albedo(
+            isotropic.albedo,
+            &isotropic.texture,
+        )))
+        .into(),
+        MaterialDescription::HenyeyGreenstein(phase) => Arc::new(HenyeyGreenstein::new_textured(
+            albedo(phase.albedo, &phase.texture),
+            phase.anisotropy,
+        ))
+        .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::description::tests::parse_test_scene;
+
+    #[test]
+    fn when_from_toml_str_given_unknown_texture_returns_error_at_key() {
+        let err = parse_test_scene(
+            r#"
+[materials.wood]
+type = "lambertian"
+texture = "wood_grain"
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "materials.wood.texture: unknown texture \"wood_grain\""
+        );
+
+        let err = parse_test_scene(
+            r#"
+[materials.blank]
+type = "lambertian"
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "materials.blank.albedo: either albedo or texture must be set"
+        );
+    }
+}
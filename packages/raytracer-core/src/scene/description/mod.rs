@@ -0,0 +1,628 @@
+//! Declarative scene files
+//!
+//! A scene file describes the camera, render settings, tables of named textures
+//! and materials, a list of objects that reference those materials, and a list
+//! of lights. Materials can use a texture in place of a constant albedo. Smoke
+//! and fire from a simulation can be added as a list of `volumes` read from
+//! voxel grids. Files ending in `.json` are read as JSON, anything else is read
+//! as TOML:
+//!
+//! ```toml
+//! [camera]
+//! position = [0, 0, 0]
+//! look_at = [0, 0, -1]
+//! field_of_view = 45
+//! aperture_f_stop = 2.0
+//!
+//! [render]
+//! width = 720
+//! height = 405
+//! light_sampling = "power"
+//! sampler = "sobol"
+//!
+//! [materials.green]
+//! type = "lambertian"
+//! albedo = [0.2, 0.7, 0.1]
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0, -100.5, -1]
+//! radius = 100
+//! material = "green"
+//!
+//! [[lights]]
+//! type = "spot"
+//! position = [0, 2, -1]
+//! direction = [0, -1, 0]
+//! intensity = [4, 4, 4]
+//! cone_angle = 30
+//! falloff_angle = 20
+//! ```
+mod background;
+mod geometry;
+mod lights;
+mod materials;
+mod render;
+mod textures;
+mod volumes;
+
+pub use background::{
+    BackgroundDescription, EnvironmentMapDescription, GradientDescription, PhysicalSkyDescription,
+    SolidBackgroundDescription,
+};
+pub use geometry::{
+    AnimatedDescription, BoxDescription, ConstantMediumDescription, DiskDescription,
+    InstanceDescription, KeyframeDescription, ModelDescription, MovingSphereDescription,
+    ObjectDescription, PlaneDescription, QuadDescription, SphereDescription, TriangleDescription,
+};
+pub use lights::{
+    DirectionalLightDescription, LightDescription, PointLightDescription, QuadLightDescription,
+    SphereLightDescription, SpotLightDescription,
+};
+pub use materials::{
+    DielectricDescription, DiffuseLightDescription, HenyeyGreensteinDescription,
+    IsotropicDescription, LambertianDescription, MaterialDescription, MetallicDescription,
+};
+pub use render::{
+    CameraDescription, DisplaySettings, LightSamplingDescription, RenderSettings,
+    SamplerDescription, ToneMapDescription, TransferDescription,
+};
+pub use textures::{
+    CheckerDescription, ColorSpaceDescription, FilterDescription, ImageTextureDescription,
+    MarbleDescription, NoiseDescription, TextureDescription, WrapModeDescription,
+};
+pub use volumes::{FogDescription, VolumeDescription, VolumeEmissionDescription};
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use cgmath::{point3, vec3};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    geometry::{Geometry, Point, Vector},
+    image::loader::ImageError,
+    shader::Material,
+    texture::Texture,
+    volume::grid::VolumeError,
+};
+
+use super::{obj::ObjError, SceneGraph};
+
+/// Something that went wrong while loading or validating a scene file
+#[derive(Debug)]
+pub enum SceneFileError {
+    /// The scene file couldn't be read
+    Io(PathBuf, io::Error),
+    /// The file wasn't valid TOML or JSON, or didn't match the schema
+    Parse {
+        /// The path to the offending key, eg `objects[2].radius`
+        key: String,
+        message: String,
+    },
+    /// A key had a value that parsed, but doesn't make sense
+    Invalid { key: String, message: String },
+    /// A model referenced by the scene couldn't be loaded
+    Model { key: String, error: ObjError },
+    /// An image referenced by the scene couldn't be loaded
+    Image { key: String, error: ImageError },
+    /// A voxel grid referenced by the scene couldn't be loaded
+    Volume { key: String, error: VolumeError },
+}
+
+impl SceneFileError {
+    fn invalid(key: String, message: &str) -> Self {
+        Self::Invalid {
+            key,
+            message: message.to_string(),
+        }
+    }
+
+    /// Prefix the key of a parse error with the key of the table it came from
+    fn within(self, parent_key: &str) -> Self {
+        match self {
+            Self::Parse { key, message } if key.is_empty() || key == "." => Self::Parse {
+                key: parent_key.to_string(),
+                message,
+            },
+            Self::Parse { key, message } => Self::Parse {
+                key: format!("{}.{}", parent_key, key),
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+impl Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "could not read {}: {}", path.display(), err),
+            Self::Parse { key, message } if key.is_empty() || key == "." => {
+                write!(f, "{}", message)
+            }
+            Self::Parse { key, message } => write!(f, "{}: {}", key, message),
+            Self::Invalid { key, message } => write!(f, "{}: {}", key, message),
+            Self::Model { key, error } => write!(f, "{}: {}", key, error),
+            Self::Image { key, error } => write!(f, "{}: {}", key, error),
+            Self::Volume { key, error } => write!(f, "{}: {}", key, error),
+        }
+    }
+}
+
+impl Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Option::Some(err),
+            Self::Model { error, .. } => Option::Some(error),
+            Self::Image { error, .. } => Option::Some(error),
+            Self::Volume { error, .. } => Option::Some(error),
+            _ => Option::None,
+        }
+    }
+}
+
+/// A whole scene, as read from a scene file
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub display: DisplaySettings,
+    /// If not set, a white-to-blue sky gradient
+    #[serde(default)]
+    pub background: Option<BackgroundDescription>,
+    #[serde(default)]
+    pub textures: BTreeMap<String, TextureDescription>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    /// Lights that aren't objects. Emissive objects light the scene too.
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+    /// Haze filling the space between the objects
+    #[serde(default)]
+    pub fog: Option<FogDescription>,
+    /// Volumes read from voxel grids, like smoke or fire from a simulation
+    #[serde(default)]
+    pub volumes: Vec<VolumeDescription>,
+    /// The directory relative paths in this scene are resolved against
+    #[serde(skip)]
+    pub base_dir: Option<PathBuf>,
+}
+
+impl SceneDescription {
+    /// Read a scene file from disk, picking the format by file extension
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SceneFileError> {
+        let path = path.as_ref();
+        let source =
+            fs::read_to_string(path).map_err(|err| SceneFileError::Io(path.to_path_buf(), err))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let mut description = if is_json {
+            Self::from_json_str(&source)?
+        } else {
+            Self::from_toml_str(&source)?
+        };
+        description.base_dir = path.parent().map(Path::to_path_buf);
+        Ok(description)
+    }
+
+    pub fn from_toml_str(source: &str) -> Result<Self, SceneFileError> {
+        let value: Value = toml::from_str(source).map_err(|err| SceneFileError::Parse {
+            key: String::new(),
+            message: err.to_string(),
+        })?;
+        Self::from_value(value)
+    }
+
+    pub fn from_json_str(source: &str) -> Result<Self, SceneFileError> {
+        let value: Value = serde_json::from_str(source).map_err(|err| SceneFileError::Parse {
+            key: String::new(),
+            message: err.to_string(),
+        })?;
+        Self::from_value(value)
+    }
+
+    fn from_value(value: Value) -> Result<Self, SceneFileError> {
+        // Internally tagged enums buffer their contents before picking a
+        // variant, which loses the path to any error inside of them. So before
+        // parsing the whole thing, check each tagged entry against the struct
+        // for its variant on its own.
+        if let Option::Some((type_name, fields)) = value.get("background").and_then(split_tag) {
+            BackgroundDescription::check_fields(type_name, fields)
+                .map_err(|e| e.within("background"))?;
+        }
+        if let Option::Some(textures) = value.get("textures").and_then(Value::as_object) {
+            for (name, texture) in textures {
+                let key = format!("textures.{}", name);
+                if let Option::Some((type_name, fields)) = split_tag(texture) {
+                    TextureDescription::check_fields(type_name, fields)
+                        .map_err(|e| e.within(&key))?;
+                }
+            }
+        }
+        if let Option::Some(materials) = value.get("materials").and_then(Value::as_object) {
+            for (name, material) in materials {
+                let key = format!("materials.{}", name);
+                if let Option::Some((type_name, fields)) = split_tag(material) {
+                    MaterialDescription::check_fields(type_name, fields)
+                        .map_err(|e| e.within(&key))?;
+                }
+            }
+        }
+        if let Option::Some(objects) = value.get("objects").and_then(Value::as_array) {
+            for (idx, object) in objects.iter().enumerate() {
+                let key = format!("objects[{}]", idx);
+                if let Option::Some((type_name, fields)) = split_tag(object) {
+                    ObjectDescription::check_fields(type_name, fields)
+                        .map_err(|e| e.within(&key))?;
+                }
+            }
+        }
+        if let Option::Some(lights) = value.get("lights").and_then(Value::as_array) {
+            for (idx, light) in lights.iter().enumerate() {
+                let key = format!("lights[{}]", idx);
+                if let Option::Some((type_name, fields)) = split_tag(light) {
+                    LightDescription::check_fields(type_name, fields)
+                        .map_err(|e| e.within(&key))?;
+                }
+            }
+        }
+
+        let description: Self =
+            serde_path_to_error::deserialize(value).map_err(|err| SceneFileError::Parse {
+                key: err.path().to_string(),
+                message: err.inner().to_string(),
+            })?;
+        description.validate()?;
+        Ok(description)
+    }
+
+    /// Check for values that parse, but that we can't render
+    fn validate(&self) -> Result<(), SceneFileError> {
+        let camera = &self.camera;
+        if !(camera.field_of_view > 0.0 && camera.field_of_view < 180.0) {
+            return Err(SceneFileError::invalid(
+                "camera.field_of_view".to_string(),
+                "must be between 0 and 180 degrees",
+            ));
+        }
+        if camera.aperture_f_stop <= 0.0 {
+            return Err(SceneFileError::invalid(
+                "camera.aperture_f_stop".to_string(),
+                "must be positive",
+            ));
+        }
+        if camera.aspect_ratio.is_some_and(|ratio| ratio <= 0.0) {
+            return Err(SceneFileError::invalid(
+                "camera.aspect_ratio".to_string(),
+                "must be positive",
+            ));
+        }
+        if camera.position == camera.look_at {
+            return Err(SceneFileError::invalid(
+                "camera.look_at".to_string(),
+                "must be different from the camera position",
+            ));
+        }
+        if camera.time_end < camera.time_start {
+            return Err(SceneFileError::invalid(
+                "camera.time_end".to_string(),
+                "must not be before time_start",
+            ));
+        }
+
+        if let Option::Some(background) = &self.background {
+            background.validate()?;
+        }
+        if let Option::Some(fog) = &self.fog {
+            fog.validate()?;
+        }
+
+        self.render.validate()?;
+        self.display.validate()?;
+
+        for (name, texture) in &self.textures {
+            let key = |field: &str| format!("textures.{}.{}", name, field);
+            match texture {
+                TextureDescription::Checker(checker) if checker.size <= 0.0 => {
+                    return Err(SceneFileError::invalid(key("size"), "must be positive"));
+                }
+                TextureDescription::Noise(noise) if noise.octaves == 0 => {
+                    return Err(SceneFileError::invalid(
+                        key("octaves"),
+                        "must be at least 1",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        for (name, material) in &self.materials {
+            let key = |field: &str| format!("materials.{}.{}", name, field);
+            if let MaterialDescription::HenyeyGreenstein(phase) = material {
+                if !(phase.anisotropy > -1.0 && phase.anisotropy < 1.0) {
+                    return Err(SceneFileError::invalid(
+                        key("anisotropy"),
+                        "must be between -1 and 1",
+                    ));
+                }
+            }
+            match material.albedo() {
+                Option::Some((Option::None, Option::None)) => {
+                    return Err(SceneFileError::invalid(
+                        key("albedo"),
+                        "either albedo or texture must be set",
+                    ));
+                }
+                Option::Some((Option::Some(_), Option::Some(_))) => {
+                    return Err(SceneFileError::invalid(
+                        key("texture"),
+                        "can't be set along with albedo",
+                    ));
+                }
+                Option::Some((_, Option::Some(texture)))
+                    if !self.textures.contains_key(texture) =>
+                {
+                    return Err(SceneFileError::Invalid {
+                        key: key("texture"),
+                        message: format!("unknown texture {:?}", texture),
+                    });
+                }
+                _ => {}
+            }
+            match material {
+                MaterialDescription::Metallic(metallic) if metallic.fuzziness < 0.0 => {
+                    return Err(SceneFileError::invalid(
+                        key("fuzziness"),
+                        "must not be negative",
+                    ));
+                }
+                MaterialDescription::Dielectric(dielectric)
+                    if dielectric.refraction_index <= 0.0 =>
+                {
+                    return Err(SceneFileError::invalid(
+                        key("refraction_index"),
+                        "must be positive",
+                    ));
+                }
+                MaterialDescription::DiffuseLight(light)
+                    if light.emit.iter().any(|&channel| channel < 0.0) =>
+                {
+                    return Err(SceneFileError::invalid(key("emit"), "must not be negative"));
+                }
+                _ => {}
+            }
+        }
+
+        for (idx, object) in self.objects.iter().enumerate() {
+            self.validate_object(object, &format!("objects[{}]", idx))?;
+        }
+
+        for (idx, light) in self.lights.iter().enumerate() {
+            light.validate(idx)?;
+        }
+
+        for (idx, volume) in self.volumes.iter().enumerate() {
+            self.validate_volume(volume, idx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create the objects described by this scene
+    pub fn build_scene(&self) -> Result<SceneGraph, SceneFileError> {
+        let mut textures: BTreeMap<&String, Texture> = BTreeMap::new();
+        for (name, texture) in &self.textures {
+            let texture = self
+                .build_texture(texture)
+                .map_err(|error| SceneFileError::Image {
+                    key: format!("textures.{}.path", name),
+                    error,
+                })?;
+            textures.insert(name, texture);
+        }
+        let materials: BTreeMap<&String, Material> = self
+            .materials
+            .iter()
+            .map(|(name, material)| (name, materials::build_material(material, &textures)))
+            .collect();
+
+        let mut objects: Vec<Geometry> = Vec::with_capacity(self.objects.len());
+        for (idx, object) in self.objects.iter().enumerate() {
+            self.build_object(
+                object,
+                &format!("objects[{}]", idx),
+                &materials,
+                &mut objects,
+            )?;
+        }
+
+        let scene = SceneGraph::new_with_time_interval(
+            objects,
+            self.camera.time_start,
+            self.camera.time_end,
+        )
+        .with_lights(self.lights.iter().map(LightDescription::build).collect());
+        let scene = match &self.fog {
+            Option::Some(fog) => scene.with_fog(fog.build()),
+            Option::None => scene,
+        };
+        let mut volumes = Vec::with_capacity(self.volumes.len());
+        for (idx, volume) in self.volumes.iter().enumerate() {
+            volumes.push(Arc::new(self.build_volume(volume, idx, &materials)?));
+        }
+        let scene = scene.with_volumes(volumes);
+        Ok(match &self.background {
+            Option::Some(background) => {
+                scene.with_background(background.build(self.base_dir.as_deref())?)
+            }
+            Option::None => scene,
+        })
+    }
+}
+
+impl SceneGraph {
+    /// Load the objects in a scene file
+    ///
+    /// To also get the camera and render settings, use
+    /// `SceneDescription::from_file` instead.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SceneGraph, SceneFileError> {
+        SceneDescription::from_file(path)?.build_scene()
+    }
+}
+
+/// Split the `type` key off of a tagged table, returning it and the other fields
+fn split_tag(value: &Value) -> Option<(&str, Value)> {
+    let table = value.as_object()?;
+    let type_name = table.get("type")?.as_str()?;
+    let mut fields = table.clone();
+    fields.remove("type");
+    Option::Some((type_name, Value::Object(fields)))
+}
+
+/// Try to parse the fields of a tagged variant as the struct for that variant
+fn check_fields<T: DeserializeOwned>(fields: Value) -> Result<(), SceneFileError> {
+    serde_path_to_error::deserialize::<_, T>(fields)
+        .map(|_| ())
+        .map_err(|err| SceneFileError::Parse {
+            key: err.path().to_string(),
+            message: err.inner().to_string(),
+        })
+}
+
+#[inline(always)]
+fn to_point(value: [f64; 3]) -> Point {
+    point3(value[0], value[1], value[2])
+}
+
+#[inline(always)]
+fn to_vector(value: [f64; 3]) -> Vector {
+    vec3(value[0], value[1], value[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{Ray, RayCollidable};
+
+    use super::*;
+
+    /// A camera looking down -z at a red sphere and a glass one, for the tests
+    /// of each part of a scene to add that part to
+    pub(super) const TEST_SCENE: &str = r#"
+[camera]
+position = [0, 0, 0]
+look_at = [0, 0, -1]
+field_of_view = 45
+aperture_f_stop = 2.0
+
+[render]
+width = 64
+height = 32
+light_sampling = "balance"
+sampler = "blue_noise"
+
+[materials.red]
+type = "lambertian"
+albedo = [1, 0, 0]
+
+[materials.glass]
+type = "dielectric"
+refraction_index = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0, 0, -1]
+radius = 0.5
+material = "red"
+
+[[objects]]
+type = "sphere"
+center = [1, 0, -1]
+radius = 0.5
+material = "glass"
+"#;
+
+    /// Parse the test scene with some more TOML added to the end of it, so
+    /// anything added to `objects` starts at `objects[2]`
+    pub(super) fn parse_test_scene(extra: &str) -> Result<SceneDescription, SceneFileError> {
+        SceneDescription::from_toml_str(&format!("{}\n{}", TEST_SCENE, extra))
+    }
+
+    #[test]
+    fn when_from_toml_str_given_valid_scene_returns_description() {
+        let description = SceneDescription::from_toml_str(TEST_SCENE).unwrap();
+        assert_eq!(description.render.width, 64);
+        assert_eq!(
+            description.render.light_sampling,
+            LightSamplingDescription::Balance
+        );
+        assert_eq!(description.render.sampler, SamplerDescription::BlueNoise);
+        // unspecified settings fall back to their defaults
+        assert_eq!(
+            description.render.max_ray_depth,
+            RenderSettings::default().max_ray_depth
+        );
+        assert_eq!(description.camera.up, [0.0, 1.0, 0.0]);
+        assert_eq!(description.objects.len(), 2);
+
+        let scene = description.build_scene().unwrap();
+        let ray = Ray::new(point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = scene.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(collision.t, 0.5);
+    }
+
+    #[test]
+    fn when_from_json_str_given_valid_scene_returns_description() {
+        let toml_description = SceneDescription::from_toml_str(TEST_SCENE).unwrap();
+        let json = serde_json::to_string(&toml_description).unwrap();
+        let json_description = SceneDescription::from_json_str(&json).unwrap();
+        assert_eq!(toml_description, json_description);
+    }
+
+    #[test]
+    fn when_from_toml_str_given_wrong_type_returns_error_at_key() {
+        let err = parse_test_scene(
+            r#"
+[[objects]]
+type = "sphere"
+center = [0, 0, -3]
+radius = "big"
+material = "red"
+"#,
+        )
+        .unwrap_err();
+        match err {
+            SceneFileError::Parse { key, .. } => assert_eq!(key, "objects[2].radius"),
+            _ => panic!("Expected a parse error, got {}", err),
+        }
+    }
+
+    #[test]
+    fn when_from_file_given_example_scenes_builds_scenes() {
+        let scenes_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scenes");
+        for name in [
+            "hello_world.toml",
+            "cornell_box.toml",
+            "textures.toml",
+            "lights.toml",
+            "volumes.toml",
+        ] {
+            let description = SceneDescription::from_file(scenes_dir.join(name))
+                .unwrap_or_else(|err| panic!("Could not load {}: {}", name, err));
+            description.build_scene().unwrap();
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! How the scene is looked at and rendered: the camera, render settings and
+//! display transform
+use cgmath::{Deg, MetricSpace};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    image::display::{DisplayTransform, ToneMap, TransferFunction},
+    render::{
+        camera::Camera,
+        renderer::{AdaptiveSampling, LightSampling},
+    },
+    sampler::SamplerKind,
+};
+
+use super::{to_point, to_vector, SceneDescription, SceneFileError};
+
+/// The camera, mirroring the arguments to `Camera::new`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: [f64; 3],
+    pub look_at: [f64; 3],
+    #[serde(default = "default_up")]
+    pub up: [f64; 3],
+    /// Defaults to the aspect ratio of the rendered image
+    #[serde(default)]
+    pub aspect_ratio: Option<f64>,
+    /// The vertical field of view, in degrees
+    pub field_of_view: f64,
+    pub aperture_f_stop: f64,
+    /// Defaults to the distance between the camera and the look_at point
+    #[serde(default)]
+    pub focal_length: Option<f64>,
+    #[serde(default)]
+    pub time_start: f64,
+    #[serde(default)]
+    pub time_end: f64,
+}
+
+fn default_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+/// Settings for the renderer, any of which the CLI can override
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_ray_depth: usize,
+    pub light_sampling: LightSamplingDescription,
+    /// Stop sampling pixels once the noise left is under this fraction of
+    /// their brightness, making samples_per_pixel the most any pixel takes.
+    /// If not set, every pixel takes every sample.
+    pub adaptive_threshold: Option<f64>,
+    /// How many samples every pixel takes with adaptive sampling
+    pub min_samples: usize,
+    pub sampler: SamplerDescription,
+    /// Where the random numbers behind every sample start from, so the same
+    /// seed always renders the same image
+    pub seed: u64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 720,
+            height: 405,
+            samples_per_pixel: 4,
+            max_ray_depth: 4,
+            light_sampling: LightSamplingDescription::default(),
+            adaptive_threshold: Option::None,
+            min_samples: 16,
+            sampler: SamplerDescription::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The adaptive sampling these settings ask for, if any
+    pub fn adaptive_sampling(&self) -> Option<AdaptiveSampling> {
+        self.adaptive_threshold
+            .map(|threshold| AdaptiveSampling::new(threshold).with_min_samples(self.min_samples))
+    }
+
+    /// Check for values that parse, but that we can't render. Run it again
+    /// after overriding any of them.
+    pub fn validate(&self) -> Result<(), SceneFileError> {
+        // pixels are spread from one edge of the image to the other, which
+        // takes at least two of them
+        for (key, value) in [("width", self.width), ("height", self.height)] {
+            if value < 2 {
+                return Err(SceneFileError::invalid(
+                    format!("render.{}", key),
+                    "must be at least 2",
+                ));
+            }
+        }
+        for (key, value) in [
+            ("samples_per_pixel", self.samples_per_pixel),
+            ("min_samples", self.min_samples),
+        ] {
+            if value == 0 {
+                return Err(SceneFileError::invalid(
+                    format!("render.{}", key),
+                    "must be at least 1",
+                ));
+            }
+        }
+        if self
+            .adaptive_threshold
+            .is_some_and(|threshold| threshold <= 0.0)
+        {
+            return Err(SceneFileError::invalid(
+                "render.adaptive_threshold".to_string(),
+                "must be positive",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether lights are sampled directly, and how that's weighed against
+/// scattered rays that hit them
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LightSamplingDescription {
+    Off,
+    Balance,
+    #[default]
+    Power,
+}
+
+impl From<LightSamplingDescription> for LightSampling {
+    fn from(value: LightSamplingDescription) -> Self {
+        match value {
+            LightSamplingDescription::Off => LightSampling::Off,
+            LightSamplingDescription::Balance => LightSampling::Balance,
+            LightSamplingDescription::Power => LightSampling::Power,
+        }
+    }
+}
+
+/// How the samples for each pixel are picked
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerDescription {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+    BlueNoise,
+}
+
+impl From<SamplerDescription> for SamplerKind {
+    fn from(value: SamplerDescription) -> Self {
+        match value {
+            SamplerDescription::Independent => SamplerKind::Independent,
+            SamplerDescription::Stratified => SamplerKind::Stratified,
+            SamplerDescription::Halton => SamplerKind::Halton,
+            SamplerDescription::Sobol => SamplerKind::Sobol,
+            SamplerDescription::BlueNoise => SamplerKind::BlueNoise,
+        }
+    }
+}
+
+/// How the render is turned into an image for display, any of which the CLI
+/// can override
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplaySettings {
+    /// In stops, so 1 doubles the brightness and -1 halves it
+    pub exposure: f64,
+    pub tone_map: ToneMapDescription,
+    /// The luminance that maps to white, for extended Reinhard
+    pub white_point: f64,
+    pub transfer: TransferDescription,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_map: ToneMapDescription::None,
+            white_point: 4.0,
+            transfer: TransferDescription::Srgb,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Check for values that parse, but that we can't display. Run it again
+    /// after overriding any of them.
+    pub fn validate(&self) -> Result<(), SceneFileError> {
+        if self.white_point <= 0.0 {
+            return Err(SceneFileError::invalid(
+                "display.white_point".to_string(),
+                "must be positive",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> DisplayTransform {
+        let tone_map = match self.tone_map {
+            ToneMapDescription::None => ToneMap::Clamp,
+            ToneMapDescription::Reinhard => ToneMap::Reinhard,
+            ToneMapDescription::ExtendedReinhard => ToneMap::ExtendedReinhard {
+                white_point: self.white_point as f32,
+            },
+            ToneMapDescription::Aces => ToneMap::Aces,
+            ToneMapDescription::Agx => ToneMap::Agx,
+        };
+        let transfer = match self.transfer {
+            TransferDescription::Srgb => TransferFunction::Srgb,
+            TransferDescription::Gamma22 => TransferFunction::Gamma(2.2),
+        };
+        DisplayTransform::new(self.exposure as f32, tone_map, transfer)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapDescription {
+    /// Clip anything above 1
+    None,
+    Reinhard,
+    ExtendedReinhard,
+    Aces,
+    Agx,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum TransferDescription {
+    #[serde(rename = "srgb")]
+    Srgb,
+    #[serde(rename = "gamma_2.2")]
+    Gamma22,
+}
+
+impl SceneDescription {
+    /// Create the camera described by this scene
+    pub fn build_camera(&self) -> Camera {
+        let camera = &self.camera;
+        let position = to_point(camera.position);
+        let look_at = to_point(camera.look_at);
+        let aspect_ratio = camera
+            .aspect_ratio
+            .unwrap_or(self.render.width as f64 / self.render.height as f64);
+        let focal_length = camera
+            .focal_length
+            .unwrap_or_else(|| position.distance(look_at));
+        Camera::new(
+            position,
+            look_at,
+            to_vector(camera.up),
+            aspect_ratio,
+            Deg(camera.field_of_view),
+            camera.aperture_f_stop,
+            focal_length,
+            camera.time_start,
+            camera.time_end,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::description::tests::{parse_test_scene, TEST_SCENE};
+
+    use super::*;
+
+    #[test]
+    fn when_from_toml_str_given_bad_value_returns_error_at_key() {
+        for width in [0, 1] {
+            let source = TEST_SCENE.replace("width = 64", &format!("width = {}", width));
+            let err = SceneDescription::from_toml_str(&source).unwrap_err();
+            assert_eq!(err.to_string(), "render.width: must be at least 2");
+        }
+    }
+
+    #[test]
+    fn when_from_toml_str_given_display_settings_builds_transform() {
+        let display = |setting: &str| {
+            format!(
+                r#"
+[display]
+{}
+tone_map = "extended_reinhard"
+transfer = "gamma_2.2"
+"#,
+                setting
+            )
+        };
+        let description = parse_test_scene(&display("exposure = -1")).unwrap();
+        assert_eq!(
+            description.display.build(),
+            DisplayTransform::new(
+                -1.0,
+                ToneMap::ExtendedReinhard { white_point: 4.0 },
+                TransferFunction::Gamma(2.2)
+            )
+        );
+
+        let err = parse_test_scene(&display("white_point = 0")).unwrap_err();
+        assert_eq!(err.to_string(), "display.white_point: must be positive");
+    }
+
+    #[test]
+    fn when_validate_given_overridden_settings_returns_error_at_key() {
+        assert!(RenderSettings::default().validate().is_ok());
+        for (settings, expected) in [
+            (
+                RenderSettings {
+                    height: 1,
+                    ..RenderSettings::default()
+                },
+                "render.height: must be at least 2",
+            ),
+            (
+                RenderSettings {
+                    samples_per_pixel: 0,
+                    ..RenderSettings::default()
+                },
+                "render.samples_per_pixel: must be at least 1",
+            ),
+        ] {
+            assert_eq!(settings.validate().unwrap_err().to_string(), expected);
+        }
+        let display = DisplaySettings {
+            white_point: 0.0,
+            ..DisplaySettings::default()
+        };
+        assert_eq!(
+            display.validate().unwrap_err().to_string(),
+            "display.white_point: must be positive"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Textures, which materials can take their colors from
+use std::{path::PathBuf, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    image::{
+        loader::{load_image, ColorSpace, ImageError},
+        mipmap::{Filter, WrapMode},
+    },
+    texture::{Checker, ImageTexture, Marble, NoiseTexture, Texture},
+};
+
+use super::{check_fields, to_vector, SceneDescription, SceneFileError};
+
+/// A 3D checkerboard of two colors
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CheckerDescription {
+    pub even: [f64; 3],
+    pub odd: [f64; 3],
+    /// The length of each side of the checkerboard's cubes
+    #[serde(default = "default_texture_scale")]
+    pub size: f64,
+}
+
+/// Perlin noise, or turbulence with more than one octave
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NoiseDescription {
+    #[serde(default = "default_texture_scale")]
+    pub scale: f64,
+    #[serde(default = "default_octaves")]
+    pub octaves: usize,
+    #[serde(default = "default_texture_color")]
+    pub color: [f64; 3],
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MarbleDescription {
+    #[serde(default = "default_texture_scale")]
+    pub scale: f64,
+    #[serde(default = "default_texture_color")]
+    pub color: [f64; 3],
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ImageTextureDescription {
+    /// Relative paths are resolved against the scene file's directory
+    pub path: PathBuf,
+    /// Use "linear" for images that hold data rather than colors, like
+    /// roughness or normal maps
+    #[serde(default)]
+    pub color_space: ColorSpaceDescription,
+    #[serde(default)]
+    pub wrap: WrapModeDescription,
+    #[serde(default)]
+    pub filter: FilterDescription,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpaceDescription {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl From<ColorSpaceDescription> for ColorSpace {
+    fn from(value: ColorSpaceDescription) -> Self {
+        match value {
+            ColorSpaceDescription::Srgb => ColorSpace::Srgb,
+            ColorSpaceDescription::Linear => ColorSpace::Linear,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapModeDescription {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl From<WrapModeDescription> for WrapMode {
+    fn from(value: WrapModeDescription) -> Self {
+        match value {
+            WrapModeDescription::Repeat => WrapMode::Repeat,
+            WrapModeDescription::Clamp => WrapMode::Clamp,
+            WrapModeDescription::Mirror => WrapMode::Mirror,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterDescription {
+    Nearest,
+    Bilinear,
+    #[default]
+    Trilinear,
+}
+
+impl From<FilterDescription> for Filter {
+    fn from(value: FilterDescription) -> Self {
+        match value {
+            FilterDescription::Nearest => Filter::Nearest,
+            FilterDescription::Bilinear => Filter::Bilinear,
+            FilterDescription::Trilinear => Filter::Trilinear,
+        }
+    }
+}
+
+fn default_texture_scale() -> f64 {
+    1.0
+}
+
+fn default_octaves() -> usize {
+    1
+}
+
+fn default_texture_color() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+/// A texture, picked by the `type` key
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextureDescription {
+    Checker(CheckerDescription),
+    Noise(NoiseDescription),
+    Marble(MarbleDescription),
+    Image(ImageTextureDescription),
+}
+
+impl TextureDescription {
+    pub(super) fn check_fields(type_name: &str, fields: Value) -> Result<(), SceneFileError> {
+        match type_name {
+            "checker" => check_fields::<CheckerDescription>(fields),
+            "noise" => check_fields::<NoiseDescription>(fields),
+            "marble" => check_fields::<MarbleDescription>(fields),
+            "image" => check_fields::<ImageTextureDescription>(fields),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SceneDescription {
+    pub(super) fn build_texture(
+        &self,
+        texture: &TextureDescription,
+    ) -> Result<Texture, ImageError> {
+        let texture = match texture {
+            TextureDescription::Checker(checker) => Arc::new(Checker::new(
+                to_vector(checker.even).into(),
+                to_vector(checker.odd).into(),
+                checker.size,
+            ))
+            .into(),
+            TextureDescription::Noise(noise) => Arc::new(NoiseTexture::new(
+                noise.scale,
+                noise.octaves,
+                to_vector(noise.color),
+            ))
+            .into(),
+            TextureDescription::Marble(marble) => {
+                Arc::new(Marble::new(marble.scale, to_vector(marble.color))).into()
+            }
+            TextureDescription::Image(image) => {
+                let path = match &self.base_dir {
+                    Option::Some(base_dir) => base_dir.join(&image.path),
+                    Option::None => image.path.clone(),
+                };
+                let pixels = load_image(path, image.color_space.into())?;
+                Arc::new(ImageTexture::new(
+                    pixels,
+                    image.wrap.into(),
+                    image.filter.into(),
+                ))
+                .into()
+            }
+        };
+        Ok(texture)
+    }
+}
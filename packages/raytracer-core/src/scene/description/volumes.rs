@@ -0,0 +1,356 @@
+//! Participating media that aren't bounded by an object: fog filling the
+//! scene, and volumes read from voxel grids
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geometry::constant_medium::Fog,
+    shader::{HenyeyGreenstein, Isotropic, Material},
+    volume::{grid::VoxelGrid, Emission, GridMedium},
+};
+
+use super::{to_point, to_vector, MaterialDescription, SceneDescription, SceneFileError};
+
+/// Haze filling the whole scene, out to the edge of the box around its
+/// objects
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FogDescription {
+    /// The odds of scattering per unit of distance
+    pub density: f64,
+    /// How much light survives each scattering event
+    #[serde(default = "default_fog_albedo")]
+    pub albedo: [f64; 3],
+    /// From -1 for straight back, through 0 for evenly, to 1 for straight on
+    #[serde(default)]
+    pub anisotropy: f64,
+}
+
+impl FogDescription {
+    pub(super) fn validate(&self) -> Result<(), SceneFileError> {
+        if self.density <= 0.0 {
+            return Err(SceneFileError::invalid(
+                "fog.density".to_string(),
+                "must be positive",
+            ));
+        }
+        if !(self.anisotropy > -1.0 && self.anisotropy < 1.0) {
+            return Err(SceneFileError::invalid(
+                "fog.anisotropy".to_string(),
+                "must be between -1 and 1",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) fn build(&self) -> Fog {
+        let albedo = to_vector(self.albedo);
+        let phase_function: Material = if self.anisotropy == 0.0 {
+            Arc::new(Isotropic::new(albedo)).into()
+        } else {
+            Arc::new(HenyeyGreenstein::new(albedo, self.anisotropy)).into()
+        };
+        Fog::new(self.density, phase_function)
+    }
+}
+
+fn default_fog_albedo() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+/// A volume whose density is read from a voxel grid file, stretched over a
+/// box. Its material should be a phase function, like `isotropic` or
+/// `henyey_greenstein`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeDescription {
+    /// The grid file, relative to the scene file
+    pub density: PathBuf,
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    /// Multiplies the grid's values, to get the odds of scattering per unit
+    /// of distance
+    #[serde(default = "default_volume_scale")]
+    pub density_scale: f64,
+    /// If not set, white and scattering evenly in every direction
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub emission: Option<VolumeEmissionDescription>,
+}
+
+/// Light given off by a volume, like the flames in a fire. Either a constant
+/// color, or the color of something as hot as a temperature grid says.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeEmissionDescription {
+    /// A grid of how brightly each part glows. If not set, the density grid
+    /// is used.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default = "default_volume_scale")]
+    pub scale: f64,
+    /// If neither this nor temperature is set, white
+    #[serde(default)]
+    pub color: Option<[f64; 3]>,
+    /// A grid of temperatures, in Kelvin once multiplied by temperature_scale
+    #[serde(default)]
+    pub temperature: Option<PathBuf>,
+    #[serde(default = "default_volume_scale")]
+    pub temperature_scale: f64,
+}
+
+fn default_volume_scale() -> f64 {
+    1.0
+}
+
+impl SceneDescription {
+    pub(super) fn validate_volume(
+        &self,
+        volume: &VolumeDescription,
+        idx: usize,
+    ) -> Result<(), SceneFileError> {
+        let key = |field: &str| format!("volumes[{}].{}", idx, field);
+        if (0..3).any(|axis| volume.min[axis] >= volume.max[axis]) {
+            return Err(SceneFileError::invalid(
+                key("max"),
+                "must be greater than min along every axis",
+            ));
+        }
+        if volume.density_scale < 0.0 {
+            return Err(SceneFileError::invalid(
+                key("density_scale"),
+                "must not be negative",
+            ));
+        }
+        if let Option::Some(material) = &volume.material {
+            match self.materials.get(material) {
+                Option::Some(MaterialDescription::Isotropic(_))
+                | Option::Some(MaterialDescription::HenyeyGreenstein(_)) => {}
+                Option::Some(_) => {
+                    return Err(SceneFileError::invalid(
+                        key("material"),
+                        "must be an isotropic or henyey_greenstein material",
+                    ));
+                }
+                Option::None => {
+                    return Err(SceneFileError::Invalid {
+                        key: key("material"),
+                        message: format!("unknown material {:?}", material),
+                    });
+                }
+            }
+        }
+        if let Option::Some(emission) = &volume.emission {
+            if emission.scale < 0.0 {
+                return Err(SceneFileError::invalid(
+                    key("emission.scale"),
+                    "must not be negative",
+                ));
+            }
+            if emission.color.is_some() && emission.temperature.is_some() {
+                return Err(SceneFileError::invalid(
+                    key("emission.temperature"),
+                    "can't be set along with color",
+                ));
+            }
+            if emission
+                .color
+                .is_some_and(|color| color.iter().any(|&channel| channel < 0.0))
+            {
+                return Err(SceneFileError::invalid(
+                    key("emission.color"),
+                    "must not be negative",
+                ));
+            }
+            if emission.temperature_scale <= 0.0 {
+                return Err(SceneFileError::invalid(
+                    key("emission.temperature_scale"),
+                    "must be positive",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn build_volume(
+        &self,
+        volume: &VolumeDescription,
+        idx: usize,
+        materials: &BTreeMap<&String, Material>,
+    ) -> Result<GridMedium, SceneFileError> {
+        let load = |path: &Path, field: &str| {
+            let path = match &self.base_dir {
+                Option::Some(base_dir) => base_dir.join(path),
+                Option::None => path.to_path_buf(),
+            };
+            VoxelGrid::load(path)
+                .map(Arc::new)
+                .map_err(|error| SceneFileError::Volume {
+                    key: format!("volumes[{}].{}", idx, field),
+                    error,
+                })
+        };
+        let density = load(&volume.density, "density")?;
+        let (min, max) = (to_point(volume.min), to_point(volume.max));
+        let medium = match &volume.material {
+            Option::Some(material) => GridMedium::new_with_material(
+                density.clone(),
+                min,
+                max,
+                materials[material].clone(),
+            ),
+            Option::None => GridMedium::new(density.clone(), min, max),
+        }
+        .with_density_scale(volume.density_scale);
+        let emission = match &volume.emission {
+            Option::Some(emission) => emission,
+            Option::None => return Ok(medium),
+        };
+        let grid = match &emission.path {
+            Option::Some(path) => load(path, "emission.path")?,
+            Option::None => density,
+        };
+        let emission = match (&emission.temperature, emission.color) {
+            (Option::Some(path), _) => Emission::new_blackbody(
+                grid,
+                emission.scale,
+                load(path, "emission.temperature")?,
+                emission.temperature_scale,
+            ),
+            (Option::None, color) => Emission::new(
+                grid,
+                emission.scale,
+                to_vector(color.unwrap_or([1.0, 1.0, 1.0])),
+            ),
+        };
+        Ok(medium.with_emission(emission))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cgmath::{point3, vec3};
+
+    use crate::{
+        geometry::{Ray, RayCollidable},
+        sampler::{Sampler, SamplerKind},
+        scene::description::tests::parse_test_scene,
+        volume::grid::DenseGrid,
+    };
+
+    use super::*;
+
+    /// Fog, and a ball of smoke filled with `material`
+    fn medium_and_fog(fog_anisotropy: f64, material: &str) -> String {
+        format!(
+            r#"
+[fog]
+density = 0.01
+anisotropy = {}
+
+[materials.smoke]
+type = "henyey_greenstein"
+albedo = [0.5, 0.5, 0.5]
+anisotropy = -0.3
+
+[[objects]]
+type = "constant_medium"
+density = 1000
+material = "{}"
+
+[objects.boundary]
+type = "sphere"
+center = [0, 3, -1]
+radius = 0.5
+material = "red"
+"#,
+            fog_anisotropy, material
+        )
+    }
+
+    #[test]
+    fn when_from_toml_str_given_volumes_builds_medium_and_fog() {
+        let description = parse_test_scene(&medium_and_fog(0.5, "smoke")).unwrap();
+        let scene = description.build_scene().unwrap();
+        assert!(scene.fog().is_some());
+        // the smoke is so thick that rays scatter as soon as they enter it
+        let ray = Ray::new(point3(0.0, 3.0, 0.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let collision = scene
+            .will_intersect_sampled(&ray, 0.001, f64::INFINITY, &mut sampler)
+            .unwrap();
+        assert!(collision.t > 0.5 && collision.t < 0.6);
+        assert!(matches!(collision.material, Material::HenyeyGreenstein(_)));
+
+        let err = parse_test_scene(&medium_and_fog(0.5, "red")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "objects[2].material: must be an isotropic or henyey_greenstein material"
+        );
+        let err = parse_test_scene(&medium_and_fog(1.0, "smoke")).unwrap_err();
+        assert_eq!(err.to_string(), "fog.anisotropy: must be between -1 and 1");
+    }
+
+    /// A volume whose density and temperature come from smoke.vol, with
+    /// `emission` added to its emission table
+    fn grid_volume(emission: &str) -> String {
+        format!(
+            r#"
+[[volumes]]
+density = "smoke.vol"
+min = [-1, 2, -2]
+max = [1, 4, 0]
+
+[volumes.emission]
+temperature = "smoke.vol"
+temperature_scale = 2
+{}
+"#,
+            emission
+        )
+    }
+
+    #[test]
+    fn when_build_scene_given_volume_loads_grid_relative_to_scene() {
+        let base_dir =
+            std::env::temp_dir().join(format!("raytracer-volume-{}", std::process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+        let grid: VoxelGrid = DenseGrid::new([2, 2, 2], vec![1000.0; 8]).into();
+        grid.write(fs::File::create(base_dir.join("smoke.vol")).unwrap())
+            .unwrap();
+
+        let mut description = parse_test_scene(&grid_volume("")).unwrap();
+        description.base_dir = Option::Some(base_dir.clone());
+        let scene = description.build_scene().unwrap();
+        assert_eq!(scene.volumes().len(), 1);
+        // the volume is so thick that rays scatter as soon as they enter it,
+        // glowing as hot as the grid says on the way in
+        let ray = Ray::new(point3(0.0, 3.0, 1.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        let interaction = scene.medium_interaction(&ray, 0.001, f64::INFINITY, &mut sampler);
+        let collision = interaction.collision.unwrap();
+        assert!(collision.t > 1.0 && collision.t < 1.01);
+        assert!(matches!(collision.material, Material::Isotropic(_)));
+
+        description.base_dir = Option::None;
+        match description.build_scene() {
+            Err(SceneFileError::Volume { key, .. }) => assert_eq!(key, "volumes[0].density"),
+            _ => panic!("Expected the grid not to be found without the scene's directory"),
+        }
+        fs::remove_dir_all(&base_dir).unwrap();
+
+        let err = parse_test_scene(&grid_volume("color = [1, 0, 0]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "volumes[0].emission.temperature: can't be set along with color"
+        );
+    }
+}
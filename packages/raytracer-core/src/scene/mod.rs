@@ -1,3 +1,21 @@
+mod description;
+pub mod mtl;
+pub mod obj;
 mod scenegraph;
 
+pub use description::{
+    AnimatedDescription, BackgroundDescription, BoxDescription, CameraDescription,
+    CheckerDescription, ColorSpaceDescription, ConstantMediumDescription, DielectricDescription,
+    DiffuseLightDescription, DirectionalLightDescription, DiskDescription, DisplaySettings,
+    EnvironmentMapDescription, FilterDescription, FogDescription, GradientDescription,
+    HenyeyGreensteinDescription, ImageTextureDescription, InstanceDescription,
+    IsotropicDescription, KeyframeDescription, LambertianDescription, LightDescription,
+    LightSamplingDescription, MarbleDescription, MaterialDescription, MetallicDescription,
+    ModelDescription, MovingSphereDescription, NoiseDescription, ObjectDescription,
+    PhysicalSkyDescription, PlaneDescription, PointLightDescription, QuadDescription,
+    QuadLightDescription, RenderSettings, SamplerDescription, SceneDescription, SceneFileError,
+    SolidBackgroundDescription, SphereDescription, SphereLightDescription, SpotLightDescription,
+    TextureDescription, ToneMapDescription, TransferDescription, TriangleDescription,
+    VolumeDescription, VolumeEmissionDescription, WrapModeDescription,
+};
 pub use scenegraph::{new_random_world, new_test_world, SceneGraph};
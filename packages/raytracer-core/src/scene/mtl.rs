@@ -0,0 +1,238 @@
+//! Parser for Wavefront MTL material libraries
+//!
+//! Only the handful of statements that map onto our materials are read, the
+//! rest are silently skipped:
+//!
+//! ```text
+//! newmtl glass
+//! Kd 1.0 1.0 1.0  # diffuse color
+//! Ks 0.0 0.0 0.0  # specular color
+//! Ke 0.0 0.0 0.0  # emitted color
+//! Ns 10.0         # specular exponent, 0 to 1000
+//! Ni 1.5          # index of refraction
+//! d 0.1           # dissolve (opacity), or Tr for 1 - d
+//! ```
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use cgmath::vec3;
+
+use crate::{
+    geometry::Vector,
+    shader::{Dielectric, DiffuseLight, Lambertian, Material, Metallic},
+};
+
+use super::obj::{parse_float, ObjError, ObjErrorKind};
+
+/// The refractive index used for transparent materials that don't specify one
+const DEFAULT_REFRACTION_INDEX: f64 = 1.5;
+
+/// A material as described by an MTL file
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    /// Diffuse color (Kd)
+    pub diffuse: Vector,
+    /// Specular color (Ks)
+    pub specular: Vector,
+    /// Emitted color (Ke), which is black for anything that isn't a light
+    pub emission: Vector,
+    /// Specular exponent (Ns), where higher numbers are shinier
+    pub shininess: f64,
+    /// Index of refraction (Ni)
+    pub refraction_index: Option<f64>,
+    /// Opacity (d), where 1.0 is fully opaque
+    pub dissolve: f64,
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            diffuse: vec3(0.8, 0.8, 0.8),
+            specular: vec3(0.0, 0.0, 0.0),
+            emission: vec3(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refraction_index: Option::None,
+            dissolve: 1.0,
+        }
+    }
+
+    /// Map this onto the closest of our materials
+    ///
+    /// Glowing materials become lights, transparent materials become
+    /// dielectrics, materials with more specular than diffuse color become
+    /// metals (with the specular exponent setting the fuzziness), and
+    /// everything else becomes lambertian.
+    pub fn to_material(&self) -> Material {
+        if self.emission.x > 0.0 || self.emission.y > 0.0 || self.emission.z > 0.0 {
+            return Arc::new(DiffuseLight::new(self.emission)).into();
+        }
+        if self.dissolve < 1.0 {
+            let refraction_index = self.refraction_index.unwrap_or(DEFAULT_REFRACTION_INDEX);
+            return Arc::new(Dielectric::new(refraction_index)).into();
+        }
+        let max_specular = self.specular.x.max(self.specular.y).max(self.specular.z);
+        let max_diffuse = self.diffuse.x.max(self.diffuse.y).max(self.diffuse.z);
+        if max_specular > 0.0 && max_specular >= max_diffuse {
+            // a rough fit of the Phong exponent to a fuzz radius, so that an
+            // exponent of 0 is completely rough and 1000 is almost a mirror
+            let fuzziness = f64::sqrt(2.0 / (self.shininess.max(0.0) + 2.0));
+            return Arc::new(Metallic::new(self.specular, fuzziness)).into();
+        }
+        return Arc::new(Lambertian::new(self.diffuse)).into();
+    }
+}
+
+/// Parse the text of an MTL file into a table of materials by name
+///
+/// The path is only used for error messages.
+pub fn parse_mtl(
+    source: &str,
+    path: Option<&Path>,
+) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlMaterial> = Option::None;
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_number = line_idx + 1;
+        let error = |kind: ObjErrorKind| ObjError::new(path, line_number, kind);
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Option::Some(statement) => statement,
+            Option::None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if statement == "newmtl" {
+            if let Option::Some(material) = current.take() {
+                materials.insert(material.name.clone(), material);
+            }
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(error(ObjErrorKind::MissingArgument("material name")));
+            }
+            current = Option::Some(MtlMaterial::new(&name));
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Option::Some(material) => material,
+            // anything before the first newmtl has nowhere to go
+            Option::None => match statement {
+                "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" => {
+                    return Err(error(ObjErrorKind::NoCurrentMaterial));
+                }
+                _ => continue,
+            },
+        };
+
+        match statement {
+            "Kd" => material.diffuse = parse_color(&args).map_err(error)?,
+            "Ks" => material.specular = parse_color(&args).map_err(error)?,
+            "Ke" => material.emission = parse_color(&args).map_err(error)?,
+            "Ns" => material.shininess = parse_scalar(&args).map_err(error)?,
+            "Ni" => material.refraction_index = Option::Some(parse_scalar(&args).map_err(error)?),
+            "d" => material.dissolve = parse_scalar(&args).map_err(error)?,
+            "Tr" => material.dissolve = 1.0 - parse_scalar(&args).map_err(error)?,
+            // textures, illumination models and the like aren't supported
+            _ => {}
+        }
+    }
+
+    if let Option::Some(material) = current {
+        materials.insert(material.name.clone(), material);
+    }
+
+    Ok(materials)
+}
+
+/// Read and parse an MTL file from disk
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|err| ObjError::new(Option::Some(path), 0, ObjErrorKind::Io(err)))?;
+    parse_mtl(&source, Option::Some(path))
+}
+
+fn parse_color(args: &[&str]) -> Result<Vector, ObjErrorKind> {
+    match args {
+        // a lone value is a grey
+        [value] => {
+            let value = parse_float(value)?;
+            Ok(vec3(value, value, value))
+        }
+        [r, g, b, ..] => Ok(vec3(parse_float(r)?, parse_float(g)?, parse_float(b)?)),
+        _ => Err(ObjErrorKind::MissingArgument("color")),
+    }
+}
+
+fn parse_scalar(args: &[&str]) -> Result<f64, ObjErrorKind> {
+    match args.first() {
+        Option::Some(value) => parse_float(value),
+        Option::None => Err(ObjErrorKind::MissingArgument("value")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MTL: &str = "# a test library
+newmtl red_matte
+Kd 0.8 0.1 0.1
+
+newmtl chrome
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+Ns 1000
+
+newmtl glass
+Ni 1.45
+d 0.2
+
+newmtl lamp
+Kd 0.0 0.0 0.0
+Ke 10.0 9.0 8.0
+";
+
+    #[test]
+    fn when_parse_mtl_given_valid_library_returns_materials() {
+        let materials = parse_mtl(TEST_MTL, Option::None).unwrap();
+        assert_eq!(materials.len(), 4);
+        assert_eq!(materials["red_matte"].diffuse, vec3(0.8, 0.1, 0.1));
+        assert_eq!(materials["chrome"].shininess, 1000.0);
+        assert_eq!(materials["glass"].refraction_index, Some(1.45));
+        assert_eq!(materials["glass"].dissolve, 0.2);
+        assert_eq!(materials["lamp"].emission, vec3(10.0, 9.0, 8.0));
+    }
+
+    #[test]
+    fn when_to_material_given_mtl_materials_picks_closest_material() {
+        let materials = parse_mtl(TEST_MTL, Option::None).unwrap();
+        assert!(matches!(
+            materials["red_matte"].to_material(),
+            Material::Lambertian(_)
+        ));
+        assert!(matches!(
+            materials["chrome"].to_material(),
+            Material::Metallic(_)
+        ));
+        assert!(matches!(
+            materials["glass"].to_material(),
+            Material::Dielectric(_)
+        ));
+        assert!(matches!(
+            materials["lamp"].to_material(),
+            Material::DiffuseLight(_)
+        ));
+    }
+
+    #[test]
+    fn when_parse_mtl_given_bad_number_returns_error_with_line() {
+        let result = parse_mtl("newmtl a\nKd 0.1 zero 0.3\n", Option::None);
+        let err = result.unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(matches!(err.kind, ObjErrorKind::InvalidNumber(_)));
+    }
+}
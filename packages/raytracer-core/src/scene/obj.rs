@@ -0,0 +1,753 @@
+//! Parser for Wavefront OBJ models
+//!
+//! This reads vertex data (`v`, `vn`, `vt`), faces (`f`), groups (`g` and
+//! `o`), and materials (`mtllib` and `usemtl`). Polygons with more than 3
+//! sides are triangulated by ear clipping. Each combination of group and
+//! material becomes its own `TriangleMesh`.
+//!
+//! Anything else (smoothing groups, lines, curves, etc) is skipped. Faces
+//! using a material that isn't in any loaded library get the default
+//! material, and faces using a normal with no length are flat-shaded, both
+//! with a warning.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use cgmath::{point3, vec2, vec3, InnerSpace, Zero};
+use log::warn;
+
+use crate::{
+    geometry::{
+        mesh::{MeshBuffers, MeshFace, TriangleMesh},
+        Geometry, Point, TexCoord, Vector,
+    },
+    shader::Material,
+};
+
+use super::mtl::{parse_mtl, MtlMaterial};
+
+/// The name given to faces that appear before any `g` or `o` statement
+const DEFAULT_GROUP_NAME: &str = "default";
+
+/// What went wrong while parsing an OBJ or MTL file
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// A material library referenced by `mtllib` couldn't be read
+    MissingMaterialLibrary(PathBuf, io::Error),
+    /// A material library referenced by `mtllib` had an error in it
+    MaterialLibrary(Box<ObjError>),
+    /// A number couldn't be parsed
+    InvalidNumber(String),
+    /// A statement was missing a required argument
+    MissingArgument(&'static str),
+    /// A face vertex wasn't in the form v, v/vt, v//vn or v/vt/vn
+    InvalidFaceVertex(String),
+    /// A face referred to a vertex, normal or UV that hasn't been declared
+    IndexOutOfRange(i64),
+    /// A face had fewer than 3 vertices
+    DegenerateFace,
+    /// A material property was given before any `newmtl` statement
+    NoCurrentMaterial,
+}
+
+/// An error encountered while parsing, along with where it happened
+#[derive(Debug)]
+pub struct ObjError {
+    /// The file being parsed, if known
+    pub path: Option<PathBuf>,
+    /// The 1-based line number of the offending statement, or 0 if the error
+    /// isn't tied to a line
+    pub line: usize,
+    pub kind: ObjErrorKind,
+}
+
+impl ObjError {
+    pub fn new(path: Option<&Path>, line: usize, kind: ObjErrorKind) -> Self {
+        Self {
+            path: path.map(Path::to_path_buf),
+            line,
+            kind,
+        }
+    }
+}
+
+impl Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::MissingMaterialLibrary(path, err) => {
+                write!(f, "could not read material library {:?}: {}", path, err)
+            }
+            Self::MaterialLibrary(err) => write!(f, "in material library: {}", err),
+            Self::InvalidNumber(value) => write!(f, "{:?} is not a valid number", value),
+            Self::MissingArgument(name) => write!(f, "missing {}", name),
+            Self::InvalidFaceVertex(value) => write!(f, "{:?} is not a valid face vertex", value),
+            Self::IndexOutOfRange(idx) => write!(f, "index {} is out of range", idx),
+            Self::DegenerateFace => write!(f, "faces need at least 3 vertices"),
+            Self::NoCurrentMaterial => write!(f, "material property given before newmtl"),
+        }
+    }
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Option::Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if self.line > 0 {
+            write!(f, "{}:", self.line)?;
+        }
+        write!(f, " {}", self.kind)
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ObjErrorKind::Io(err) => Option::Some(err),
+            ObjErrorKind::MissingMaterialLibrary(_, err) => Option::Some(err),
+            ObjErrorKind::MaterialLibrary(err) => Option::Some(err.as_ref()),
+            _ => Option::None,
+        }
+    }
+}
+
+/// One mesh from an OBJ file, made of all the faces sharing a group and material
+pub struct ObjMesh {
+    /// The name of the group this mesh came from
+    pub name: String,
+    /// The name of the material given by `usemtl`, if any
+    pub material_name: Option<String>,
+    pub mesh: Arc<TriangleMesh>,
+}
+
+/// The meshes parsed out of an OBJ file
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+}
+
+impl ObjModel {
+    /// Get every mesh in this model as geometry, ready to add to a scene
+    pub fn to_geometry(&self) -> Vec<Geometry> {
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.mesh.clone().into())
+            .collect()
+    }
+}
+
+/// A face vertex, as 0-based indices into the position, UV and normal lists
+#[derive(Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Faces that share a group and material, before they're turned into a mesh
+struct PendingMesh {
+    name: String,
+    material_name: Option<String>,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+struct ObjParser<'a> {
+    path: Option<&'a Path>,
+    positions: Vec<Point>,
+    normals: Vec<Vector>,
+    uvs: Vec<TexCoord>,
+    materials: HashMap<String, MtlMaterial>,
+    group: String,
+    material_name: Option<String>,
+    pending: Vec<PendingMesh>,
+    /// Maps (group, material) pairs to their index in `pending`
+    pending_lookup: HashMap<(String, Option<String>), usize>,
+}
+
+/// Read and parse an OBJ file from disk, along with any MTL files it references
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|err| ObjError::new(Option::Some(path), 0, ObjErrorKind::Io(err)))?;
+    parse_obj(&source, Option::Some(path))
+}
+
+/// Parse the text of an OBJ file
+///
+/// If a path is given, material libraries are looked up relative to it, and
+/// it's included in error messages. Otherwise they're looked up relative to
+/// the working directory.
+pub fn parse_obj(source: &str, path: Option<&Path>) -> Result<ObjModel, ObjError> {
+    let mut parser = ObjParser {
+        path,
+        positions: vec![],
+        normals: vec![],
+        uvs: vec![],
+        materials: HashMap::new(),
+        group: DEFAULT_GROUP_NAME.to_string(),
+        material_name: Option::None,
+        pending: vec![],
+        pending_lookup: HashMap::new(),
+    };
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_number = line_idx + 1;
+        parser
+            .parse_line(line)
+            .map_err(|kind| ObjError::new(path, line_number, kind))?;
+    }
+
+    Ok(parser.finish())
+}
+
+impl<'a> ObjParser<'a> {
+    fn parse_line(&mut self, line: &str) -> Result<(), ObjErrorKind> {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Option::Some(statement) => statement,
+            Option::None => return Ok(()),
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match statement {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&args, "vertex coordinate")?;
+                self.positions.push(point3(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&args, "normal coordinate")?;
+                // normals with no length are kept as zero, so that faces
+                // using them can fall back to their own normal
+                let normal = vec3(x, y, z).normalize();
+                self.normals.push(if is_finite(normal) {
+                    normal
+                } else {
+                    Vector::zero()
+                });
+            }
+            "vt" => {
+                let u = parse_float(args.first().ok_or(ObjErrorKind::MissingArgument("u"))?)?;
+                // v is optional, and any w coordinate is ignored
+                let v = match args.get(1) {
+                    Option::Some(v) => parse_float(v)?,
+                    Option::None => 0.0,
+                };
+                self.uvs.push(vec2(u, v));
+            }
+            "f" => self.parse_face(&args)?,
+            "g" | "o" => {
+                self.group = if args.is_empty() {
+                    DEFAULT_GROUP_NAME.to_string()
+                } else {
+                    args.join(" ")
+                };
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if name.is_empty() {
+                    return Err(ObjErrorKind::MissingArgument("material name"));
+                }
+                self.material_name = Option::Some(name);
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(ObjErrorKind::MissingArgument("material library"));
+                }
+                for library in args {
+                    self.load_material_library(library)?;
+                }
+            }
+            // smoothing groups, lines, curves, etc aren't supported
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn load_material_library(&mut self, library: &str) -> Result<(), ObjErrorKind> {
+        let mtl_path = match self.path.and_then(Path::parent) {
+            Option::Some(dir) => dir.join(library),
+            Option::None => PathBuf::from(library),
+        };
+        let source = fs::read_to_string(&mtl_path)
+            .map_err(|err| ObjErrorKind::MissingMaterialLibrary(mtl_path.clone(), err))?;
+        // errors inside the library carry their own path and line number
+        let materials = parse_mtl(&source, Option::Some(&mtl_path))
+            .map_err(|err| ObjErrorKind::MaterialLibrary(Box::new(err)))?;
+        self.materials.extend(materials);
+        Ok(())
+    }
+
+    fn parse_face(&mut self, args: &[&str]) -> Result<(), ObjErrorKind> {
+        if args.len() < 3 {
+            return Err(ObjErrorKind::DegenerateFace);
+        }
+        let mut vertices = Vec::with_capacity(args.len());
+        for arg in args {
+            vertices.push(self.parse_face_vertex(arg)?);
+        }
+
+        let points: Vec<Point> = vertices
+            .iter()
+            .map(|vertex| self.positions[vertex.position])
+            .collect();
+        let triangles: Vec<[FaceVertex; 3]> = triangulate(&points)
+            .into_iter()
+            .map(|triangle| triangle.map(|idx| vertices[idx]))
+            .collect();
+
+        let key = (self.group.clone(), self.material_name.clone());
+        let pending_idx = match self.pending_lookup.get(&key) {
+            Option::Some(&idx) => idx,
+            Option::None => {
+                self.pending.push(PendingMesh {
+                    name: self.group.clone(),
+                    material_name: self.material_name.clone(),
+                    faces: vec![],
+                });
+                self.pending_lookup.insert(key, self.pending.len() - 1);
+                self.pending.len() - 1
+            }
+        };
+        self.pending[pending_idx].faces.extend(triangles);
+        Ok(())
+    }
+
+    fn parse_face_vertex(&self, arg: &str) -> Result<FaceVertex, ObjErrorKind> {
+        let mut parts = arg.split('/');
+        let position = match parts.next() {
+            Option::Some(idx) if !idx.is_empty() => resolve_index(idx, self.positions.len())?,
+            _ => return Err(ObjErrorKind::InvalidFaceVertex(arg.to_string())),
+        };
+        let uv = match parts.next() {
+            Option::Some(idx) if !idx.is_empty() => {
+                Option::Some(resolve_index(idx, self.uvs.len())?)
+            }
+            _ => Option::None,
+        };
+        let normal = match parts.next() {
+            Option::Some(idx) if !idx.is_empty() => {
+                Option::Some(resolve_index(idx, self.normals.len())?)
+            }
+            _ => Option::None,
+        };
+        if parts.next().is_some() {
+            return Err(ObjErrorKind::InvalidFaceVertex(arg.to_string()));
+        }
+        Ok(FaceVertex {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    fn finish(self) -> ObjModel {
+        let mut material_cache: HashMap<Option<String>, Material> = HashMap::new();
+        let mut meshes = vec![];
+        let mut flat_faces = 0;
+
+        for pending in self.pending {
+            let material = material_cache
+                .entry(pending.material_name.clone())
+                .or_insert_with(|| {
+                    let Option::Some(name) = &pending.material_name else {
+                        return MtlMaterial::new(DEFAULT_GROUP_NAME).to_material();
+                    };
+                    match self.materials.get(name) {
+                        Option::Some(material) => material.to_material(),
+                        Option::None => {
+                            // exporters often leave these behind, so it's not
+                            // worth failing the whole model over
+                            warn!(
+                                "{}unknown material {:?}, using the default material",
+                                self.path
+                                    .map(|path| format!("{}: ", path.display()))
+                                    .unwrap_or_default(),
+                                name
+                            );
+                            MtlMaterial::new(DEFAULT_GROUP_NAME).to_material()
+                        }
+                    }
+                })
+                .clone();
+
+            // OBJ indices are global to the file, so only copy what this mesh uses
+            let mut positions = vec![];
+            let mut normals = vec![];
+            let mut uvs = vec![];
+            let mut position_map = HashMap::new();
+            let mut normal_map = HashMap::new();
+            let mut uv_map = HashMap::new();
+            let mut faces = Vec::with_capacity(pending.faces.len());
+
+            for triangle in &pending.faces {
+                let vertices = triangle.map(|vertex| {
+                    remap(
+                        vertex.position,
+                        &mut position_map,
+                        &mut positions,
+                        &self.positions,
+                    )
+                });
+                let has_normals = triangle.iter().all(|vertex| {
+                    vertex
+                        .normal
+                        .is_some_and(|idx| !self.normals[idx].is_zero())
+                });
+                if !has_normals && triangle.iter().any(|vertex| vertex.normal.is_some()) {
+                    flat_faces += 1;
+                }
+                let face_normals = if has_normals {
+                    Option::Some(triangle.map(|vertex| {
+                        remap(
+                            vertex.normal.unwrap(),
+                            &mut normal_map,
+                            &mut normals,
+                            &self.normals,
+                        )
+                    }))
+                } else {
+                    Option::None
+                };
+                let face_uvs =
+                    if triangle.iter().all(|vertex| vertex.uv.is_some()) {
+                        Option::Some(triangle.map(|vertex| {
+                            remap(vertex.uv.unwrap(), &mut uv_map, &mut uvs, &self.uvs)
+                        }))
+                    } else {
+                        Option::None
+                    };
+                faces.push(MeshFace {
+                    vertices,
+                    normals: face_normals,
+                    uvs: face_uvs,
+                });
+            }
+
+            let mesh = TriangleMesh::new(MeshBuffers {
+                positions,
+                normals,
+                uvs,
+                faces,
+                material,
+            });
+            meshes.push(ObjMesh {
+                name: pending.name,
+                material_name: pending.material_name,
+                mesh: Arc::new(mesh),
+            });
+        }
+
+        if flat_faces > 0 {
+            warn!(
+                "{}{} faces have normals with no length, using their flat normals",
+                self.path
+                    .map(|path| format!("{}: ", path.display()))
+                    .unwrap_or_default(),
+                flat_faces
+            );
+        }
+
+        ObjModel { meshes }
+    }
+}
+
+#[inline(always)]
+fn is_finite(vector: Vector) -> bool {
+    vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()
+}
+
+/// Look up the local index for a global one, copying the value over if this
+/// is the first time it's been seen
+fn remap<T: Copy>(
+    global_idx: usize,
+    map: &mut HashMap<usize, usize>,
+    local: &mut Vec<T>,
+    global: &[T],
+) -> usize {
+    *map.entry(global_idx).or_insert_with(|| {
+        local.push(global[global_idx]);
+        local.len() - 1
+    })
+}
+
+/// Turn a 1-based (or negative, relative) OBJ index into a 0-based index
+fn resolve_index(value: &str, count: usize) -> Result<usize, ObjErrorKind> {
+    let idx: i64 = value
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(value.to_string()))?;
+    let resolved = if idx > 0 {
+        idx - 1
+    } else {
+        // negative indices count backwards from the most recent element
+        count as i64 + idx
+    };
+    if idx == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange(idx));
+    }
+    Ok(resolved as usize)
+}
+
+pub(super) fn parse_float(value: &str) -> Result<f64, ObjErrorKind> {
+    value
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidNumber(value.to_string()))
+}
+
+fn parse_floats<const N: usize>(
+    args: &[&str],
+    name: &'static str,
+) -> Result<[f64; N], ObjErrorKind> {
+    let mut values = [0.0; N];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = parse_float(args.get(i).ok_or(ObjErrorKind::MissingArgument(name))?)?;
+    }
+    Ok(values)
+}
+
+/// Split a planar polygon into triangles by ear clipping, returning indices
+/// into the given list of points.
+///
+/// The polygon is projected onto whichever axis-aligned plane it's most
+/// parallel to. If the polygon is too badly behaved to find an ear (eg, it
+/// intersects itself), whatever is left is fan-triangulated instead.
+fn triangulate(points: &[Point]) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives a robust normal even for concave polygons
+    let mut normal = Vector::zero();
+    for (i, current) in points.iter().enumerate() {
+        let next = points[(i + 1) % points.len()];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    // drop the dominant axis of the normal, and flip the projection if needed
+    // so that the polygon always winds counter-clockwise in 2D
+    let abs_normal = vec3(normal.x.abs(), normal.y.abs(), normal.z.abs());
+    let (u_axis, v_axis, drop_axis) = if abs_normal.x > abs_normal.y && abs_normal.x > abs_normal.z
+    {
+        (1, 2, 0)
+    } else if abs_normal.y > abs_normal.z {
+        (2, 0, 1)
+    } else {
+        (0, 1, 2)
+    };
+    let sign = if normal[drop_axis] < 0.0 { -1.0 } else { 1.0 };
+    let projected: Vec<(f64, f64)> = points
+        .iter()
+        .map(|point| (point[u_axis], sign * point[v_axis]))
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let prev = remaining[(i + count - 1) % count];
+            let current = remaining[i];
+            let next = remaining[(i + 1) % count];
+            is_ear(&projected, &remaining, prev, current, next)
+        });
+        match ear {
+            Option::Some(i) => {
+                let prev = remaining[(i + count - 1) % count];
+                let next = remaining[(i + 1) % count];
+                triangles.push([prev, remaining[i], next]);
+                remaining.remove(i);
+            }
+            Option::None => break,
+        }
+    }
+
+    // either a single triangle is left, or no ear could be found
+    for i in 1..(remaining.len() - 1) {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+fn is_ear(
+    projected: &[(f64, f64)],
+    remaining: &[usize],
+    prev: usize,
+    current: usize,
+    next: usize,
+) -> bool {
+    let (a, b, c) = (projected[prev], projected[current], projected[next]);
+    // reflex corners can't be ears
+    if cross_2d(a, b, c) <= 0.0 {
+        return false;
+    }
+    // and no other vertex can be inside the triangle
+    !remaining.iter().any(|&idx| {
+        if idx == prev || idx == current || idx == next {
+            return false;
+        }
+        let p = projected[idx];
+        cross_2d(a, b, p) >= 0.0 && cross_2d(b, c, p) >= 0.0 && cross_2d(c, a, p) >= 0.0
+    })
+}
+
+/// The z component of (b - a) x (c - a)
+#[inline(always)]
+fn cross_2d(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{Ray, RayCollidable};
+
+    use super::*;
+
+    const CUBE_FACE: &str = "# a unit square in the xy plane, as a quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+g front
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn when_parse_obj_given_quad_returns_two_triangles() {
+        let model = parse_obj(CUBE_FACE, Option::None).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.name, "front");
+        let buffers = mesh.mesh.buffers();
+        assert_eq!(buffers.faces.len(), 2);
+        assert_eq!(buffers.positions.len(), 4);
+        assert_eq!(buffers.normals.len(), 1);
+        assert_eq!(buffers.uvs.len(), 4);
+
+        let ray = Ray::new(point3(0.25, 0.75, 1.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = mesh.mesh.will_intersect(&ray, 0.001, f64::INFINITY);
+        assert!(collision.is_some());
+    }
+
+    #[test]
+    fn when_parse_obj_given_negative_indices_resolves_relative_to_end() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        let model = parse_obj(source, Option::None).unwrap();
+        assert_eq!(model.meshes[0].mesh.buffers().faces.len(), 1);
+    }
+
+    #[test]
+    fn when_parse_obj_given_groups_returns_mesh_per_group() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0
+g a
+f 1 2 3
+g b
+f 2 4 3
+g a
+f 1 3 2
+";
+        let model = parse_obj(source, Option::None).unwrap();
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[0].name, "a");
+        assert_eq!(model.meshes[0].mesh.buffers().faces.len(), 2);
+        assert_eq!(model.meshes[1].name, "b");
+        assert_eq!(model.meshes[1].mesh.buffers().positions.len(), 3);
+    }
+
+    #[test]
+    fn when_triangulate_given_concave_polygon_returns_triangles_inside_it() {
+        // an L shape, which a naive fan from vertex 1 would get wrong
+        let points = vec![
+            point3(1.0, 1.0, 0.0),
+            point3(0.0, 1.0, 0.0),
+            point3(0.0, 0.0, 0.0),
+            point3(2.0, 0.0, 0.0),
+            point3(2.0, 2.0, 0.0),
+            point3(1.0, 2.0, 0.0),
+        ];
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        let total_area: f64 = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                let cross = (points[*b] - points[*a]).cross(points[*c] - points[*a]);
+                // every triangle should wind the same way as the polygon
+                assert!(
+                    cross.z > 0.0,
+                    "Triangle {:?} is flipped or degenerate",
+                    [a, b, c]
+                );
+                cross.z / 2.0
+            })
+            .sum();
+        assert!((total_area - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn when_parse_obj_given_bad_index_returns_error_with_line() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n";
+        let err = parse_obj(source, Option::None).err().unwrap();
+        assert_eq!(err.line, 5);
+        assert!(matches!(err.kind, ObjErrorKind::IndexOutOfRange(4)));
+    }
+
+    #[test]
+    fn when_parse_obj_given_zero_normal_uses_face_normal() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 0\nvn 0 0 1\nf 1//1 2//2 3//2\n";
+        let model = parse_obj(source, Option::None).unwrap();
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.buffers().faces[0].normals, Option::None);
+
+        let ray = Ray::new(point3(0.25, 0.25, 1.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let collision = mesh.will_intersect(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(collision.normal, vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn when_parse_obj_given_unknown_material_uses_default_material() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl nonexistent\nf 1 2 3\n";
+        let model = parse_obj(source, Option::None).unwrap();
+        assert_eq!(
+            model.meshes[0].material_name.as_deref(),
+            Some("nonexistent")
+        );
+        assert!(matches!(
+            model.meshes[0].mesh.buffers().material,
+            Material::Lambertian(_)
+        ));
+    }
+
+    #[test]
+    fn when_load_obj_given_material_library_maps_materials() {
+        let dir = std::env::temp_dir().join(format!("raytracer-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("test.mtl"), "newmtl shiny\nKs 1 1 1\nNs 500\n").unwrap();
+        fs::write(
+            dir.join("test.obj"),
+            "mtllib test.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl shiny\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let model = load_obj(dir.join("test.obj")).unwrap();
+        assert_eq!(model.meshes[0].material_name.as_deref(), Some("shiny"));
+        assert!(matches!(
+            model.meshes[0].mesh.buffers().material,
+            Material::Metallic(_)
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,88 +3,312 @@ use std::sync::Arc;
 use cgmath::{point3, vec3, InnerSpace};
 
 use crate::{
+    background::{Background, BackgroundTrait},
     geometry::{
-        aabb::{AxisAlignedBoundingBox, AABB},
-        moving_sphere::MovingSphere,
-        sphere::Sphere,
-        Collision, Geometry, Ray, RayCollidable, Vector,
+        aabb::AxisAlignedBoundingBox, animated::AnimatedInstance, bvh::BVH, constant_medium::Fog,
+        sampling::Sampleable, sphere::Sphere, Collision, Geometry, Point, Ray, RayCollidable,
+        Vector,
     },
+    light::{Light, LightSample, LightTrait},
+    sampler::{Sampler, SamplerTrait},
     shader::{Dielectric, Lambertian, Material, Metallic},
+    volume::{GridMedium, MediumInteraction},
 };
 
+/// The default shutter interval scenes are built for, matching the 1 scene
+/// second moving spheres take to travel from start to end
+const DEFAULT_TIME_START: f64 = 0.0;
+const DEFAULT_TIME_END: f64 = 1.0;
+
 #[derive(Clone)]
 pub struct SceneGraph {
     objects: Vec<Geometry>,
+    /// Acceleration structure over `objects`, used for all ray queries
+    bvh: BVH,
+    /// The lights that are sampled directly, including any objects that give
+    /// off light
+    lights: Vec<Light>,
+    /// What rays that escape the scene see
+    background: Background,
+    /// Haze between the objects, if there's any
+    fog: Option<Fog>,
+    /// Volumes read from voxel grids, which are tracked through separately
+    /// from the BVH since rays don't stop at their boundaries
+    volumes: Vec<Arc<GridMedium>>,
+    /// The shutter interval the BVH is built for
+    time_start: f64,
+    time_end: f64,
 }
 
-impl RayCollidable for SceneGraph {
-    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
-        let mut collision: Option<Collision> = None;
-        let mut closest_hit = t_max;
-
-        for object in &self.objects {
-            match object.will_intersect(ray, t_min, closest_hit) {
-                None => {}
-                Some(i_collision) => {
-                    closest_hit = i_collision.t;
-                    collision = Some(i_collision);
-                }
-            }
+impl SceneGraph {
+    /// Create a new scene from a list of objects, building a BVH over them
+    pub fn new(objects: Vec<Geometry>) -> Self {
+        Self::new_with_time_interval(objects, DEFAULT_TIME_START, DEFAULT_TIME_END)
+    }
+
+    /// Create a new scene whose BVH is built for rays cast between time_start
+    /// and time_end.
+    ///
+    /// This should cover the shutter interval of the camera rendering this
+    /// scene, or moving objects may be clipped.
+    pub fn new_with_time_interval(objects: Vec<Geometry>, time_start: f64, time_end: f64) -> Self {
+        let bvh = BVH::new(objects.clone(), time_start, time_end);
+        let lights = objects
+            .iter()
+            .filter(|object| object.is_emissive())
+            .map(|object| object.clone().into())
+            .collect();
+        Self {
+            objects,
+            bvh,
+            lights,
+            background: Background::default(),
+            fog: Option::None,
+            volumes: vec![],
+            time_start,
+            time_end,
+        }
+    }
+
+    /// Add lights to the scene. Lights with a surface, like sphere and quad
+    /// lights, are added to the scene's objects as well, so they can be seen.
+    pub fn with_lights(mut self, lights: Vec<Light>) -> Self {
+        let surfaces: Vec<Geometry> = lights.iter().filter_map(Light::geometry).collect();
+        if !surfaces.is_empty() {
+            self.objects.extend(surfaces);
+            self.bvh = BVH::new(self.objects.clone(), self.time_start, self.time_end);
         }
+        self.lights.extend(lights);
+        self
+    }
 
-        collision
+    /// Replace the default sky gradient. For scenes that are lit only by
+    /// emissive materials, this should be solid black.
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
     }
 
-    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
-        if self.objects.is_empty() {
+    /// Fill the scene with fog, out to the edge of the box around all of its
+    /// objects
+    pub fn with_fog(mut self, fog: Fog) -> Self {
+        self.fog = Option::Some(fog);
+        self
+    }
+
+    /// Add volumes whose density is read from voxel grids, like smoke from a
+    /// simulation
+    pub fn with_volumes(mut self, volumes: Vec<Arc<GridMedium>>) -> Self {
+        self.volumes.extend(volumes);
+        self
+    }
+
+    /// The objects that make up this scene
+    pub fn objects(&self) -> &[Geometry] {
+        &self.objects
+    }
+
+    /// The lights in this scene, including any objects that give off light
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Pick a direction from the point towards one of the scene's lights,
+    /// with the light and the point on it taken from the sampler
+    ///
+    /// The sample's density includes the odds of picking that light. Returns
+    /// None if there aren't any lights, or the one picked can't reach the
+    /// point.
+    pub fn sample_light(
+        &self,
+        point: &Point,
+        time: f64,
+        sampler: &mut Sampler,
+    ) -> Option<LightSample> {
+        if self.lights.is_empty() {
             return Option::None;
         }
-        let first_object_bounds = self.objects[0].get_bounds(time_start, time_end);
-        let mut bounds: AABB;
-        if let Option::Some(first_obj_bounds) = first_object_bounds {
-            bounds = first_obj_bounds
-        } else {
-            return Option::None;
+        let pick = (sampler.next_1d() * self.lights.len() as f64) as usize;
+        let light = &self.lights[pick.min(self.lights.len() - 1)];
+        let mut sample = light.sample(point, time, sampler)?;
+        sample.pdf /= self.lights.len() as f64;
+        return Option::Some(sample);
+    }
+
+    /// The density `sample_light` would pick the ray's direction with, given
+    /// that the first thing it reaches is a light `t` along it. That's
+    /// infinitely far away for rays that leave the scene.
+    ///
+    /// Only the light that was reached counts, since the others are either
+    /// behind it or not in the way at all.
+    pub fn light_pdf(&self, ray: &Ray, t_min: f64, t: f64) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
         }
-        for object in &self.objects[1..] {
-            let object_bound = object.get_bounds(time_start, time_end);
-            if let Option::Some(aabb) = object_bound {
-                bounds = bounds.bounding_box(&aabb);
-            } else {
-                return Option::None;
+        // leave some room for rounding, so the light that was hit is included
+        let t_max = t * (1.0 + 1e-6);
+        let pdf: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf(ray, t_min, t_max))
+            .sum();
+        return pdf / self.lights.len() as f64;
+    }
+
+    /// The light from lights infinitely far away, like the sun, along a ray
+    /// that hit nothing. This isn't included in `background_color`.
+    pub fn escaped_light(&self, ray: &Ray) -> Vector {
+        self.lights
+            .iter()
+            .map(|light| light.escaped(ray))
+            .fold(vec3(0.0, 0.0, 0.0), |total, light| total + light)
+    }
+
+    pub fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+
+    pub fn volumes(&self) -> &[Arc<GridMedium>] {
+        &self.volumes
+    }
+
+    /// Where the ray scatters in the scene's fog and volumes between t_min
+    /// and t_max, if it does, and the light the volumes give off along the
+    /// way. Rays that hit nothing should pass infinity for t_max.
+    pub fn medium_interaction(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> MediumInteraction {
+        let mut collision = match (&self.fog, self.fog_interval(ray, t_min, t_max)) {
+            (Option::Some(fog), Option::Some((t_min, t_max))) => {
+                fog.will_scatter(ray, t_min, t_max, sampler.next_1d())
+            }
+            _ => Option::None,
+        };
+        if self.volumes.is_empty() {
+            return MediumInteraction {
+                collision,
+                emitted: vec3(0.0, 0.0, 0.0),
+            };
+        }
+        let rng = sampler.rng();
+        // each volume only needs tracking up to wherever the ray has already
+        // scattered, but its light is only known to count once they all have
+        let mut t_end = collision.as_ref().map_or(t_max, |collision| collision.t);
+        let mut emitted_at = vec![];
+        for volume in &self.volumes {
+            let scattered = volume.track(ray, t_min, t_end, &rng, |t, light| {
+                emitted_at.push((t, light));
+            });
+            if let Option::Some(scattered) = scattered {
+                t_end = scattered.t;
+                collision = Option::Some(scattered);
+            }
+        }
+        let emitted = emitted_at
+            .into_iter()
+            .filter(|(t, _)| *t < t_end)
+            .fold(vec3(0.0, 0.0, 0.0), |total, (_, light)| total + light);
+        return MediumInteraction { collision, emitted };
+    }
+
+    /// How much light gets through the scene's fog and volumes between t_min
+    /// and t_max
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut Sampler) -> f64 {
+        let mut transmittance = match (&self.fog, self.fog_interval(ray, t_min, t_max)) {
+            (Option::Some(fog), Option::Some((t_min, t_max))) => {
+                fog.transmittance(ray, t_min, t_max)
+            }
+            _ => 1.0,
+        };
+        if self.volumes.is_empty() {
+            return transmittance;
+        }
+        let rng = sampler.rng();
+        for volume in &self.volumes {
+            if transmittance == 0.0 {
+                break;
             }
+            transmittance *= volume.transmittance(ray, t_min, t_max, &rng);
         }
-        return Option::Some(bounds);
+        return transmittance;
+    }
+
+    /// The part of the ray between t_min and t_max that's inside the box
+    /// the fog fills. Infinite objects like planes aren't counted, and scenes
+    /// with nothing else are filled all the way.
+    fn fog_interval(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        return match self.bvh.bounded_bounds() {
+            Option::Some(bounds) => bounds
+                .will_intersect_aabb(ray, t_min, t_max)
+                .map(|interval| (interval.0, interval.1)),
+            Option::None => Option::Some((t_min, t_max)),
+        };
+    }
+
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+
+    /// The light coming from outside the scene along a ray that hit nothing
+    #[inline(always)]
+    pub fn background_color(&self, ray: &Ray) -> Vector {
+        self.background.color(ray)
     }
 }
 
-pub fn new_test_world() -> SceneGraph {
-    SceneGraph {
-        objects: vec![
-            Arc::new(Sphere::new(point3(0.0, 0.0, -1.0), 0.5)).into(),
-            Arc::new(Sphere::new_with_material(
-                point3(0.0, -100.5, -1.0),
-                100.0,
-                Arc::new(Lambertian::new(vec3(0.2, 0.7, 0.1))).into(),
-            ))
-            .into(),
-            Arc::new(Sphere::new_with_material(
-                point3(-1.0, 0.0, -1.0),
-                0.5,
-                Arc::new(Metallic::new(vec3(0.7, 0.7, 1.0), 0.0)).into(),
-            ))
-            .into(),
-            Arc::new(Sphere::new_with_material(
-                point3(1.1, 0.0, -1.0),
-                0.5,
-                Arc::new(Dielectric::new(1.5)).into(),
-            ))
-            .into(),
-        ],
+impl RayCollidable for SceneGraph {
+    #[inline(always)]
+    fn will_intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Collision> {
+        self.bvh.will_intersect(ray, t_min, t_max)
+    }
+
+    #[inline(always)]
+    fn will_intersect_sampled(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut Sampler,
+    ) -> Option<Collision> {
+        self.bvh.will_intersect_sampled(ray, t_min, t_max, sampler)
     }
+
+    fn get_bounds(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        self.bvh.get_bounds(time_start, time_end)
+    }
+}
+
+pub fn new_test_world() -> SceneGraph {
+    SceneGraph::new(vec![
+        Arc::new(Sphere::new(point3(0.0, 0.0, -1.0), 0.5)).into(),
+        Arc::new(Sphere::new_with_material(
+            point3(0.0, -100.5, -1.0),
+            100.0,
+            Arc::new(Lambertian::new(vec3(0.2, 0.7, 0.1))).into(),
+        ))
+        .into(),
+        Arc::new(Sphere::new_with_material(
+            point3(-1.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Metallic::new(vec3(0.7, 0.7, 1.0), 0.0)).into(),
+        ))
+        .into(),
+        Arc::new(Sphere::new_with_material(
+            point3(1.1, 0.0, -1.0),
+            0.5,
+            Arc::new(Dielectric::new(1.5)).into(),
+        ))
+        .into(),
+    ])
 }
 
-pub fn new_random_world() -> SceneGraph {
+/// The cover scene from Ray Tracing in One Weekend, with the small spheres
+/// scattered at random. The same seed always scatters them the same way.
+pub fn new_random_world(seed: u64) -> SceneGraph {
     let ground = Arc::new(Sphere::new_with_material(
         point3(0.0, -1000.0, -1.0),
         1000.0,
@@ -93,7 +317,7 @@ pub fn new_random_world() -> SceneGraph {
 
     let mut objects: Vec<Geometry> = vec![ground.into()];
 
-    let rng = fastrand::Rng::new();
+    let rng = fastrand::Rng::with_seed(seed);
 
     for a in -11..11 {
         for b in -11..11 {
@@ -106,9 +330,9 @@ pub fn new_random_world() -> SceneGraph {
                 if choose_material < 0.8 {
                     let albedo: Vector = vec3(rng.f64(), rng.f64(), rng.f64());
                     material = Arc::new(Lambertian::new(albedo)).into();
-                    object = Arc::new(MovingSphere::new_with_material(
+                    object = Arc::new(AnimatedInstance::new_moving_sphere(
                         center,
-                        center + vec3(0.0, fastrand::f64() / 2.0, 0.0),
+                        center + vec3(0.0, rng.f64() / 2.0, 0.0),
                         0.2,
                         material,
                     ))
@@ -148,5 +372,5 @@ pub fn new_random_world() -> SceneGraph {
     );
     objects.push(Arc::new(metal_ball).into());
 
-    SceneGraph { objects }
+    SceneGraph::new(objects)
 }
@@ -1,4 +1,7 @@
-use crate::geometry::{Collision, Ray, Vector};
+use crate::{
+    geometry::{Collision, Ray, Vector},
+    sampler::{Sampler, SamplerTrait},
+};
 use cgmath::{vec3, InnerSpace};
 
 use super::MaterialTrait;
@@ -25,7 +28,12 @@ impl Dielectric {
 }
 
 impl MaterialTrait for Dielectric {
-    fn scatter(&self, ray: &Ray, collision: &Collision) -> Option<(Vector, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
         let is_front_face = cgmath::dot(ray.direction, collision.normal) < 0.0;
         let face_normal = if is_front_face {
             collision.normal
@@ -46,7 +54,8 @@ impl MaterialTrait for Dielectric {
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
         let can_refract = refractive_ratio * sin_theta <= 1.0;
-        let should_reflect = Dielectric::reflectance(cos_theta, refractive_ratio) > fastrand::f64();
+        let should_reflect =
+            Dielectric::reflectance(cos_theta, refractive_ratio) > sampler.next_1d();
         let refracted_ray_direction = if can_refract && !should_reflect {
             refract_hack(
                 ray.direction.normalize(),
@@ -0,0 +1,105 @@
+use cgmath::{vec3, InnerSpace};
+
+use crate::{
+    geometry::{Collision, Ray, Vector},
+    sampler::Sampler,
+};
+
+use super::MaterialTrait;
+
+/// A material that glows, and doesn't reflect any light of its own
+pub struct DiffuseLight {
+    /// The radiance given off by the surface, which can be brighter than 1
+    emit: Vector,
+    /// Whether light is given off the back of the surface as well as the front
+    two_sided: bool,
+}
+
+impl DiffuseLight {
+    /// Create a light that only shines out of the front of a surface, the side
+    /// its outward normal points to
+    pub fn new(emit: Vector) -> DiffuseLight {
+        DiffuseLight {
+            emit,
+            two_sided: false,
+        }
+    }
+
+    /// Create a light that shines out of both sides of a surface
+    pub fn new_two_sided(emit: Vector) -> DiffuseLight {
+        DiffuseLight {
+            emit,
+            two_sided: true,
+        }
+    }
+}
+
+impl MaterialTrait for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _collision: &Collision,
+        _sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
+        return Option::None;
+    }
+
+    fn emitted(&self, ray: &Ray, collision: &Collision) -> Vector {
+        let is_front_face = ray.direction.dot(collision.normal) < 0.0;
+        return if self.two_sided || is_front_face {
+            self.emit
+        } else {
+            vec3(0.0, 0.0, 0.0)
+        };
+    }
+
+    fn is_emissive(&self) -> bool {
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{point3, vec2};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    fn make_collision(light: DiffuseLight) -> Collision {
+        Collision {
+            point: point3(0.0, 0.0, 0.0),
+            normal: vec3(0.0, 0.0, 1.0),
+            t: 1.0,
+            uv: vec2(0.0, 0.0),
+            uv_footprint: 0.0,
+            material: Arc::new(light).into(),
+        }
+    }
+
+    #[test]
+    fn when_emitted_given_ray_from_behind_returns_black() {
+        let light = DiffuseLight::new(vec3(4.0, 4.0, 4.0));
+        let front = Ray::new(point3(0.0, 0.0, 1.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let back = Ray::new(point3(0.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let collision = make_collision(light);
+        let material = &collision.material;
+        assert_eq!(material.emitted(&front, &collision), vec3(4.0, 4.0, 4.0));
+        assert_eq!(material.emitted(&back, &collision), vec3(0.0, 0.0, 0.0));
+        let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+        assert!(material.scatter(&front, &collision, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn when_emitted_given_two_sided_light_returns_emit_from_behind() {
+        let light = DiffuseLight::new_two_sided(vec3(4.0, 4.0, 4.0));
+        let back = Ray::new(point3(0.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let collision = make_collision(light);
+        assert_eq!(
+            collision.material.emitted(&back, &collision),
+            vec3(4.0, 4.0, 4.0)
+        );
+    }
+}
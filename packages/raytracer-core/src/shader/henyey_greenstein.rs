@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+
+use cgmath::InnerSpace;
+
+use crate::{
+    geometry::{sampling::orthonormal_basis, Collision, Ray, Vector},
+    sampler::{Sampler, SamplerTrait},
+    texture::{Texture, TextureTrait},
+};
+
+use super::MaterialTrait;
+
+/// The phase function of a volume that scatters light mostly forwards or
+/// mostly backwards, like fog, clouds or skin
+///
+/// From Henyey & Greenstein (1941), "Diffuse radiation in the galaxy". Like
+/// any volume, the collision's normal is ignored.
+pub struct HenyeyGreenstein {
+    /// How much light survives each scattering event, rather than being
+    /// absorbed
+    albedo: Texture,
+    /// The average cosine of the angle light is scattered by, from -1 for
+    /// straight back, through 0 for evenly in every direction, to 1 for
+    /// straight on
+    anisotropy: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Vector, anisotropy: f64) -> HenyeyGreenstein {
+        Self::new_textured(albedo.into(), anisotropy)
+    }
+
+    pub fn new_textured(albedo: Texture, anisotropy: f64) -> HenyeyGreenstein {
+        // at exactly 1 or -1 the light never changes direction, which can't be
+        // evaluated
+        let anisotropy = anisotropy.clamp(-0.999, 0.999);
+        HenyeyGreenstein { albedo, anisotropy }
+    }
+
+    /// The density of scattering by an angle with the given cosine
+    #[inline(always)]
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.anisotropy;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        return (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt());
+    }
+}
+
+impl MaterialTrait for HenyeyGreenstein {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
+        let g = self.anisotropy;
+        // invert the distribution's CDF to pick the angle off of straight on
+        let [xi, turn] = sampler.next_2d();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * turn;
+        let forward = ray.direction.normalize();
+        let (tangent, bitangent) = orthonormal_basis(forward);
+        let direction = tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin())
+            + forward * cos_theta;
+
+        // the direction is picked in proportion to the phase function, so
+        // they cancel out
+        let scatter = Ray::new(collision.point, direction, ray.time);
+        let albedo = self
+            .albedo
+            .value(collision.uv, &collision.point, collision.uv_footprint);
+        return Option::Some((albedo, scatter));
+    }
+
+    fn is_specular(&self) -> bool {
+        return false;
+    }
+
+    fn evaluate(&self, ray: &Ray, collision: &Collision, direction: Vector) -> Vector {
+        let albedo = self
+            .albedo
+            .value(collision.uv, &collision.point, collision.uv_footprint);
+        return albedo * self.pdf(ray, collision, direction);
+    }
+
+    fn pdf(&self, ray: &Ray, _collision: &Collision, direction: Vector) -> f64 {
+        let cos_theta = ray.direction.normalize().dot(direction.normalize());
+        return self.phase(cos_theta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{point3, vec2, vec3};
+
+    use crate::sampler::SamplerKind;
+
+    use super::*;
+
+    #[test]
+    fn when_scatter_given_anisotropy_returns_directions_with_that_mean_cosine() {
+        for g in [-0.6, 0.0, 0.3, 0.9] {
+            let collision = Collision {
+                point: point3(0.0, 0.0, 0.0),
+                normal: vec3(0.0, 0.0, 1.0),
+                t: 1.0,
+                uv: vec2(0.0, 0.0),
+                uv_footprint: 0.0,
+                material: Arc::new(HenyeyGreenstein::new(vec3(1.0, 1.0, 1.0), g)).into(),
+            };
+            let ray = Ray::new(point3(0.0, 0.0, 1.0), vec3(1.0, 2.0, -1.0), 0.0);
+            let count = 100_000;
+            let mut total = 0.0;
+            let mut sampler = Sampler::new(SamplerKind::Independent, 1, 0);
+            for idx in 0..count {
+                sampler.start_pixel_sample(0, 0, idx);
+                let (_, scattered) = collision
+                    .material
+                    .scatter(&ray, &collision, &mut sampler)
+                    .unwrap();
+                total += ray.direction.normalize().dot(scattered.direction);
+                assert!((scattered.direction.magnitude() - 1.0).abs() < 1e-9);
+            }
+            assert!((total / count as f64 - g).abs() < 0.01, "g = {}", g);
+
+            // the phase function covers the whole sphere of directions
+            let material = HenyeyGreenstein::new(vec3(1.0, 1.0, 1.0), g);
+            let steps = 100_000;
+            let integral: f64 = (0..steps)
+                .map(|idx| {
+                    let cos_theta = -1.0 + 2.0 * (idx as f64 + 0.5) / steps as f64;
+                    material.phase(cos_theta) * 2.0 * PI * (2.0 / steps as f64)
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "g = {}", g);
+        }
+    }
+}
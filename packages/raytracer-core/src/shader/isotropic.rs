@@ -0,0 +1,63 @@
+use std::f64::consts::PI;
+
+use crate::{
+    geometry::{util::vector::unit_vector_from, Collision, Ray, Vector},
+    sampler::{Sampler, SamplerTrait},
+    texture::{Texture, TextureTrait},
+};
+
+use super::MaterialTrait;
+
+/// The phase function of a volume that scatters light evenly in every
+/// direction, like thick smoke
+///
+/// Volumes don't have a surface, so the collision's normal is ignored.
+pub struct Isotropic {
+    /// How much light survives each scattering event, rather than being
+    /// absorbed
+    albedo: Texture,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Vector) -> Isotropic {
+        Self::new_textured(albedo.into())
+    }
+
+    pub fn new_textured(albedo: Texture) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl MaterialTrait for Isotropic {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
+        let scatter = Ray::new(
+            collision.point,
+            unit_vector_from(sampler.next_2d()),
+            ray.time,
+        );
+        let albedo = self
+            .albedo
+            .value(collision.uv, &collision.point, collision.uv_footprint);
+        return Option::Some((albedo, scatter));
+    }
+
+    fn is_specular(&self) -> bool {
+        return false;
+    }
+
+    fn evaluate(&self, ray: &Ray, collision: &Collision, direction: Vector) -> Vector {
+        let albedo = self
+            .albedo
+            .value(collision.uv, &collision.point, collision.uv_footprint);
+        return albedo * self.pdf(ray, collision, direction);
+    }
+
+    fn pdf(&self, _ray: &Ray, _collision: &Collision, _direction: Vector) -> f64 {
+        return 1.0 / (4.0 * PI);
+    }
+}
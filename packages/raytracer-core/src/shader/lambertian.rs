@@ -1,29 +1,69 @@
-use crate::geometry::{
-    util::vector::{near_zero, random_unit_vector},
-    Collision, Ray, Vector,
+use std::f64::consts::PI;
+
+use cgmath::InnerSpace;
+
+use crate::{
+    geometry::{
+        util::vector::{near_zero, to_face_normal, unit_vector_from},
+        Collision, Ray, Vector,
+    },
+    sampler::{Sampler, SamplerTrait},
+    texture::{Texture, TextureTrait},
 };
 
 use super::MaterialTrait;
 
 pub struct Lambertian {
-    albedo: Vector,
+    albedo: Texture,
 }
 
 impl MaterialTrait for Lambertian {
-    fn scatter(&self, ray: &Ray, collision: &Collision) -> Option<(Vector, Ray)> {
-        let mut scatter_direction = collision.normal + random_unit_vector();
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
+        // open surfaces like triangles can be hit from either side
+        let normal = to_face_normal(ray, collision.normal);
+        let mut scatter_direction = normal + unit_vector_from(sampler.next_2d());
 
         if near_zero(scatter_direction) {
-            scatter_direction = collision.normal;
+            scatter_direction = normal;
         }
 
         let scatter = Ray::new(collision.point, scatter_direction, ray.time);
-        return Option::Some((self.albedo, scatter));
+        let albedo = self
+            .albedo
+            .value(collision.uv, &collision.point, collision.uv_footprint);
+        return Option::Some((albedo, scatter));
+    }
+
+    fn is_specular(&self) -> bool {
+        return false;
+    }
+
+    fn evaluate(&self, ray: &Ray, collision: &Collision, direction: Vector) -> Vector {
+        let albedo = self
+            .albedo
+            .value(collision.uv, &collision.point, collision.uv_footprint);
+        return albedo * self.pdf(ray, collision, direction);
+    }
+
+    /// Scattered rays follow a cosine distribution around the normal
+    fn pdf(&self, ray: &Ray, collision: &Collision, direction: Vector) -> f64 {
+        let normal = to_face_normal(ray, collision.normal);
+        let cosine = normal.dot(direction.normalize());
+        return cosine.max(0.0) / PI;
     }
 }
 
 impl Lambertian {
     pub fn new(albedo: Vector) -> Lambertian {
+        Self::new_textured(albedo.into())
+    }
+
+    pub fn new_textured(albedo: Texture) -> Lambertian {
         Lambertian { albedo }
     }
 }
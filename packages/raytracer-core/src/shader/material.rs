@@ -1,27 +1,144 @@
 use std::sync::Arc;
 
-use crate::geometry::{Collision, Ray, Vector};
+use cgmath::vec3;
 
-use super::{Dielectric, Lambertian, Metallic};
+use crate::{
+    geometry::{Collision, Ray, Vector},
+    sampler::Sampler,
+};
+
+use super::{Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Metallic};
 
 pub trait MaterialTrait {
-    fn scatter(&self, ray: &Ray, collision: &Collision) -> Option<(Vector, Ray)>;
+    /// Pick a direction to scatter the ray in, returning the ray and how much
+    /// it's attenuated by. That's the BSDF times the cosine term, divided by
+    /// the density the direction was picked with. Any random choices are
+    /// taken from the sampler.
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)>;
+
+    /// The light given off by this material where the ray hit it, on top of
+    /// any light that it scatters. Most materials don't glow.
+    fn emitted(&self, _ray: &Ray, _collision: &Collision) -> Vector {
+        return vec3(0.0, 0.0, 0.0);
+    }
+
+    /// Whether the material gives off any light, so that lights can be
+    /// found in a scene
+    fn is_emissive(&self) -> bool {
+        return false;
+    }
+
+    /// Whether the material only scatters in directions that can't be
+    /// evaluated, like a mirror. Lights aren't sampled from specular
+    /// materials, so it defaults to true for materials that don't implement
+    /// `evaluate` and `pdf`.
+    fn is_specular(&self) -> bool {
+        return true;
+    }
+
+    /// How much of the light arriving from `direction` is scattered back
+    /// along the ray: the BSDF times the cosine term
+    fn evaluate(&self, _ray: &Ray, _collision: &Collision, _direction: Vector) -> Vector {
+        return vec3(0.0, 0.0, 0.0);
+    }
+
+    /// The density over solid angle that `scatter` picks `direction` with
+    fn pdf(&self, _ray: &Ray, _collision: &Collision, _direction: Vector) -> f64 {
+        return 0.0;
+    }
 }
 
 #[derive(Clone)]
 pub enum Material {
     Dielectric(Arc<Dielectric>),
+    DiffuseLight(Arc<DiffuseLight>),
     Lambertian(Arc<Lambertian>),
     Metallic(Arc<Metallic>),
+    /// Phase functions, for volumes rather than surfaces
+    Isotropic(Arc<Isotropic>),
+    HenyeyGreenstein(Arc<HenyeyGreenstein>),
 }
 
 impl MaterialTrait for Material {
     #[inline(always)]
-    fn scatter(&self, ray: &Ray, collision: &Collision) -> Option<(Vector, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
+        match self {
+            Material::Dielectric(dielectric) => dielectric.scatter(ray, collision, sampler),
+            Material::DiffuseLight(light) => light.scatter(ray, collision, sampler),
+            Material::Lambertian(lambertian) => lambertian.scatter(ray, collision, sampler),
+            Material::Metallic(metallic) => metallic.scatter(ray, collision, sampler),
+            Material::Isotropic(isotropic) => isotropic.scatter(ray, collision, sampler),
+            Material::HenyeyGreenstein(phase) => phase.scatter(ray, collision, sampler),
+        }
+    }
+
+    #[inline(always)]
+    fn emitted(&self, ray: &Ray, collision: &Collision) -> Vector {
+        match self {
+            Material::Dielectric(dielectric) => dielectric.emitted(ray, collision),
+            Material::DiffuseLight(light) => light.emitted(ray, collision),
+            Material::Lambertian(lambertian) => lambertian.emitted(ray, collision),
+            Material::Metallic(metallic) => metallic.emitted(ray, collision),
+            Material::Isotropic(isotropic) => isotropic.emitted(ray, collision),
+            Material::HenyeyGreenstein(phase) => phase.emitted(ray, collision),
+        }
+    }
+
+    #[inline(always)]
+    fn is_emissive(&self) -> bool {
         match self {
-            Material::Dielectric(dielectric) => dielectric.scatter(ray, collision),
-            Material::Lambertian(lambertian) => lambertian.scatter(ray, collision),
-            Material::Metallic(metallic) => metallic.scatter(ray, collision),
+            Material::Dielectric(dielectric) => dielectric.is_emissive(),
+            Material::DiffuseLight(light) => light.is_emissive(),
+            Material::Lambertian(lambertian) => lambertian.is_emissive(),
+            Material::Metallic(metallic) => metallic.is_emissive(),
+            Material::Isotropic(isotropic) => isotropic.is_emissive(),
+            Material::HenyeyGreenstein(phase) => phase.is_emissive(),
+        }
+    }
+
+    #[inline(always)]
+    fn is_specular(&self) -> bool {
+        match self {
+            Material::Dielectric(dielectric) => dielectric.is_specular(),
+            Material::DiffuseLight(light) => light.is_specular(),
+            Material::Lambertian(lambertian) => lambertian.is_specular(),
+            Material::Metallic(metallic) => metallic.is_specular(),
+            Material::Isotropic(isotropic) => isotropic.is_specular(),
+            Material::HenyeyGreenstein(phase) => phase.is_specular(),
+        }
+    }
+
+    #[inline(always)]
+    fn evaluate(&self, ray: &Ray, collision: &Collision, direction: Vector) -> Vector {
+        match self {
+            Material::Dielectric(dielectric) => dielectric.evaluate(ray, collision, direction),
+            Material::DiffuseLight(light) => light.evaluate(ray, collision, direction),
+            Material::Lambertian(lambertian) => lambertian.evaluate(ray, collision, direction),
+            Material::Metallic(metallic) => metallic.evaluate(ray, collision, direction),
+            Material::Isotropic(isotropic) => isotropic.evaluate(ray, collision, direction),
+            Material::HenyeyGreenstein(phase) => phase.evaluate(ray, collision, direction),
+        }
+    }
+
+    #[inline(always)]
+    fn pdf(&self, ray: &Ray, collision: &Collision, direction: Vector) -> f64 {
+        match self {
+            Material::Dielectric(dielectric) => dielectric.pdf(ray, collision, direction),
+            Material::DiffuseLight(light) => light.pdf(ray, collision, direction),
+            Material::Lambertian(lambertian) => lambertian.pdf(ray, collision, direction),
+            Material::Metallic(metallic) => metallic.pdf(ray, collision, direction),
+            Material::Isotropic(isotropic) => isotropic.pdf(ray, collision, direction),
+            Material::HenyeyGreenstein(phase) => phase.pdf(ray, collision, direction),
         }
     }
 }
@@ -31,6 +148,11 @@ impl From<Arc<Dielectric>> for Material {
         Self::Dielectric(value)
     }
 }
+impl From<Arc<DiffuseLight>> for Material {
+    fn from(value: Arc<DiffuseLight>) -> Self {
+        Self::DiffuseLight(value)
+    }
+}
 impl From<Arc<Lambertian>> for Material {
     fn from(value: Arc<Lambertian>) -> Self {
         Self::Lambertian(value)
@@ -41,3 +163,13 @@ impl From<Arc<Metallic>> for Material {
         Self::Metallic(value)
     }
 }
+impl From<Arc<Isotropic>> for Material {
+    fn from(value: Arc<Isotropic>) -> Self {
+        Self::Isotropic(value)
+    }
+}
+impl From<Arc<HenyeyGreenstein>> for Material {
+    fn from(value: Arc<HenyeyGreenstein>) -> Self {
+        Self::HenyeyGreenstein(value)
+    }
+}
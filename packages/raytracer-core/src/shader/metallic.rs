@@ -1,18 +1,26 @@
 use cgmath::InnerSpace;
 
-use crate::geometry::{util, Collision, Ray, Vector};
+use crate::{
+    geometry::{util, Collision, Ray, Vector},
+    sampler::{Sampler, SamplerTrait},
+    texture::{Texture, TextureTrait},
+};
 
 use super::MaterialTrait;
 
 pub struct Metallic {
     /// The 'color' of the metal
-    albedo: Vector,
+    albedo: Texture,
     /// How 'fuzzy' the material's reflections are, to mimic matte finishes
     fuzziness: f64,
 }
 
 impl Metallic {
     pub fn new(albedo: Vector, fuzziness: f64) -> Metallic {
+        Self::new_textured(albedo.into(), fuzziness)
+    }
+
+    pub fn new_textured(albedo: Texture, fuzziness: f64) -> Metallic {
         Metallic { albedo, fuzziness }
     }
 
@@ -22,16 +30,25 @@ impl Metallic {
 }
 
 impl MaterialTrait for Metallic {
-    fn scatter(&self, ray: &Ray, collision: &Collision) -> Option<(Vector, Ray)> {
-        let reflection = Metallic::reflect(ray.direction.normalize(), collision.normal);
-        return if cgmath::dot(reflection, collision.normal) > 0.0 {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        collision: &Collision,
+        sampler: &mut Sampler,
+    ) -> Option<(Vector, Ray)> {
+        let normal = util::vector::to_face_normal(ray, collision.normal);
+        let reflection = Metallic::reflect(ray.direction.normalize(), normal);
+        return if cgmath::dot(reflection, normal) > 0.0 {
             let reflection_fuzzed = if self.fuzziness != 0.0 {
-                reflection + (self.fuzziness * util::vector::random_unit_vector())
+                reflection + (self.fuzziness * util::vector::unit_vector_from(sampler.next_2d()))
             } else {
                 reflection
             };
             let scatter_ray = Ray::new(collision.point, reflection_fuzzed, ray.time);
-            Option::Some((self.albedo, scatter_ray))
+            let albedo = self
+                .albedo
+                .value(collision.uv, &collision.point, collision.uv_footprint);
+            Option::Some((albedo, scatter_ray))
         } else {
             Option::None
         };
@@ -1,9 +1,15 @@
 mod dielectric;
+mod diffuse_light;
+mod henyey_greenstein;
+mod isotropic;
 mod lambertian;
 mod material;
 mod metallic;
 
 pub use dielectric::Dielectric;
+pub use diffuse_light::DiffuseLight;
+pub use henyey_greenstein::HenyeyGreenstein;
+pub use isotropic::Isotropic;
 pub use lambertian::Lambertian;
 pub use material::{Material, MaterialTrait};
 pub use metallic::Metallic;
@@ -0,0 +1,54 @@
+use crate::geometry::{Point, TexCoord, Vector};
+
+use super::{Texture, TextureTrait};
+
+/// A 3D checkerboard, alternating between two textures in cubes
+///
+/// Since it's solid, the pattern doesn't depend on texture coordinates and
+/// lines up across every object it's applied to.
+pub struct Checker {
+    even: Texture,
+    odd: Texture,
+    /// The length of each side of the cubes
+    size: f64,
+}
+
+impl Checker {
+    pub fn new(even: Texture, odd: Texture, size: f64) -> Checker {
+        assert!(size > 0.0, "Checker cubes must have a positive size");
+        Checker { even, odd, size }
+    }
+}
+
+impl TextureTrait for Checker {
+    fn value(&self, uv: TexCoord, point: &Point, footprint: f64) -> Vector {
+        let cell = (point.x / self.size).floor()
+            + (point.y / self.size).floor()
+            + (point.z / self.size).floor();
+        return if cell.rem_euclid(2.0) == 0.0 {
+            self.even.value(uv, point, footprint)
+        } else {
+            self.odd.value(uv, point, footprint)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec2, vec3};
+
+    use super::*;
+
+    #[test]
+    fn when_value_given_neighboring_cells_alternates() {
+        let black = vec3(0.0, 0.0, 0.0);
+        let white = vec3(1.0, 1.0, 1.0);
+        let checker = Checker::new(black.into(), white.into(), 0.5);
+        let uv = vec2(0.0, 0.0);
+        assert_eq!(checker.value(uv, &point3(0.1, 0.1, 0.1), 0.0), black);
+        assert_eq!(checker.value(uv, &point3(0.6, 0.1, 0.1), 0.0), white);
+        assert_eq!(checker.value(uv, &point3(0.6, 0.6, 0.1), 0.0), black);
+        // negative cells continue the pattern, rather than mirroring it
+        assert_eq!(checker.value(uv, &point3(-0.1, 0.1, 0.1), 0.0), white);
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use cgmath::vec3;
+
+use crate::{
+    geometry::{Point, TexCoord, Vector},
+    image::{
+        buffer::FloatImageBuffer,
+        loader::{load_image, ColorSpace, ImageError},
+        mipmap::{Filter, MipMap, WrapMode},
+    },
+};
+
+use super::TextureTrait;
+
+/// An image stretched over an object's texture coordinates
+///
+/// (0, 0) is the bottom-left corner of the image. Coordinates outside of
+/// [0, 1] are handled by the texture's wrap mode.
+pub struct ImageTexture {
+    mipmap: MipMap,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: FloatImageBuffer, wrap: WrapMode, filter: Filter) -> ImageTexture {
+        assert!(
+            image.width > 0 && image.height > 0,
+            "Image textures can't be empty"
+        );
+        ImageTexture {
+            mipmap: MipMap::new(image, wrap),
+            filter,
+        }
+    }
+
+    /// Read a repeating, trilinear-filtered texture from a PNG, JPEG or
+    /// Radiance HDR file
+    ///
+    /// Use `ColorSpace::Srgb` for color maps, and `ColorSpace::Linear` for
+    /// data like roughness or normal maps.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        color_space: ColorSpace,
+    ) -> Result<ImageTexture, ImageError> {
+        let image = load_image(path, color_space)?;
+        Ok(Self::new(image, WrapMode::Repeat, Filter::Trilinear))
+    }
+}
+
+impl TextureTrait for ImageTexture {
+    fn value(&self, uv: TexCoord, _point: &Point, footprint: f64) -> Vector {
+        // flip v, since image rows run top to bottom
+        let [r, g, b] = self.mipmap.sample(uv.x, 1.0 - uv.y, footprint, self.filter);
+        return vec3(r as f64, g as f64, b as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec2};
+
+    use crate::image::buffer::BufferFormat;
+
+    use super::*;
+
+    /// 2x2, top row red and green, bottom row blue and white
+    fn make_image() -> FloatImageBuffer {
+        FloatImageBuffer {
+            format: BufferFormat::RGB32F,
+            width: 2,
+            height: 2,
+            data: vec![
+                1.0, 0.0, 0.0, 0.0, 1.0, 0.0, //
+                0.0, 0.0, 1.0, 1.0, 1.0, 1.0,
+            ],
+        }
+    }
+
+    #[test]
+    fn when_value_given_uv_corners_returns_image_corners() {
+        let texture = ImageTexture::new(make_image(), WrapMode::Repeat, Filter::Bilinear);
+        let origin = point3(0.0, 0.0, 0.0);
+        // pixel centers are a quarter of the way in from each edge
+        assert_eq!(
+            texture.value(vec2(0.25, 0.25), &origin, 0.0),
+            vec3(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            texture.value(vec2(0.75, 0.75), &origin, 0.0),
+            vec3(0.0, 1.0, 0.0)
+        );
+        // and it repeats outside of [0, 1]
+        assert_eq!(
+            texture.value(vec2(1.25, 1.75), &origin, 0.0),
+            vec3(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn when_value_given_clamp_wrap_returns_edge_color() {
+        let texture = ImageTexture::new(make_image(), WrapMode::Clamp, Filter::Nearest);
+        let origin = point3(0.0, 0.0, 0.0);
+        assert_eq!(
+            texture.value(vec2(1.25, 1.75), &origin, 0.0),
+            vec3(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            texture.value(vec2(-3.0, -3.0), &origin, 0.0),
+            vec3(0.0, 0.0, 1.0)
+        );
+    }
+}
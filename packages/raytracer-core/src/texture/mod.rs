@@ -0,0 +1,73 @@
+//! Textures vary a material's color over its surface, looked up by the
+//! texture coordinate and world position of a collision.
+use std::sync::Arc;
+
+use crate::geometry::{Point, TexCoord, Vector};
+
+mod checker;
+mod image_texture;
+mod noise;
+mod perlin;
+
+pub use checker::Checker;
+pub use image_texture::ImageTexture;
+pub use noise::{Marble, NoiseTexture};
+pub use perlin::Perlin;
+
+pub trait TextureTrait {
+    /// Look up the texture's color
+    ///
+    /// The footprint is roughly how much of the texture's [0, 1] square is
+    /// covered by the lookup, which image textures use to pick how much to
+    /// blur. Use 0 for a point sample.
+    fn value(&self, uv: TexCoord, point: &Point, footprint: f64) -> Vector;
+}
+
+#[derive(Clone)]
+pub enum Texture {
+    /// The same color everywhere
+    Solid(Vector),
+    Checker(Arc<Checker>),
+    Noise(Arc<NoiseTexture>),
+    Marble(Arc<Marble>),
+    Image(Arc<ImageTexture>),
+}
+
+impl TextureTrait for Texture {
+    #[inline(always)]
+    fn value(&self, uv: TexCoord, point: &Point, footprint: f64) -> Vector {
+        match self {
+            Texture::Solid(color) => *color,
+            Texture::Checker(checker) => checker.value(uv, point, footprint),
+            Texture::Noise(noise) => noise.value(uv, point, footprint),
+            Texture::Marble(marble) => marble.value(uv, point, footprint),
+            Texture::Image(image) => image.value(uv, point, footprint),
+        }
+    }
+}
+
+impl From<Vector> for Texture {
+    fn from(value: Vector) -> Self {
+        Self::Solid(value)
+    }
+}
+impl From<Arc<Checker>> for Texture {
+    fn from(value: Arc<Checker>) -> Self {
+        Self::Checker(value)
+    }
+}
+impl From<Arc<NoiseTexture>> for Texture {
+    fn from(value: Arc<NoiseTexture>) -> Self {
+        Self::Noise(value)
+    }
+}
+impl From<Arc<Marble>> for Texture {
+    fn from(value: Arc<Marble>) -> Self {
+        Self::Marble(value)
+    }
+}
+impl From<Arc<ImageTexture>> for Texture {
+    fn from(value: Arc<ImageTexture>) -> Self {
+        Self::Image(value)
+    }
+}
@@ -0,0 +1,129 @@
+use cgmath::vec3;
+
+use crate::geometry::{Point, TexCoord, Vector};
+
+use super::{Perlin, TextureTrait};
+
+/// The number of octaves of turbulence that make up marble's veins
+const MARBLE_OCTAVES: usize = 7;
+
+/// Perlin noise, shading a color from black to full brightness
+pub struct NoiseTexture {
+    perlin: Perlin,
+    /// How many features there are per unit of space
+    scale: f64,
+    /// With one octave this is plain noise, more gives turbulence
+    octaves: usize,
+    color: Vector,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, octaves: usize, color: Vector) -> NoiseTexture {
+        assert!(octaves > 0, "Noise needs at least one octave");
+        NoiseTexture {
+            perlin: Perlin::new(),
+            scale,
+            octaves,
+            color,
+        }
+    }
+}
+
+impl TextureTrait for NoiseTexture {
+    fn value(&self, _uv: TexCoord, point: &Point, _footprint: f64) -> Vector {
+        let point = point * self.scale;
+        let shade = if self.octaves == 1 {
+            0.5 * (1.0 + self.perlin.noise(&point))
+        } else {
+            self.perlin.turbulence(&point, self.octaves).min(1.0)
+        };
+        return shade * self.color;
+    }
+}
+
+/// Marble-like bands along the z axis, with veins disturbed by turbulence
+pub struct Marble {
+    perlin: Perlin,
+    /// How many bands there are per unit of space
+    scale: f64,
+    color: Vector,
+}
+
+impl Marble {
+    pub fn new(scale: f64, color: Vector) -> Marble {
+        Marble {
+            perlin: Perlin::new(),
+            scale,
+            color,
+        }
+    }
+
+    pub fn new_white(scale: f64) -> Marble {
+        Self::new(scale, vec3(1.0, 1.0, 1.0))
+    }
+}
+
+impl TextureTrait for Marble {
+    fn value(&self, _uv: TexCoord, point: &Point, _footprint: f64) -> Vector {
+        let phase = self.scale * point.z + 10.0 * self.perlin.turbulence(point, MARBLE_OCTAVES);
+        return 0.5 * (1.0 + phase.sin()) * self.color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec2, InnerSpace};
+
+    use super::*;
+
+    /// Points scattered through a few units of space, off the lattice
+    fn points() -> impl Iterator<Item = Point> {
+        (0..1000).map(|i| {
+            let i = i as f64;
+            point3(i * 0.037 - 20.0, i * 0.0113 + 0.3, 5.0 - i * 0.021)
+        })
+    }
+
+    #[test]
+    fn when_value_given_any_point_stays_between_black_and_color() {
+        let color = vec3(0.2, 0.5, 1.0);
+        let textures = [
+            NoiseTexture::new(4.0, 1, color),
+            NoiseTexture::new(4.0, 7, color),
+        ];
+        let marble = Marble::new(4.0, color);
+        for point in points() {
+            for value in textures
+                .iter()
+                .map(|texture| texture.value(vec2(0.0, 0.0), &point, 0.0))
+                .chain([marble.value(vec2(0.0, 0.0), &point, 0.0)])
+            {
+                let shade = value.x / color.x;
+                assert!((0.0..=1.0).contains(&shade));
+                // every channel is the same shade of the color
+                assert!((value - shade * color).magnitude() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn when_value_given_new_textures_returns_the_same_pattern() {
+        let color = vec3(1.0, 1.0, 1.0);
+        let (noise, other_noise) = (
+            NoiseTexture::new(4.0, 3, color),
+            NoiseTexture::new(4.0, 3, color),
+        );
+        let (marble, other_marble) = (Marble::new_white(4.0), Marble::new_white(4.0));
+        for point in points() {
+            let uv = vec2(0.0, 0.0);
+            assert_eq!(
+                noise.value(uv, &point, 0.0),
+                other_noise.value(uv, &point, 0.0)
+            );
+            assert_eq!(
+                marble.value(uv, &point, 0.0),
+                other_marble.value(uv, &point, 0.0)
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Gradient noise, after Ken Perlin's "Improved Noise" (2002)
+use cgmath::{dot, vec3, InnerSpace};
+
+use crate::geometry::{Point, Vector};
+
+const POINT_COUNT: usize = 256;
+
+/// The seed used by `Perlin::new`, so that noise is the same on every render
+const DEFAULT_SEED: u64 = 0x5eed;
+
+/// A smooth, random field over 3D space
+pub struct Perlin {
+    gradients: Vec<Vector>,
+    permute_x: Vec<usize>,
+    permute_y: Vec<usize>,
+    permute_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new() -> Perlin {
+        Self::new_with_seed(DEFAULT_SEED)
+    }
+
+    pub fn new_with_seed(seed: u64) -> Perlin {
+        let rng = fastrand::Rng::with_seed(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let gradient = vec3(
+                    2.0 * rng.f64() - 1.0,
+                    2.0 * rng.f64() - 1.0,
+                    2.0 * rng.f64() - 1.0,
+                );
+                let length = gradient.magnitude2();
+                if length > 1e-6 && length <= 1.0 {
+                    break gradient.normalize();
+                }
+            })
+            .collect();
+        let permute = || {
+            let mut permutation: Vec<usize> = (0..POINT_COUNT).collect();
+            rng.shuffle(&mut permutation);
+            permutation
+        };
+        Perlin {
+            permute_x: permute(),
+            permute_y: permute(),
+            permute_z: permute(),
+            gradients,
+        }
+    }
+
+    /// Noise at a point, in [-1, 1]
+    pub fn noise(&self, point: &Point) -> f64 {
+        let cell = point.map(f64::floor);
+        let offset = point - cell;
+        // a quintic fade curve keeps the second derivative continuous
+        let fade = offset.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+        let (i, j, k) = (cell.x as i64, cell.y as i64, cell.z as i64);
+        let wrap = |n: i64| n.rem_euclid(POINT_COUNT as i64) as usize;
+        let mut result = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.permute_x[wrap(i + di)]
+                        ^ self.permute_y[wrap(j + dj)]
+                        ^ self.permute_z[wrap(k + dk)]];
+                    let (di, dj, dk) = (di as f64, dj as f64, dk as f64);
+                    let weight = (di * fade.x + (1.0 - di) * (1.0 - fade.x))
+                        * (dj * fade.y + (1.0 - dj) * (1.0 - fade.y))
+                        * (dk * fade.z + (1.0 - dk) * (1.0 - fade.z));
+                    result += weight * dot(gradient, offset - vec3(di, dj, dk));
+                }
+            }
+        }
+        return result;
+    }
+
+    /// Several octaves of noise summed together, each at double the frequency
+    /// and half the weight of the last. The result is always positive.
+    pub fn turbulence(&self, point: &Point, octaves: usize) -> f64 {
+        let mut result = 0.0;
+        let mut point = *point;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            result += weight * self.noise(&point);
+            weight *= 0.5;
+            point *= 2.0;
+        }
+        return result.abs();
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    #[test]
+    fn when_noise_given_lattice_points_returns_zero() {
+        let perlin = Perlin::new();
+        // gradient noise is always zero where the gradients are defined
+        assert!(perlin.noise(&point3(3.0, -2.0, 7.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn when_noise_given_nearby_points_is_smooth_and_bounded() {
+        let perlin = Perlin::new();
+        let mut previous = perlin.noise(&point3(0.0, 0.5, 0.5));
+        for i in 1..1000 {
+            let value = perlin.noise(&point3(i as f64 * 0.01, 0.5, 0.5));
+            assert!((-1.0..=1.0).contains(&value));
+            assert!((value - previous).abs() < 0.05);
+            previous = value;
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! The color of light given off by something hot, like fire or embers
+use cgmath::vec3;
+
+use crate::geometry::Vector;
+
+/// The hottest temperature the lookup table covers, in Kelvin. Past this the
+/// color barely changes.
+const TABLE_MAX: f64 = 20000.0;
+const TABLE_STEP: f64 = 100.0;
+
+/// The linear sRGB color of a blackbody at a temperature in Kelvin, scaled to
+/// a luminance of 1
+///
+/// Colors outside of sRGB, like the deep red of embers, are clipped to it.
+/// Things too cold to glow visibly are black.
+pub fn blackbody(temperature: f64) -> Vector {
+    if temperature < 400.0 {
+        return vec3(0.0, 0.0, 0.0);
+    }
+    // integrate Planck's law against the CIE 1931 color matching functions
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+    for step in 0..=80 {
+        let wavelength = 380.0 + 5.0 * step as f64;
+        let radiance = planck(wavelength, temperature);
+        let (xbar, ybar, zbar) = color_matching(wavelength);
+        x += radiance * xbar;
+        y += radiance * ybar;
+        z += radiance * zbar;
+    }
+    let (x, y, z) = (x / y, 1.0, z / y);
+    let rgb = vec3(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    );
+    return vec3(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0));
+}
+
+/// Planck's law, up to a constant factor, with the wavelength in nanometers
+#[inline(always)]
+fn planck(wavelength: f64, temperature: f64) -> f64 {
+    // the second radiation constant, hc/k, in nanometer Kelvin
+    const C2: f64 = 1.4387769e7;
+    let lambda = wavelength * 1e-3;
+    return 1.0 / (lambda.powi(5) * ((C2 / (wavelength * temperature)).exp() - 1.0));
+}
+
+/// The CIE 1931 2° standard observer, from the multi-lobe fit in Wyman, Sloan
+/// & Shirley (2013), "Simple Analytic Approximations to the CIE XYZ Color
+/// Matching Functions"
+#[inline(always)]
+fn color_matching(wavelength: f64) -> (f64, f64, f64) {
+    let lobe = |center: f64, below: f64, above: f64| {
+        let spread = if wavelength < center { below } else { above };
+        let t = (wavelength - center) * spread;
+        (-0.5 * t * t).exp()
+    };
+    let x = 1.056 * lobe(599.8, 0.0264, 0.0323) + 0.362 * lobe(442.0, 0.0624, 0.0374)
+        - 0.065 * lobe(501.1, 0.0490, 0.0382);
+    let y = 0.821 * lobe(568.8, 0.0213, 0.0247) + 0.286 * lobe(530.9, 0.0613, 0.0322);
+    let z = 1.217 * lobe(437.0, 0.0845, 0.0278) + 0.681 * lobe(459.0, 0.0385, 0.0725);
+    return (x, y, z);
+}
+
+/// Blackbody colors for a range of temperatures, worked out ahead of time
+/// since each one takes a lot of exponentials
+pub struct BlackbodyTable {
+    colors: Vec<Vector>,
+}
+
+impl BlackbodyTable {
+    pub fn new() -> Self {
+        let steps = (TABLE_MAX / TABLE_STEP) as usize;
+        Self {
+            colors: (0..=steps)
+                .map(|step| blackbody(step as f64 * TABLE_STEP))
+                .collect(),
+        }
+    }
+
+    /// The color at a temperature in Kelvin, blended between the nearest
+    /// entries
+    #[inline(always)]
+    pub fn color(&self, temperature: f64) -> Vector {
+        let position = (temperature / TABLE_STEP).clamp(0.0, (self.colors.len() - 1) as f64);
+        let index = (position as usize).min(self.colors.len() - 2);
+        let amount = position - index as f64;
+        return self.colors[index] * (1.0 - amount) + self.colors[index + 1] * amount;
+    }
+}
+
+impl Default for BlackbodyTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_blackbody_given_temperatures_returns_familiar_colors() {
+        // a candle flame is orange, the sun is close to white, and a clear
+        // sky is blue
+        let candle = blackbody(1900.0);
+        assert!(candle.x > candle.y && candle.y > candle.z);
+        let sun = blackbody(6500.0);
+        for channel in [sun.x, sun.y, sun.z] {
+            assert!((channel - 1.0).abs() < 0.05, "{:?} should be white", sun);
+        }
+        let sky = blackbody(15000.0);
+        assert!(sky.z > sky.y && sky.y > sky.x);
+        assert_eq!(blackbody(300.0), vec3(0.0, 0.0, 0.0));
+
+        let table = BlackbodyTable::new();
+        assert!((table.color(1900.0) - candle).x.abs() < 1e-9);
+    }
+}
@@ -0,0 +1,514 @@
+//! Voxel grids of values like density or temperature, and a simple file format
+//! for them
+//!
+//! Grid files are little-endian binary, made of a header followed by either
+//! every voxel, or just the bricks of voxels that aren't all zero:
+//!
+//! | Field      | Type       | Notes                                        |
+//! |------------|------------|----------------------------------------------|
+//! | magic      | 4 bytes    | `RVOL`                                       |
+//! | version    | u32        | 1                                            |
+//! | kind       | u32        | 0 for dense, 1 for sparse                    |
+//! | resolution | 3 × u32    | Voxels along x, y and z                      |
+//!
+//! Dense grids follow with every voxel as an f32, with x changing fastest,
+//! then y, then z. Sparse grids follow with a u32 count of bricks, then for
+//! each brick its position in bricks as 3 × u32, and its 8 × 8 × 8 voxels as
+//! f32s in the same order as a dense grid. Voxels in missing bricks are 0.
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::geometry::Point;
+
+/// How many voxels along each side of a sparse grid's bricks
+pub const BRICK_SIZE: usize = 8;
+const BRICK_VOXELS: usize = BRICK_SIZE * BRICK_SIZE * BRICK_SIZE;
+/// Marks a brick that hasn't been allocated, because it's all zero
+const EMPTY_BRICK: u32 = u32::MAX;
+
+const MAGIC: &[u8; 4] = b"RVOL";
+const VERSION: u32 = 1;
+const KIND_DENSE: u32 = 0;
+const KIND_SPARSE: u32 = 1;
+/// Anything bigger is more likely to be a corrupt header than a real grid
+const MAX_VOXELS: u64 = 1 << 32;
+/// How many voxels a dense grid reads at a time
+const READ_CHUNK: usize = 4096;
+
+#[derive(Debug)]
+pub enum VolumeError {
+    Io(io::Error),
+    /// The file didn't start with the grid file magic number
+    NotVolume,
+    UnsupportedVersion(u32),
+    /// The grid was neither dense nor sparse
+    UnknownKind(u32),
+    InvalidResolution([u32; 3]),
+    /// A sparse grid's brick was outside of the grid
+    InvalidBrick([u32; 3]),
+}
+
+impl Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::NotVolume => write!(f, "not a voxel grid file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported grid file version {}", version)
+            }
+            Self::UnknownKind(kind) => write!(f, "unknown kind of grid {}", kind),
+            Self::InvalidResolution(resolution) => {
+                write!(f, "invalid resolution {:?}", resolution)
+            }
+            Self::InvalidBrick(brick) => write!(f, "brick {:?} is outside of the grid", brick),
+        }
+    }
+}
+
+impl Error for VolumeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Option::Some(err),
+            _ => Option::None,
+        }
+    }
+}
+
+impl From<io::Error> for VolumeError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// A grid that stores every voxel, for volumes that fill most of their box
+pub struct DenseGrid {
+    resolution: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl DenseGrid {
+    /// Create a grid from its voxels, with x changing fastest, then y, then z
+    pub fn new(resolution: [usize; 3], values: Vec<f32>) -> Self {
+        assert_eq!(
+            values.len(),
+            resolution.iter().product::<usize>(),
+            "A dense grid needs exactly one value per voxel"
+        );
+        Self { resolution, values }
+    }
+
+    #[inline(always)]
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        let [nx, ny, _] = self.resolution;
+        self.values[(z * ny + y) * nx + x]
+    }
+}
+
+/// A grid split into bricks of voxels, where bricks that are all zero aren't
+/// stored at all, for wisps of smoke in mostly empty boxes
+pub struct SparseGrid {
+    resolution: [usize; 3],
+    /// How many bricks there are along each axis
+    brick_resolution: [usize; 3],
+    /// Each brick's index in `bricks`, or EMPTY_BRICK
+    brick_indices: Vec<u32>,
+    bricks: Vec<[f32; BRICK_VOXELS]>,
+}
+
+impl SparseGrid {
+    /// Create a grid that's zero everywhere
+    pub fn new(resolution: [usize; 3]) -> Self {
+        let brick_resolution = resolution.map(|n| n.div_ceil(BRICK_SIZE));
+        Self {
+            resolution,
+            brick_resolution,
+            brick_indices: vec![EMPTY_BRICK; brick_resolution.iter().product()],
+            bricks: vec![],
+        }
+    }
+
+    /// Set a voxel, adding a brick for it if it needs one
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: f32) {
+        let brick = self.brick_index([x, y, z].map(|i| i / BRICK_SIZE));
+        if self.brick_indices[brick] == EMPTY_BRICK {
+            if value == 0.0 {
+                return;
+            }
+            self.brick_indices[brick] = self.bricks.len() as u32;
+            self.bricks.push([0.0; BRICK_VOXELS]);
+        }
+        let voxels = &mut self.bricks[self.brick_indices[brick] as usize];
+        voxels[voxel_in_brick(x, y, z)] = value;
+    }
+
+    /// How many bricks have been stored
+    pub fn brick_count(&self) -> usize {
+        self.bricks.len()
+    }
+
+    #[inline(always)]
+    fn brick_index(&self, brick: [usize; 3]) -> usize {
+        let [bx, by, _] = self.brick_resolution;
+        (brick[2] * by + brick[1]) * bx + brick[0]
+    }
+
+    #[inline(always)]
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        let brick = self.brick_index([x / BRICK_SIZE, y / BRICK_SIZE, z / BRICK_SIZE]);
+        match self.brick_indices[brick] {
+            EMPTY_BRICK => 0.0,
+            index => self.bricks[index as usize][voxel_in_brick(x, y, z)],
+        }
+    }
+}
+
+#[inline(always)]
+fn voxel_in_brick(x: usize, y: usize, z: usize) -> usize {
+    let (x, y, z) = (x % BRICK_SIZE, y % BRICK_SIZE, z % BRICK_SIZE);
+    (z * BRICK_SIZE + y) * BRICK_SIZE + x
+}
+
+/// A 3D grid of values, looked up by position in voxels
+///
+/// Voxel (i, j, k) covers the unit cube from (i, j, k) to (i + 1, j + 1, k + 1)
+/// and its value is at the cube's center. Between centers values are blended
+/// linearly, and outside of the grid they're zero.
+pub enum VoxelGrid {
+    Dense(DenseGrid),
+    Sparse(SparseGrid),
+}
+
+impl From<DenseGrid> for VoxelGrid {
+    fn from(value: DenseGrid) -> Self {
+        Self::Dense(value)
+    }
+}
+
+impl From<SparseGrid> for VoxelGrid {
+    fn from(value: SparseGrid) -> Self {
+        Self::Sparse(value)
+    }
+}
+
+impl VoxelGrid {
+    /// Read a grid file from disk
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, VolumeError> {
+        let file = File::open(path)?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Decode a grid file from a reader
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, VolumeError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(VolumeError::NotVolume);
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(VolumeError::UnsupportedVersion(version));
+        }
+        let kind = read_u32(&mut reader)?;
+        let raw_resolution = [
+            read_u32(&mut reader)?,
+            read_u32(&mut reader)?,
+            read_u32(&mut reader)?,
+        ];
+        // counted in u64, as usize is only 32 bits on the web
+        let voxel_count = raw_resolution
+            .iter()
+            .try_fold(1u64, |total, &n| total.checked_mul(n as u64))
+            .filter(|&count| count > 0 && count <= MAX_VOXELS)
+            .and_then(|count| usize::try_from(count).ok());
+        let voxel_count = match voxel_count {
+            Option::Some(count) => count,
+            Option::None => return Err(VolumeError::InvalidResolution(raw_resolution)),
+        };
+        let resolution = raw_resolution.map(|n| n as usize);
+
+        match kind {
+            KIND_DENSE => {
+                let values = read_f32_vec(&mut reader, voxel_count)?;
+                Ok(DenseGrid::new(resolution, values).into())
+            }
+            KIND_SPARSE => {
+                let mut grid = SparseGrid::new(resolution);
+                let count = read_u32(&mut reader)?;
+                for _ in 0..count {
+                    let raw_brick = [
+                        read_u32(&mut reader)?,
+                        read_u32(&mut reader)?,
+                        read_u32(&mut reader)?,
+                    ];
+                    let brick = raw_brick.map(|i| i as usize);
+                    if (0..3).any(|axis| brick[axis] >= grid.brick_resolution[axis]) {
+                        return Err(VolumeError::InvalidBrick(raw_brick));
+                    }
+                    let mut voxels = [0.0; BRICK_VOXELS];
+                    read_f32s(&mut reader, &mut voxels)?;
+                    let index = grid.brick_index(brick);
+                    match grid.brick_indices[index] {
+                        EMPTY_BRICK => {
+                            grid.brick_indices[index] = grid.bricks.len() as u32;
+                            grid.bricks.push(voxels);
+                        }
+                        // a brick given twice replaces the first one
+                        existing => grid.bricks[existing as usize] = voxels,
+                    }
+                }
+                Ok(grid.into())
+            }
+            _ => Err(VolumeError::UnknownKind(kind)),
+        }
+    }
+
+    /// Encode the grid as a grid file
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        let kind = match self {
+            Self::Dense(_) => KIND_DENSE,
+            Self::Sparse(_) => KIND_SPARSE,
+        };
+        writer.write_all(&kind.to_le_bytes())?;
+        for n in self.resolution() {
+            writer.write_all(&(n as u32).to_le_bytes())?;
+        }
+        match self {
+            Self::Dense(grid) => write_f32s(&mut writer, &grid.values)?,
+            Self::Sparse(grid) => {
+                writer.write_all(&(grid.bricks.len() as u32).to_le_bytes())?;
+                let [bx, by, bz] = grid.brick_resolution;
+                for z in 0..bz {
+                    for y in 0..by {
+                        for x in 0..bx {
+                            let index = grid.brick_indices[grid.brick_index([x, y, z])];
+                            if index == EMPTY_BRICK {
+                                continue;
+                            }
+                            for i in [x, y, z] {
+                                writer.write_all(&(i as u32).to_le_bytes())?;
+                            }
+                            write_f32s(&mut writer, &grid.bricks[index as usize])?;
+                        }
+                    }
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// How many voxels there are along each axis
+    pub fn resolution(&self) -> [usize; 3] {
+        match self {
+            Self::Dense(grid) => grid.resolution,
+            Self::Sparse(grid) => grid.resolution,
+        }
+    }
+
+    /// The value of a single voxel, or zero outside of the grid
+    #[inline(always)]
+    pub fn voxel(&self, x: i64, y: i64, z: i64) -> f32 {
+        let [nx, ny, nz] = self.resolution();
+        if x < 0 || y < 0 || z < 0 || x >= nx as i64 || y >= ny as i64 || z >= nz as i64 {
+            return 0.0;
+        }
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        match self {
+            Self::Dense(grid) => grid.voxel(x, y, z),
+            Self::Sparse(grid) => grid.voxel(x, y, z),
+        }
+    }
+
+    /// The value at a point measured in voxels, blended between the nearest
+    /// voxel centers
+    #[inline(always)]
+    pub fn lookup(&self, point: Point) -> f64 {
+        let (x, y, z) = (point.x - 0.5, point.y - 0.5, point.z - 0.5);
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+        let mut value = 0.0;
+        for (dz, wz) in [(0, 1.0 - fz), (1, fz)] {
+            for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+                for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                    let voxel = self.voxel(x0 + dx, y0 + dy, z0 + dz) as f64;
+                    value += voxel * wx * wy * wz;
+                }
+            }
+        }
+        return value;
+    }
+
+    /// Call a function with the position and value of every voxel that isn't
+    /// zero
+    pub fn for_each_voxel<F: FnMut([usize; 3], f32)>(&self, mut f: F) {
+        match self {
+            Self::Dense(grid) => {
+                let [nx, ny, nz] = grid.resolution;
+                for z in 0..nz {
+                    for y in 0..ny {
+                        for x in 0..nx {
+                            let value = grid.voxel(x, y, z);
+                            if value != 0.0 {
+                                f([x, y, z], value);
+                            }
+                        }
+                    }
+                }
+            }
+            Self::Sparse(grid) => {
+                let [bx, by, bz] = grid.brick_resolution;
+                for brick_z in 0..bz {
+                    for brick_y in 0..by {
+                        for brick_x in 0..bx {
+                            let index =
+                                grid.brick_indices[grid.brick_index([brick_x, brick_y, brick_z])];
+                            if index == EMPTY_BRICK {
+                                continue;
+                            }
+                            let voxels = &grid.bricks[index as usize];
+                            for (i, &value) in voxels.iter().enumerate() {
+                                let voxel = [
+                                    brick_x * BRICK_SIZE + i % BRICK_SIZE,
+                                    brick_y * BRICK_SIZE + (i / BRICK_SIZE) % BRICK_SIZE,
+                                    brick_z * BRICK_SIZE + i / (BRICK_SIZE * BRICK_SIZE),
+                                ];
+                                // edge bricks can hang off the end of the grid
+                                let inside = (0..3).all(|axis| voxel[axis] < grid.resolution[axis]);
+                                if value != 0.0 && inside {
+                                    f(voxel, value);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, values: &mut [f32]) -> io::Result<()> {
+    let mut bytes = [0u8; 4];
+    for value in values {
+        reader.read_exact(&mut bytes)?;
+        *value = f32::from_le_bytes(bytes);
+    }
+    Ok(())
+}
+
+/// Read `count` f32s, growing the vector as they arrive rather than allocating
+/// them all up front, so a header that claims more voxels than the file holds
+/// fails at the end of the file instead of allocating the whole grid
+fn read_f32_vec<R: Read>(reader: &mut R, count: usize) -> io::Result<Vec<f32>> {
+    let mut values = Vec::with_capacity(count.min(READ_CHUNK));
+    let mut chunk = [0.0; READ_CHUNK];
+    while values.len() < count {
+        let chunk = &mut chunk[..(count - values.len()).min(READ_CHUNK)];
+        read_f32s(reader, chunk)?;
+        values.extend_from_slice(chunk);
+    }
+    Ok(values)
+}
+
+fn write_f32s<W: Write>(writer: &mut W, values: &[f32]) -> io::Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use super::*;
+
+    fn make_grids() -> (VoxelGrid, VoxelGrid) {
+        let resolution = [10, 5, 12];
+        let mut values = vec![0.0; 10 * 5 * 12];
+        let mut sparse = SparseGrid::new(resolution);
+        for (x, y, z) in [(1, 2, 3), (9, 4, 11), (2, 2, 3)] {
+            let value = (x + y + z) as f32;
+            values[(z * 5 + y) * 10 + x] = value;
+            sparse.set(x, y, z, value);
+        }
+        (DenseGrid::new(resolution, values).into(), sparse.into())
+    }
+
+    #[test]
+    fn when_lookup_given_dense_and_sparse_grids_returns_same_values() {
+        let (dense, sparse) = make_grids();
+        if let VoxelGrid::Sparse(grid) = &sparse {
+            assert_eq!(grid.brick_count(), 2);
+        }
+        for point in [
+            point3(1.5, 2.5, 3.5),
+            point3(2.0, 2.5, 3.5),
+            point3(9.9, 4.9, 11.9),
+            point3(-3.0, 1.0, 1.0),
+        ] {
+            assert_eq!(dense.lookup(point), sparse.lookup(point));
+        }
+        assert_eq!(dense.lookup(point3(1.5, 2.5, 3.5)), 6.0);
+        // halfway between two voxel centers
+        assert_eq!(dense.lookup(point3(2.0, 2.5, 3.5)), 6.5);
+    }
+
+    #[test]
+    fn when_read_given_written_grid_returns_same_grid() {
+        let (dense, sparse) = make_grids();
+        for grid in [dense, sparse] {
+            let mut bytes = vec![];
+            grid.write(&mut bytes).unwrap();
+            let read = VoxelGrid::read(bytes.as_slice()).unwrap();
+            assert_eq!(read.resolution(), grid.resolution());
+            let mut expected = vec![];
+            grid.for_each_voxel(|voxel, value| expected.push((voxel, value)));
+            let mut actual = vec![];
+            read.for_each_voxel(|voxel, value| actual.push((voxel, value)));
+            assert_eq!(actual, expected);
+        }
+        assert!(matches!(
+            VoxelGrid::read(&b"RVOX"[..]),
+            Err(VolumeError::NotVolume)
+        ));
+    }
+
+    #[test]
+    fn when_read_given_header_bigger_than_data_returns_error() {
+        let header = |resolution: [u32; 3]| {
+            let mut bytes = MAGIC.to_vec();
+            for value in [VERSION, KIND_DENSE].into_iter().chain(resolution) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            // a few voxels, far fewer than the header asks for
+            bytes.extend_from_slice(&[0; 16]);
+            bytes
+        };
+        assert!(matches!(
+            VoxelGrid::read(header([2048, 2048, 1024]).as_slice()),
+            Err(VolumeError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            VoxelGrid::read(header([4096, 4096, 4096]).as_slice()),
+            Err(VolumeError::InvalidResolution(_))
+        ));
+        assert!(matches!(
+            VoxelGrid::read(header([u32::MAX, u32::MAX, u32::MAX]).as_slice()),
+            Err(VolumeError::InvalidResolution(_))
+        ));
+    }
+}
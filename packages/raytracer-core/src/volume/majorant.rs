@@ -0,0 +1,184 @@
+//! Coarse bounds on how dense a voxel grid gets, so tracking can take long
+//! steps through thin or empty parts of a volume
+use crate::geometry::{Point, Vector};
+
+use super::grid::VoxelGrid;
+
+/// How many voxels along each side of a majorant cell
+pub const CELL_SIZE: usize = 8;
+
+/// The largest value found in each block of a voxel grid
+///
+/// Lookups blend the voxels around them, so each cell also covers the voxels
+/// just outside of it.
+pub struct MajorantGrid {
+    /// How many cells there are along each axis
+    resolution: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl MajorantGrid {
+    pub fn new(grid: &VoxelGrid) -> Self {
+        let resolution = grid.resolution().map(|n| n.div_ceil(CELL_SIZE));
+        let mut values = vec![0.0f32; resolution.iter().product()];
+        grid.for_each_voxel(|voxel, value| {
+            // lookups from half a voxel before this one's start to half a
+            // voxel after its end read it
+            let first = voxel.map(|i| (i.saturating_sub(1)) / CELL_SIZE);
+            let last =
+                [0, 1, 2].map(|axis| ((voxel[axis] + 1) / CELL_SIZE).min(resolution[axis] - 1));
+            for z in first[2]..=last[2] {
+                for y in first[1]..=last[1] {
+                    for x in first[0]..=last[0] {
+                        let cell = &mut values[(z * resolution[1] + y) * resolution[0] + x];
+                        *cell = cell.max(value);
+                    }
+                }
+            }
+        });
+        Self { resolution, values }
+    }
+
+    #[inline(always)]
+    fn value(&self, cell: [i64; 3]) -> f32 {
+        let [nx, ny, _] = self.resolution;
+        let [x, y, z] = cell.map(|i| i as usize);
+        self.values[(z * ny + y) * nx + x]
+    }
+
+    /// Walk a ray measured in voxels through the cells it crosses between
+    /// t_min and t_max, which should be inside of the grid
+    pub fn segments(
+        &self,
+        origin: Point,
+        direction: Vector,
+        t_min: f64,
+        t_max: f64,
+    ) -> Segments<'_> {
+        let origin = origin / CELL_SIZE as f64;
+        let direction = direction / CELL_SIZE as f64;
+        let start = origin + direction * t_min;
+        let mut cell = [0i64; 3];
+        let mut step = [0i64; 3];
+        let mut t_next = [f64::INFINITY; 3];
+        let mut t_delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            let last = self.resolution[axis] as i64 - 1;
+            cell[axis] = (start[axis].floor() as i64).clamp(0, last);
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                t_next[axis] = (((cell[axis] + 1) as f64) - origin[axis]) / direction[axis];
+                t_delta[axis] = 1.0 / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                t_next[axis] = ((cell[axis] as f64) - origin[axis]) / direction[axis];
+                t_delta[axis] = -1.0 / direction[axis];
+            }
+        }
+        Segments {
+            grid: self,
+            cell,
+            step,
+            t_next,
+            t_delta,
+            t: t_min,
+            t_max,
+        }
+    }
+}
+
+/// The stretches of a ray inside each majorant cell, in order, as the t they
+/// start and end at and the cell's majorant
+pub struct Segments<'a> {
+    grid: &'a MajorantGrid,
+    cell: [i64; 3],
+    step: [i64; 3],
+    /// Where the ray crosses into the next cell along each axis
+    t_next: [f64; 3],
+    /// How far apart those crossings are
+    t_delta: [f64; 3],
+    t: f64,
+    t_max: f64,
+}
+
+impl Iterator for Segments<'_> {
+    type Item = (f64, f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.t >= self.t_max {
+            return Option::None;
+        }
+        let axis = if self.t_next[0] < self.t_next[1] {
+            if self.t_next[0] < self.t_next[2] {
+                0
+            } else {
+                2
+            }
+        } else if self.t_next[1] < self.t_next[2] {
+            1
+        } else {
+            2
+        };
+        let start = self.t;
+        let end = self.t_next[axis].min(self.t_max);
+        let majorant = self.grid.value(self.cell) as f64;
+
+        self.t = end;
+        self.cell[axis] += self.step[axis];
+        self.t_next[axis] += self.t_delta[axis];
+        if self.cell[axis] < 0 || self.cell[axis] >= self.grid.resolution[axis] as i64 {
+            // the ray's left the grid
+            self.t = self.t_max;
+        }
+        return Option::Some((start, end, majorant));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3};
+
+    use crate::volume::grid::SparseGrid;
+
+    use super::*;
+
+    #[test]
+    fn when_segments_given_ray_through_grid_covers_it_with_majorants_above_lookups() {
+        let resolution = [40, 24, 33];
+        let mut grid = SparseGrid::new(resolution);
+        let rng = fastrand::Rng::with_seed(7);
+        for _ in 0..200 {
+            grid.set(rng.usize(..40), rng.usize(..24), rng.usize(..33), rng.f32());
+        }
+        let grid: VoxelGrid = grid.into();
+        let majorants = MajorantGrid::new(&grid);
+
+        for _ in 0..200 {
+            let origin = point3(rng.f64() * 40.0, rng.f64() * 24.0, rng.f64() * 33.0);
+            let direction = vec3(rng.f64() - 0.5, rng.f64() - 0.5, rng.f64() - 0.5);
+            // how far the ray goes before it leaves the grid
+            let t_max = (0..3)
+                .map(|axis| {
+                    let end = if direction[axis] > 0.0 {
+                        resolution[axis] as f64
+                    } else {
+                        0.0
+                    };
+                    (end - origin[axis]) / direction[axis]
+                })
+                .fold(f64::INFINITY, f64::min);
+
+            let mut t = 0.0;
+            for (start, end, majorant) in majorants.segments(origin, direction, 0.0, t_max) {
+                assert!((start - t).abs() < 1e-9, "Segments should be contiguous");
+                assert!(end >= start);
+                for step in 0..=10 {
+                    let point = origin + direction * (start + (end - start) * step as f64 / 10.0);
+                    assert!(grid.lookup(point) <= majorant + 1e-6);
+                }
+                t = end;
+            }
+            assert!((t - t_max).abs() < 1e-9, "Segments should reach the end");
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Volumes whose density changes from place to place, like smoke and fire
+//! from a simulation
+//!
+//! Unlike a constant medium, the distance a ray travels before scattering in
+//! one of these can't be picked directly. Instead it's found by delta
+//! tracking (Woodcock et al., 1965): steps are picked as if the volume were
+//! as dense as its majorant, an upper bound on its density, and at each one
+//! the ray scatters with the odds of the real density over the majorant.
+//! Otherwise it carries on as though nothing happened. Ratio tracking (Novák
+//! et al., 2014) takes the same steps to find how much light gets through,
+//! for shadow rays.
+//!
+//! A single majorant for the whole grid makes for tiny steps through thin
+//! parts of a volume with a dense core, so each block of voxels gets its own,
+//! and rays are walked through the blocks one at a time.
+use std::sync::Arc;
+
+use cgmath::{vec2, vec3, ElementWise, EuclideanSpace, InnerSpace};
+
+use crate::{
+    geometry::{aabb::AxisAlignedBoundingBox, Collision, Point, Ray, Vector},
+    shader::{Isotropic, Material},
+};
+
+use self::{
+    blackbody::BlackbodyTable,
+    grid::VoxelGrid,
+    majorant::{MajorantGrid, Segments},
+};
+
+pub mod blackbody;
+pub mod grid;
+pub mod majorant;
+
+/// What a ray picked up passing through the volumes in a scene
+pub struct MediumInteraction {
+    /// Where it scattered, if it did
+    pub collision: Option<Collision>,
+    /// Light given off by the volumes, between where the ray started and
+    /// where it scattered or left them
+    pub emitted: Vector,
+}
+
+/// The color of the light a glowing volume gives off
+pub enum EmissionColor {
+    Constant(Vector),
+    /// The color of a blackbody at the temperature in a grid, in Kelvin once
+    /// multiplied by the scale
+    Blackbody {
+        temperature: Arc<VoxelGrid>,
+        scale: f64,
+        table: BlackbodyTable,
+    },
+}
+
+/// Light given off by a volume, like the flames in a fire
+///
+/// Like real flames, only the parts of a volume with some density glow.
+pub struct Emission {
+    /// How brightly each part of the volume glows, per unit of distance
+    pub grid: Arc<VoxelGrid>,
+    pub scale: f64,
+    pub color: EmissionColor,
+}
+
+impl Emission {
+    pub fn new(grid: Arc<VoxelGrid>, scale: f64, color: Vector) -> Self {
+        Self {
+            grid,
+            scale,
+            color: EmissionColor::Constant(color),
+        }
+    }
+
+    /// Glow with the color of something at the temperature in a grid, which
+    /// should line up with the emission grid
+    pub fn new_blackbody(
+        grid: Arc<VoxelGrid>,
+        scale: f64,
+        temperature: Arc<VoxelGrid>,
+        temperature_scale: f64,
+    ) -> Self {
+        Self {
+            grid,
+            scale,
+            color: EmissionColor::Blackbody {
+                temperature,
+                scale: temperature_scale,
+                table: BlackbodyTable::new(),
+            },
+        }
+    }
+
+    /// The light given off per unit of distance at a point measured in the
+    /// emission grid's voxels
+    #[inline(always)]
+    fn radiance(&self, point: Point) -> Vector {
+        let strength = self.grid.lookup(point) * self.scale;
+        if strength <= 0.0 {
+            return vec3(0.0, 0.0, 0.0);
+        }
+        let color = match &self.color {
+            EmissionColor::Constant(color) => *color,
+            EmissionColor::Blackbody {
+                temperature,
+                scale,
+                table,
+            } => {
+                // the grids can have different resolutions over the same box
+                let position = rescale(point, self.grid.resolution(), temperature.resolution());
+                table.color(temperature.lookup(position) * scale)
+            }
+        };
+        return color * strength;
+    }
+}
+
+/// Move a point measured in the voxels of one grid to the voxels of another
+/// covering the same box
+#[inline(always)]
+fn rescale(point: Point, from: [usize; 3], to: [usize; 3]) -> Point {
+    Point::new(
+        point.x * to[0] as f64 / from[0] as f64,
+        point.y * to[1] as f64 / from[1] as f64,
+        point.z * to[2] as f64 / from[2] as f64,
+    )
+}
+
+/// A volume filling an axis-aligned box, with its density read from a voxel
+/// grid stretched over the box
+pub struct GridMedium {
+    density: Arc<VoxelGrid>,
+    majorants: MajorantGrid,
+    /// Multiplies the grid's values, to get the odds of scattering per unit
+    /// of distance
+    density_scale: f64,
+    pub phase_function: Material,
+    emission: Option<Emission>,
+    bounds: AxisAlignedBoundingBox,
+    /// How many voxels there are per unit of distance along each axis
+    voxels_per_unit: Vector,
+}
+
+impl GridMedium {
+    /// Create a volume that scatters light evenly in every direction
+    pub fn new(density: Arc<VoxelGrid>, min: Point, max: Point) -> Self {
+        let material = Isotropic::new(vec3(1.0, 1.0, 1.0));
+        Self::new_with_material(density, min, max, Arc::new(material).into())
+    }
+
+    pub fn new_with_material(
+        density: Arc<VoxelGrid>,
+        min: Point,
+        max: Point,
+        phase_function: Material,
+    ) -> Self {
+        let [nx, ny, nz] = density.resolution();
+        let size = max - min;
+        assert!(
+            size.x > 0.0 && size.y > 0.0 && size.z > 0.0,
+            "A volume's box must have some size along every axis"
+        );
+        Self {
+            majorants: MajorantGrid::new(&density),
+            density,
+            density_scale: 1.0,
+            phase_function,
+            emission: Option::None,
+            bounds: AxisAlignedBoundingBox::new(min, max),
+            voxels_per_unit: vec3(nx as f64, ny as f64, nz as f64).div_element_wise(size),
+        }
+    }
+
+    pub fn with_density_scale(mut self, density_scale: f64) -> Self {
+        self.density_scale = density_scale;
+        self
+    }
+
+    pub fn with_emission(mut self, emission: Emission) -> Self {
+        self.emission = Option::Some(emission);
+        self
+    }
+
+    pub fn bounds(&self) -> &AxisAlignedBoundingBox {
+        &self.bounds
+    }
+
+    /// Find where the ray scatters between t_min and t_max by delta
+    /// tracking, if it does, with the random steps taken from `rng`
+    ///
+    /// Light given off along the way is passed to `emit` along with the t it
+    /// was picked up at, since a ray that scatters in another volume first
+    /// mustn't count anything from past there.
+    pub fn track<F: FnMut(f64, Vector)>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &fastrand::Rng,
+        mut emit: F,
+    ) -> Option<Collision> {
+        let (segments, ray_length, origin, direction) = self.segments(ray, t_min, t_max)?;
+        for (start, end, majorant) in segments {
+            // the odds of stopping per unit of t, rather than of distance
+            let majorant = majorant * self.density_scale * ray_length;
+            if majorant <= 0.0 {
+                continue;
+            }
+            let mut t = start;
+            loop {
+                t -= (1.0 - rng.f64()).ln() / majorant;
+                if t >= end {
+                    break;
+                }
+                let point = origin + direction * t;
+                if let Option::Some(emission) = &self.emission {
+                    // every step is a sample of the light given off along
+                    // the ray, up to where it scatters
+                    let emission_point =
+                        rescale(point, self.density.resolution(), emission.grid.resolution());
+                    emit(
+                        t,
+                        emission.radiance(emission_point) * (ray_length / majorant),
+                    );
+                }
+                let density = self.density.lookup(point) * self.density_scale * ray_length;
+                if rng.f64() * majorant < density {
+                    return Option::Some(Collision {
+                        point: ray.point_at(t),
+                        // there's no surface, so this just faces back along
+                        // the ray
+                        normal: -ray.direction / ray_length,
+                        t,
+                        uv: vec2(0.0, 0.0),
+                        uv_footprint: 0.0,
+                        material: self.phase_function.clone(),
+                    });
+                }
+            }
+        }
+        return Option::None;
+    }
+
+    /// How much light gets through the volume between t_min and t_max, by
+    /// ratio tracking with the random steps taken from `rng`
+    pub fn transmittance(&self, ray: &Ray, t_min: f64, t_max: f64, rng: &fastrand::Rng) -> f64 {
+        let (segments, ray_length, origin, direction) = match self.segments(ray, t_min, t_max) {
+            Option::Some(segments) => segments,
+            Option::None => return 1.0,
+        };
+        let mut transmittance = 1.0;
+        for (start, end, majorant) in segments {
+            let majorant = majorant * self.density_scale * ray_length;
+            if majorant <= 0.0 {
+                continue;
+            }
+            let mut t = start;
+            loop {
+                t -= (1.0 - rng.f64()).ln() / majorant;
+                if t >= end {
+                    break;
+                }
+                let density =
+                    self.density.lookup(origin + direction * t) * self.density_scale * ray_length;
+                transmittance *= 1.0 - density / majorant;
+            }
+            // past this, the light that gets through is too little to matter
+            // but costs as much to track, so stop at random and make up for
+            // it in the paths that carry on
+            if transmittance < 0.1 {
+                if rng.f64() < 0.5 {
+                    return 0.0;
+                }
+                transmittance *= 2.0;
+            }
+        }
+        return transmittance;
+    }
+
+    /// The part of the ray between t_min and t_max that's inside the box,
+    /// split up by majorant cell, along with the length of the ray's
+    /// direction and the ray measured in voxels
+    fn segments(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(Segments<'_>, f64, Point, Vector)> {
+        let interval = self.bounds.will_intersect_aabb(ray, t_min, t_max)?;
+        let origin = Point::from_vec(
+            (ray.origin - self.bounds.start_point).mul_element_wise(self.voxels_per_unit),
+        );
+        let direction = ray.direction.mul_element_wise(self.voxels_per_unit);
+        let segments = self
+            .majorants
+            .segments(origin, direction, interval.0, interval.1);
+        return Option::Some((segments, ray.direction.magnitude(), origin, direction));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point3;
+
+    use self::grid::DenseGrid;
+
+    use super::*;
+
+    /// A dense grid with a smooth blob in the middle, and the integral of its
+    /// density along the line through the middle of it in x
+    fn make_blob() -> (Arc<VoxelGrid>, f64) {
+        let resolution = [32, 16, 16];
+        let mut values = vec![];
+        for z in 0..16 {
+            for y in 0..16 {
+                for x in 0..32 {
+                    let offset = vec3(
+                        x as f64 - 15.5,
+                        (y as f64 - 7.5) * 2.0,
+                        (z as f64 - 7.5) * 2.0,
+                    );
+                    values.push((2.0 - offset.magnitude() / 8.0).max(0.0) as f32);
+                }
+            }
+        }
+        let grid: Arc<VoxelGrid> = Arc::new(DenseGrid::new(resolution, values).into());
+        let steps = 100_000;
+        let integral: f64 = (0..steps)
+            .map(|step| {
+                let x = 32.0 * (step as f64 + 0.5) / steps as f64;
+                grid.lookup(point3(x, 8.0, 8.0))
+            })
+            .sum::<f64>()
+            * (32.0 / steps as f64);
+        (grid, integral)
+    }
+
+    #[test]
+    fn when_track_and_transmittance_given_blob_agree_with_exact_transmittance() {
+        let (grid, integral) = make_blob();
+        // stretched over a box 4 units long, so the integral along it is
+        // scaled to match
+        let medium = GridMedium::new(grid, point3(-2.0, -1.0, -1.0), point3(2.0, 1.0, 1.0))
+            .with_density_scale(0.5);
+        let expected = (-integral * 0.5 * 4.0 / 32.0).exp();
+
+        let ray = Ray::new(point3(-5.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0), 0.0);
+        let count = 40_000;
+        let rng = fastrand::Rng::with_seed(3);
+        let passed = (0..count)
+            .filter(|_| {
+                medium
+                    .track(&ray, 0.0, f64::INFINITY, &rng, |_, _| {})
+                    .is_none()
+            })
+            .count();
+        assert!(
+            (passed as f64 / count as f64 - expected).abs() < 0.01,
+            "Expected {} of rays through, got {}",
+            expected,
+            passed as f64 / count as f64
+        );
+        let mean = (0..count)
+            .map(|_| medium.transmittance(&ray, 0.0, f64::INFINITY, &rng))
+            .sum::<f64>()
+            / count as f64;
+        assert!((mean - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn when_track_given_glowing_volume_picks_up_light_in_front_of_scattering() {
+        let (grid, _) = make_blob();
+        let medium = GridMedium::new(
+            grid.clone(),
+            point3(0.0, 0.0, 0.0),
+            point3(32.0, 16.0, 16.0),
+        )
+        .with_density_scale(0.05)
+        .with_emission(Emission::new(grid.clone(), 1.0, vec3(1.0, 0.5, 0.25)));
+        // light given off at each point along the ray, dimmed by how much of
+        // the volume it has to get through on the way out
+        let steps = 100_000;
+        let step = 32.0 / steps as f64;
+        let mut optical_depth = 0.0f64;
+        let mut expected = 0.0;
+        for idx in 0..steps {
+            let density = grid.lookup(point3((idx as f64 + 0.5) * step, 8.0, 8.0));
+            expected += (-optical_depth).exp() * density * step;
+            optical_depth += density * 0.05 * step;
+        }
+
+        let ray = Ray::new(point3(-1.0, 8.0, 8.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let count = 20_000;
+        let mut emitted = vec3(0.0, 0.0, 0.0);
+        let rng = fastrand::Rng::with_seed(5);
+        for _ in 0..count {
+            medium.track(&ray, 0.0, f64::INFINITY, &rng, |_, light| emitted += light);
+        }
+        emitted /= count as f64;
+        assert!(
+            (emitted.x - expected).abs() < 0.03 * expected,
+            "Expected {} of light, got {}",
+            expected,
+            emitted.x
+        );
+        assert!((emitted.y - emitted.x * 0.5).abs() < 1e-9);
+    }
+}
@@ -2,8 +2,9 @@
 //!
 //! The helpers in this file are intended to be called in JS, and are made
 //! available as globals on the WASM binary.
-use crate::image::buffer;
-use crate::render;
+use crate::image::{buffer, display::DisplayTransform};
+use crate::render::{self, progressive::Accumulator, renderer::Renderer};
+use crate::scene::SceneGraph;
 use console_error_panic_hook;
 use console_log;
 use log::{info, Level};
@@ -26,3 +27,54 @@ pub fn draw_scene() -> Vec<u8> {
     let result = buffer::convert::rgb_to_rgba(&test_image, 255);
     result.data
 }
+
+/// The test scene, rendered a pass at a time so the page can show it refining
+///
+/// Each call to `step` renders a single pass and returns straight away, so
+/// the page can draw the image so far before asking for the next.
+#[wasm_bindgen]
+pub struct ProgressiveScene {
+    renderer: Renderer,
+    scene: SceneGraph,
+    accumulator: Accumulator,
+    display: DisplayTransform,
+}
+
+#[wasm_bindgen]
+impl ProgressiveScene {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        let (renderer, scene) = render::helloworld_scene();
+        let (width, height) = (renderer.width(), renderer.height());
+        Self {
+            renderer,
+            scene,
+            accumulator: Accumulator::new(width, height),
+            display: DisplayTransform::default(),
+        }
+    }
+
+    /// Render another pass, returning the average so far as RGBA bytes
+    pub fn step(&mut self) -> Vec<u8> {
+        self.renderer
+            .render_pass(&self.scene, 1, &mut self.accumulator);
+        let image = self.display.to_rgb8(self.accumulator.image());
+        buffer::convert::rgb_to_rgba(&image, 255).data
+    }
+
+    /// How many passes have been rendered so far
+    pub fn passes(&self) -> usize {
+        self.accumulator.passes()
+    }
+
+    /// How many passes it takes to finish the image
+    pub fn total_passes(&self) -> usize {
+        self.renderer.samples_per_pixel()
+    }
+}
+
+impl Default for ProgressiveScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
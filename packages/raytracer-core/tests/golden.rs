@@ -0,0 +1,202 @@
+//! Renders of the example scenes, checked against reference images
+//!
+//! Each scene is rendered small and with a fixed seed, so it comes out the
+//! same every time, and compared against a PNG in `tests/golden`. Renders
+//! are compared after the scene's display transform, the way they'd be
+//! looked at, so that changes in how bright a light is matter as much as
+//! they'd show up. Small differences are allowed, since floating point math
+//! can come out a little differently on other platforms and send the odd
+//! sample elsewhere.
+//!
+//! When a render doesn't match, it's written out next to an image of where
+//! it differs, under `target/tmp/golden`. When a change to a render is meant
+//! to happen, the references can be rendered again with
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test -p raytracer-core --test golden
+//! ```
+use std::{
+    env, fs,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use raytracer_core::{
+    image::{
+        buffer::{convert, FloatImageBuffer, ImageBuffer},
+        compare::{compare_images, difference_image, Tolerance},
+        display::{DisplayTransform, ToneMap, TransferFunction},
+        loader::{load_image, ColorSpace},
+        writer::{write_ldr_image, OutputFormat},
+    },
+    render::renderer::Renderer,
+    scene::{RenderSettings, SamplerDescription, SceneDescription},
+};
+
+/// How small and how noisy to render a scene
+struct GoldenSettings {
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    max_ray_depth: usize,
+}
+
+impl Default for GoldenSettings {
+    fn default() -> Self {
+        Self {
+            width: 48,
+            height: 48,
+            samples_per_pixel: 16,
+            max_ray_depth: 8,
+        }
+    }
+}
+
+fn scenes_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scenes")
+}
+
+fn references_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn failures_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+/// Render a scene file with the given settings, after its display transform
+fn render_scene(scene_file: &str, settings: &GoldenSettings) -> ImageBuffer {
+    let path = scenes_dir().join(scene_file);
+    let mut description = SceneDescription::from_file(&path)
+        .unwrap_or_else(|err| panic!("Failed to load {}: {}", path.display(), err));
+    // everything that could change from run to run is pinned down here,
+    // rather than left to the scene file
+    description.render = RenderSettings {
+        width: settings.width,
+        height: settings.height,
+        samples_per_pixel: settings.samples_per_pixel,
+        max_ray_depth: settings.max_ray_depth,
+        light_sampling: description.render.light_sampling,
+        adaptive_threshold: Option::None,
+        sampler: SamplerDescription::Sobol,
+        seed: 0,
+        ..RenderSettings::default()
+    };
+    let scene = description
+        .build_scene()
+        .unwrap_or_else(|err| panic!("Failed to build {}: {}", path.display(), err));
+    let renderer = Renderer::new(
+        settings.width,
+        settings.height,
+        settings.samples_per_pixel,
+        settings.max_ray_depth as i64,
+        description.build_camera(),
+    )
+    .with_light_sampling(description.render.light_sampling.into())
+    .with_sampler(description.render.sampler.into())
+    .with_seed(description.render.seed);
+
+    let mut image = FloatImageBuffer::new_rgb(settings.width, settings.height);
+    renderer.render_parallel(&scene, &mut image, 1);
+    description.display.build().to_rgb8(&image)
+}
+
+fn write_png(path: &Path, image: &ImageBuffer) {
+    let file = File::create(path)
+        .unwrap_or_else(|err| panic!("Failed to create {}: {}", path.display(), err));
+    write_ldr_image(&mut BufWriter::new(file), image, OutputFormat::Png)
+        .unwrap_or_else(|err| panic!("Failed to write {}: {}", path.display(), err));
+}
+
+/// Render a scene and check it against its reference image, or save it as
+/// the reference when `UPDATE_GOLDEN` is set
+fn check_golden(name: &str, scene_file: &str, settings: GoldenSettings, tolerance: Tolerance) {
+    let render = render_scene(scene_file, &settings);
+    let reference = references_dir().join(format!("{}.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(references_dir()).unwrap();
+        write_png(&reference, &render);
+        return;
+    }
+
+    // the reference is read back without decoding its gamma, so that it
+    // compares like for like with the 8-bit render
+    let actual = convert::rgb8_to_float(&render);
+    let expected = load_image(&reference, ColorSpace::Linear).unwrap_or_else(|err| {
+        panic!(
+            "Failed to load the reference {}: {}. Run with UPDATE_GOLDEN=1 to render it.",
+            reference.display(),
+            err
+        )
+    });
+    let difference = compare_images(&actual, &expected, tolerance.pixel_threshold)
+        .unwrap_or_else(|err| panic!("{} doesn't match its reference: {}", name, err));
+    if difference.is_within(&tolerance) {
+        return;
+    }
+
+    fs::create_dir_all(failures_dir()).unwrap();
+    let actual_path = failures_dir().join(format!("{}.actual.png", name));
+    let diff_path = failures_dir().join(format!("{}.diff.png", name));
+    write_png(&actual_path, &render);
+    let diff = difference_image(&actual, &expected, tolerance.pixel_threshold, 8.0).unwrap();
+    // the differences are written as they are, without any gamma
+    let linear = DisplayTransform::new(0.0, ToneMap::Clamp, TransferFunction::Gamma(1.0));
+    write_png(&diff_path, &linear.to_rgb8(&diff));
+    panic!(
+        "{} doesn't match its reference: {}\nThe render is at {} and where it differs is at {}",
+        name,
+        difference,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+#[test]
+fn when_render_given_cornell_box_matches_reference() {
+    check_golden(
+        "cornell_box",
+        "cornell_box.toml",
+        GoldenSettings::default(),
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn when_render_given_volumes_matches_reference() {
+    check_golden(
+        "volumes",
+        "volumes.toml",
+        GoldenSettings::default(),
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn when_render_given_lights_matches_reference() {
+    check_golden(
+        "lights",
+        "lights.toml",
+        GoldenSettings {
+            width: 64,
+            height: 36,
+            ..GoldenSettings::default()
+        },
+        Tolerance::default(),
+    );
+}
+
+#[test]
+fn when_render_given_textures_matches_reference() {
+    check_golden(
+        "textures",
+        "textures.toml",
+        GoldenSettings {
+            width: 64,
+            height: 36,
+            ..GoldenSettings::default()
+        },
+        Tolerance::default(),
+    );
+}
@@ -39,4 +39,16 @@ export class WasmBinary {
         console.log("Rendering complete, took ", end - start, "ms");
         return data;
     }
+
+    /**
+     * Start rendering the scene a pass at a time. Call `step` on the result
+     * for each pass, which returns the image so far as RGBA bytes.
+     */
+    public render_progressive() {
+        if (!this.isLoaded) {
+            throw new Error("Module uninitialized");
+        }
+        console.log("Rendering scene progressively...");
+        return new this.module!.ProgressiveScene();
+    }
 }
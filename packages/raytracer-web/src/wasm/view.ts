@@ -56,7 +56,24 @@ export class HTMLRaytracerViewElement extends HTMLElement {
         if (!this.isReady) {
             throw new Error("WASM binary not yet initialized, cannot render");
         }
-        const data = this.binary.render_image();
+        const render = this.binary.render_progressive();
+        const start = Date.now();
+        // render a pass per frame, so the browser can show each one before
+        // the next is started
+        const step = () => {
+            const data = render.step();
+            this.paint(data);
+            if (render.passes() < render.total_passes()) {
+                requestAnimationFrame(step);
+            } else {
+                console.log("Rendering complete, took ", Date.now() - start, "ms");
+                render.free();
+            }
+        };
+        requestAnimationFrame(step);
+    }
+
+    private paint(data: Uint8Array) {
         const resultBuffer = new Uint8ClampedArray(data);
         const imageData = new ImageData(resultBuffer, HTMLRaytracerViewElement.WIDTH, HTMLRaytracerViewElement.HEIGHT);
         this.drawImageData(imageData);